@@ -0,0 +1,86 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::Cell;
+use std::io;
+
+use libc::{self, c_int, c_char};
+
+/// Base of the error numbers nanomsg defines itself, the same value is used so C code can compare them.
+pub const NN_HAUSNUMERO: c_int = 156384712;
+
+/// The library is terminating, see `nn_term`.
+pub const ETERM: c_int = NN_HAUSNUMERO + 53;
+/// The operation cannot be performed in the current state of the socket, like receiving a reply before sending a request.
+pub const EFSM: c_int = NN_HAUSNUMERO + 54;
+
+thread_local!(static ERRNO: Cell<c_int> = Cell::new(0));
+
+pub fn get() -> c_int {
+    ERRNO.with(|errno| errno.get())
+}
+
+pub fn set(value: c_int) {
+    ERRNO.with(|errno| errno.set(value));
+}
+
+/// Maps the errors reported by scaproust to the error numbers a nanomsg application expects.
+/// Operating system errors keep their own number, the other ones are mapped from their kind,
+/// `Other` being what the protocols report when an operation is not possible in their current state.
+pub fn from_io_error(err: &io::Error) -> c_int {
+    if let Some(errno) = err.raw_os_error() {
+        return errno;
+    }
+
+    match err.kind() {
+        io::ErrorKind::TimedOut          => libc::ETIMEDOUT,
+        io::ErrorKind::WouldBlock        => libc::EAGAIN,
+        io::ErrorKind::InvalidInput      => libc::EINVAL,
+        io::ErrorKind::InvalidData       => libc::EPROTO,
+        io::ErrorKind::AddrInUse         => libc::EADDRINUSE,
+        io::ErrorKind::AddrNotAvailable  => libc::EADDRNOTAVAIL,
+        io::ErrorKind::ConnectionRefused => libc::ECONNREFUSED,
+        io::ErrorKind::ConnectionReset   => libc::ECONNRESET,
+        io::ErrorKind::NotFound          => libc::ENOENT,
+        io::ErrorKind::PermissionDenied  => libc::EACCES,
+        _                                => EFSM
+    }
+}
+
+pub fn strerror(errnum: c_int) -> *const c_char {
+    match errnum {
+        ETERM => b"Nanomsg library was terminated\0".as_ptr() as *const c_char,
+        EFSM => b"Operation cannot be performed in this state\0".as_ptr() as *const c_char,
+        _ => unsafe { libc::strerror(errnum) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use libc;
+
+    use super::*;
+
+    #[test]
+    fn io_error_kinds_are_mapped_to_errno() {
+        let timed_out = io::Error::new(io::ErrorKind::TimedOut, "Recv timed out");
+        let invalid = io::Error::new(io::ErrorKind::InvalidInput, "option not supported");
+        let no_request = io::Error::new(io::ErrorKind::Other, "Can't recv: no active request");
+
+        assert_eq!(libc::ETIMEDOUT, from_io_error(&timed_out));
+        assert_eq!(libc::EINVAL, from_io_error(&invalid));
+        assert_eq!(EFSM, from_io_error(&no_request));
+    }
+
+    #[test]
+    fn os_errors_keep_their_number() {
+        let err = io::Error::from_raw_os_error(libc::ECONNABORTED);
+
+        assert_eq!(libc::ECONNABORTED, from_io_error(&err));
+    }
+}
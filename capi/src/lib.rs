@@ -0,0 +1,789 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+//! C ABI of nanomsg implemented on top of scaproust.
+//!
+//! The library is built as `libnanomsg`, so applications written against the nanomsg headers
+//! can be linked with it instead of the C library. Sockets are identified by integer handles
+//! and the errors are reported through `nn_errno`, with the error numbers of nanomsg.
+//!
+//! Like in nanomsg, a blocking send or recv is a sequence of non-blocking attempts separated by
+//! waits for the socket to be ready, so a thread blocked in `nn_recv` does not prevent the others
+//! from sending or changing the options of the same socket.
+//! Raw sockets can be given to `nn_device`, but `nn_send` and `nn_recv` only transfer the message body.
+
+#![cfg_attr(feature = "cargo-clippy", allow(bool_comparison))]
+
+extern crate libc;
+extern crate scaproust;
+#[macro_use]
+extern crate lazy_static;
+
+pub mod errno;
+pub mod msg;
+pub mod options;
+pub mod state;
+
+use std::ffi::CStr;
+use std::io;
+use std::ptr;
+use std::slice;
+use std::time::{Duration, Instant};
+use std::usize;
+
+use libc::{c_char, c_int, c_short, c_void, size_t};
+
+use scaproust::{ConfigOption, ConfigOptionKind, Device, PollReq, Socket};
+
+use options::*;
+use state::*;
+
+pub const NN_MSG: size_t = usize::MAX;
+pub const NN_DONTWAIT: c_int = 1;
+pub const NN_POLLIN: c_short = 1;
+pub const NN_POLLOUT: c_short = 2;
+
+/// Same layout as the `nn_pollfd` structure of nanomsg.
+#[repr(C)]
+pub struct nn_pollfd {
+    pub fd: c_int,
+    pub events: c_short,
+    pub revents: c_short
+}
+
+/// Probes report a socket once all the requested sides are ready,
+/// so `nn_poll` waits by slices to return as soon as any of them is.
+const POLL_SLICE_MS: u64 = 10;
+
+fn ffi_result(result: Result<c_int, c_int>) -> c_int {
+    match result {
+        Ok(x) => x,
+        Err(e) => {
+            errno::set(e);
+            -1
+        }
+    }
+}
+
+/// Runs the specified function with the socket identified by the handle.
+fn with_socket<T, F>(s: c_int, f: F) -> Result<T, c_int> where F : FnOnce(&SocketEntry, &mut Socket) -> Result<T, c_int> {
+    let entry = try!(get_socket(s));
+    let mut socket = lock(&entry.socket);
+
+    match *socket {
+        Some(ref mut socket) => f(&entry, socket),
+        None => Err(libc::EBADF)
+    }
+}
+
+/// Runs the non-blocking send or recv once, failing with `EFSM` when the socket is not in the exchange it needs.
+fn with_socket_once<T, F>(s: c_int, events: c_short, f: F) -> Result<T, c_int> where F : FnOnce(&mut Socket) -> io::Result<T> {
+    let recv = events == NN_POLLIN;
+
+    with_socket(s, |entry, socket| {
+        try!(entry.check_exchange(recv));
+
+        let result = try!(f(socket).map_err(error));
+
+        entry.on_transferred(socket, recv);
+
+        Ok(result)
+    })
+}
+
+/// Runs the non-blocking operation until it does not fail with `EAGAIN`, waiting for the socket to be ready
+/// in between, up to the send or recv timeout of the socket, or the end of the survey being answered.
+/// The socket is only locked during each attempt.
+fn with_socket_until_ready<T, F>(s: c_int, events: c_short, mut f: F) -> Result<T, c_int> where F : FnMut(&mut Socket) -> io::Result<T> {
+    let recv = events == NN_POLLIN;
+    let (sid, timeout, exchange_deadline) = try!(with_socket(s, |entry, socket| {
+        let exchange_deadline = try!(entry.check_exchange(recv));
+        let kind = if recv { ConfigOptionKind::RecvTimeout } else { ConfigOptionKind::SendTimeout };
+
+        match try!(socket.get_option(kind).map_err(error)) {
+            ConfigOption::RecvTimeout(x) | ConfigOption::SendTimeout(x) => Ok((entry.sid, x, exchange_deadline)),
+            _ => Err(libc::EINVAL)
+        }
+    }));
+    let deadline = match (timeout.map(|timeout| Instant::now() + timeout), exchange_deadline) {
+        (Some(x), Some(y)) => Some(if x < y { x } else { y }),
+        (x, None) => x,
+        (None, y) => y
+    };
+    let mut probe = None;
+
+    loop {
+        let attempt = try!(with_socket(s, |entry, socket| {
+            let result = f(socket);
+
+            if result.is_ok() {
+                entry.on_transferred(socket, recv);
+            }
+
+            Ok(result)
+        }));
+
+        match attempt {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {},
+            res => return res.map_err(error)
+        }
+
+        let slice = Duration::from_millis(POLL_SLICE_MS);
+        let wait = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+
+                if now >= deadline {
+                    return Err(libc::ETIMEDOUT);
+                }
+                if deadline - now < slice { deadline - now } else { slice }
+            },
+            None => slice
+        };
+
+        if probe.is_none() {
+            let poll_req = PollReq { sid: sid, recv: recv, send: recv == false };
+
+            probe = Some(try!(with_session(|session| session.create_probe(vec![poll_req]))));
+        }
+        if let Some(ref mut probe) = probe {
+            try!(probe.poll(wait).map_err(error));
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* errors                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+#[no_mangle]
+pub extern "C" fn nn_errno() -> c_int {
+    errno::get()
+}
+
+#[no_mangle]
+pub extern "C" fn nn_strerror(errnum: c_int) -> *const c_char {
+    errno::strerror(errnum)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* messages                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+#[no_mangle]
+pub extern "C" fn nn_allocmsg(size: size_t, kind: c_int) -> *mut c_void {
+    if kind != 0 {
+        errno::set(libc::EINVAL);
+        return ptr::null_mut();
+    }
+
+    let msg = unsafe { msg::alloc(size) };
+
+    if msg.is_null() {
+        errno::set(libc::ENOMEM);
+    }
+
+    msg
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_freemsg(msg: *mut c_void) -> c_int {
+    if msg.is_null() {
+        errno::set(libc::EFAULT);
+        return -1;
+    }
+
+    msg::free(msg);
+    0
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* sockets                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+#[no_mangle]
+pub extern "C" fn nn_socket(domain: c_int, protocol: c_int) -> c_int {
+    ffi_result(open_socket(domain, protocol))
+}
+
+#[no_mangle]
+pub extern "C" fn nn_close(s: c_int) -> c_int {
+    ffi_result(close_socket(s).map(|_| 0))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_bind(s: c_int, addr: *const c_char) -> c_int {
+    ffi_result(add_endpoint(s, addr, true))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_connect(s: c_int, addr: *const c_char) -> c_int {
+    ffi_result(add_endpoint(s, addr, false))
+}
+
+unsafe fn add_endpoint(s: c_int, addr: *const c_char, bind: bool) -> Result<c_int, c_int> {
+    if addr.is_null() {
+        return Err(libc::EFAULT);
+    }
+
+    let url = try!(CStr::from_ptr(addr).to_str().map_err(|_| libc::EINVAL));
+
+    with_socket(s, |entry, socket| {
+        let result = if bind { socket.bind(url) } else { socket.connect(url) };
+        let endpoint = try!(result.map_err(error));
+        let eid: usize = endpoint.id().into();
+        let eid = eid as c_int;
+
+        lock(&entry.endpoints).insert(eid, endpoint);
+
+        Ok(eid)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn nn_shutdown(s: c_int, how: c_int) -> c_int {
+    let result = get_socket(s).and_then(|entry| {
+        let endpoint = lock(&entry.endpoints).remove(&how);
+
+        match endpoint {
+            Some(endpoint) => endpoint.close().map(|_| 0).map_err(error),
+            None => Err(libc::EINVAL)
+        }
+    });
+
+    ffi_result(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_send(s: c_int, buf: *const c_void, len: size_t, flags: c_int) -> c_int {
+    let (buffer, msg) = if len == NN_MSG {
+        let msg = if buf.is_null() { ptr::null_mut() } else { *(buf as *const *mut c_void) };
+
+        if msg.is_null() {
+            return ffi_result(Err(libc::EFAULT));
+        }
+
+        (msg::to_vec(msg), msg)
+    } else if buf.is_null() && len != 0 {
+        return ffi_result(Err(libc::EFAULT));
+    } else if len == 0 {
+        (Vec::new(), ptr::null_mut())
+    } else {
+        (slice::from_raw_parts(buf as *const u8, len).to_vec(), ptr::null_mut())
+    };
+    let sent_len = buffer.len() as c_int;
+    let result = if flags & NN_DONTWAIT != 0 {
+        with_socket_once(s, NN_POLLOUT, |socket| socket.try_send(buffer))
+    } else {
+        with_socket_until_ready(s, NN_POLLOUT, |socket| socket.try_send(buffer.clone()))
+    }.map(|_| sent_len);
+
+    // like nanomsg, the message is only freed when it was sent
+    if result.is_ok() && msg.is_null() == false {
+        msg::free(msg);
+    }
+
+    ffi_result(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_recv(s: c_int, buf: *mut c_void, len: size_t, flags: c_int) -> c_int {
+    if buf.is_null() && len != 0 {
+        return ffi_result(Err(libc::EFAULT));
+    }
+
+    let result = if flags & NN_DONTWAIT != 0 {
+        with_socket_once(s, NN_POLLIN, |socket| socket.try_recv())
+    } else {
+        with_socket_until_ready(s, NN_POLLIN, |socket| socket.try_recv())
+    };
+    let buffer = match result {
+        Ok(x) => x,
+        Err(e) => return ffi_result(Err(e))
+    };
+
+    if len == NN_MSG {
+        let msg = msg::from_vec(&buffer);
+
+        if msg.is_null() {
+            return ffi_result(Err(libc::ENOMEM));
+        }
+
+        *(buf as *mut *mut c_void) = msg;
+    } else {
+        // like nanomsg, the message is truncated to the buffer but its full length is returned
+        let copy_len = if buffer.len() < len { buffer.len() } else { len };
+
+        ptr::copy_nonoverlapping(buffer.as_ptr(), buf as *mut u8, copy_len);
+    }
+
+    buffer.len() as c_int
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* options                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_setsockopt(s: c_int, level: c_int, option: c_int, optval: *const c_void, optvallen: size_t) -> c_int {
+    if optval.is_null() && optvallen != 0 {
+        return ffi_result(Err(libc::EFAULT));
+    }
+
+    let value = if is_bytes_option(level, option) {
+        OptionValue::Bytes(if optvallen == 0 { &[] } else { slice::from_raw_parts(optval as *const u8, optvallen) })
+    } else if optvallen == ::std::mem::size_of::<c_int>() {
+        OptionValue::Int(*(optval as *const c_int))
+    } else {
+        return ffi_result(Err(libc::EINVAL));
+    };
+    let result = with_socket(s, |entry, socket| {
+        if level > 0 && level != entry.protocol {
+            return Err(libc::ENOPROTOOPT);
+        }
+
+        let cfg_opt = try!(to_config_option(level, option, value));
+
+        socket.set_option(cfg_opt).map(|_| 0).map_err(error)
+    });
+
+    ffi_result(result)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_getsockopt(s: c_int, level: c_int, option: c_int, optval: *mut c_void, optvallen: *mut size_t) -> c_int {
+    if optval.is_null() || optvallen.is_null() {
+        return ffi_result(Err(libc::EFAULT));
+    }
+
+    let result = with_socket(s, |entry, socket| {
+        match (level, option) {
+            (NN_SOL_SOCKET, NN_DOMAIN) => return Ok(entry.domain),
+            (NN_SOL_SOCKET, NN_PROTOCOL) => return Ok(entry.protocol),
+            (NN_SOL_SOCKET, NN_SNDFD) => return get_send_fd(socket),
+            (NN_SOL_SOCKET, NN_RCVFD) => return get_recv_fd(socket),
+            _ => {}
+        }
+
+        if level > 0 && level != entry.protocol {
+            return Err(libc::ENOPROTOOPT);
+        }
+
+        let kind = try!(to_config_option_kind(level, option));
+        let cfg_opt = try!(socket.get_option(kind).map_err(error));
+
+        from_config_option(cfg_opt)
+    });
+    let value = match result {
+        Ok(x) => x,
+        Err(e) => return ffi_result(Err(e))
+    };
+    let value_len = ::std::mem::size_of::<c_int>();
+    let copy_len = if *optvallen < value_len { *optvallen } else { value_len };
+
+    ptr::copy_nonoverlapping(&value as *const c_int as *const u8, optval as *mut u8, copy_len);
+    *optvallen = value_len;
+
+    0
+}
+
+#[cfg(unix)]
+fn get_send_fd(socket: &mut Socket) -> Result<c_int, c_int> {
+    socket.send_fd().map_err(error)
+}
+
+#[cfg(unix)]
+fn get_recv_fd(socket: &mut Socket) -> Result<c_int, c_int> {
+    socket.recv_fd().map_err(error)
+}
+
+#[cfg(not(unix))]
+fn get_send_fd(_: &mut Socket) -> Result<c_int, c_int> {
+    Err(libc::ENOPROTOOPT)
+}
+
+#[cfg(not(unix))]
+fn get_recv_fd(_: &mut Socket) -> Result<c_int, c_int> {
+    Err(libc::ENOPROTOOPT)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* poll                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+#[no_mangle]
+pub unsafe extern "C" fn nn_poll(fds: *mut nn_pollfd, nfds: c_int, timeout: c_int) -> c_int {
+    if nfds < 0 || (fds.is_null() && nfds != 0) {
+        return ffi_result(Err(libc::EFAULT));
+    }
+
+    let fds = if nfds == 0 { &mut [] } else { slice::from_raw_parts_mut(fds, nfds as usize) };
+
+    ffi_result(poll(fds, timeout))
+}
+
+fn poll(fds: &mut [nn_pollfd], timeout: c_int) -> Result<c_int, c_int> {
+    let mut poll_reqs = Vec::with_capacity(fds.len());
+
+    for fd in fds.iter() {
+        let entry = try!(get_socket(fd.fd));
+
+        poll_reqs.push(PollReq {
+            sid: entry.sid,
+            recv: fd.events & NN_POLLIN != 0,
+            send: fd.events & NN_POLLOUT != 0
+        });
+    }
+
+    let mut probe = try!(with_session(|session| session.create_probe(poll_reqs)));
+    let deadline = if timeout < 0 {
+        None
+    } else {
+        Some(Instant::now() + Duration::from_millis(timeout as u64))
+    };
+
+    loop {
+        let slice = Duration::from_millis(POLL_SLICE_MS);
+        let wait = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+
+                if deadline > now && deadline - now < slice { deadline - now } else { slice }
+            },
+            None => slice
+        };
+        let results = try!(probe.poll(wait).map_err(error));
+        let mut ready = 0;
+
+        for (fd, res) in fds.iter_mut().zip(results.iter()) {
+            fd.revents = 0;
+            if res.recv {
+                fd.revents |= NN_POLLIN;
+            }
+            if res.send {
+                fd.revents |= NN_POLLOUT;
+            }
+            if fd.revents != 0 {
+                ready += 1;
+            }
+        }
+
+        if ready > 0 || deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Ok(ready);
+        }
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* device                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Takes the socket away from its handle, so it can be owned by a device.
+fn take_socket(s: c_int) -> Result<Socket, c_int> {
+    let entry = try!(close_socket(s));
+    let socket = lock(&entry.socket).take();
+
+    socket.ok_or(libc::EBADF)
+}
+
+/// Forwards the messages between the two sockets, or back to the same socket when `s2` is negative.
+/// Like in nanomsg, this only returns when the library is terminated or when an error occurs.
+/// The sockets are owned by the device from then on and their handles become invalid.
+#[no_mangle]
+pub extern "C" fn nn_device(s1: c_int, s2: c_int) -> c_int {
+    if let Err(e) = get_socket(s1).and_then(|_| if s2 < 0 { Ok(()) } else { get_socket(s2).map(|_| ()) }) {
+        return ffi_result(Err(e));
+    }
+
+    let device = if s2 < 0 {
+        take_socket(s1).and_then(|socket| with_session(|session| session.create_relay_device(socket)))
+    } else {
+        take_socket(s1).and_then(|left| {
+            take_socket(s2).and_then(|right| with_session(|session| session.create_bridge_device(left, right)))
+        })
+    };
+    let result = device.and_then(|device: Box<Device>| device.run().map(|_| 0).map_err(error));
+
+    match result {
+        Ok(_) => ffi_result(Err(errno::ETERM)),
+        Err(e) => ffi_result(Err(e))
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* term                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+#[no_mangle]
+pub extern "C" fn nn_term() {
+    terminate();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::mem;
+    use std::ptr;
+    use std::thread;
+    use std::time::Duration;
+
+    use libc::{self, c_int, c_void, size_t};
+
+    use super::*;
+
+    fn send(s: c_int, buffer: &[u8]) -> c_int {
+        unsafe { nn_send(s, buffer.as_ptr() as *const c_void, buffer.len(), 0) }
+    }
+
+    fn connect_pair(name: &str) -> (c_int, c_int) {
+        let url = CString::new(format!("inproc://{}", name)).unwrap();
+        let left = nn_socket(AF_SP, NN_PAIR);
+        let right = nn_socket(AF_SP, NN_PAIR);
+
+        assert!(left >= 0 && right >= 0);
+        assert!(unsafe { nn_bind(left, url.as_ptr()) } >= 0);
+        assert!(unsafe { nn_connect(right, url.as_ptr()) } >= 0);
+
+        (left, right)
+    }
+
+    fn connect_sockets(name: &str, bound: c_int, connected: c_int) -> (c_int, c_int) {
+        let url = CString::new(format!("inproc://{}", name)).unwrap();
+        let server = nn_socket(AF_SP, bound);
+        let client = nn_socket(AF_SP, connected);
+
+        assert!(server >= 0 && client >= 0);
+        assert!(unsafe { nn_bind(server, url.as_ptr()) } >= 0);
+        assert!(unsafe { nn_connect(client, url.as_ptr()) } >= 0);
+
+        (server, client)
+    }
+
+    fn recv(s: c_int, buffer: &mut [u8], flags: c_int) -> c_int {
+        unsafe { nn_recv(s, buffer.as_mut_ptr() as *mut c_void, buffer.len(), flags) }
+    }
+
+    fn try_send(s: c_int, buffer: &[u8]) -> c_int {
+        unsafe { nn_send(s, buffer.as_ptr() as *const c_void, buffer.len(), NN_DONTWAIT) }
+    }
+
+    #[test]
+    fn messages_can_be_exchanged_with_user_or_library_buffers() {
+        let (left, right) = connect_pair("capi_exchange");
+        let mut buffer = [0u8; 3];
+
+        assert_eq!(5, send(left, b"ABCDE"));
+        assert_eq!(5, unsafe { nn_recv(right, buffer.as_mut_ptr() as *mut c_void, buffer.len(), 0) });
+        assert_eq!(b"ABC", &buffer);
+
+        let mut msg = nn_allocmsg(2, 0);
+        unsafe { ptr::copy_nonoverlapping(b"OK".as_ptr(), msg as *mut u8, 2) };
+        assert_eq!(2, unsafe { nn_send(right, &mut msg as *mut *mut c_void as *const c_void, NN_MSG, 0) });
+
+        let mut received: *mut c_void = ptr::null_mut();
+        assert_eq!(2, unsafe { nn_recv(left, &mut received as *mut *mut c_void as *mut c_void, NN_MSG, 0) });
+        assert_eq!(b"OK".to_vec(), unsafe { msg::to_vec(received) });
+        assert_eq!(0, unsafe { nn_freemsg(received) });
+
+        assert_eq!(0, nn_close(left));
+        assert_eq!(0, nn_close(right));
+    }
+
+    #[test]
+    fn a_socket_can_send_while_another_thread_is_receiving() {
+        let (left, right) = connect_pair("capi_threads");
+        let receiver = thread::spawn(move || {
+            let mut buffer = [0u8; 8];
+            let len = unsafe { nn_recv(left, buffer.as_mut_ptr() as *mut c_void, buffer.len(), 0) };
+
+            buffer[..len as usize].to_vec()
+        });
+        let mut buffer = [0u8; 8];
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(4, send(left, b"PING"));
+        assert_eq!(4, unsafe { nn_recv(right, buffer.as_mut_ptr() as *mut c_void, buffer.len(), 0) });
+        assert_eq!(b"PING", &buffer[..4]);
+        assert_eq!(4, send(right, b"PONG"));
+        assert_eq!(b"PONG".to_vec(), receiver.join().unwrap());
+
+        assert_eq!(0, nn_close(left));
+        assert_eq!(0, nn_close(right));
+    }
+
+    #[test]
+    fn req_receives_only_while_a_request_is_in_progress() {
+        let (rep, req) = connect_sockets("capi_req_exchange", NN_REP, NN_REQ);
+        let mut buffer = [0u8; 8];
+
+        assert_eq!(-1, recv(req, &mut buffer, NN_DONTWAIT));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        assert_eq!(4, send(req, b"PING"));
+        assert_eq!(-1, recv(req, &mut buffer, NN_DONTWAIT));
+        assert_eq!(libc::EAGAIN, nn_errno());
+
+        assert_eq!(4, recv(rep, &mut buffer, 0));
+        assert_eq!(4, send(rep, b"PONG"));
+        assert_eq!(4, recv(req, &mut buffer, 0));
+        assert_eq!(b"PONG", &buffer[..4]);
+
+        assert_eq!(-1, recv(req, &mut buffer, 0));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        assert_eq!(0, nn_close(rep));
+        assert_eq!(0, nn_close(req));
+    }
+
+    #[test]
+    fn rep_sends_only_the_reply_to_a_received_request() {
+        let (rep, req) = connect_sockets("capi_rep_exchange", NN_REP, NN_REQ);
+        let mut buffer = [0u8; 8];
+
+        assert_eq!(-1, try_send(rep, b"PONG"));
+        assert_eq!(errno::EFSM, nn_errno());
+        assert_eq!(-1, send(rep, b"PONG"));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        assert_eq!(4, send(req, b"PING"));
+        assert_eq!(4, recv(rep, &mut buffer, 0));
+        assert_eq!(4, send(rep, b"PONG"));
+
+        assert_eq!(-1, send(rep, b"PONG"));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        assert_eq!(0, nn_close(rep));
+        assert_eq!(0, nn_close(req));
+    }
+
+    #[test]
+    fn surveyor_receives_only_until_the_survey_deadline() {
+        let (surveyor, respondent) = connect_sockets("capi_surveyor_exchange", NN_SURVEYOR, NN_RESPONDENT);
+        let deadline: c_int = 100;
+        let int_len = mem::size_of::<c_int>();
+        let mut buffer = [0u8; 8];
+
+        assert_eq!(0, unsafe { nn_setsockopt(surveyor, NN_SURVEYOR, NN_SURVEYOR_DEADLINE, &deadline as *const c_int as *const c_void, int_len) });
+        assert_eq!(-1, recv(surveyor, &mut buffer, NN_DONTWAIT));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(4, send(surveyor, b"POLL"));
+        assert_eq!(4, recv(respondent, &mut buffer, 0));
+        assert_eq!(3, send(respondent, b"YES"));
+        assert_eq!(3, recv(surveyor, &mut buffer, 0));
+
+        assert_eq!(-1, recv(surveyor, &mut buffer, 0));
+        assert_eq!(libc::ETIMEDOUT, nn_errno());
+        assert_eq!(-1, recv(surveyor, &mut buffer, NN_DONTWAIT));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        assert_eq!(0, nn_close(surveyor));
+        assert_eq!(0, nn_close(respondent));
+    }
+
+    #[test]
+    fn respondent_sends_only_the_response_to_a_received_survey() {
+        let (surveyor, respondent) = connect_sockets("capi_respondent_exchange", NN_SURVEYOR, NN_RESPONDENT);
+        let mut buffer = [0u8; 8];
+
+        assert_eq!(-1, try_send(respondent, b"YES"));
+        assert_eq!(errno::EFSM, nn_errno());
+        assert_eq!(-1, send(respondent, b"YES"));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(4, send(surveyor, b"POLL"));
+        assert_eq!(4, recv(respondent, &mut buffer, 0));
+        assert_eq!(3, send(respondent, b"YES"));
+
+        assert_eq!(-1, send(respondent, b"YES"));
+        assert_eq!(errno::EFSM, nn_errno());
+
+        assert_eq!(0, nn_close(surveyor));
+        assert_eq!(0, nn_close(respondent));
+    }
+
+    #[test]
+    fn raw_sockets_are_never_out_of_an_exchange() {
+        let req = nn_socket(AF_SP_RAW, NN_REQ);
+        let mut buffer = [0u8; 8];
+
+        assert_eq!(-1, recv(req, &mut buffer, NN_DONTWAIT));
+        assert_eq!(libc::EAGAIN, nn_errno());
+
+        assert_eq!(0, nn_close(req));
+    }
+
+    #[test]
+    fn options_can_be_set_and_read_back() {
+        let s = nn_socket(AF_SP, NN_REQ);
+        let timeout: c_int = 150;
+        let mut value: c_int = 0;
+        let mut value_len: size_t = mem::size_of::<c_int>();
+        let int_len = mem::size_of::<c_int>();
+
+        assert_eq!(0, unsafe { nn_setsockopt(s, NN_SOL_SOCKET, NN_RCVTIMEO, &timeout as *const c_int as *const c_void, int_len) });
+        assert_eq!(0, unsafe { nn_getsockopt(s, NN_SOL_SOCKET, NN_RCVTIMEO, &mut value as *mut c_int as *mut c_void, &mut value_len) });
+        assert_eq!(150, value);
+
+        assert_eq!(0, unsafe { nn_getsockopt(s, NN_SOL_SOCKET, NN_PROTOCOL, &mut value as *mut c_int as *mut c_void, &mut value_len) });
+        assert_eq!(NN_REQ, value);
+
+        assert_eq!(-1, unsafe { nn_setsockopt(s, NN_SUB, NN_SUB_SUBSCRIBE, b"x".as_ptr() as *const c_void, 1) });
+        assert_eq!(libc::ENOPROTOOPT, nn_errno());
+
+        let mut buffer = [0u8; 8];
+        assert_eq!(-1, unsafe { nn_recv(s, buffer.as_mut_ptr() as *mut c_void, buffer.len(), 0) });
+        assert_eq!(errno::EFSM, nn_errno());
+
+        assert_eq!(0, nn_close(s));
+    }
+
+    #[test]
+    fn invalid_handles_are_reported() {
+        assert_eq!(-1, send(4096, b"ABC"));
+        assert_eq!(libc::EBADF, nn_errno());
+        assert_eq!(-1, nn_close(-1));
+        assert_eq!(libc::EBADF, nn_errno());
+        assert_eq!(-1, nn_socket(AF_SP, 4242));
+        assert_eq!(libc::EINVAL, nn_errno());
+    }
+
+    #[test]
+    fn poll_tells_which_sockets_are_ready() {
+        let (left, right) = connect_pair("capi_poll");
+        let mut fds = [
+            nn_pollfd { fd: left, events: NN_POLLIN | NN_POLLOUT, revents: 0 },
+            nn_pollfd { fd: right, events: NN_POLLIN, revents: 0 }
+        ];
+
+        assert_eq!(1, unsafe { nn_poll(fds.as_mut_ptr(), 2, 500) });
+        assert_eq!(NN_POLLOUT, fds[0].revents);
+        assert_eq!(0, fds[1].revents);
+
+        assert_eq!(3, send(left, b"ABC"));
+        assert_eq!(2, unsafe { nn_poll(fds.as_mut_ptr(), 2, 500) });
+        assert_eq!(NN_POLLIN, fds[1].revents);
+
+        assert_eq!(0, nn_close(left));
+        assert_eq!(0, nn_close(right));
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module manages the messages exchanged with `NN_MSG`, allocated by the library and freed by the application.
+/// The size of a message is stored in front of the buffer given to the application,
+/// in a header large enough to keep the buffer aligned like `malloc` does.
+
+use std::ptr;
+use std::mem;
+
+use libc::{self, c_void};
+
+const HEADER_LEN: usize = 2 * mem::size_of::<usize>();
+
+pub unsafe fn alloc(size: usize) -> *mut c_void {
+    let chunk = libc::malloc(HEADER_LEN + size) as *mut u8;
+
+    if chunk.is_null() {
+        return ptr::null_mut();
+    }
+
+    *(chunk as *mut usize) = size;
+
+    chunk.offset(HEADER_LEN as isize) as *mut c_void
+}
+
+pub unsafe fn free(msg: *mut c_void) {
+    libc::free(chunk(msg) as *mut c_void);
+}
+
+pub unsafe fn size(msg: *const c_void) -> usize {
+    *(chunk(msg) as *const usize)
+}
+
+pub unsafe fn from_vec(buffer: &[u8]) -> *mut c_void {
+    let msg = alloc(buffer.len());
+
+    if msg.is_null() == false {
+        ptr::copy_nonoverlapping(buffer.as_ptr(), msg as *mut u8, buffer.len());
+    }
+
+    msg
+}
+
+pub unsafe fn to_vec(msg: *const c_void) -> Vec<u8> {
+    let len = size(msg);
+    let mut buffer = Vec::with_capacity(len);
+
+    ptr::copy_nonoverlapping(msg as *const u8, buffer.as_mut_ptr(), len);
+    buffer.set_len(len);
+    buffer
+}
+
+unsafe fn chunk(msg: *const c_void) -> *mut u8 {
+    (msg as *mut u8).offset(-(HEADER_LEN as isize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_keeps_its_size_and_content() {
+        unsafe {
+            let msg = from_vec(b"ABCDE");
+
+            assert_eq!(5, size(msg));
+            assert_eq!(b"ABCDE".to_vec(), to_vec(msg));
+            assert_eq!(0, msg as usize % HEADER_LEN);
+
+            free(msg);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module translates the nanomsg socket options to the scaproust ones.
+/// Durations are given in milliseconds, a negative value meaning infinite where nanomsg allows it.
+
+use std::time::Duration;
+use std::u64;
+
+use libc::{self, c_int};
+
+use scaproust::{ConfigOption, ConfigOptionKind};
+
+use state::{NN_SUB, NN_REQ, NN_SURVEYOR};
+
+pub const NN_SOL_SOCKET: c_int = 0;
+pub const NN_TCP: c_int = -3;
+
+pub const NN_LINGER: c_int = 1;
+pub const NN_SNDBUF: c_int = 2;
+pub const NN_RCVBUF: c_int = 3;
+pub const NN_SNDTIMEO: c_int = 4;
+pub const NN_RCVTIMEO: c_int = 5;
+pub const NN_RECONNECT_IVL: c_int = 6;
+pub const NN_RECONNECT_IVL_MAX: c_int = 7;
+pub const NN_SNDPRIO: c_int = 8;
+pub const NN_RCVPRIO: c_int = 9;
+pub const NN_SNDFD: c_int = 10;
+pub const NN_RCVFD: c_int = 11;
+pub const NN_DOMAIN: c_int = 12;
+pub const NN_PROTOCOL: c_int = 13;
+pub const NN_IPV4ONLY: c_int = 14;
+pub const NN_SOCKET_NAME: c_int = 15;
+pub const NN_RCVMAXSIZE: c_int = 16;
+
+pub const NN_SUB_SUBSCRIBE: c_int = 1;
+pub const NN_SUB_UNSUBSCRIBE: c_int = 2;
+pub const NN_REQ_RESEND_IVL: c_int = 1;
+pub const NN_SURVEYOR_DEADLINE: c_int = 1;
+pub const NN_TCP_NODELAY: c_int = 1;
+
+/// Value given to `nn_setsockopt`, either an integer or the bytes of a subscription topic.
+pub enum OptionValue<'a> {
+    Int(c_int),
+    Bytes(&'a [u8])
+}
+
+fn to_duration(millis: c_int) -> Duration {
+    Duration::from_millis(millis as u64)
+}
+
+fn to_optional_duration(millis: c_int) -> Option<Duration> {
+    if millis < 0 {
+        None
+    } else {
+        Some(to_duration(millis))
+    }
+}
+
+fn from_duration(duration: Duration) -> c_int {
+    let millis = duration.as_secs() * 1000 + u64::from(duration.subsec_nanos() / 1_000_000);
+
+    if millis > c_int::max_value() as u64 {
+        c_int::max_value()
+    } else {
+        millis as c_int
+    }
+}
+
+fn from_optional_duration(duration: Option<Duration>) -> c_int {
+    duration.map_or(-1, from_duration)
+}
+
+fn to_priority(value: c_int) -> Result<u8, c_int> {
+    if value >= 1 && value <= 16 {
+        Ok(value as u8)
+    } else {
+        Err(libc::EINVAL)
+    }
+}
+
+fn int(value: OptionValue) -> Result<c_int, c_int> {
+    match value {
+        OptionValue::Int(x) => Ok(x),
+        OptionValue::Bytes(_) => Err(libc::EINVAL)
+    }
+}
+
+fn non_negative(value: OptionValue) -> Result<c_int, c_int> {
+    int(value).and_then(|x| if x >= 0 { Ok(x) } else { Err(libc::EINVAL) })
+}
+
+/// Tells whether the value of the option is a byte string rather than an integer.
+pub fn is_bytes_option(level: c_int, option: c_int) -> bool {
+    level == NN_SUB && (option == NN_SUB_SUBSCRIBE || option == NN_SUB_UNSUBSCRIBE)
+}
+
+pub fn to_config_option(level: c_int, option: c_int, value: OptionValue) -> Result<ConfigOption, c_int> {
+    let cfg_opt = match (level, option) {
+        (NN_SOL_SOCKET, NN_LINGER)            => ConfigOption::Linger(to_duration(try!(non_negative(value)))),
+        (NN_SOL_SOCKET, NN_SNDTIMEO)          => ConfigOption::SendTimeout(to_optional_duration(try!(int(value)))),
+        (NN_SOL_SOCKET, NN_RCVTIMEO)          => ConfigOption::RecvTimeout(to_optional_duration(try!(int(value)))),
+        (NN_SOL_SOCKET, NN_RECONNECT_IVL)     => ConfigOption::RetryIvl(to_duration(try!(non_negative(value)))),
+        (NN_SOL_SOCKET, NN_RECONNECT_IVL_MAX) => {
+            match try!(non_negative(value)) {
+                0 => ConfigOption::RetryIvlMax(None),
+                x => ConfigOption::RetryIvlMax(Some(to_duration(x)))
+            }
+        },
+        (NN_SOL_SOCKET, NN_SNDPRIO)           => ConfigOption::SendPriority(try!(to_priority(try!(int(value))))),
+        (NN_SOL_SOCKET, NN_RCVPRIO)           => ConfigOption::RecvPriority(try!(to_priority(try!(int(value))))),
+        (NN_SOL_SOCKET, NN_RCVMAXSIZE)        => {
+            match try!(int(value)) {
+                -1 => ConfigOption::RecvMaxSize(u64::MAX),
+                x if x >= 0 => ConfigOption::RecvMaxSize(x as u64),
+                _ => return Err(libc::EINVAL)
+            }
+        },
+        (NN_TCP, NN_TCP_NODELAY)              => ConfigOption::TcpNoDelay(try!(int(value)) != 0),
+        (NN_SUB, NN_SUB_SUBSCRIBE)            => {
+            match value {
+                OptionValue::Bytes(topic) => ConfigOption::SubscribeTopic(topic.to_vec()),
+                OptionValue::Int(_) => return Err(libc::EINVAL)
+            }
+        },
+        (NN_SUB, NN_SUB_UNSUBSCRIBE)          => {
+            match value {
+                OptionValue::Bytes(topic) => ConfigOption::UnsubscribeTopic(topic.to_vec()),
+                OptionValue::Int(_) => return Err(libc::EINVAL)
+            }
+        },
+        (NN_REQ, NN_REQ_RESEND_IVL)           => ConfigOption::ReqResendIvl(to_duration(try!(non_negative(value)))),
+        (NN_SURVEYOR, NN_SURVEYOR_DEADLINE)   => ConfigOption::SurveyDeadline(to_duration(try!(non_negative(value)))),
+        _ => return Err(libc::ENOPROTOOPT)
+    };
+
+    Ok(cfg_opt)
+}
+
+pub fn to_config_option_kind(level: c_int, option: c_int) -> Result<ConfigOptionKind, c_int> {
+    match (level, option) {
+        (NN_SOL_SOCKET, NN_LINGER)            => Ok(ConfigOptionKind::Linger),
+        (NN_SOL_SOCKET, NN_SNDTIMEO)          => Ok(ConfigOptionKind::SendTimeout),
+        (NN_SOL_SOCKET, NN_RCVTIMEO)          => Ok(ConfigOptionKind::RecvTimeout),
+        (NN_SOL_SOCKET, NN_RECONNECT_IVL)     => Ok(ConfigOptionKind::RetryIvl),
+        (NN_SOL_SOCKET, NN_RECONNECT_IVL_MAX) => Ok(ConfigOptionKind::RetryIvlMax),
+        (NN_SOL_SOCKET, NN_SNDPRIO)           => Ok(ConfigOptionKind::SendPriority),
+        (NN_SOL_SOCKET, NN_RCVPRIO)           => Ok(ConfigOptionKind::RecvPriority),
+        (NN_SOL_SOCKET, NN_RCVMAXSIZE)        => Ok(ConfigOptionKind::RecvMaxSize),
+        (NN_TCP, NN_TCP_NODELAY)              => Ok(ConfigOptionKind::TcpNoDelay),
+        (NN_REQ, NN_REQ_RESEND_IVL)           => Ok(ConfigOptionKind::ReqResendIvl),
+        (NN_SURVEYOR, NN_SURVEYOR_DEADLINE)   => Ok(ConfigOptionKind::SurveyDeadline),
+        _ => Err(libc::ENOPROTOOPT)
+    }
+}
+
+pub fn from_config_option(cfg_opt: ConfigOption) -> Result<c_int, c_int> {
+    match cfg_opt {
+        ConfigOption::Linger(x)         => Ok(from_duration(x)),
+        ConfigOption::SendTimeout(x)    => Ok(from_optional_duration(x)),
+        ConfigOption::RecvTimeout(x)    => Ok(from_optional_duration(x)),
+        ConfigOption::RetryIvl(x)       => Ok(from_duration(x)),
+        ConfigOption::RetryIvlMax(x)    => Ok(x.map_or(0, from_duration)),
+        ConfigOption::SendPriority(x)   => Ok(c_int::from(x)),
+        ConfigOption::RecvPriority(x)   => Ok(c_int::from(x)),
+        ConfigOption::RecvMaxSize(x)    => Ok(if x > c_int::max_value() as u64 { -1 } else { x as c_int }),
+        ConfigOption::TcpNoDelay(x)     => Ok(if x { 1 } else { 0 }),
+        ConfigOption::ReqResendIvl(x)   => Ok(from_duration(x)),
+        ConfigOption::SurveyDeadline(x) => Ok(from_duration(x)),
+        _ => Err(libc::ENOPROTOOPT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use libc;
+
+    use scaproust::{ConfigOption, ConfigOptionKind};
+
+    use super::*;
+    use state::NN_SUB;
+
+    #[test]
+    fn negative_timeouts_are_infinite() {
+        let infinite = to_config_option(NN_SOL_SOCKET, NN_RCVTIMEO, OptionValue::Int(-1)).unwrap();
+        let finite = to_config_option(NN_SOL_SOCKET, NN_RCVTIMEO, OptionValue::Int(250)).unwrap();
+
+        assert_eq!(ConfigOption::RecvTimeout(None), infinite);
+        assert_eq!(ConfigOption::RecvTimeout(Some(Duration::from_millis(250))), finite);
+        assert_eq!(Ok(-1), from_config_option(infinite));
+        assert_eq!(Ok(250), from_config_option(finite));
+    }
+
+    #[test]
+    fn subscriptions_are_byte_strings() {
+        let cfg_opt = to_config_option(NN_SUB, NN_SUB_SUBSCRIBE, OptionValue::Bytes(&[0, 255])).unwrap();
+
+        assert!(is_bytes_option(NN_SUB, NN_SUB_SUBSCRIBE));
+        assert_eq!(ConfigOption::SubscribeTopic(vec![0, 255]), cfg_opt);
+    }
+
+    #[test]
+    fn unknown_options_are_reported() {
+        assert_eq!(Err(libc::ENOPROTOOPT), to_config_option(NN_SOL_SOCKET, NN_SNDBUF, OptionValue::Int(1024)));
+        assert_eq!(Err(libc::ENOPROTOOPT), to_config_option_kind(NN_SOL_SOCKET, NN_IPV4ONLY));
+        assert_eq!(Ok(ConfigOptionKind::Linger), to_config_option_kind(NN_SOL_SOCKET, NN_LINGER));
+    }
+
+    #[test]
+    fn priorities_are_checked() {
+        assert_eq!(Err(libc::EINVAL), to_config_option(NN_SOL_SOCKET, NN_SNDPRIO, OptionValue::Int(0)));
+        assert_eq!(Err(libc::EINVAL), to_config_option(NN_SOL_SOCKET, NN_SNDPRIO, OptionValue::Int(17)));
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module holds the session shared by the whole library and the table mapping integer handles to sockets.
+/// The session is created along with the first socket, and dropped by `nn_term`.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use libc::{self, c_int};
+
+use scaproust::*;
+use scaproust::core::SocketId;
+
+use errno;
+
+/// Same limit as nanomsg, handles are reused so this is the number of sockets open at the same time.
+pub const MAX_SOCKETS: usize = 512;
+
+pub const AF_SP: c_int = 1;
+pub const AF_SP_RAW: c_int = 2;
+
+pub const NN_PAIR: c_int = 16;
+pub const NN_PUB: c_int = 32;
+pub const NN_SUB: c_int = 33;
+pub const NN_REQ: c_int = 48;
+pub const NN_REP: c_int = 49;
+pub const NN_PUSH: c_int = 80;
+pub const NN_PULL: c_int = 81;
+pub const NN_SURVEYOR: c_int = 98;
+pub const NN_RESPONDENT: c_int = 99;
+pub const NN_BUS: c_int = 112;
+
+/// A socket and what nanomsg remembers about it.
+/// The socket is taken away when it is given to a device.
+pub struct SocketEntry {
+    pub domain: c_int,
+    pub protocol: c_int,
+    pub sid: SocketId,
+    pub socket: Mutex<Option<Socket>>,
+    pub endpoints: Mutex<HashMap<c_int, Endpoint>>,
+    pub exchange: Mutex<Exchange>
+}
+
+/// Where a socket of the request/reply or survey protocols stands.
+/// Like nanomsg, receiving a reply or a response, or sending a reply, fails with `EFSM` out of an exchange,
+/// so the socket does not wait for a message that cannot come.
+pub enum Exchange {
+    Idle,
+    /// A request lasts until its reply is received, a survey until its deadline.
+    Active(Option<Instant>)
+}
+
+impl SocketEntry {
+    /// Tells whether the operation is only valid in the middle of an exchange,
+    /// raw sockets have no notion of exchange and just forward the messages.
+    fn needs_exchange(&self, recv: bool) -> bool {
+        self.domain == AF_SP && match self.protocol {
+            NN_REQ | NN_SURVEYOR => recv,
+            NN_REP | NN_RESPONDENT => recv == false,
+            _ => false
+        }
+    }
+
+    /// Fails with `EFSM` when the operation needs an exchange and the socket is not in one,
+    /// otherwise gets the time after which the operation can no longer succeed.
+    pub fn check_exchange(&self, recv: bool) -> Result<Option<Instant>, c_int> {
+        if self.needs_exchange(recv) == false {
+            return Ok(None);
+        }
+
+        let mut exchange = lock(&self.exchange);
+        let deadline = match *exchange {
+            Exchange::Active(deadline) => deadline,
+            Exchange::Idle => return Err(errno::EFSM)
+        };
+
+        match deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                *exchange = Exchange::Idle;
+                Err(errno::EFSM)
+            },
+            _ => Ok(deadline)
+        }
+    }
+
+    /// Tracks the exchange once a message was sent or received.
+    pub fn on_transferred(&self, socket: &Socket, recv: bool) {
+        if self.domain != AF_SP {
+            return;
+        }
+
+        let mut exchange = lock(&self.exchange);
+
+        match (self.protocol, recv) {
+            (NN_REQ, false) => *exchange = Exchange::Active(None),
+            (NN_REQ, true) => *exchange = Exchange::Idle,
+            (NN_SURVEYOR, false) => {
+                let deadline = match socket.get_option(ConfigOptionKind::SurveyDeadline) {
+                    Ok(ConfigOption::SurveyDeadline(x)) => Some(Instant::now() + x),
+                    _ => None
+                };
+
+                *exchange = Exchange::Active(deadline);
+            },
+            (NN_REP, true) | (NN_RESPONDENT, true) => *exchange = Exchange::Active(None),
+            (NN_REP, false) | (NN_RESPONDENT, false) => *exchange = Exchange::Idle,
+            _ => {}
+        }
+    }
+}
+
+struct State {
+    session: Option<Session>,
+    sockets: Vec<Option<Arc<SocketEntry>>>
+}
+
+lazy_static! {
+    static ref STATE: Mutex<State> = Mutex::new(State {
+        session: None,
+        sockets: Vec::new()
+    });
+}
+
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+fn lock_state() -> MutexGuard<'static, State> {
+    match STATE.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner()
+    }
+}
+
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner()
+    }
+}
+
+pub fn is_terminated() -> bool {
+    TERMINATED.load(Ordering::SeqCst)
+}
+
+/// Converts an error of scaproust, an operation failing because the session is gone is reported as `ETERM`.
+pub fn error(err: io::Error) -> c_int {
+    if is_terminated() {
+        errno::ETERM
+    } else {
+        errno::from_io_error(&err)
+    }
+}
+
+fn create_session() -> io::Result<Session> {
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("ipc", Ipc).
+        with("inproc", Inproc).
+        with("ws", Ws).
+        build()
+}
+
+fn create_socket(session: &mut Session, domain: c_int, protocol: c_int) -> Result<Socket, c_int> {
+    let raw = match domain {
+        AF_SP => false,
+        AF_SP_RAW => true,
+        _ => return Err(libc::EAFNOSUPPORT)
+    };
+    let result = match (protocol, raw) {
+        (NN_PAIR, false)       => session.create_socket::<Pair>(),
+        (NN_PAIR, true)        => session.create_raw_socket::<Pair>(),
+        (NN_PUB, false)        => session.create_socket::<Pub>(),
+        (NN_PUB, true)         => session.create_raw_socket::<Pub>(),
+        (NN_SUB, false)        => session.create_socket::<Sub>(),
+        (NN_SUB, true)         => session.create_raw_socket::<Sub>(),
+        (NN_REQ, false)        => session.create_socket::<Req>(),
+        (NN_REQ, true)         => session.create_raw_socket::<Req>(),
+        (NN_REP, false)        => session.create_socket::<Rep>(),
+        (NN_REP, true)         => session.create_raw_socket::<Rep>(),
+        (NN_PUSH, false)       => session.create_socket::<Push>(),
+        (NN_PUSH, true)        => session.create_raw_socket::<Push>(),
+        (NN_PULL, false)       => session.create_socket::<Pull>(),
+        (NN_PULL, true)        => session.create_raw_socket::<Pull>(),
+        (NN_SURVEYOR, false)   => session.create_socket::<Surveyor>(),
+        (NN_SURVEYOR, true)    => session.create_raw_socket::<Surveyor>(),
+        (NN_RESPONDENT, false) => session.create_socket::<Respondent>(),
+        (NN_RESPONDENT, true)  => session.create_raw_socket::<Respondent>(),
+        (NN_BUS, false)        => session.create_socket::<Bus>(),
+        (NN_BUS, true)         => session.create_raw_socket::<Bus>(),
+        _ => return Err(libc::EINVAL)
+    };
+
+    result.map_err(error)
+}
+
+pub fn open_socket(domain: c_int, protocol: c_int) -> Result<c_int, c_int> {
+    if is_terminated() {
+        return Err(errno::ETERM);
+    }
+
+    let mut state = lock_state();
+    let handle = match state.sockets.iter().position(|entry| entry.is_none()) {
+        Some(x) => x,
+        None if state.sockets.len() < MAX_SOCKETS => state.sockets.len(),
+        None => return Err(libc::EMFILE)
+    };
+
+    if state.session.is_none() {
+        state.session = Some(try!(create_session().map_err(error)));
+    }
+
+    let socket = match state.session {
+        Some(ref mut session) => try!(create_socket(session, domain, protocol)),
+        None => return Err(errno::ETERM)
+    };
+    let entry = SocketEntry {
+        domain: domain,
+        protocol: protocol,
+        sid: socket.create_poll_req(false, false).sid,
+        socket: Mutex::new(Some(socket)),
+        endpoints: Mutex::new(HashMap::new()),
+        exchange: Mutex::new(Exchange::Idle)
+    };
+
+    if handle == state.sockets.len() {
+        state.sockets.push(Some(Arc::new(entry)));
+    } else {
+        state.sockets[handle] = Some(Arc::new(entry));
+    }
+
+    Ok(handle as c_int)
+}
+
+pub fn get_socket(handle: c_int) -> Result<Arc<SocketEntry>, c_int> {
+    if is_terminated() {
+        return Err(errno::ETERM);
+    }
+
+    let state = lock_state();
+
+    match state.sockets.get(handle as usize) {
+        Some(&Some(ref entry)) if handle >= 0 => Ok(entry.clone()),
+        _ => Err(libc::EBADF)
+    }
+}
+
+/// Removes the socket from the table, it is closed as soon as the operations in progress on it are completed.
+pub fn close_socket(handle: c_int) -> Result<Arc<SocketEntry>, c_int> {
+    if is_terminated() {
+        return Err(errno::ETERM);
+    }
+
+    let mut state = lock_state();
+
+    match state.sockets.get_mut(handle as usize) {
+        Some(slot) if handle >= 0 && slot.is_some() => Ok(slot.take().unwrap()),
+        _ => Err(libc::EBADF)
+    }
+}
+
+/// Runs the specified function with the session, to create probes and devices.
+pub fn with_session<T, F>(f: F) -> Result<T, c_int> where F : FnOnce(&mut Session) -> io::Result<T> {
+    let mut state = lock_state();
+
+    match state.session {
+        Some(ref mut session) => f(session).map_err(error),
+        None => Err(errno::ETERM)
+    }
+}
+
+/// Closes all the sockets and drops the session, making the blocking operations and devices fail with `ETERM`.
+pub fn terminate() {
+    TERMINATED.store(true, Ordering::SeqCst);
+
+    let (sockets, session) = {
+        let mut state = lock_state();
+        let sockets: Vec<Option<Arc<SocketEntry>>> = state.sockets.drain(..).collect();
+
+        (sockets, state.session.take())
+    };
+
+    drop(sockets);
+    drop(session);
+}
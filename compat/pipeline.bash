@@ -10,7 +10,7 @@ function testcase_pipeline1 {
     URL=$1
     msg="asterohache"
     $EXAMPLE_PATH/pipeline node0 $URL > /tmp/pipeline_tc_1.log & node0=$!
-    nanocat --push --connect $URL --data "$msg" > /dev/null & ncat=$!
+    $NANOCAT --push --connect $URL --data "$msg" > /dev/null & ncat=$!
     sleep 0.3 && kill $ncat $node0
     result=`cat /tmp/pipeline_tc_1.log`
     expected=`cat $COMPAT_PATH/pipeline_tc_1_expected.log`
@@ -27,7 +27,7 @@ function testcase_pipeline1 {
 function testcase_pipeline2 {
     URL=$1
     msg="cornofulgur"
-    nanocat --pull --bind $URL --ascii > /tmp/pipeline_tc_2.log & ncat=$!
+    $NANOCAT --pull --bind $URL --ascii > /tmp/pipeline_tc_2.log & ncat=$!
     ./target/debug/examples/pipeline node1 $URL "$msg" > /dev/null & node1=$!
     sleep 0.3 && kill $ncat $node1
     result=`cat /tmp/pipeline_tc_2.log`
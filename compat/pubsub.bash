@@ -9,8 +9,8 @@ source "$COMPAT_PATH/test_helper.bash"
 function testcase_pubsub1 {
     URL=$1
     $EXAMPLE_PATH/pubsub server $URL > /tmp/pubsub_tc1_server.log & server=$!
-    nanocat --sub --connect $URL --ascii > /tmp/pubsub_tc1_nanocat1.log & ncat1=$!
-    nanocat --sub --connect $URL --ascii > /tmp/pubsub_tc1_nanocat2.log & ncat2=$!
+    $NANOCAT --sub --connect $URL --ascii > /tmp/pubsub_tc1_nanocat1.log & ncat1=$!
+    $NANOCAT --sub --connect $URL --ascii > /tmp/pubsub_tc1_nanocat2.log & ncat2=$!
     sleep 3.5 && kill $ncat1 $ncat2 $server
     result_server=`cat /tmp/pubsub_tc1_server.log`
     expected_server=`cat $COMPAT_PATH/pubsub_tc1_server_expected.log`
@@ -31,7 +31,7 @@ function testcase_pubsub1 {
 # Arguments : URL
 function testcase_pubsub2 {
     URL=$1
-    nanocat --pub --bind $URL --data "retrolaser" -d 1 -i 1 > /dev/null & ncat=$!
+    $NANOCAT --pub --bind $URL --data "retrolaser" -d 1 -i 1 > /dev/null & ncat=$!
     $EXAMPLE_PATH/pubsub client $URL "raoul" > /tmp/pubsub_tc2_client1.log & client1=$!
     $EXAMPLE_PATH/pubsub client $URL "simone" > /tmp/pubsub_tc2_client2.log & client2=$!
     sleep 3.5 && kill $client1 $client2 $ncat
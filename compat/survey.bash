@@ -8,8 +8,8 @@ source "$COMPAT_PATH/test_helper.bash"
 # Arguments : URL
 function testcase_survey1 {
     URL=$1
-    nanocat --resp --connect $URL --data "mammouth" --ascii -i 2 > /tmp/survey_tc1_nanocat1.log & ncat1=$!
-    nanocat --resp --connect $URL --data "mammouth" --ascii -i 2 > /tmp/survey_tc1_nanocat2.log & ncat2=$!
+    $NANOCAT --resp --connect $URL --data "mammouth" --ascii -i 2 > /tmp/survey_tc1_nanocat1.log & ncat1=$!
+    $NANOCAT --resp --connect $URL --data "mammouth" --ascii -i 2 > /tmp/survey_tc1_nanocat2.log & ncat2=$!
     $EXAMPLE_PATH/survey server $URL > /tmp/survey_tc1_server.log & server=$!
     sleep 1.5 && kill $ncat1 $ncat2 $server
     result_server=`cat /tmp/survey_tc1_server.log`
@@ -28,7 +28,7 @@ function testcase_survey1 {
 # Arguments : URL
 function testcase_survey2 {
     URL=$1
-    nanocat --surv --bind $URL --data "clavicogyre" --ascii -i 2 -d 1 > /tmp/survey_tc2_nanocat.log & ncat=$!
+    $NANOCAT --surv --bind $URL --data "clavicogyre" --ascii -i 2 -d 1 > /tmp/survey_tc2_nanocat.log & ncat=$!
     $EXAMPLE_PATH/survey client $URL "mammouth" > /tmp/survey_tc2_client1.log & client1=$!
     $EXAMPLE_PATH/survey client $URL "mammouth" > /tmp/survey_tc2_client2.log & client2=$!
     sleep 1.5 && kill $ncat $client1 $client2
@@ -1,6 +1,7 @@
 #!/bin/bash
 
 EXAMPLE_PATH="$( cd "$COMPAT_PATH/../target/debug/examples" ; pwd -P )"
+NANOCAT="${NANOCAT:-nanocat}"
 
 NO_COLOR='\033[0m' 
 RED_COLOR='\033[0;31m'
@@ -0,0 +1,718 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+//
+// Command line tool sending and receiving messages, with the options of the nanomsg nanocat.
+// Long options can be abbreviated as long as they are not ambiguous, `--resp` for `--respondent` for example.
+
+#![cfg_attr(feature = "cargo-clippy", allow(bool_comparison))]
+
+extern crate scaproust;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use scaproust::*;
+
+const USAGE: &'static str = "Usage: nanocat SOCKET_TYPE ENDPOINT... [OPTIONS]
+
+Socket types:
+    --push, --pull, --pub, --sub, --req, --rep,
+    --surveyor, --respondent, --bus, --pair
+
+Endpoints, can be repeated:
+    --bind, -b URL            bind the socket to the url
+    --connect, -c URL         connect the socket to the url
+    --bind-ipc, -X PATH       bind the socket to ipc://PATH
+    --connect-ipc, -x PATH    connect the socket to ipc://PATH
+    --bind-local, -L PORT     bind the socket to tcp://127.0.0.1:PORT
+    --connect-local, -l PORT  connect the socket to tcp://127.0.0.1:PORT
+
+Options:
+    --recv-timeout SEC        stop receiving after SEC seconds without messages
+    --send-timeout SEC        give up sending a message after SEC seconds
+    --subscribe PREFIX        subscribe to PREFIX, can be repeated, defaults to all messages
+    --data, -D DATA           send DATA to the socket
+    --file, -F PATH           send the content of the file at PATH to the socket
+    --interval, -i SEC        send the data again every SEC seconds
+    --delay, -d SEC           wait SEC seconds before sending the data
+    --format FORMAT           print the received messages as raw, ascii, quoted, msgpack or hex
+    --raw                     same as --format raw
+    --ascii, -A               same as --format ascii
+    --quoted, -Q              same as --format quoted
+    --msgpack                 same as --format msgpack
+    --hex                     same as --format hex
+    --help, -h                print this help
+";
+
+/*****************************************************************************/
+/*                                                                           */
+/* Options                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SocketType {
+    Push,
+    Pull,
+    Pub,
+    Sub,
+    Req,
+    Rep,
+    Surveyor,
+    Respondent,
+    Bus,
+    Pair
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Format {
+    NoEcho,
+    Raw,
+    Ascii,
+    Quoted,
+    Msgpack,
+    Hex
+}
+
+#[derive(Debug, PartialEq)]
+enum Endpoint {
+    Bind(String),
+    Connect(String)
+}
+
+#[derive(Debug)]
+struct Options {
+    socket_type: Option<SocketType>,
+    endpoints: Vec<Endpoint>,
+    recv_timeout: Option<Duration>,
+    send_timeout: Option<Duration>,
+    subscriptions: Vec<String>,
+    data: Option<Vec<u8>>,
+    interval: Option<Duration>,
+    delay: Option<Duration>,
+    format: Format,
+    help: bool
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            socket_type: None,
+            endpoints: Vec::new(),
+            recv_timeout: None,
+            send_timeout: None,
+            subscriptions: Vec::new(),
+            data: None,
+            interval: None,
+            delay: None,
+            format: Format::NoEcho,
+            help: false
+        }
+    }
+}
+
+const LONG_OPTIONS: &'static [&'static str] = &[
+    "push", "pull", "pub", "sub", "req", "rep", "surveyor", "respondent", "bus", "pair",
+    "bind", "connect", "bind-ipc", "connect-ipc", "bind-local", "connect-local",
+    "recv-timeout", "send-timeout", "subscribe", "data", "file", "interval", "delay",
+    "format", "raw", "ascii", "quoted", "msgpack", "hex", "help"
+];
+
+fn short_option(flag: char) -> Option<&'static str> {
+    match flag {
+        'b' => Some("bind"),
+        'c' => Some("connect"),
+        'X' => Some("bind-ipc"),
+        'x' => Some("connect-ipc"),
+        'L' => Some("bind-local"),
+        'l' => Some("connect-local"),
+        'D' => Some("data"),
+        'F' => Some("file"),
+        'i' => Some("interval"),
+        'd' => Some("delay"),
+        'A' => Some("ascii"),
+        'Q' => Some("quoted"),
+        'h' => Some("help"),
+        _ => None
+    }
+}
+
+/// Finds the long option named or unambiguously abbreviated by `name`.
+fn long_option(name: &str) -> Result<&'static str, String> {
+    if let Some(option) = LONG_OPTIONS.iter().find(|option| **option == name) {
+        return Ok(option);
+    }
+
+    let candidates: Vec<&'static str> = LONG_OPTIONS.iter().cloned().filter(|option| option.starts_with(name)).collect();
+
+    match candidates.len() {
+        1 => Ok(candidates[0]),
+        0 => Err(format!("unknown option --{}", name)),
+        _ => Err(format!("ambiguous option --{}, could be --{}", name, candidates.join(", --")))
+    }
+}
+
+fn takes_value(option: &str) -> bool {
+    match option {
+        "bind" | "connect" | "bind-ipc" | "connect-ipc" | "bind-local" | "connect-local" |
+        "recv-timeout" | "send-timeout" | "subscribe" | "data" | "file" | "interval" | "delay" | "format" => true,
+        _ => false
+    }
+}
+
+fn parse_args<I>(args: I) -> Result<Options, String> where I : IntoIterator<Item=String> {
+    let mut options = Options::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let (option, inline_value) = if arg.starts_with("--") {
+            match arg[2..].find('=') {
+                Some(index) => (try!(long_option(&arg[2..index + 2])), Some(String::from(&arg[index + 3..]))),
+                None => (try!(long_option(&arg[2..])), None)
+            }
+        } else if arg.starts_with('-') && arg.len() == 2 {
+            match arg[1..].chars().next().and_then(short_option) {
+                Some(option) => (option, None),
+                None => return Err(format!("unknown option {}", arg))
+            }
+        } else {
+            return Err(format!("unexpected argument {}", arg));
+        };
+
+        let value = if takes_value(option) {
+            match inline_value.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(format!("option --{} requires a value", option))
+            }
+        } else if inline_value.is_some() {
+            return Err(format!("option --{} does not take a value", option));
+        } else {
+            String::new()
+        };
+
+        try!(apply_option(&mut options, option, value));
+    }
+
+    Ok(options)
+}
+
+fn apply_option(options: &mut Options, option: &str, value: String) -> Result<(), String> {
+    match option {
+        "push"          => return set_socket_type(options, SocketType::Push),
+        "pull"          => return set_socket_type(options, SocketType::Pull),
+        "pub"           => return set_socket_type(options, SocketType::Pub),
+        "sub"           => return set_socket_type(options, SocketType::Sub),
+        "req"           => return set_socket_type(options, SocketType::Req),
+        "rep"           => return set_socket_type(options, SocketType::Rep),
+        "surveyor"      => return set_socket_type(options, SocketType::Surveyor),
+        "respondent"    => return set_socket_type(options, SocketType::Respondent),
+        "bus"           => return set_socket_type(options, SocketType::Bus),
+        "pair"          => return set_socket_type(options, SocketType::Pair),
+        "bind"          => options.endpoints.push(Endpoint::Bind(value)),
+        "connect"       => options.endpoints.push(Endpoint::Connect(value)),
+        "bind-ipc"      => options.endpoints.push(Endpoint::Bind(format!("ipc://{}", value))),
+        "connect-ipc"   => options.endpoints.push(Endpoint::Connect(format!("ipc://{}", value))),
+        "bind-local"    => options.endpoints.push(Endpoint::Bind(format!("tcp://127.0.0.1:{}", try!(parse_port(&value))))),
+        "connect-local" => options.endpoints.push(Endpoint::Connect(format!("tcp://127.0.0.1:{}", try!(parse_port(&value))))),
+        "recv-timeout"  => options.recv_timeout = Some(try!(parse_seconds(&value))),
+        "send-timeout"  => options.send_timeout = Some(try!(parse_seconds(&value))),
+        "subscribe"     => options.subscriptions.push(value),
+        "data"          => return set_data(options, value.into_bytes()),
+        "file"          => return read_file(&value).and_then(|data| set_data(options, data)),
+        "interval"      => options.interval = Some(try!(parse_seconds(&value))),
+        "delay"         => options.delay = Some(try!(parse_seconds(&value))),
+        "format"        => return parse_format(&value).and_then(|format| set_format(options, format)),
+        "raw"           => return set_format(options, Format::Raw),
+        "ascii"         => return set_format(options, Format::Ascii),
+        "quoted"        => return set_format(options, Format::Quoted),
+        "msgpack"       => return set_format(options, Format::Msgpack),
+        "hex"           => return set_format(options, Format::Hex),
+        "help"          => options.help = true,
+        _ => return Err(format!("unknown option --{}", option))
+    }
+
+    Ok(())
+}
+
+fn set_socket_type(options: &mut Options, socket_type: SocketType) -> Result<(), String> {
+    if options.socket_type.is_some() {
+        return Err(String::from("only one socket type can be specified"));
+    }
+
+    options.socket_type = Some(socket_type);
+    Ok(())
+}
+
+fn set_data(options: &mut Options, data: Vec<u8>) -> Result<(), String> {
+    if options.data.is_some() {
+        return Err(String::from("only one of --data and --file can be specified"));
+    }
+
+    options.data = Some(data);
+    Ok(())
+}
+
+fn set_format(options: &mut Options, format: Format) -> Result<(), String> {
+    if options.format != Format::NoEcho {
+        return Err(String::from("only one format can be specified"));
+    }
+
+    options.format = format;
+    Ok(())
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "raw"     => Ok(Format::Raw),
+        "ascii"   => Ok(Format::Ascii),
+        "quoted"  => Ok(Format::Quoted),
+        "msgpack" => Ok(Format::Msgpack),
+        "hex"     => Ok(Format::Hex),
+        _ => Err(format!("unknown format {}", value))
+    }
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    match f64::from_str(value) {
+        Ok(secs) if secs >= 0f64 => {
+            let millis = (secs * 1000f64).round() as u64;
+
+            Ok(Duration::from_millis(millis))
+        },
+        _ => Err(format!("invalid number of seconds {}", value))
+    }
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+    u16::from_str(value).map_err(|_| format!("invalid port {}", value))
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+
+    File::open(path).
+        and_then(|mut file| file.read_to_end(&mut data)).
+        map(|_| data).
+        map_err(|e| format!("failed to read {}: {}", path, e))
+}
+
+fn check_options(options: &Options) -> Result<SocketType, String> {
+    let socket_type = match options.socket_type {
+        Some(x) => x,
+        None => return Err(String::from("a socket type is required"))
+    };
+
+    if options.endpoints.is_empty() {
+        return Err(String::from("at least one --bind or --connect is required"));
+    }
+
+    if options.subscriptions.is_empty() == false && socket_type != SocketType::Sub {
+        return Err(String::from("--subscribe is only valid for --sub sockets"));
+    }
+
+    match (socket_type, options.data.is_some()) {
+        (SocketType::Pull, true) |
+        (SocketType::Sub, true) => Err(String::from("--pull and --sub sockets can not send data")),
+        (SocketType::Push, false) |
+        (SocketType::Pub, false) |
+        (SocketType::Req, false) |
+        (SocketType::Surveyor, false) => Err(String::from("--data or --file is required")),
+        _ => Ok(socket_type)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Output                                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+const HEX_DIGITS: &'static [u8] = b"0123456789ABCDEF";
+
+fn is_printable(byte: u8) -> bool {
+    byte >= 32 && byte < 127
+}
+
+fn push_hex(output: &mut Vec<u8>, byte: u8) {
+    output.push(b'\\');
+    output.push(b'x');
+    output.push(HEX_DIGITS[(byte >> 4) as usize]);
+    output.push(HEX_DIGITS[(byte & 15) as usize]);
+}
+
+fn format_message(format: Format, msg: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(msg.len() + 8);
+
+    match format {
+        Format::NoEcho => {},
+        Format::Raw => output.extend_from_slice(msg),
+        Format::Ascii => {
+            output.extend(msg.iter().map(|&byte| if is_printable(byte) { byte } else { b'.' }));
+            output.push(b'\n');
+        },
+        Format::Quoted => {
+            output.push(b'"');
+            for &byte in msg {
+                if byte == b'"' || byte == b'\\' {
+                    output.push(b'\\');
+                    output.push(byte);
+                } else if is_printable(byte) {
+                    output.push(byte);
+                } else {
+                    push_hex(&mut output, byte);
+                }
+            }
+            output.push(b'"');
+            output.push(b'\n');
+        },
+        Format::Msgpack => {
+            let len = msg.len();
+
+            if len < 256 {
+                output.push(0xc4);
+                output.push(len as u8);
+            } else if len < 65536 {
+                output.push(0xc5);
+                output.push((len >> 8) as u8);
+                output.push(len as u8);
+            } else {
+                output.push(0xc6);
+                output.push((len >> 24) as u8);
+                output.push((len >> 16) as u8);
+                output.push((len >> 8) as u8);
+                output.push(len as u8);
+            }
+            output.extend_from_slice(msg);
+        },
+        Format::Hex => {
+            output.push(b'"');
+            for &byte in msg {
+                push_hex(&mut output, byte);
+            }
+            output.push(b'"');
+            output.push(b'\n');
+        }
+    }
+
+    output
+}
+
+fn print_message(format: Format, msg: &[u8]) {
+    if format == Format::NoEcho {
+        return;
+    }
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    if let Err(e) = handle.write_all(&format_message(format, msg)).and_then(|_| handle.flush()) {
+        fail(&format!("failed to print a message: {}", e));
+    }
+}
+
+fn fail(msg: &str) -> ! {
+    let _ = writeln!(io::stderr(), "nanocat: {}", msg);
+    process::exit(1)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Loops                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Tells whether a recv error means no more messages will come,
+/// either because of the timeout or because the protocol does not expect any.
+fn is_end_of_recv(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::Other => true,
+        _ => false
+    }
+}
+
+fn send_data(socket: &mut Socket, data: &[u8]) {
+    match socket.send(data.to_vec()) {
+        Ok(_) => {},
+        Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {
+            let _ = writeln!(io::stderr(), "nanocat: message not sent, send timed out");
+        },
+        Err(e) => fail(&format!("failed to send: {}", e))
+    }
+}
+
+fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+
+    if deadline > now {
+        thread::sleep(deadline - now);
+    }
+}
+
+fn send_loop(socket: &mut Socket, options: &Options, data: &[u8]) {
+    let mut next_send = Instant::now();
+
+    loop {
+        send_data(socket, data);
+
+        match options.interval {
+            Some(interval) => next_send += interval,
+            None => return
+        }
+
+        sleep_until(next_send);
+    }
+}
+
+fn recv_loop(socket: &mut Socket, options: &Options) {
+    loop {
+        match socket.recv() {
+            Ok(msg) => print_message(options.format, &msg),
+            Err(ref e) if is_end_of_recv(e) => return,
+            Err(e) => fail(&format!("failed to receive: {}", e))
+        }
+    }
+}
+
+/// Sends the data, then prints what is received until it is time to send again.
+fn send_recv_loop(socket: &mut Socket, options: &Options, data: &[u8]) {
+    let mut next_send = Instant::now();
+
+    loop {
+        send_data(socket, data);
+
+        match options.interval {
+            Some(interval) => next_send += interval,
+            None => return recv_loop(socket, options)
+        }
+
+        loop {
+            let now = Instant::now();
+
+            if now >= next_send {
+                break;
+            }
+
+            let mut timeout = next_send - now;
+
+            if let Some(recv_timeout) = options.recv_timeout {
+                if timeout > recv_timeout {
+                    timeout = recv_timeout;
+                }
+            }
+
+            if let Err(e) = socket.set_recv_timeout(Some(timeout)) {
+                fail(&format!("failed to set the recv timeout: {}", e));
+            }
+
+            match socket.recv() {
+                Ok(msg) => print_message(options.format, &msg),
+                Err(ref e) if is_end_of_recv(e) => sleep_until(next_send),
+                Err(e) => fail(&format!("failed to receive: {}", e))
+            }
+        }
+    }
+}
+
+/// Replies the data to each message received.
+fn reply_loop(socket: &mut Socket, options: &Options, data: &[u8]) {
+    loop {
+        match socket.recv() {
+            Ok(msg) => print_message(options.format, &msg),
+            Err(ref e) if is_end_of_recv(e) => return,
+            Err(e) => fail(&format!("failed to receive: {}", e))
+        }
+
+        send_data(socket, data);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* main                                                                      */
+/*                                                                           */
+/*****************************************************************************/
+
+fn create_session() -> io::Result<Session> {
+    SessionBuilder::new().
+        with("tcp", Tcp).
+        with("ipc", Ipc).
+        with("ws", Ws).
+        build()
+}
+
+fn create_socket(session: &mut Session, socket_type: SocketType) -> io::Result<Socket> {
+    match socket_type {
+        SocketType::Push       => session.create_socket::<Push>(),
+        SocketType::Pull       => session.create_socket::<Pull>(),
+        SocketType::Pub        => session.create_socket::<Pub>(),
+        SocketType::Sub        => session.create_socket::<Sub>(),
+        SocketType::Req        => session.create_socket::<Req>(),
+        SocketType::Rep        => session.create_socket::<Rep>(),
+        SocketType::Surveyor   => session.create_socket::<Surveyor>(),
+        SocketType::Respondent => session.create_socket::<Respondent>(),
+        SocketType::Bus        => session.create_socket::<Bus>(),
+        SocketType::Pair       => session.create_socket::<Pair>()
+    }
+}
+
+fn setup_socket(socket: &mut Socket, socket_type: SocketType, options: &Options) -> io::Result<()> {
+    try!(socket.set_recv_timeout(options.recv_timeout));
+    try!(socket.set_send_timeout(options.send_timeout));
+
+    if socket_type == SocketType::Sub {
+        if options.subscriptions.is_empty() {
+            try!(socket.set_option(ConfigOption::Subscribe(String::new())));
+        }
+        for topic in &options.subscriptions {
+            try!(socket.set_option(ConfigOption::Subscribe(topic.clone())));
+        }
+    }
+
+    for endpoint in &options.endpoints {
+        match *endpoint {
+            Endpoint::Bind(ref url) => try!(socket.bind(url)),
+            Endpoint::Connect(ref url) => try!(socket.connect(url))
+        };
+    }
+
+    Ok(())
+}
+
+fn run(socket: &mut Socket, socket_type: SocketType, options: &Options) {
+    if let Some(delay) = options.delay {
+        thread::sleep(delay);
+    }
+
+    match (socket_type, options.data.as_ref()) {
+        (SocketType::Push, Some(data)) |
+        (SocketType::Pub, Some(data)) => send_loop(socket, options, data),
+        (SocketType::Req, Some(data)) |
+        (SocketType::Surveyor, Some(data)) |
+        (SocketType::Bus, Some(data)) |
+        (SocketType::Pair, Some(data)) => send_recv_loop(socket, options, data),
+        (SocketType::Rep, Some(data)) |
+        (SocketType::Respondent, Some(data)) => reply_loop(socket, options, data),
+        _ => recv_loop(socket, options)
+    }
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg) => {
+            let _ = write!(io::stderr(), "nanocat: {}\n\n{}", msg, USAGE);
+            process::exit(1)
+        }
+    };
+
+    if options.help {
+        print!("{}", USAGE);
+        return;
+    }
+
+    let socket_type = match check_options(&options) {
+        Ok(x) => x,
+        Err(msg) => {
+            let _ = write!(io::stderr(), "nanocat: {}\n\n{}", msg, USAGE);
+            process::exit(1)
+        }
+    };
+
+    let mut session = create_session().unwrap_or_else(|e| fail(&format!("failed to create the session: {}", e)));
+    let mut socket = create_socket(&mut session, socket_type).unwrap_or_else(|e| fail(&format!("failed to create the socket: {}", e)));
+
+    if let Err(e) = setup_socket(&mut socket, socket_type, &options) {
+        fail(&format!("failed to setup the socket: {}", e));
+    }
+
+    run(&mut socket, socket_type, &options);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| String::from(*arg)))
+    }
+
+    #[test]
+    fn long_options_can_be_abbreviated() {
+        let options = parse(&["--resp", "--connect", "tcp://127.0.0.1:5454"]).unwrap();
+
+        assert_eq!(Some(SocketType::Respondent), options.socket_type);
+        assert_eq!(vec![Endpoint::Connect(String::from("tcp://127.0.0.1:5454"))], options.endpoints);
+    }
+
+    #[test]
+    fn exact_long_options_win_over_abbreviations() {
+        let options = parse(&["--sub", "--rep"]);
+
+        assert!(options.is_err());
+        assert_eq!(Some(SocketType::Sub), parse(&["--sub"]).unwrap().socket_type);
+        assert_eq!(Some(SocketType::Rep), parse(&["--rep"]).unwrap().socket_type);
+    }
+
+    #[test]
+    fn ambiguous_long_options_are_rejected() {
+        assert!(parse(&["--pu"]).is_err());
+        assert!(parse(&["--conn", "tcp://127.0.0.1:5454"]).is_err());
+    }
+
+    #[test]
+    fn options_can_be_short_or_long_with_inline_value() {
+        let options = parse(&["--push", "-b", "tcp://*:5454", "--connect=ipc:///tmp/x", "-L", "5455", "-D", "hello", "-i", "1.5", "-A"]).unwrap();
+
+        assert_eq!(3, options.endpoints.len());
+        assert_eq!(Endpoint::Bind(String::from("tcp://*:5454")), options.endpoints[0]);
+        assert_eq!(Endpoint::Connect(String::from("ipc:///tmp/x")), options.endpoints[1]);
+        assert_eq!(Endpoint::Bind(String::from("tcp://127.0.0.1:5455")), options.endpoints[2]);
+        assert_eq!(Some(b"hello".to_vec()), options.data);
+        assert_eq!(Some(Duration::from_millis(1500)), options.interval);
+        assert_eq!(Format::Ascii, options.format);
+    }
+
+    #[test]
+    fn options_are_checked_against_the_socket_type() {
+        let push_without_data = parse(&["--push", "--connect", "tcp://127.0.0.1:5454"]).unwrap();
+        let pull_with_data = parse(&["--pull", "--bind", "tcp://*:5454", "--data", "x"]).unwrap();
+        let pair_without_endpoint = parse(&["--pair"]).unwrap();
+        let pair = parse(&["--pair", "--bind", "tcp://*:5454"]).unwrap();
+
+        assert!(check_options(&push_without_data).is_err());
+        assert!(check_options(&pull_with_data).is_err());
+        assert!(check_options(&pair_without_endpoint).is_err());
+        assert_eq!(Ok(SocketType::Pair), check_options(&pair));
+    }
+
+    #[test]
+    fn messages_are_formatted_like_nanocat_does() {
+        let msg = b"a\"b\x01";
+
+        assert_eq!(b"a\"b\x01".to_vec(), format_message(Format::Raw, msg));
+        assert_eq!(b"a\"b.\n".to_vec(), format_message(Format::Ascii, msg));
+        assert_eq!(b"\"a\\\"b\\x01\"\n".to_vec(), format_message(Format::Quoted, msg));
+        assert_eq!(b"\xc4\x04a\"b\x01".to_vec(), format_message(Format::Msgpack, msg));
+        assert_eq!(b"\"\\x61\\x22\\x62\\x01\"\n".to_vec(), format_message(Format::Hex, msg));
+        assert!(format_message(Format::NoEcho, msg).is_empty());
+    }
+
+    #[test]
+    fn long_messages_have_a_longer_msgpack_header() {
+        let msg = vec![0u8; 300];
+        let output = format_message(Format::Msgpack, &msg);
+
+        assert_eq!(&[0xc5, 0x01, 0x2c], &output[..3]);
+        assert_eq!(303, output.len());
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cmp;
+use std::time::Duration;
+
+use rand;
+use rand::Rng;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+/// Computes the delays between the successive attempts to re-establish an endpoint.
+/// The interval starts at `retry_ivl` and doubles after each attempt until `retry_ivl_max` is reached.
+/// When there is no maximum, or when it is lower than the initial interval, the interval stays flat.
+/// The actual delay is picked at random between half the interval and the interval itself,
+/// so that many peers losing the same connection do not all retry at the same time.
+pub struct Backoff {
+    ivl: Duration,
+    ivl_max: Duration
+}
+
+impl Backoff {
+    pub fn new(retry_ivl: Duration, retry_ivl_max: Option<Duration>) -> Backoff {
+        let ivl_max = match retry_ivl_max {
+            Some(ivl_max) if ivl_max > retry_ivl => ivl_max,
+            _ => retry_ivl
+        };
+
+        Backoff {
+            ivl: retry_ivl,
+            ivl_max: ivl_max
+        }
+    }
+
+    /// Returns the delay to wait before the next attempt, and doubles the interval for the following one.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = jitter(self.ivl);
+
+        self.ivl = match self.ivl.checked_mul(2) {
+            Some(ivl) => cmp::min(ivl, self.ivl_max),
+            None => self.ivl_max
+        };
+
+        delay
+    }
+}
+
+fn jitter(ivl: Duration) -> Duration {
+    let nanos = to_nanos(ivl);
+    let half = nanos / 2;
+
+    if half == 0 {
+        return ivl;
+    }
+
+    let delay = half + rand::thread_rng().gen_range(0, nanos - half + 1);
+
+    from_nanos(delay)
+}
+
+fn to_nanos(ivl: Duration) -> u64 {
+    ivl.as_secs().saturating_mul(NANOS_PER_SEC).saturating_add(ivl.subsec_nanos() as u64)
+}
+
+fn from_nanos(nanos: u64) -> Duration {
+    Duration::new(nanos / NANOS_PER_SEC, (nanos % NANOS_PER_SEC) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn assert_delay_within(delay: Duration, ivl_ms: u64) {
+        assert!(delay >= Duration::from_millis(ivl_ms / 2), "{:?} is below {}ms / 2", delay, ivl_ms);
+        assert!(delay <= Duration::from_millis(ivl_ms), "{:?} is above {}ms", delay, ivl_ms);
+    }
+
+    #[test]
+    fn without_max_the_interval_stays_flat() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), None);
+
+        for _ in 0..10 {
+            assert_delay_within(backoff.next_delay(), 100);
+        }
+    }
+
+    #[test]
+    fn with_max_the_interval_doubles_until_max_is_reached() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Some(Duration::from_millis(500)));
+
+        assert_delay_within(backoff.next_delay(), 100);
+        assert_delay_within(backoff.next_delay(), 200);
+        assert_delay_within(backoff.next_delay(), 400);
+        assert_delay_within(backoff.next_delay(), 500);
+        assert_delay_within(backoff.next_delay(), 500);
+    }
+
+    #[test]
+    fn max_lower_than_interval_is_ignored() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Some(Duration::from_millis(50)));
+
+        assert_delay_within(backoff.next_delay(), 100);
+        assert_delay_within(backoff.next_delay(), 100);
+    }
+
+    #[test]
+    fn zero_interval_is_not_randomised() {
+        let mut backoff = Backoff::new(Duration::from_millis(0), None);
+
+        assert_eq!(Duration::from_millis(0), backoff.next_delay());
+    }
+}
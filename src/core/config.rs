@@ -10,6 +10,7 @@ use std::time::Duration;
 use io_error::*;
 
 pub struct Config {
+    pub linger: Duration,
     pub send_timeout: Option<Duration>,
     pub send_priority: u8,
     pub recv_timeout: Option<Duration>,
@@ -17,12 +18,41 @@ pub struct Config {
     pub retry_ivl: Duration,
     pub retry_ivl_max: Option<Duration>,
     pub tcp_no_delay: bool,
-    pub recv_max_size: u64
+    pub recv_max_size: u64,
+    pub recv_buffer: usize,
+    pub send_buffer: SendBuffer,
+    pub send_buffer_policy: SendBufferPolicy
 }
 
+/// Limit of the messages waiting to be sent on each endpoint of a socket,
+/// in addition to the messages the transport is currently sending.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SendBuffer {
+    /// At most this number of messages.
+    Messages(usize),
+    /// Messages are buffered until their total size reaches this number of bytes.
+    Bytes(usize)
+}
+
+/// What happens to a message sent to an endpoint whose send buffer is full.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SendBufferPolicy {
+    /// The endpoint is not ready to send until there is room in its buffer,
+    /// if none of the endpoints is ready the socket cannot send.
+    Block,
+    /// The message is discarded, the endpoint stays ready to send.
+    DropNewest,
+    /// The oldest buffered message is discarded to make room, the endpoint stays ready to send.
+    DropOldest,
+    /// The endpoint is closed, and reconnected later if it was created by `connect`.
+    Disconnect
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum ConfigOption {
     /// Specifies how long the socket should try to send pending outbound messages 
-    /// after `drop` have been called. Default value is 1 second.
+    /// after `drop` have been called. A zero duration means pending messages are discarded.
+    /// Default value is 1 second.
     Linger(Duration),
 
     /// See [Socket::set_send_timeout](struct.Socket.html#method.set_send_timeout).
@@ -42,6 +72,14 @@ pub enum ConfigOption {
     /// Default is 1024kB.
     RecvMaxSize(u64),
 
+    /// Size of the inbound messages, in bytes, that each endpoint of a stream based transport
+    /// such as TCP or IPC reads ahead while the socket is not receiving.
+    /// The socket then receives them without waiting for the transport, and the endpoint
+    /// stops reading from the peer once the limit is reached, a message being never split.
+    /// Applies to the endpoints created afterwards.
+    /// Default value is 0, a message is only read when the socket receives.
+    RecvBuffer(usize),
+
     /// For connection-based transports such as TCP, this option specifies how long to wait, 
     /// when connection is broken before trying to re-establish it. 
     /// Note that actual reconnect interval may be randomised to some extent 
@@ -59,6 +97,17 @@ pub enum ConfigOption {
     /// See [Socket::set_tcp_nodelay](struct.Socket.html#method.set_tcp_nodelay).
     TcpNoDelay(bool),
 
+    /// High-water mark of the outbound messages buffered by each endpoint while its transport is busy sending.
+    /// A slow peer no longer makes the socket skip or wait for its endpoint until the buffer is full.
+    /// Applies to the endpoints created afterwards.
+    /// Default value is `SendBuffer::Messages(0)`, nothing is buffered.
+    SendBuffer(SendBuffer),
+
+    /// What happens when the send buffer of an endpoint is full, see [SendBufferPolicy](enum.SendBufferPolicy.html).
+    /// Applies to the endpoints created afterwards.
+    /// Default value is `SendBufferPolicy::Block`.
+    SendBufferPolicy(SendBufferPolicy),
+
     /// Defined on `Sub` socket. Subscribes for a particular topic.
     /// A single `Sub` socket can handle multiple subscriptions.
     Subscribe(String),
@@ -66,6 +115,24 @@ pub enum ConfigOption {
     /// Defined on Sub` socket. Unsubscribes from a particular topic.
     Unsubscribe(String),
 
+    /// Defined on `Sub` socket. Same as `Subscribe` for a topic that can be any sequence of bytes,
+    /// not only a text, like the topics of nanomsg.
+    SubscribeTopic(Vec<u8>),
+
+    /// Defined on `Sub` socket. Same as `Unsubscribe` for a topic that can be any sequence of bytes.
+    UnsubscribeTopic(Vec<u8>),
+
+    /// Defined on `Pub` and `Sub` sockets. When both ends of a connection enable it,
+    /// the `Sub` socket sends its subscriptions to the `Pub` socket, which then only sends the matching messages.
+    /// It is negotiated in the protocol handshake so it must be set before connecting or binding,
+    /// the peers not supporting it, like nanomsg ones or websocket connections, keep receiving all the messages.
+    /// Default value is false.
+    SubscriptionForwarding(bool),
+
+    /// Defined on `Sub` socket. The topics currently subscribed to, in ascending byte order.
+    /// This option can only be read, see [Socket::get_option](struct.Socket.html#method.get_option).
+    Subscriptions(Vec<Vec<u8>>),
+
     /// This option is defined on the Req socket.
     /// If a reply is not received in the specified amount of time, 
     /// the request will be automatically resent. 
@@ -79,9 +146,32 @@ pub enum ConfigOption {
     SurveyDeadline(Duration)
 }
 
+/// Identifies an option to be read with [Socket::get_option](struct.Socket.html#method.get_option),
+/// each kind matching the [ConfigOption](enum.ConfigOption.html) variant of the same name.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigOptionKind {
+    Linger,
+    SendTimeout,
+    SendPriority,
+    RecvTimeout,
+    RecvPriority,
+    RecvMaxSize,
+    RecvBuffer,
+    RetryIvl,
+    RetryIvlMax,
+    TcpNoDelay,
+    SendBuffer,
+    SendBufferPolicy,
+    SubscriptionForwarding,
+    Subscriptions,
+    ReqResendIvl,
+    SurveyDeadline
+}
+
 impl Default for Config {
     fn default() -> Config {
         Config {
+            linger: Duration::from_secs(1),
             send_timeout: None,
             send_priority: 8,
             recv_timeout: None,
@@ -89,7 +179,10 @@ impl Default for Config {
             retry_ivl: Duration::from_millis(100),
             retry_ivl_max: None,
             tcp_no_delay: false,
-            recv_max_size: 1024 * 1024
+            recv_max_size: 1024 * 1024,
+            recv_buffer: 0,
+            send_buffer: SendBuffer::Messages(0),
+            send_buffer_policy: SendBufferPolicy::Block
         }
     }
 }
@@ -97,6 +190,7 @@ impl Default for Config {
 impl Config {
     pub fn set(&mut self, cfg_opt: ConfigOption) -> Result<()> {
         match cfg_opt {
+            ConfigOption::Linger(linger) => self.linger = linger,
             ConfigOption::SendTimeout(timeout) => self.send_timeout = timeout,
             ConfigOption::SendPriority(priority) => self.send_priority = priority,
             ConfigOption::RecvTimeout(timeout) => self.recv_timeout = timeout,
@@ -104,11 +198,33 @@ impl Config {
             ConfigOption::RetryIvl(ivl) => self.retry_ivl = ivl,
             ConfigOption::RetryIvlMax(ivl) => self.retry_ivl_max = ivl,
             ConfigOption::RecvMaxSize(x) => self.recv_max_size = x,
+            ConfigOption::RecvBuffer(x) => self.recv_buffer = x,
             ConfigOption::TcpNoDelay(x) => self.tcp_no_delay = x,
+            ConfigOption::SendBuffer(x) => self.send_buffer = x,
+            ConfigOption::SendBufferPolicy(x) => self.send_buffer_policy = x,
             _ => return Err(invalid_input_io_error("option not supported"))
         }
         Ok(())
     }
+
+    pub fn get(&self, kind: ConfigOptionKind) -> Result<ConfigOption> {
+        let opt = match kind {
+            ConfigOptionKind::Linger => ConfigOption::Linger(self.linger),
+            ConfigOptionKind::SendTimeout => ConfigOption::SendTimeout(self.send_timeout),
+            ConfigOptionKind::SendPriority => ConfigOption::SendPriority(self.send_priority),
+            ConfigOptionKind::RecvTimeout => ConfigOption::RecvTimeout(self.recv_timeout),
+            ConfigOptionKind::RecvPriority => ConfigOption::RecvPriority(self.recv_priority),
+            ConfigOptionKind::RetryIvl => ConfigOption::RetryIvl(self.retry_ivl),
+            ConfigOptionKind::RetryIvlMax => ConfigOption::RetryIvlMax(self.retry_ivl_max),
+            ConfigOptionKind::RecvMaxSize => ConfigOption::RecvMaxSize(self.recv_max_size),
+            ConfigOptionKind::RecvBuffer => ConfigOption::RecvBuffer(self.recv_buffer),
+            ConfigOptionKind::TcpNoDelay => ConfigOption::TcpNoDelay(self.tcp_no_delay),
+            ConfigOptionKind::SendBuffer => ConfigOption::SendBuffer(self.send_buffer),
+            ConfigOptionKind::SendBufferPolicy => ConfigOption::SendBufferPolicy(self.send_buffer_policy),
+            _ => return Err(invalid_input_io_error("option not supported"))
+        };
+        Ok(opt)
+    }
 }
 
 impl ConfigOption {
@@ -122,7 +238,32 @@ impl ConfigOption {
             ConfigOption::RecvPriority(_) |
             ConfigOption::RetryIvl(_)     |
             ConfigOption::RetryIvlMax(_)  |
-            ConfigOption::TcpNoDelay(_)   => true,
+            ConfigOption::RecvMaxSize(_)  |
+            ConfigOption::RecvBuffer(_)   |
+            ConfigOption::TcpNoDelay(_)   |
+            ConfigOption::SendBuffer(_)   |
+            ConfigOption::SendBufferPolicy(_) => true,
+            _ => false
+        }
+    }
+}
+
+impl ConfigOptionKind {
+    #[doc(hidden)]
+    pub fn is_generic(&self) -> bool {
+        match *self {
+            ConfigOptionKind::Linger       |
+            ConfigOptionKind::SendTimeout  |
+            ConfigOptionKind::SendPriority |
+            ConfigOptionKind::RecvTimeout  |
+            ConfigOptionKind::RecvPriority |
+            ConfigOptionKind::RecvMaxSize  |
+            ConfigOptionKind::RecvBuffer   |
+            ConfigOptionKind::RetryIvl     |
+            ConfigOptionKind::RetryIvlMax  |
+            ConfigOptionKind::TcpNoDelay   |
+            ConfigOptionKind::SendBuffer   |
+            ConfigOptionKind::SendBufferPolicy => true,
             _ => false
         }
     }
@@ -8,7 +8,7 @@ use std::fmt;
 use std::io::Result;
 use std::time::Duration;
 
-use core::{EndpointId, EndpointSpec, Scheduled};
+use core::{EndpointId, ContextId, EndpointSpec, Scheduled};
 use core::network::Network;
 
 pub trait Context : Network + Scheduler + fmt::Debug {
@@ -47,8 +47,12 @@ pub enum Schedulable {
     Rebind(EndpointId, EndpointSpec),
     SendTimeout,
     RecvTimeout,
+    Linger,
     ReqResend,
-    SurveyCancel
+    SurveyCancel,
+    ContextSendTimeout(ContextId),
+    ContextRecvTimeout(ContextId),
+    ReqContextResend(ContextId)
 }
 
 impl fmt::Debug for Scheduled {
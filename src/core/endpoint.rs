@@ -5,18 +5,28 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::io;
 
 use super::{EndpointId, Message, EndpointSpec, EndpointDesc};
 use super::context::Context;
 
 pub enum Request {
-    Close(bool)
+    Close(bool, Sender<Reply>),
+    GetRemoteAddr(Sender<Reply>)
+}
+
+pub enum Reply {
+    Err(io::Error),
+    Close,
+    RemoteAddr(Option<String>)
 }
 
 pub struct Endpoint {
     id: EndpointId,
     url: Option<String>,
-    desc: EndpointDesc
+    desc: EndpointDesc,
+    peer_proto_flags: u8
 }
 
 pub struct Pipe(Endpoint);
@@ -28,6 +38,7 @@ impl Endpoint {
             id: id,
             url: Some(url),
             desc: desc,
+            peer_proto_flags: 0
         }
     }
 
@@ -35,7 +46,8 @@ impl Endpoint {
         Endpoint {
             id: id,
             url: None,
-            desc: desc
+            desc: desc,
+            peer_proto_flags: 0
         }
     }
 
@@ -43,7 +55,8 @@ impl Endpoint {
         Endpoint {
             id: id,
             url: Some(spec.url),
-            desc: spec.desc
+            desc: spec.desc,
+            peer_proto_flags: 0
         }
     }
 
@@ -72,6 +85,15 @@ impl Endpoint {
     fn get_recv_priority(&self) -> u8 {
         self.desc.recv_priority
     }
+    fn get_proto_flags(&self) -> u8 {
+        self.desc.proto_flags
+    }
+    fn is_accepted(&self) -> bool {
+        self.url.is_none()
+    }
+    fn get_url(&self) -> Option<&str> {
+        self.url.as_ref().map(|url| url.as_str())
+    }
 }
 
 impl Pipe {
@@ -105,6 +127,23 @@ impl Pipe {
     pub fn get_recv_priority(&self) -> u8 {
         self.0.get_recv_priority()
     }
+    /// The flags this side sent in the protocol handshake.
+    pub fn get_proto_flags(&self) -> u8 {
+        self.0.get_proto_flags()
+    }
+    /// The flags the peer sent in the protocol handshake, known once the pipe is opened.
+    pub fn get_peer_proto_flags(&self) -> u8 {
+        self.0.peer_proto_flags
+    }
+    pub fn set_peer_proto_flags(&mut self, flags: u8) {
+        self.0.peer_proto_flags = flags;
+    }
+    pub fn is_accepted(&self) -> bool {
+        self.0.is_accepted()
+    }
+    pub fn get_url(&self) -> Option<&str> {
+        self.0.get_url()
+    }
 }
 
 impl Acceptor {
@@ -126,4 +165,7 @@ impl Acceptor {
     pub fn get_recv_priority(&self) -> u8 {
         self.0.get_recv_priority()
     }
+    pub fn get_proto_flags(&self) -> u8 {
+        self.0.get_proto_flags()
+    }
 }
@@ -8,6 +8,7 @@
 #[doc(hidden)] pub mod context;
 #[doc(hidden)] pub mod config;
 #[doc(hidden)] pub mod socket;
+#[doc(hidden)] pub mod backoff;
 #[doc(hidden)] pub mod session;
 #[doc(hidden)] pub mod endpoint;
 #[doc(hidden)] pub mod device;
@@ -17,8 +18,13 @@
 pub mod tests;
 
 use std::fmt;
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
 use std::hash::{BuildHasher, Hasher};
 
+use self::config::{SendBuffer, SendBufferPolicy};
+
 #[doc(hidden)]
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Scheduled(usize);
@@ -29,7 +35,8 @@ pub struct Scheduled(usize);
 /*                                                                           */
 /*****************************************************************************/
 
-#[doc(hidden)]
+/// Identifies an endpoint of a socket, see [Endpoint::id](struct.Endpoint.html#method.id).  
+/// Each pipe accepted by a bound endpoint has its own id.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct EndpointId(usize);
 
@@ -79,8 +86,12 @@ pub struct EndpointSpec {
 pub struct EndpointDesc {
     pub send_priority: u8,
     pub recv_priority: u8,
+    pub proto_flags: u8,
     pub tcp_no_delay: bool,
-    pub recv_max_size: u64
+    pub recv_max_size: u64,
+    pub recv_buffer: usize,
+    pub send_buffer: SendBuffer,
+    pub send_buffer_policy: SendBufferPolicy
 }
 
 /*****************************************************************************/
@@ -149,6 +160,28 @@ impl From<usize> for ProbeId {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* ContextId                                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+#[doc(hidden)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ContextId(usize);
+
+impl fmt::Debug for ContextId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<usize> for ContextId {
+    fn from(value: usize) -> ContextId {
+        ContextId(value)
+    }
+}
+
 /// Request for socket polling, tells whether the poll should wait for the socket to become readable or writable.
 pub struct PollReq {
     pub sid: SocketId,
@@ -215,6 +248,16 @@ impl Message {
     pub fn without_header(self) -> Message {
         Message::from_body(self.body)
     }
+
+    /// Takes the message out of a received pipe message,
+    /// copying it only when other pipes still hold a reference to it.
+    #[doc(hidden)]
+    pub fn from_rc(msg: Rc<Message>) -> Message {
+        match Rc::try_unwrap(msg) {
+            Ok(msg) => msg,
+            Err(msg) => Message::from_header_and_body(msg.header.clone(), msg.body.clone())
+        }
+    }
 }
 
 impl Into<Vec<u8>> for Message {
@@ -229,6 +272,82 @@ impl From<Vec<u8>> for Message {
     }
 }
 
+/// Identifies a request received by a `Rep` socket, and the way back to the peer that sent it.
+/// It is obtained with the request, and must be given back when sending the reply,
+/// which can happen later, after other requests have been received.
+#[derive(Debug)]
+pub struct ReplyToken(Vec<u8>);
+
+impl ReplyToken {
+    #[doc(hidden)]
+    pub fn new(backtrace: Vec<u8>) -> ReplyToken {
+        ReplyToken(backtrace)
+    }
+
+    #[doc(hidden)]
+    pub fn into_backtrace(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Statistics                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Counters describing the activity of a socket since its creation,
+/// mirroring the `NN_STAT_*` statistics of nanomsg.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// Number of connections established by the connected endpoints.
+    pub established_connections: u64,
+    /// Number of connections accepted by the bound endpoints.
+    pub accepted_connections: u64,
+    /// Number of accepted connections that failed before the handshake completed.
+    pub dropped_connections: u64,
+    /// Number of established or accepted connections that failed afterwards.
+    pub broken_connections: u64,
+    /// Number of failed attempts to connect, either immediately or during the handshake.
+    pub connect_errors: u64,
+    /// Number of failed attempts to bind.
+    pub bind_errors: u64,
+    /// Number of messages sent, counted once per pipe the message was sent to.
+    pub messages_sent: u64,
+    /// Number of messages received.
+    pub messages_received: u64,
+    /// Number of bytes sent, header included.
+    pub bytes_sent: u64,
+    /// Number of bytes received, header included.
+    pub bytes_received: u64,
+    /// Number of connections currently open.
+    pub current_connections: u64
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Monitor                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+/// Lifecycle events of the endpoints of a socket, 
+/// see [Socket::monitor](struct.Socket.html#method.monitor).
+#[derive(Debug)]
+pub enum SocketEvent {
+    /// A connected endpoint has established its connection to the specified url.
+    Connected(EndpointId, String),
+    /// A bound endpoint has accepted a connection, from the specified peer address if the transport knows it.
+    Accepted(EndpointId, Option<String>),
+    /// A connection has been lost, or could not be established.
+    Disconnected(EndpointId, io::Error),
+    /// A connected endpoint will try to connect again after the specified delay.
+    ReconnectScheduled(EndpointId, Duration),
+    /// A bound endpoint has failed and could not be bound again, another attempt will be made later.
+    BindFailed(EndpointId, io::Error),
+    /// The peer sent a handshake that does not match the protocol of the socket.
+    HandshakeRejected(EndpointId, io::Error)
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Hash                                                                      */
@@ -19,4 +19,7 @@ pub trait Network {
     fn close(&mut self, eid: EndpointId, remote: bool);
     fn send(&mut self, eid: EndpointId, msg: Rc<Message>);
     fn recv(&mut self, eid: EndpointId);
+    fn has_pending_send(&self, sid: SocketId) -> bool;
+    fn local_addr(&self, eid: EndpointId) -> Option<String>;
+    fn remote_addr(&self, eid: EndpointId) -> Option<String>;
 }
\ No newline at end of file
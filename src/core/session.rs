@@ -12,7 +12,7 @@ use core::{BuildIdHasher, SocketId, DeviceId, ProbeId, PollReq, socket, device,
 use sequence::Sequence;
 
 pub enum Request {
-    CreateSocket(socket::ProtocolCtor),
+    CreateSocket(socket::ProtocolCtor, bool),
     CreateDevice(SocketId, SocketId),
     CreateProbe(Vec<PollReq>),
     Shutdown
@@ -46,7 +46,7 @@ struct DeviceCollection {
 
 struct ProbeCollection {
     ids: Sequence,
-    mapping: HashMap<SocketId, ProbeId, BuildIdHasher>,
+    mapping: HashMap<SocketId, Vec<ProbeId>, BuildIdHasher>,
     probes: HashMap<ProbeId, probe::Probe, BuildIdHasher>
 }
 
@@ -70,13 +70,15 @@ impl Session {
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn add_socket(&mut self, protocol_ctor: socket::ProtocolCtor) {
+    pub fn add_socket(&mut self, protocol_ctor: socket::ProtocolCtor) -> SocketId {
         let (tx, rx) = mpsc::channel();
         let protocol_ctor_args = (tx.clone(),);
         let protocol = protocol_ctor.call_box(protocol_ctor_args);
         let id = self.sockets.add(tx, protocol);
 
         self.send_reply(Reply::SocketCreated(id, rx));
+
+        id
     }
 
     pub fn get_socket_mut(&mut self, id: SocketId) -> Option<&mut socket::Socket> {
@@ -129,8 +131,9 @@ impl Session {
         self.probes.get_probe_mut(id)
     }
 
-    pub fn find_probe_mut(&mut self, id: SocketId) -> Option<(&ProbeId, &mut probe::Probe)> {
-        self.probes.find_probe_mut(id)
+    /// Several probes may watch the same socket, they all need to know about its readiness.
+    pub fn find_probe_ids(&self, id: SocketId) -> Vec<ProbeId> {
+        self.probes.find_probe_ids(id)
     }
 
     pub fn remove_probe(&mut self, id: ProbeId) {
@@ -235,7 +238,11 @@ impl ProbeCollection {
         let id = ProbeId::from(self.ids.next());
         
         for poll_opt in &poll_opts {
-            self.mapping.insert(poll_opt.sid, id);
+            let pids = self.mapping.entry(poll_opt.sid).or_insert_with(Vec::new);
+
+            if pids.contains(&id) == false {
+                pids.push(id);
+            }
         }
 
         let probe = probe::Probe::new(reply_tx, poll_opts);
@@ -249,20 +256,19 @@ impl ProbeCollection {
         self.probes.get_mut(&id)
     }
 
-    fn find_probe_mut(&mut self, sid: SocketId) -> Option<(&ProbeId, &mut probe::Probe)> {
-        if let Some(pid) = self.mapping.get(&sid) {
-            self.probes.get_mut(pid).map(|probe| (pid, probe))
-        } else {
-            None
-        }
+    fn find_probe_ids(&self, sid: SocketId) -> Vec<ProbeId> {
+        self.mapping.get(&sid).cloned().unwrap_or_default()
     }
 
     fn remove(&mut self, id: ProbeId) {
         if let Some(probe) = self.probes.remove(&id) {
-            let ids = probe.get_socket_ids();
-
-            for id in &ids {
-                self.mapping.remove(id);
+            for sid in probe.get_socket_ids() {
+                if let Some(pids) = self.mapping.get_mut(&sid) {
+                    pids.retain(|pid| *pid != id);
+                }
+                if self.mapping.get(&sid).map_or(false, |pids| pids.is_empty()) {
+                    self.mapping.remove(&sid);
+                }
             }
         }
     }
@@ -5,15 +5,18 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::collections::HashMap;
-use std::sync::mpsc::Sender;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Sender, Receiver};
 use std::io;
 use std::boxed::FnBox;
 use std::time::Duration;
 
-use super::{BuildIdHasher, SocketId, EndpointId, Message, EndpointTmpl, EndpointSpec, EndpointDesc, Scheduled };
+use super::{BuildIdHasher, SocketId, EndpointId, ContextId, Message, ReplyToken, Statistics, SocketEvent, EndpointTmpl, EndpointSpec, EndpointDesc, Scheduled };
 use super::endpoint::{Pipe, Acceptor};
-use super::config::{Config, ConfigOption};
+use super::config::{Config, ConfigOption, ConfigOptionKind};
+use super::backoff::Backoff;
 use super::context::{Context, Schedulable, Event};
+use sequence::Sequence;
 use io_error::*;
 
 pub enum Request {
@@ -21,26 +24,61 @@ pub enum Request {
     Bind(String),
     Send(Message, bool),
     Recv(bool),
+    RecvRequest,
+    SendReply(ReplyToken, Message),
     SetOption(ConfigOption),
+    GetOption(ConfigOptionKind),
+    CreateContext,
+    ContextSend(ContextId, Message),
+    ContextRecv(ContextId),
+    CloseContext(ContextId),
+    SetWaker(Waker),
+    SetRecvNotifier(Notifier),
+    SetSendNotifier(Notifier),
+    GetStatistics,
+    Monitor,
     Close
 }
 
 pub enum Reply {
     Err(io::Error),
     Connect(EndpointId),
-    Bind(EndpointId),
+    Bind(EndpointId, Option<String>),
     Send,
     Recv(Message),
-    SetOption
+    RecvRequest(Message, ReplyToken),
+    SetOption,
+    GetOption(ConfigOption),
+    ContextCreated(ContextId, Receiver<Reply>),
+    Statistics(Statistics),
+    Monitor(Receiver<SocketEvent>)
 }
 
+/// Called each time the socket may have sent replies to the facade,
+/// so that a facade not blocking on the replies knows when to check for them.
+pub type Waker = Box<Fn() + Send>;
+
+/// Called with the new readiness each time the socket becomes able or unable to receive or send,
+/// so that a facade can forward it to an external event loop.
+pub type Notifier = Box<Fn(bool) + Send>;
+
 pub struct Socket {
     id: SocketId,
     reply_sender: Sender<Reply>,
     protocol: Box<Protocol>,
     pipes: HashMap<EndpointId, Pipe, BuildIdHasher>,
     acceptors: HashMap<EndpointId, Acceptor, BuildIdHasher>,
-    config: Config
+    config: Config,
+    backoffs: HashMap<EndpointId, Backoff, BuildIdHasher>,
+    retries: HashMap<EndpointId, Scheduled, BuildIdHasher>,
+    context_ids: Sequence,
+    waker: Option<Waker>,
+    recv_notifier: Option<Notifier>,
+    send_notifier: Option<Notifier>,
+    stats: Statistics,
+    monitor: Option<Sender<SocketEvent>>,
+    is_closing: bool,
+    linger_timeout: Option<Scheduled>
 }
 
 /*****************************************************************************/
@@ -63,6 +101,11 @@ pub trait Protocol {
     
     fn recv(&mut self, ctx: &mut Context, timeout: Option<Scheduled>);
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message);
+    /// Handles a received message that other pipes may still hold, like a broadcast over inproc.
+    /// Protocols able to drop a message without looking past its body can override it to avoid copying it.
+    fn on_recv_shared_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Rc<Message>) {
+        self.on_recv_ack(ctx, eid, Message::from_rc(msg))
+    }
     fn on_recv_timeout(&mut self, ctx: &mut Context);
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId);
 
@@ -72,9 +115,40 @@ pub trait Protocol {
     fn set_option(&mut self, _: ConfigOption) -> io::Result<()> {
         Err(invalid_input_io_error("option not supported"))
     }
+    /// Called once `set_option` succeeded, for the protocols that tell their peers about their options.
+    fn on_option_set(&mut self, _: &mut Context) {}
+    fn get_option(&self, _: ConfigOptionKind) -> io::Result<ConfigOption> {
+        Err(invalid_input_io_error("option not supported"))
+    }
     fn on_timer_tick(&mut self, _: &mut Context, _: Schedulable) {
     }
     fn on_device_plugged(&mut self, _: &mut Context) {}
+
+    /// Flags sent to the peers in the reserved bytes of the protocol handshake,
+    /// each endpoint keeps the flags it was created with.
+    fn proto_flags(&self) -> u8 {
+        0
+    }
+
+    /// Receives a request that will be replied with `send_reply`, letting the socket receive other requests meanwhile.
+    /// The request is sent to the facade along with the token required to reply to it.
+    fn recv_request(&mut self, _: &mut Context, _: Option<Scheduled>) -> io::Result<()> {
+        Err(invalid_input_io_error("reply tokens not supported"))
+    }
+    fn send_reply(&mut self, _: &mut Context, _: ReplyToken, _: Message, _: Option<Scheduled>) -> io::Result<()> {
+        Err(invalid_input_io_error("reply tokens not supported"))
+    }
+
+    /// Contexts share the pipes of the socket, but each one of them has its own
+    /// state machine, so several exchanges can be in progress at the same time.
+    /// The replies of a context are sent to the specified channel.
+    fn create_context(&mut self, _: ContextId, _: Sender<Reply>) -> io::Result<()> {
+        Err(invalid_input_io_error("contexts not supported"))
+    }
+    fn context_send(&mut self, _: &mut Context, _: ContextId, _: Message, _: Option<Scheduled>) {}
+    fn context_recv(&mut self, _: &mut Context, _: ContextId, _: Option<Scheduled>) {}
+    fn close_context(&mut self, _: &mut Context, _: ContextId) {}
+
     fn close(&mut self, ctx: &mut Context);
 }
 
@@ -94,7 +168,17 @@ impl Socket {
             protocol: proto,
             pipes: HashMap::default(),
             acceptors: HashMap::default(),
-            config: Config::default()
+            config: Config::default(),
+            backoffs: HashMap::default(),
+            retries: HashMap::default(),
+            context_ids: Sequence::new(),
+            waker: None,
+            recv_notifier: None,
+            send_notifier: None,
+            stats: Statistics::default(),
+            monitor: None,
+            is_closing: false,
+            linger_timeout: None
         }
     }
 
@@ -114,6 +198,38 @@ impl Socket {
         ctx.raise(Event::CanSend(self.protocol.is_send_ready()));
     }
 
+    pub fn set_waker(&mut self, _: &mut Context, waker: Waker) {
+        self.waker = Some(waker);
+    }
+
+    pub fn wake(&self) {
+        if let Some(ref waker) = self.waker {
+            waker();
+        }
+    }
+
+    pub fn set_recv_notifier(&mut self, _: &mut Context, notifier: Notifier) {
+        notifier(self.protocol.is_recv_ready());
+        self.recv_notifier = Some(notifier);
+    }
+
+    pub fn set_send_notifier(&mut self, _: &mut Context, notifier: Notifier) {
+        notifier(self.protocol.is_send_ready());
+        self.send_notifier = Some(notifier);
+    }
+
+    pub fn on_can_recv(&self, ready: bool) {
+        if let Some(ref notifier) = self.recv_notifier {
+            notifier(ready);
+        }
+    }
+
+    pub fn on_can_send(&self, ready: bool) {
+        if let Some(ref notifier) = self.send_notifier {
+            notifier(ready);
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* endpoint creation                                                         */
@@ -124,8 +240,12 @@ impl Socket {
         EndpointDesc {
             send_priority: self.config.send_priority,
             recv_priority: self.config.recv_priority,
+            proto_flags: self.protocol.proto_flags(),
             tcp_no_delay: self.config.tcp_no_delay,
-            recv_max_size: self.config.recv_max_size
+            recv_max_size: self.config.recv_max_size,
+            recv_buffer: self.config.recv_buffer,
+            send_buffer: self.config.send_buffer,
+            send_buffer_policy: self.config.send_buffer_policy
         }
     }
 
@@ -166,18 +286,24 @@ impl Socket {
     }
 
     fn on_connect_error(&mut self, err: io::Error) {
+        self.stats.connect_errors += 1;
         self.send_reply(Reply::Err(err));
     }
 
     fn schedule_reconnect(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
         let task = Schedulable::Reconnect(eid, spec);
-        let delay = self.config.retry_ivl;
-        let _ = ctx.schedule(task, delay); 
-        // TODO maybe we should keep track of the scheduled reconnection
-        // In case the facade wants to close the ep somewhere between the error and the timeout
+        let delay = self.next_retry_delay(eid);
+
+        if self.schedule_retry(ctx, eid, task, delay) {
+            self.notify(SocketEvent::ReconnectScheduled(eid, delay));
+        }
     }
 
     pub fn reconnect(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        if self.retries.remove(&eid).is_none() || self.is_closing {
+            return;
+        }
+
         let pids = self.get_protocol_ids();
         let tmpl = EndpointTmpl {
             pids: pids,
@@ -186,7 +312,7 @@ impl Socket {
 
         match ctx.reconnect(self.id, eid, &tmpl) {
             Ok(_)  => self.on_reconnect_success(ctx, eid, tmpl.spec),
-            Err(_) => self.on_reconnect_error(ctx, eid, tmpl.spec)
+            Err(e) => self.on_reconnect_error(ctx, eid, tmpl.spec, e)
         }
     }
 
@@ -194,7 +320,9 @@ impl Socket {
         self.insert_pipe(ctx, eid, Pipe::from_spec(eid, spec));
     }
 
-    fn on_reconnect_error(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+    fn on_reconnect_error(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec, err: io::Error) {
+        self.stats.connect_errors += 1;
+        self.notify(SocketEvent::Disconnected(eid, err));
         self.schedule_reconnect(ctx, eid, spec);
     }
 
@@ -218,23 +346,29 @@ impl Socket {
 
         acceptor.open(ctx);
 
+        let local_addr = ctx.local_addr(eid);
+
         self.acceptors.insert(eid, acceptor);
-        self.send_reply(Reply::Bind(eid));
+        self.send_reply(Reply::Bind(eid, local_addr));
     }
 
     fn on_bind_error(&mut self, err: io::Error) {
+        self.stats.bind_errors += 1;
         self.send_reply(Reply::Err(err));
     }
 
     fn schedule_rebind(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
         let task = Schedulable::Rebind(eid, spec);
-        let delay = self.config.retry_ivl;
-        let _ = ctx.schedule(task, delay); 
-        // TODO maybe we should keep track of the scheduled reconnection
-        // In case the facade wants to close the ep somewhere between the error and the timeout
+        let delay = self.next_retry_delay(eid);
+
+        self.schedule_retry(ctx, eid, task, delay);
     }
 
     pub fn rebind(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+        if self.retries.remove(&eid).is_none() || self.is_closing {
+            return;
+        }
+
         let pids = self.get_protocol_ids();
         let tmpl = EndpointTmpl {
             pids: pids,
@@ -243,46 +377,128 @@ impl Socket {
 
         match ctx.rebind(self.id, eid, &tmpl) {
             Ok(_)  => self.on_rebind_success(ctx, eid, tmpl.spec),
-            Err(_) => self.on_rebind_error(ctx, eid, tmpl.spec)
+            Err(e) => self.on_rebind_error(ctx, eid, tmpl.spec, e)
         };
     }
 
     fn on_rebind_success(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
         let acceptor = Acceptor::from_spec(eid, spec);
 
+        self.backoffs.remove(&eid);
+
         self.insert_acceptor(ctx, eid, acceptor)
     }
 
-    fn on_rebind_error(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec) {
+    fn on_rebind_error(&mut self, ctx: &mut Context, eid: EndpointId, spec: EndpointSpec, err: io::Error) {
+        self.stats.bind_errors += 1;
+        self.notify(SocketEvent::BindFailed(eid, err));
         self.schedule_rebind(ctx, eid, spec);
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* retry                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+    fn schedule_retry(&mut self, ctx: &mut Context, eid: EndpointId, task: Schedulable, delay: Duration) -> bool {
+        if let Ok(scheduled) = ctx.schedule(task, delay) {
+            self.retries.insert(eid, scheduled);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Prevents a closed endpoint from being re-established by a retry scheduled after its last failure.
+    /// Returns whether a retry was pending.
+    fn cancel_retry(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
+        self.backoffs.remove(&eid);
+
+        match self.retries.remove(&eid) {
+            Some(scheduled) => {
+                ctx.cancel(scheduled);
+                true
+            },
+            None => false
+        }
+    }
+
+    fn next_retry_delay(&mut self, eid: EndpointId) -> Duration {
+        let retry_ivl = self.config.retry_ivl;
+        let retry_ivl_max = self.config.retry_ivl_max;
+
+        self.backoffs.
+            entry(eid).
+            or_insert_with(|| Backoff::new(retry_ivl, retry_ivl_max)).
+            next_delay()
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* pipe                                                                      */
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn on_pipe_opened(&mut self, ctx: &mut Context, eid: EndpointId) {
-        if let Some(pipe) = self.pipes.remove(&eid) {
+    pub fn on_pipe_opened(&mut self, ctx: &mut Context, eid: EndpointId, peer_flags: u8) {
+        self.backoffs.remove(&eid);
+
+        if let Some(mut pipe) = self.pipes.remove(&eid) {
+            pipe.set_peer_proto_flags(peer_flags);
+            self.on_pipe_established(eid, &pipe);
             self.protocol.add_pipe(ctx, eid, pipe);
         }
     }
 
-    pub fn on_pipe_accepted(&mut self, ctx: &mut Context, aid: EndpointId, eid: EndpointId) {
+    pub fn on_pipe_accepted(&mut self, ctx: &mut Context, aid: EndpointId, eid: EndpointId, peer_addr: Option<String>) {
         let pipe = self.accept_pipe(aid, eid);
 
-        self.insert_pipe(ctx, eid, pipe);
+        if self.is_closing {
+            let _ = pipe.close(ctx);
+        } else {
+            self.insert_pipe(ctx, eid, pipe);
+            self.notify(SocketEvent::Accepted(eid, peer_addr));
+        }
     }
 
-    pub fn close_pipe(&mut self, ctx: &mut Context, eid: EndpointId) {
-        let _ = self.remove_pipe(ctx, eid);
+    /// The address of the peer is only known once the pipe has been opened.
+    pub fn get_remote_addr(&self, ctx: &mut Context, eid: EndpointId) -> Option<String> {
+        if self.pipes.contains_key(&eid) {
+            None
+        } else {
+            ctx.remote_addr(eid)
+        }
     }
 
-    pub fn on_pipe_error(&mut self, ctx: &mut Context, eid: EndpointId, _: io::Error) {
-        if let Some(spec) = self.remove_pipe(ctx, eid) {
-            self.schedule_reconnect(ctx, eid, spec);
+    pub fn close_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> io::Result<()> {
+        let was_retrying = self.cancel_retry(ctx, eid);
+        let was_alive = self.remove_pipe(ctx, eid).is_some();
+
+        if was_retrying || was_alive {
+            Ok(())
+        } else {
+            Err(invalid_input_io_error("endpoint not found"))
+        }
+    }
+
+    pub fn on_pipe_error(&mut self, ctx: &mut Context, eid: EndpointId, err: io::Error) {
+        let spec = if let Some(pipe) = self.pipes.remove(&eid) {
+            self.on_pipe_dropped(eid, pipe.is_accepted(), err);
+            pipe.close(ctx)
+        } else if let Some(pipe) = self.protocol.remove_pipe(ctx, eid) {
+            self.on_pipe_broken(eid, err);
+            pipe.close(ctx)
+        } else {
+            None
+        };
+
+        if let Some(spec) = spec {
+            if self.is_closing == false {
+                self.schedule_reconnect(ctx, eid, spec);
+            }
         }
+
+        self.check_linger(ctx);
     }
 
     fn insert_pipe(&mut self, ctx: &mut Context, eid: EndpointId, pipe: Pipe) {
@@ -296,26 +512,64 @@ impl Socket {
             return pipe.close(ctx)
         }
         if let Some(pipe) = self.protocol.remove_pipe(ctx, eid) {
+            self.stats.current_connections -= 1;
             return pipe.close(ctx)
         }
         None
     }
 
+    fn on_pipe_established(&mut self, eid: EndpointId, pipe: &Pipe) {
+        if let Some(url) = pipe.get_url() {
+            self.stats.established_connections += 1;
+            self.notify(SocketEvent::Connected(eid, url.to_owned()));
+        } else {
+            self.stats.accepted_connections += 1;
+        }
+
+        self.stats.current_connections += 1;
+    }
+
+    /// A pipe failing before being opened is either an accepted connection
+    /// whose handshake failed, or a failed connection attempt.
+    fn on_pipe_dropped(&mut self, eid: EndpointId, accepted: bool, err: io::Error) {
+        if accepted {
+            self.stats.dropped_connections += 1;
+        } else {
+            self.stats.connect_errors += 1;
+        }
+
+        if err.kind() == io::ErrorKind::InvalidData {
+            self.notify(SocketEvent::HandshakeRejected(eid, err));
+        } else {
+            self.notify(SocketEvent::Disconnected(eid, err));
+        }
+    }
+
+    fn on_pipe_broken(&mut self, eid: EndpointId, err: io::Error) {
+        self.stats.broken_connections += 1;
+        self.stats.current_connections -= 1;
+        self.notify(SocketEvent::Disconnected(eid, err));
+    }
+
     fn connect_pipe(&self, eid: EndpointId, spec: EndpointSpec) -> Pipe {
         Pipe::from_spec(eid, spec)
     }
 
     fn accept_pipe(&self, aid: EndpointId, eid: EndpointId) -> Pipe {
-        let (send_prio, recv_prio) = if let Some(acceptor) = self.acceptors.get(&aid) {
-            (acceptor.get_send_priority(), acceptor.get_recv_priority())
+        let (send_prio, recv_prio, flags) = if let Some(acceptor) = self.acceptors.get(&aid) {
+            (acceptor.get_send_priority(), acceptor.get_recv_priority(), acceptor.get_proto_flags())
         } else {
-            (self.config.send_priority, self.config.recv_priority)
+            (self.config.send_priority, self.config.recv_priority, self.protocol.proto_flags())
         };
         let desc = EndpointDesc {
             send_priority: send_prio,
             recv_priority: recv_prio,
+            proto_flags: flags,
             tcp_no_delay: self.config.tcp_no_delay,
-            recv_max_size: self.config.recv_max_size
+            recv_max_size: self.config.recv_max_size,
+            recv_buffer: self.config.recv_buffer,
+            send_buffer: self.config.send_buffer,
+            send_buffer_policy: self.config.send_buffer_policy
         };
 
         Pipe::new_accepted(eid, desc)
@@ -327,14 +581,22 @@ impl Socket {
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn on_acceptor_error(&mut self, ctx: &mut Context, eid: EndpointId, _: io::Error) {
+    pub fn on_acceptor_error(&mut self, ctx: &mut Context, eid: EndpointId, err: io::Error) {
         if let Some(spec) = self.remove_acceptor(ctx, eid) {
+            self.notify(SocketEvent::BindFailed(eid, err));
             self.schedule_rebind(ctx, eid, spec);
         }
     }
 
-    pub fn close_acceptor(&mut self, ctx: &mut Context, eid: EndpointId) {
-        let _ = self.remove_acceptor(ctx, eid);
+    pub fn close_acceptor(&mut self, ctx: &mut Context, eid: EndpointId) -> io::Result<()> {
+        let was_retrying = self.cancel_retry(ctx, eid);
+        let was_alive = self.remove_acceptor(ctx, eid).is_some();
+
+        if was_retrying || was_alive {
+            Ok(())
+        } else {
+            Err(invalid_input_io_error("endpoint not found"))
+        }
     }
 
     fn insert_acceptor(&mut self, ctx: &mut Context, eid: EndpointId, acceptor: Acceptor) {
@@ -382,11 +644,28 @@ impl Socket {
         }
     }
 
-    pub fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
+    pub fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId, len: usize) {
         #[cfg(debug_assertions)] debug!("[{:?}] send ack from ep {:?}", ctx, eid);
+        self.stats.messages_sent += 1;
+        self.stats.bytes_sent += len as u64;
+        self.protocol.on_send_ack(ctx, eid);
+        self.check_linger(ctx);
+    }
+
+    /// The message was buffered by the pipe, the protocol can go on as if it was sent.
+    pub fn on_send_buffered(&mut self, ctx: &mut Context, eid: EndpointId) {
+        #[cfg(debug_assertions)] debug!("[{:?}] send buffered by ep {:?}", ctx, eid);
         self.protocol.on_send_ack(ctx, eid);
     }
 
+    /// A message previously buffered has been sent by the pipe.
+    pub fn on_send_flushed(&mut self, ctx: &mut Context, len: usize) {
+        #[cfg(debug_assertions)] debug!("[{:?}] send flushed", ctx);
+        self.stats.messages_sent += 1;
+        self.stats.bytes_sent += len as u64;
+        self.check_linger(ctx);
+    }
+
     pub fn on_send_timeout(&mut self, ctx: &mut Context) {
         #[cfg(debug_assertions)] debug!("[{:?}] send timeout", ctx);
         self.protocol.on_send_timeout(ctx);
@@ -432,9 +711,11 @@ impl Socket {
         }
     }
 
-    pub fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
+    pub fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Rc<Message>) {
         #[cfg(debug_assertions)] debug!("[{:?}] recv ack from ep {:?}", ctx, eid);
-        self.protocol.on_recv_ack(ctx, eid, msg);
+        self.stats.messages_received += 1;
+        self.stats.bytes_received += msg.len() as u64;
+        self.protocol.on_recv_shared_ack(ctx, eid, msg);
     }
 
     pub fn on_recv_timeout(&mut self, ctx: &mut Context) {
@@ -457,11 +738,17 @@ impl Socket {
 /*                                                                           */
 /*****************************************************************************/
 
-    pub fn set_option(&mut self, _: &mut Context, opt: ConfigOption) {
+    pub fn set_option(&mut self, ctx: &mut Context, opt: ConfigOption) {
         let res = if opt.is_generic() {
             self.config.set(opt)
         } else {
-            self.protocol.set_option(opt)
+            let res = self.protocol.set_option(opt);
+
+            if res.is_ok() {
+                self.protocol.on_option_set(ctx);
+            }
+
+            res
         };
         let reply = match res {
             Ok(()) => Reply::SetOption,
@@ -471,6 +758,20 @@ impl Socket {
         self.send_reply(reply);
     }
 
+    pub fn get_option(&self, _: &mut Context, kind: ConfigOptionKind) {
+        let res = if kind.is_generic() {
+            self.config.get(kind)
+        } else {
+            self.protocol.get_option(kind)
+        };
+        let reply = match res {
+            Ok(opt) => Reply::GetOption(opt),
+            Err(e)  => Reply::Err(e)
+        };
+
+        self.send_reply(reply);
+    }
+
     pub fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
         self.protocol.on_timer_tick(ctx, task)
     }
@@ -479,13 +780,176 @@ impl Socket {
         self.protocol.on_device_plugged(ctx)
     }
 
+/*****************************************************************************/
+/*                                                                           */
+/* statistics                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn get_statistics(&self, _: &mut Context) {
+        self.send_reply(Reply::Statistics(self.stats.clone()));
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* monitor                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Replaces the previous monitor, if any, so there is at most one receiver of the events.
+    pub fn monitor(&mut self, _: &mut Context) {
+        let (tx, rx) = channel();
+
+        self.monitor = Some(tx);
+        self.send_reply(Reply::Monitor(rx));
+    }
+
+    fn notify(&mut self, evt: SocketEvent) {
+        let is_monitor_alive = match self.monitor {
+            Some(ref tx) => tx.send(evt).is_ok(),
+            None => return
+        };
+
+        if is_monitor_alive == false {
+            self.monitor = None;
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* reply tokens                                                              */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn recv_request(&mut self, ctx: &mut Context) {
+        #[cfg(debug_assertions)] debug!("[{:?}] recv request", ctx);
+        let timeout = match self.get_recv_timeout() {
+            Some(delay) => match ctx.schedule(Schedulable::RecvTimeout, delay) {
+                Ok(timeout) => Some(timeout),
+                Err(e) => return self.send_reply(Reply::Err(e))
+            },
+            None => None
+        };
+
+        if let Err(e) = self.protocol.recv_request(ctx, timeout) {
+            self.on_token_error(ctx, timeout, e);
+        }
+    }
+
+    pub fn send_reply_with_token(&mut self, ctx: &mut Context, token: ReplyToken, msg: Message) {
+        #[cfg(debug_assertions)] debug!("[{:?}] send reply", ctx);
+        let timeout = match self.get_send_timeout() {
+            Some(delay) => match ctx.schedule(Schedulable::SendTimeout, delay) {
+                Ok(timeout) => Some(timeout),
+                Err(e) => return self.send_reply(Reply::Err(e))
+            },
+            None => None
+        };
+
+        if let Err(e) = self.protocol.send_reply(ctx, token, msg, timeout) {
+            self.on_token_error(ctx, timeout, e);
+        }
+    }
+
+    fn on_token_error(&mut self, ctx: &mut Context, timeout: Option<Scheduled>, err: io::Error) {
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+
+        self.send_reply(Reply::Err(err));
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* contexts                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    pub fn create_context(&mut self, _: &mut Context) {
+        let cid = ContextId::from(self.context_ids.next());
+        let (tx, rx) = channel();
+        let reply = match self.protocol.create_context(cid, tx) {
+            Ok(()) => Reply::ContextCreated(cid, rx),
+            Err(e) => Reply::Err(e)
+        };
+
+        self.send_reply(reply);
+    }
+
+    pub fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message) {
+        #[cfg(debug_assertions)] debug!("[{:?}] context {:?} send", ctx, cid);
+        let timeout = self.get_send_timeout().and_then(|delay| {
+            ctx.schedule(Schedulable::ContextSendTimeout(cid), delay).ok()
+        });
+
+        self.protocol.context_send(ctx, cid, msg, timeout);
+    }
+
+    pub fn context_recv(&mut self, ctx: &mut Context, cid: ContextId) {
+        #[cfg(debug_assertions)] debug!("[{:?}] context {:?} recv", ctx, cid);
+        let timeout = self.get_recv_timeout().and_then(|delay| {
+            ctx.schedule(Schedulable::ContextRecvTimeout(cid), delay).ok()
+        });
+
+        self.protocol.context_recv(ctx, cid, timeout);
+    }
+
+    pub fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        self.protocol.close_context(ctx, cid);
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* close                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Stops accepting new pipes, then waits for the messages already given 
+    /// to the pipes to be sent, or for the linger duration to elapse, before 
+    /// closing the remaining pipes.
     pub fn close(&mut self, ctx: &mut Context) {
+        self.is_closing = true;
+
         for (_, pipe) in self.pipes.drain() {
             pipe.close(ctx);
         }
         for (_, acceptor) in self.acceptors.drain() {
             acceptor.close(ctx);
         }
+        for (_, scheduled) in self.retries.drain() {
+            ctx.cancel(scheduled);
+        }
+
+        let linger = self.config.linger;
+
+        if linger == Duration::from_secs(0) || ctx.has_pending_send(self.id) == false {
+            return self.finish_close(ctx);
+        }
+
+        #[cfg(debug_assertions)] debug!("[{:?}] linger", ctx);
+        match ctx.schedule(Schedulable::Linger, linger) {
+            Ok(timeout) => self.linger_timeout = Some(timeout),
+            Err(_) => self.finish_close(ctx)
+        }
+    }
+
+    pub fn on_linger_timeout(&mut self, ctx: &mut Context) {
+        #[cfg(debug_assertions)] debug!("[{:?}] linger timeout", ctx);
+        if self.linger_timeout.take().is_some() {
+            self.finish_close(ctx);
+        }
+    }
+
+    fn check_linger(&mut self, ctx: &mut Context) {
+        if self.linger_timeout.is_some() && ctx.has_pending_send(self.id) == false {
+            self.finish_close(ctx);
+        }
+    }
+
+    fn finish_close(&mut self, ctx: &mut Context) {
+        if let Some(timeout) = self.linger_timeout.take() {
+            ctx.cancel(timeout);
+        }
 
         self.protocol.close(ctx);
 
@@ -556,6 +1020,15 @@ mod tests {
         }
         fn recv(&mut self, _: EndpointId) {
         }
+        fn has_pending_send(&self, _: SocketId) -> bool {
+            false
+        }
+        fn local_addr(&self, _: EndpointId) -> Option<String> {
+            None
+        }
+        fn remote_addr(&self, _: EndpointId) -> Option<String> {
+            None
+        }
     }
 
     impl Scheduler for FailingNetwork {
@@ -616,6 +1089,9 @@ mod tests {
         fn close(&mut self, _: EndpointId, _: bool) {}
         fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
         fn recv(&mut self, _: EndpointId) {}
+        fn has_pending_send(&self, _: SocketId) -> bool { false }
+        fn local_addr(&self, _: EndpointId) -> Option<String> { Some(String::from("127.0.0.1:5454")) }
+        fn remote_addr(&self, _: EndpointId) -> Option<String> { Some(String::from("127.0.0.1:5455")) }
     }
 
     impl Scheduler for WorkingNetwork {
@@ -659,4 +1135,477 @@ mod tests {
             },
         }
     }
+
+    #[test]
+    fn when_bind_succeeds_the_reply_carries_the_bound_addr() {
+        let id = SocketId::from(1);
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = WorkingNetwork(EndpointId::from(1));
+        let mut socket = Socket::new(id, tx, proto);
+
+        socket.bind(&mut network, String::from("tcp://127.0.0.1:0"));
+
+        let reply = rx.recv().expect("Socket should have sent a reply to the bind request");
+
+        match reply {
+            Reply::Bind(eid, local_addr) => {
+                assert_eq!(EndpointId::from(1), eid);
+                assert_eq!(Some(String::from("127.0.0.1:5454")), local_addr);
+            },
+            _ => {
+                assert!(false, "Socket should have replied an ack to the bind request");
+            },
+        }
+    }
+
+    #[test]
+    fn remote_addr_is_known_once_the_pipe_is_opened() {
+        let id = SocketId::from(1);
+        let (tx, _rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = WorkingNetwork(EndpointId::from(1));
+        let mut socket = Socket::new(id, tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.connect(&mut network, String::from("tcp://127.0.0.1:5455"));
+
+        assert_eq!(None, socket.get_remote_addr(&mut network, eid));
+
+        socket.on_pipe_opened(&mut network, eid, 0);
+
+        assert_eq!(Some(String::from("127.0.0.1:5455")), socket.get_remote_addr(&mut network, eid));
+    }
+
+    struct LingeringNetwork {
+        pending_send: bool,
+        closed: bool,
+        cancelled: bool
+    }
+
+    impl network::Network for LingeringNetwork {
+        fn connect(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+            Err(other_io_error("LingeringNetwork does not connect"))
+        }
+        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+            Err(other_io_error("LingeringNetwork does not connect"))
+        }
+        fn bind(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+            Err(other_io_error("LingeringNetwork does not bind"))
+        }
+        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+            Err(other_io_error("LingeringNetwork does not bind"))
+        }
+        fn open(&mut self, _: EndpointId, _: bool) {}
+        fn close(&mut self, _: EndpointId, _: bool) {}
+        fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
+        fn recv(&mut self, _: EndpointId) {}
+        fn has_pending_send(&self, _: SocketId) -> bool { self.pending_send }
+        fn local_addr(&self, _: EndpointId) -> Option<String> { None }
+        fn remote_addr(&self, _: EndpointId) -> Option<String> { None }
+    }
+
+    impl Scheduler for LingeringNetwork {
+        fn schedule(&mut self, _: Schedulable, _: Duration) -> io::Result<Scheduled> {
+            Ok(Scheduled::from(7))
+        }
+        fn cancel(&mut self, _: Scheduled){
+            self.cancelled = true;
+        }
+    }
+
+    impl fmt::Debug for LingeringNetwork {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "LingeringNetwork")
+        }
+    }
+
+    impl Context for LingeringNetwork {
+        fn raise(&mut self, evt: Event) {
+            if evt == Event::Closed {
+                self.closed = true;
+            }
+        }
+    }
+
+    fn new_lingering_network(pending_send: bool) -> LingeringNetwork {
+        LingeringNetwork {
+            pending_send: pending_send,
+            closed: false,
+            cancelled: false
+        }
+    }
+
+    #[test]
+    fn when_closed_without_pending_send() {
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_lingering_network(false);
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.close(&mut network);
+
+        assert!(network.closed);
+    }
+
+    #[test]
+    fn when_closed_with_pending_send_until_it_is_sent() {
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_lingering_network(true);
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.close(&mut network);
+        assert!(network.closed == false);
+
+        network.pending_send = false;
+        socket.on_send_ack(&mut network, EndpointId::from(1), 0);
+
+        assert!(network.closed);
+        assert!(network.cancelled);
+    }
+
+    #[test]
+    fn when_closed_with_pending_send_until_linger_expires() {
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_lingering_network(true);
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.close(&mut network);
+        assert!(network.closed == false);
+
+        socket.on_linger_timeout(&mut network);
+
+        assert!(network.closed);
+        assert!(network.cancelled == false);
+    }
+
+    #[test]
+    fn when_closed_with_pending_send_and_zero_linger() {
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_lingering_network(true);
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+
+        socket.set_option(&mut network, ConfigOption::Linger(Duration::from_secs(0)));
+        socket.close(&mut network);
+
+        assert!(network.closed);
+    }
+
+    struct RetryingNetwork {
+        reconnect_succeeds: bool,
+        delays: Vec<Duration>,
+        cancellations: usize
+    }
+
+    impl network::Network for RetryingNetwork {
+        fn connect(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(1))
+        }
+        fn reconnect(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+            if self.reconnect_succeeds {
+                Ok(())
+            } else {
+                Err(other_io_error("RetryingNetwork is told to fail"))
+            }
+        }
+        fn bind(&mut self, _: SocketId, _: &EndpointTmpl) -> io::Result<EndpointId> {
+            Ok(EndpointId::from(1))
+        }
+        fn rebind(&mut self, _: SocketId, _: EndpointId, _: &EndpointTmpl) -> io::Result<()> {
+            Ok(())
+        }
+        fn open(&mut self, _: EndpointId, _: bool) {}
+        fn close(&mut self, _: EndpointId, _: bool) {}
+        fn send(&mut self, _: EndpointId, _: Rc<Message>) {}
+        fn recv(&mut self, _: EndpointId) {}
+        fn has_pending_send(&self, _: SocketId) -> bool { false }
+        fn local_addr(&self, _: EndpointId) -> Option<String> { None }
+        fn remote_addr(&self, _: EndpointId) -> Option<String> { None }
+    }
+
+    impl Scheduler for RetryingNetwork {
+        fn schedule(&mut self, _: Schedulable, delay: Duration) -> io::Result<Scheduled> {
+            self.delays.push(delay);
+            Ok(Scheduled::from(self.delays.len()))
+        }
+        fn cancel(&mut self, _: Scheduled){
+            self.cancellations += 1;
+        }
+    }
+
+    impl fmt::Debug for RetryingNetwork {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "RetryingNetwork")
+        }
+    }
+
+    impl Context for RetryingNetwork {
+        fn raise(&mut self, _: Event) {
+        }
+    }
+
+    #[test]
+    fn when_reconnect_keeps_failing_the_delay_grows_until_it_is_reset() {
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_retrying_network();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+        let url = String::from("test://fake");
+
+        socket.set_option(&mut network, ConfigOption::RetryIvl(Duration::from_millis(100)));
+        socket.set_option(&mut network, ConfigOption::RetryIvlMax(Some(Duration::from_millis(400))));
+        socket.connect(&mut network, url.clone());
+        socket.on_pipe_error(&mut network, eid, other_io_error("connection refused"));
+
+        for _ in 0..3 {
+            let spec = socket.create_endpoint_spec(url.clone());
+
+            socket.reconnect(&mut network, eid, spec);
+        }
+
+        assert_eq!(4, network.delays.len());
+        assert!(network.delays[0] <= Duration::from_millis(100));
+        assert!(network.delays[1] >= Duration::from_millis(100));
+        assert!(network.delays[2] >= Duration::from_millis(200));
+        assert!(network.delays[3] >= Duration::from_millis(200));
+        assert!(network.delays[3] <= Duration::from_millis(400));
+
+        let spec = socket.create_endpoint_spec(url.clone());
+        network.reconnect_succeeds = true;
+        socket.reconnect(&mut network, eid, spec);
+        socket.on_pipe_opened(&mut network, eid, 0);
+
+        socket.connect(&mut network, url.clone());
+        socket.on_pipe_error(&mut network, eid, other_io_error("connection lost"));
+
+        assert_eq!(5, network.delays.len());
+        assert!(network.delays[4] <= Duration::from_millis(100));
+    }
+
+    fn new_retrying_network() -> RetryingNetwork {
+        RetryingNetwork {
+            reconnect_succeeds: false,
+            delays: Vec::new(),
+            cancellations: 0
+        }
+    }
+
+    #[test]
+    fn when_endpoint_is_closed_while_waiting_to_reconnect() {
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_retrying_network();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+        let url = String::from("test://fake");
+
+        socket.connect(&mut network, url.clone());
+        socket.on_pipe_error(&mut network, eid, other_io_error("connection refused"));
+        assert_eq!(1, network.delays.len());
+
+        assert!(socket.close_pipe(&mut network, eid).is_ok());
+        assert_eq!(1, network.cancellations);
+
+        network.reconnect_succeeds = true;
+        socket.reconnect(&mut network, eid, socket.create_endpoint_spec(url));
+        assert!(socket.pipes.is_empty());
+        assert!(socket.close_pipe(&mut network, eid).is_err());
+    }
+
+    #[test]
+    fn when_endpoint_is_closed_while_waiting_to_rebind() {
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_retrying_network();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+        let url = String::from("test://fake");
+
+        socket.bind(&mut network, url.clone());
+        socket.on_acceptor_error(&mut network, eid, other_io_error("accept failed"));
+        assert_eq!(1, network.delays.len());
+
+        assert!(socket.close_acceptor(&mut network, eid).is_ok());
+        assert_eq!(1, network.cancellations);
+
+        socket.rebind(&mut network, eid, socket.create_endpoint_spec(url));
+        assert!(socket.acceptors.is_empty());
+    }
+
+    struct PipeKeepingProto {
+        pipes: HashMap<EndpointId, Pipe>
+    }
+
+    impl Protocol for PipeKeepingProto {
+        fn id(&self) -> u16 {0}
+        fn peer_id(&self) -> u16 {0}
+        fn add_pipe(&mut self, _: &mut Context, eid: EndpointId, pipe: Pipe) { self.pipes.insert(eid, pipe); }
+        fn remove_pipe(&mut self, _: &mut Context, eid: EndpointId) -> Option<Pipe> { self.pipes.remove(&eid) }
+        fn send(&mut self, _: &mut Context, _: Message, _: Option<Scheduled>) {}
+        fn on_send_ack(&mut self, _: &mut Context, _: EndpointId) {}
+        fn on_send_timeout(&mut self, _: &mut Context) {}
+        fn on_send_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn recv(&mut self, _: &mut Context, _: Option<Scheduled>) {}
+        fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {}
+        fn on_recv_timeout(&mut self, _: &mut Context) {}
+        fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {}
+        fn is_send_ready(&self) -> bool { false }
+        fn is_recv_ready(&self) -> bool { false }
+        fn close(&mut self, _: &mut Context) {}
+    }
+
+    fn get_statistics(socket: &Socket, network: &mut RetryingNetwork, rx: &mpsc::Receiver<Reply>) -> Statistics {
+        socket.get_statistics(network);
+
+        match rx.try_recv() {
+            Ok(Reply::Statistics(stats)) => stats,
+            _ => panic!("socket should have sent its statistics")
+        }
+    }
+
+    #[test]
+    fn when_pipes_fail_the_statistics_tell_how() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(PipeKeepingProto { pipes: HashMap::new() }) as Box<Protocol>;
+        let mut network = new_retrying_network();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+        let url = String::from("test://fake");
+
+        socket.connect(&mut network, url.clone());
+        let _ = rx.try_recv();
+        socket.on_pipe_error(&mut network, eid, other_io_error("connection refused"));
+        socket.reconnect(&mut network, eid, socket.create_endpoint_spec(url.clone()));
+
+        let stats = get_statistics(&socket, &mut network, &rx);
+        assert_eq!(2, stats.connect_errors);
+        assert_eq!(0, stats.established_connections);
+
+        network.reconnect_succeeds = true;
+        socket.reconnect(&mut network, eid, socket.create_endpoint_spec(url.clone()));
+        socket.on_pipe_opened(&mut network, eid, 0);
+
+        let stats = get_statistics(&socket, &mut network, &rx);
+        assert_eq!(1, stats.established_connections);
+        assert_eq!(1, stats.current_connections);
+
+        socket.on_pipe_error(&mut network, eid, other_io_error("connection lost"));
+
+        let aid = EndpointId::from(2);
+        let dropped_eid = EndpointId::from(3);
+        let accepted_eid = EndpointId::from(4);
+        socket.on_pipe_accepted(&mut network, aid, dropped_eid, None);
+        socket.on_pipe_error(&mut network, dropped_eid, other_io_error("handshake failed"));
+        socket.on_pipe_accepted(&mut network, aid, accepted_eid, None);
+        socket.on_pipe_opened(&mut network, accepted_eid, 0);
+        socket.on_pipe_error(&mut network, EndpointId::from(5), other_io_error("unknown pipe"));
+
+        let stats = get_statistics(&socket, &mut network, &rx);
+        assert_eq!(2, stats.connect_errors);
+        assert_eq!(1, stats.established_connections);
+        assert_eq!(1, stats.accepted_connections);
+        assert_eq!(1, stats.broken_connections);
+        assert_eq!(1, stats.dropped_connections);
+        assert_eq!(1, stats.current_connections);
+    }
+
+    #[test]
+    fn when_messages_are_exchanged_the_statistics_count_them() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = new_retrying_network();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+
+        socket.on_send_ack(&mut network, eid, 5);
+        socket.on_send_ack(&mut network, eid, 3);
+        socket.on_recv_ack(&mut network, eid, Rc::new(Message::from_header_and_body(vec![0, 0, 0, 1], vec![65, 66])));
+
+        let stats = get_statistics(&socket, &mut network, &rx);
+        assert_eq!(2, stats.messages_sent);
+        assert_eq!(8, stats.bytes_sent);
+        assert_eq!(1, stats.messages_received);
+        assert_eq!(6, stats.bytes_received);
+    }
+
+    #[test]
+    fn when_monitored_the_endpoint_lifecycle_is_notified() {
+        let (tx, rx) = mpsc::channel();
+        let proto = Box::new(PipeKeepingProto { pipes: HashMap::new() }) as Box<Protocol>;
+        let mut network = new_retrying_network();
+        let mut socket = Socket::new(SocketId::from(1), tx, proto);
+        let eid = EndpointId::from(1);
+        let url = String::from("test://fake");
+
+        socket.monitor(&mut network);
+        let monitor = match rx.try_recv() {
+            Ok(Reply::Monitor(monitor)) => monitor,
+            _ => panic!("socket should have sent the monitor")
+        };
+
+        socket.connect(&mut network, url.clone());
+        socket.on_pipe_error(&mut network, eid, other_io_error("connection refused"));
+        network.reconnect_succeeds = true;
+        socket.reconnect(&mut network, eid, socket.create_endpoint_spec(url.clone()));
+        socket.on_pipe_opened(&mut network, eid, 0);
+
+        let aid = EndpointId::from(2);
+        let accepted_eid = EndpointId::from(3);
+        socket.on_pipe_accepted(&mut network, aid, accepted_eid, Some(String::from("127.0.0.1:5454")));
+        socket.on_pipe_error(&mut network, accepted_eid, invalid_data_io_error("received bad handshake"));
+
+        let events: Vec<SocketEvent> = monitor.try_iter().collect();
+
+        assert_eq!(5, events.len());
+        match events[0] {
+            SocketEvent::Disconnected(id, _) => assert_eq!(eid, id),
+            ref other => panic!("unexpected event {:?}", other)
+        }
+        match events[1] {
+            SocketEvent::ReconnectScheduled(id, _) => assert_eq!(eid, id),
+            ref other => panic!("unexpected event {:?}", other)
+        }
+        match events[2] {
+            SocketEvent::Connected(id, ref connected_url) => {
+                assert_eq!(eid, id);
+                assert_eq!(&url, connected_url);
+            },
+            ref other => panic!("unexpected event {:?}", other)
+        }
+        match events[3] {
+            SocketEvent::Accepted(id, ref peer_addr) => {
+                assert_eq!(accepted_eid, id);
+                assert_eq!(&Some(String::from("127.0.0.1:5454")), peer_addr);
+            },
+            ref other => panic!("unexpected event {:?}", other)
+        }
+        match events[4] {
+            SocketEvent::HandshakeRejected(id, _) => assert_eq!(accepted_eid, id),
+            ref other => panic!("unexpected event {:?}", other)
+        }
+    }
+
+    #[test]
+    fn when_a_notifier_is_set_it_is_told_the_readiness() {
+        let id = SocketId::from(1);
+        let (tx, _) = mpsc::channel();
+        let proto = Box::new(TestProto) as Box<Protocol>;
+        let mut network = FailingNetwork;
+        let mut socket = Socket::new(id, tx, proto);
+        let (notif_tx, notif_rx) = mpsc::channel();
+        let notifier: Notifier = Box::new(move |ready| notif_tx.send(ready).unwrap());
+
+        socket.set_recv_notifier(&mut network, notifier);
+        socket.on_can_recv(true);
+        socket.on_can_send(true);
+        socket.on_can_recv(false);
+
+        let notifications: Vec<bool> = notif_rx.try_iter().collect();
+
+        assert_eq!(vec![false, true, false], notifications);
+    }
 }
@@ -12,6 +12,7 @@ use std::time::Duration;
 
 use super::{SocketId, EndpointId, Message, EndpointTmpl, EndpointDesc, Scheduled};
 use super::endpoint::Pipe;
+use super::config::{SendBuffer, SendBufferPolicy};
 use super::context::{Context, Scheduler, Schedulable, Event};
 use super::network::Network;
 use io_error;
@@ -20,12 +21,28 @@ pub fn new_test_pipe(id: EndpointId) -> Pipe {
     Pipe::new_accepted(id, new_test_endpoint_desc())
 }
 
+/// Creates a pipe whose both sides sent the specified flags in the handshake.
+pub fn new_test_pipe_with_flags(id: EndpointId, flags: u8) -> Pipe {
+    let mut desc = new_test_endpoint_desc();
+
+    desc.proto_flags = flags;
+
+    let mut pipe = Pipe::new_accepted(id, desc);
+
+    pipe.set_peer_proto_flags(flags);
+    pipe
+}
+
 pub fn new_test_endpoint_desc() -> EndpointDesc {
     EndpointDesc {
         send_priority: 0,
         recv_priority: 0,
+        proto_flags: 0,
         tcp_no_delay: false,
-        recv_max_size: 1024
+        recv_max_size: 1024,
+        recv_buffer: 0,
+        send_buffer: SendBuffer::Messages(0),
+        send_buffer_policy: SendBufferPolicy::Block
     }
 }
 
@@ -114,6 +131,11 @@ impl TestContextSensor {
         assert_eq!(0, self.recv_calls.len());
     }
 
+    pub fn assert_recv_from(&self, eid: EndpointId, times: usize) {
+        let count = self.recv_calls.iter().filter(|id| **id == eid).count();
+        assert_eq!(times, count);
+    }
+
     pub fn assert_one_recv_from(&self, eid: EndpointId) {
         assert_eq!(1, self.recv_calls.len());
 
@@ -167,6 +189,15 @@ impl Network for TestContext {
     fn recv(&mut self, eid: EndpointId) {
         self.sensor.borrow_mut().push_recv_call(eid)
     }
+    fn has_pending_send(&self, _: SocketId) -> bool {
+        false
+    }
+    fn local_addr(&self, _: EndpointId) -> Option<String> {
+        None
+    }
+    fn remote_addr(&self, _: EndpointId) -> Option<String> {
+        None
+    }
 }
 
 impl Scheduler for TestContext {
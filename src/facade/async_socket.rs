@@ -0,0 +1,167 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::sync::Arc;
+use std::io;
+
+use futures::{Future, Poll, Async};
+use futures::task::AtomicTask;
+
+use super::socket::Socket;
+use core::Message;
+use core::socket::{Request, Reply, Waker};
+use io_error::*;
+
+/// Asynchronous version of the [Socket](struct.Socket.html).
+///
+/// Obtained via the socket [into_async](struct.Socket.html#method.into_async) method.
+/// Each operation borrows the socket and returns a future that completes once the operation is completed,
+/// so there is at most one operation in progress at any time. The operation starts when the future is first polled.
+/// No thread is blocked while waiting: the event loop notifies the task polling the future when the reply is available.
+/// The send and recv timeouts of the socket still apply.
+/// Endpoints and options are managed through the underlying socket, see [get_mut](#method.get_mut).
+///
+/// Dropping a future before it completes does not cancel the operation, nor close the socket.
+/// The next operation starts once the dropped one is completed,
+/// and a message received for a dropped recv future is given to the next recv.
+pub struct AsyncSocket {
+    socket: Socket,
+    task: Arc<AtomicTask>,
+    /// A request was sent whose reply has not been received yet, because its future was dropped.
+    pending: bool,
+    /// The message received for a dropped recv future.
+    received: Option<Message>
+}
+
+impl AsyncSocket {
+    #[doc(hidden)]
+    pub fn new(socket: Socket) -> io::Result<AsyncSocket> {
+        let task = Arc::new(AtomicTask::new());
+        let notified = task.clone();
+        let waker: Waker = Box::new(move || notified.notify());
+
+        try!(socket.send_request(Request::SetWaker(waker)));
+
+        Ok(AsyncSocket {
+            socket: socket,
+            task: task,
+            pending: false,
+            received: None
+        })
+    }
+
+    /// Gets a reference to the underlying socket.
+    pub fn get_ref(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Gets a mutable reference to the underlying socket, to connect, bind or set options.
+    /// Calling the blocking send and recv methods of the socket is possible, but does not make much sense.
+    pub fn get_mut(&mut self) -> &mut Socket {
+        &mut self.socket
+    }
+
+    /// Sends a buffer, the future completes once the buffer is sent.
+    pub fn send<'a>(&'a mut self, buffer: Vec<u8>) -> impl Future<Item=(), Error=io::Error> + 'a {
+        self.send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message, the future completes once the message is sent.
+    pub fn send_msg<'a>(&'a mut self, msg: Message) -> impl Future<Item=(), Error=io::Error> + 'a {
+        self.call(Request::Send(msg, false)).and_then(|reply| match reply {
+            Reply::Send => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        })
+    }
+
+    /// Receives a buffer, the future resolves to the received buffer.
+    pub fn recv<'a>(&'a mut self) -> impl Future<Item=Vec<u8>, Error=io::Error> + 'a {
+        self.recv_msg().map(|msg| msg.into())
+    }
+
+    /// Receives a message, the future resolves to the received message.
+    pub fn recv_msg<'a>(&'a mut self) -> impl Future<Item=Message, Error=io::Error> + 'a {
+        self.call(Request::Recv(false)).and_then(|reply| match reply {
+            Reply::Recv(msg) => Ok(msg),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        })
+    }
+
+    fn call(&mut self, request: Request) -> PendingReply {
+        PendingReply {
+            socket: self,
+            request: Some(request),
+            done: false
+        }
+    }
+
+    /// Receives the reply to the request of a dropped future, if any, keeping the message it may carry.
+    fn poll_pending(&mut self) -> Poll<(), io::Error> {
+        if self.pending == false {
+            return Ok(Async::Ready(()));
+        }
+
+        match try!(self.socket.try_recv_reply()) {
+            Some(reply) => {
+                self.pending = false;
+                if let Reply::Recv(msg) = reply {
+                    self.received = Some(msg);
+                }
+                Ok(Async::Ready(()))
+            },
+            None => Ok(Async::NotReady)
+        }
+    }
+}
+
+/// Future of the reply to a request sent to the event loop.
+/// The request is sent when the future is first polled, once the reply to the previous one is received.
+struct PendingReply<'a> {
+    socket: &'a mut AsyncSocket,
+    request: Option<Request>,
+    done: bool
+}
+
+impl<'a> Future for PendingReply<'a> {
+    type Item = Reply;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if self.done {
+            return Err(other_io_error("reply already received"));
+        }
+
+        // registering before checking for the reply, so a reply sent in between is not missed
+        self.socket.task.register();
+
+        if let Some(request) = self.request.take() {
+            if let Async::NotReady = try!(self.socket.poll_pending()) {
+                self.request = Some(request);
+                return Ok(Async::NotReady);
+            }
+            if let Request::Recv(_) = request {
+                if let Some(msg) = self.socket.received.take() {
+                    self.done = true;
+                    return Ok(Async::Ready(Reply::Recv(msg)));
+                }
+            }
+
+            try!(self.socket.socket.send_request(request));
+            self.socket.pending = true;
+        }
+
+        match try!(self.socket.socket.try_recv_reply()) {
+            Some(reply) => {
+                self.socket.pending = false;
+                self.done = true;
+                Ok(Async::Ready(reply))
+            },
+            None => Ok(Async::NotReady)
+        }
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::sync::mpsc;
+use std::io;
+
+use super::*;
+use reactor;
+use core::{SocketId, ContextId, Message};
+use core::socket::{Request, Reply};
+use io_error::*;
+
+#[doc(hidden)]
+pub type ReplyReceiver = mpsc::Receiver<Reply>;
+
+#[doc(hidden)]
+pub struct RequestSender {
+    req_tx: EventLoopRequestSender,
+    socket_id: SocketId,
+    id: ContextId
+}
+
+impl RequestSender {
+    pub fn new(tx: EventLoopRequestSender, sid: SocketId, cid: ContextId) -> RequestSender {
+        RequestSender {
+            req_tx: tx,
+            socket_id: sid,
+            id: cid
+        }
+    }
+    fn send(&self, req: Request) -> io::Result<()> {
+        self.req_tx.send(reactor::Request::Socket(self.socket_id, req)).map_err(from_send_error)
+    }
+}
+
+/// Context of a socket.
+///
+/// Obtained via the socket [create_context](struct.Socket.html#method.create_context) method.  
+/// A context has its own exchange state, but shares the endpoints of its socket,
+/// so each context can have a request in progress, independently from the socket and the other contexts.
+/// Contexts can be moved to other threads, letting them use the socket concurrently.  
+/// Only `Req` and `Rep` sockets support contexts.
+pub struct Context {
+    request_sender: RequestSender,
+    reply_receiver: ReplyReceiver
+}
+
+impl Context {
+    #[doc(hidden)]
+    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> Context {
+        Context {
+            request_sender: request_tx,
+            reply_receiver: reply_rx
+        }
+    }
+
+    /// Sends a buffer, using the send timeout of the socket.
+    pub fn send(&mut self, buffer: Vec<u8>) -> io::Result<()> {
+        self.send_msg(Message::from_body(buffer))
+    }
+
+    /// Sends a message, using the send timeout of the socket.
+    pub fn send_msg(&mut self, msg: Message) -> io::Result<()> {
+        let request = Request::ContextSend(self.request_sender.id, msg);
+
+        self.call(request, |reply| match reply {
+            Reply::Send => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        })
+    }
+
+    /// Receives a buffer, using the recv timeout of the socket.
+    pub fn recv(&mut self) -> io::Result<Vec<u8>> {
+        self.recv_msg().map(|msg| msg.into())
+    }
+
+    /// Receives a message, using the recv timeout of the socket.
+    pub fn recv_msg(&mut self) -> io::Result<Message> {
+        let request = Request::ContextRecv(self.request_sender.id);
+
+        self.call(request, |reply| match reply {
+            Reply::Recv(msg) => Ok(msg),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        })
+    }
+
+    fn call<T, F : FnOnce(Reply) -> io::Result<T>>(&self, request: Request, process: F) -> io::Result<T> {
+        self.request_sender.send(request).
+            and_then(|_| self.reply_receiver.receive()).
+            and_then(process)
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        let _ = self.request_sender.send(Request::CloseContext(self.request_sender.id));
+    }
+}
@@ -4,12 +4,13 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+use std::sync::mpsc;
 use std::io;
 
 use super::*;
 use reactor;
 use core::{SocketId, EndpointId};
-use core::endpoint::Request;
+use core::endpoint::{Request, Reply};
 use io_error::*;
 
 #[doc(hidden)]
@@ -36,23 +37,74 @@ impl RequestSender {
 ///   
 /// Obtained via the socket [bind](struct.Socket.html#method.bind) or 
 /// [connect](struct.Socket.html#method.connect) methods.  
-/// Can only be used to close an endpoint.  
+/// Tells the addresses it is bound or connected to, and can be used to close it.  
 /// Note that `drop(Endpoint)` will **NOT** close it.
+/// Once `close` has returned, the endpoint will not be re-established,
+/// even if it was waiting to reconnect or rebind after a failure.
 pub struct Endpoint {
     request_sender: RequestSender,
-    remote: bool
+    remote: bool,
+    url: String,
+    local_addr: Option<String>
 }
 
 impl Endpoint {
     #[doc(hidden)]
-    pub fn new(request_tx: RequestSender, remote: bool) -> Endpoint {
+    pub fn new(request_tx: RequestSender, remote: bool, url: String, local_addr: Option<String>) -> Endpoint {
         Endpoint {
             request_sender: request_tx,
-            remote: remote
+            remote: remote,
+            url: url,
+            local_addr: local_addr
+        }
+    }
+
+    /// Gets the id of the endpoint, that the socket [monitor](struct.Socket.html#method.monitor) events refer to.
+    pub fn id(&self) -> EndpointId {
+        self.request_sender.id
+    }
+
+    /// Gets the url of the endpoint.
+    /// For a bound endpoint, the address part is the one actually bound,
+    /// so `tcp://0.0.0.0:0` would give something like `tcp://0.0.0.0:43567`.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Gets the address a bound endpoint is actually bound to, 
+    /// which tells the port picked by the system when binding to port zero.
+    /// Returns `None` for connected endpoints.
+    pub fn local_addr(&self) -> Option<&str> {
+        self.local_addr.as_ref().map(|addr| addr.as_str())
+    }
+
+    /// Gets the address of the peer of a connected endpoint.
+    /// Returns `None` until the connection is established, and for bound endpoints.
+    pub fn remote_addr(&self) -> io::Result<Option<String>> {
+        if self.remote == false {
+            return Ok(None);
+        }
+
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        try!(self.request_sender.send(Request::GetRemoteAddr(reply_tx)));
+
+        match try!(reply_rx.receive()) {
+            Reply::RemoteAddr(addr) => Ok(addr),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
         }
     }
 
     pub fn close(self) -> io::Result<()> {
-        self.request_sender.send(Request::Close(self.remote))
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        try!(self.request_sender.send(Request::Close(self.remote, reply_tx)));
+
+        match try!(reply_rx.receive()) {
+            Reply::Close => Ok(()),
+            Reply::Err(e) => Err(e),
+            _ => Err(other_io_error("unexpected reply"))
+        }
     }
 }
\ No newline at end of file
@@ -7,8 +7,11 @@
 pub mod session;
 pub mod socket;
 pub mod endpoint;
+pub mod context;
+pub mod async_socket;
 pub mod device;
 pub mod probe;
+#[cfg(unix)] pub mod pollfd;
 
 use std::sync::mpsc;
 use std::io;
@@ -20,6 +23,7 @@ use io_error::*;
 
 pub trait Receiver<T> {
     fn receive(&self) -> io::Result<T>;
+    fn try_receive(&self) -> io::Result<Option<T>>;
 }
 
 impl<T> Receiver<T> for mpsc::Receiver<T> {
@@ -29,6 +33,13 @@ impl<T> Receiver<T> for mpsc::Receiver<T> {
             Err(_) => Err(other_io_error("evt channel closed")),
         }
     }
+    fn try_receive(&self) -> io::Result<Option<T>> {
+        match mpsc::Receiver::try_recv(self) {
+            Ok(t)  => Ok(Some(t)),
+            Err(mpsc::TryRecvError::Empty) => Ok(None),
+            Err(mpsc::TryRecvError::Disconnected) => Err(other_io_error("evt channel closed")),
+        }
+    }
 }
 
 pub type EventLoopRequestSender = mio::channel::Sender<reactor::Request>;
\ No newline at end of file
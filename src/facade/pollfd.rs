@@ -0,0 +1,121 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module provides the file descriptors applications can give to their own event loop
+/// to know when a socket is ready, like the `NN_RCVFD` and `NN_SNDFD` options of nanomsg.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use libc;
+
+/// A pipe whose read end is readable while the pipe is signaled.
+/// A single byte is written when it becomes signaled, and read back when it stops being so.
+pub struct PollFd {
+    reader: RawFd,
+    writer: RawFd,
+    signaled: AtomicBool
+}
+
+impl PollFd {
+    pub fn new() -> io::Result<PollFd> {
+        let mut fds = [0 as libc::c_int; 2];
+
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let fd = PollFd {
+            reader: fds[0],
+            writer: fds[1],
+            signaled: AtomicBool::new(false)
+        };
+
+        try!(set_nonblocking(fd.reader));
+        try!(set_nonblocking(fd.writer));
+
+        Ok(fd)
+    }
+
+    pub fn set(&self, signaled: bool) {
+        if self.signaled.swap(signaled, Ordering::SeqCst) == signaled {
+            return;
+        }
+
+        let mut byte = [0u8; 1];
+
+        if signaled {
+            unsafe { libc::write(self.writer, byte.as_ptr() as *const libc::c_void, 1) };
+        } else {
+            unsafe { libc::read(self.reader, byte.as_mut_ptr() as *mut libc::c_void, 1) };
+        }
+    }
+}
+
+impl AsRawFd for PollFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader
+    }
+}
+
+impl Drop for PollFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.reader);
+            libc::close(self.writer);
+        }
+    }
+}
+
+fn set_nonblocking(fd: RawFd) -> io::Result<()> {
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+
+    if flags < 0 ||
+       unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 ||
+       unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::io::AsRawFd;
+
+    use libc;
+
+    use super::*;
+
+    fn is_readable(fd: &PollFd) -> bool {
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0
+        };
+
+        unsafe { libc::poll(&mut pollfd, 1, 0) == 1 }
+    }
+
+    #[test]
+    fn fd_is_readable_only_while_signaled() {
+        let fd = PollFd::new().unwrap();
+
+        assert!(is_readable(&fd) == false);
+
+        fd.set(true);
+        fd.set(true);
+        assert!(is_readable(&fd));
+
+        fd.set(false);
+        assert!(is_readable(&fd) == false);
+
+        fd.set(false);
+        fd.set(true);
+        assert!(is_readable(&fd));
+    }
+}
@@ -106,9 +106,37 @@ impl Session {
     /// using [connect](struct.Socket.html#method.connect) and [bind](struct.Socket.html#method.bind) methods.
     pub fn create_socket<T>(&mut self) -> io::Result<socket::Socket>
     where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
+    {
+        self.create_socket_with_mode::<T, socket::Dynamic>(false)
+    }
+
+    /// Creates a raw socket with the specified protocol.
+    /// Raw sockets do not implement the end-to-end functionality of the protocol,
+    /// such as matching the replies with the requests or resending the requests.
+    /// Instead, the protocol header, including the backtrace of the endpoints a request went through,
+    /// is exposed in the `header` of the messages returned by [recv_msg](struct.Socket.html#method.recv_msg),
+    /// and must be provided in the `header` of the messages given to [send_msg](struct.Socket.html#method.send_msg).
+    /// This is what devices use to forward messages, and can be used to write brokers or load balancers.
+    pub fn create_raw_socket<T>(&mut self) -> io::Result<socket::Socket>
+    where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
+    {
+        self.create_socket_with_mode::<T, socket::Dynamic>(true)
+    }
+
+    /// Creates a socket with the specified protocol, like [create_socket](#method.create_socket) does,
+    /// except that the returned socket only has the methods supported by the protocol.
+    /// This way, receiving from a `Pub` socket or sending to a `Sub` socket is rejected at compile time.
+    pub fn create_typed_socket<T>(&mut self) -> io::Result<socket::Socket<T>>
+    where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
+    {
+        self.create_socket_with_mode::<T, T>(false)
+    }
+
+    fn create_socket_with_mode<T, P>(&mut self, raw: bool) -> io::Result<socket::Socket<P>>
+    where T : Protocol + From<mpsc::Sender<core::socket::Reply>> + 'static
     {
         let protocol_ctor = Session::create_protocol_ctor::<T>();
-        let request = Request::CreateSocket(protocol_ctor);
+        let request = Request::CreateSocket(protocol_ctor, raw);
 
         self.call(request, |reply| self.on_create_socket_reply(reply))
     }
@@ -121,7 +149,7 @@ impl Session {
         })
     }
 
-    fn on_create_socket_reply(&self, reply: Reply) -> io::Result<socket::Socket> {
+    fn on_create_socket_reply<P>(&self, reply: Reply) -> io::Result<socket::Socket<P>> {
         match reply {
             Reply::SocketCreated(id, rx) => {
                 let sender = self.request_sender.socket_sender(id);
@@ -172,7 +200,8 @@ impl Session {
 /*                                                                           */
 /*****************************************************************************/
 
-    /// Creates a probe for polling sockets
+    /// Creates a probe for polling sockets.  
+    /// A socket can be watched by several probes, and by a probe while being part of a device.
     pub fn create_probe(&mut self, poll_opts: Vec<core::PollReq>) -> io::Result<probe::Probe> {
         let request = Request::CreateProbe(poll_opts);
 
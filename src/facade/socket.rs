@@ -7,13 +7,20 @@
 use std::sync::mpsc;
 use std::io;
 use std::time::Duration;
+use std::net::SocketAddr;
+use std::marker::PhantomData;
+#[cfg(unix)] use std::sync::Arc;
+#[cfg(unix)] use std::os::unix::io::{AsRawFd, RawFd};
 
 use super::*;
 use reactor;
-use core::{SocketId, Message, PollReq};
+use core::{SocketId, Message, ReplyToken, PollReq, Statistics, SocketEvent};
 use core::socket::{Request, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core;
+use proto::{CanSend, CanRecv, CanReplyLater, CanCreateContext};
+use proto::sub::Sub;
+#[cfg(unix)] use super::pollfd::PollFd;
 use io_error::*;
 
 #[doc(hidden)]
@@ -35,6 +42,9 @@ impl RequestSender {
     fn child_sender(&self, eid: core::EndpointId) -> endpoint::RequestSender {
         endpoint::RequestSender::new(self.req_tx.clone(), self.socket_id, eid)
     }
+    fn context_sender(&self, cid: core::ContextId) -> context::RequestSender {
+        context::RequestSender::new(self.req_tx.clone(), self.socket_id, cid)
+    }
     fn send(&self, req: Request) -> io::Result<()> {
         self.req_tx.send(reactor::Request::Socket(self.socket_id, req)).map_err(from_send_error)
     }
@@ -43,18 +53,37 @@ impl RequestSender {
 /// Socket is what applications use to exchange messages.  
 ///   
 /// It is an abstraction of an application's "connection" to a messaging topology.
-/// Applications can have more than one Socket open at a time.
-pub struct Socket {
+/// Applications can have more than one Socket open at a time.  
+///   
+/// The type parameter tells which protocol the socket uses, and thus which operations are available.
+/// Sockets created with [create_typed_socket](struct.Session.html#method.create_typed_socket) 
+/// only have the methods supported by their protocol, a `Socket<Pub>` has no `recv` method for example.
+/// Other sockets are `Socket<Dynamic>`, where the operations the protocol does not support fail at runtime.
+pub struct Socket<P = Dynamic> {
     request_sender: RequestSender,
-    reply_receiver: ReplyReceiver
+    reply_receiver: ReplyReceiver,
+    protocol: PhantomData<fn() -> P>,
+    #[cfg(unix)] recv_fd: Option<Arc<PollFd>>,
+    #[cfg(unix)] send_fd: Option<Arc<PollFd>>
 }
 
-impl Socket {
+/// Protocol of a socket that is only known at runtime, all the socket operations are available.
+pub struct Dynamic;
+
+impl CanSend for Dynamic {}
+impl CanRecv for Dynamic {}
+impl CanReplyLater for Dynamic {}
+impl CanCreateContext for Dynamic {}
+
+impl<P> Socket<P> {
     #[doc(hidden)]
-    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> Socket {
+    pub fn new(request_tx: RequestSender, reply_rx: ReplyReceiver) -> Socket<P> {
         Socket {
             request_sender: request_tx,
-            reply_receiver: reply_rx
+            reply_receiver: reply_rx,
+            protocol: PhantomData,
+            #[cfg(unix)] recv_fd: None,
+            #[cfg(unix)] send_fd: None
         }
     }
 
@@ -90,14 +119,14 @@ impl Socket {
     pub fn connect(&mut self, url: &str) -> io::Result<endpoint::Endpoint> {
         let request = Request::Connect(From::from(url));
 
-        self.call(request, |reply| self.on_connect_reply(reply))
+        self.call(request, |reply| self.on_connect_reply(reply, url))
     }
 
-    fn on_connect_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
+    fn on_connect_reply(&self, reply: Reply, url: &str) -> io::Result<endpoint::Endpoint> {
         match reply {
             Reply::Connect(id) => {
                 let request_tx = self.request_sender.child_sender(id);
-                let ep = endpoint::Endpoint::new(request_tx, true);
+                let ep = endpoint::Endpoint::new(request_tx, true, From::from(url), None);
                 
                 Ok(ep)
             },
@@ -119,17 +148,23 @@ impl Socket {
     /// Note that bind and connect may be called multiple times on the same socket,
     /// thus allowing the socket to communicate with multiple heterogeneous endpoints.
     /// On success, returns an [Endpoint](struct.Endpoint.html) that can be later used to remove the endpoint from the socket.
+    /// When binding to port zero or to a wildcard address, the endpoint [local_addr](struct.Endpoint.html#method.local_addr)
+    /// tells the address the system actually picked.
     pub fn bind(&mut self, url: &str) -> io::Result<endpoint::Endpoint> {
         let request = Request::Bind(From::from(url));
 
-        self.call(request, |reply| self.on_bind_reply(reply))
+        self.call(request, |reply| self.on_bind_reply(reply, url))
     }
 
-    fn on_bind_reply(&self, reply: Reply) -> io::Result<endpoint::Endpoint> {
+    fn on_bind_reply(&self, reply: Reply, url: &str) -> io::Result<endpoint::Endpoint> {
         match reply {
-            Reply::Bind(id) => {
+            Reply::Bind(id, local_addr) => {
                 let request_tx = self.request_sender.child_sender(id);
-                let ep = endpoint::Endpoint::new(request_tx, false);
+                let bound_url = match local_addr {
+                    Some(ref addr) => bound_url(url, addr),
+                    None => From::from(url)
+                };
+                let ep = endpoint::Endpoint::new(request_tx, false, bound_url, local_addr);
                 
                 Ok(ep)
             },
@@ -138,6 +173,10 @@ impl Socket {
         }
     }
 
+}
+
+impl<P : CanSend> Socket<P> {
+
 /*****************************************************************************/
 /*                                                                           */
 /* send                                                                      */
@@ -178,6 +217,32 @@ impl Socket {
         }
     }
 
+    /// Gets a file descriptor that is readable while a message can be sent without blocking,
+    /// like the `NN_SNDFD` option of nanomsg. It lets an external event loop wait for the socket,
+    /// but must only be polled: reading from it or closing it would break the notifications.
+    /// The same descriptor is returned on each call, and stays valid as long as the socket.
+    #[cfg(unix)]
+    pub fn send_fd(&mut self) -> io::Result<RawFd> {
+        if let Some(ref fd) = self.send_fd {
+            return Ok(fd.as_raw_fd());
+        }
+
+        let fd = Arc::new(try!(PollFd::new()));
+        let notified = fd.clone();
+
+        try!(self.send_request(Request::SetSendNotifier(Box::new(move |ready| notified.set(ready)))));
+
+        let raw_fd = fd.as_raw_fd();
+
+        self.send_fd = Some(fd);
+
+        Ok(raw_fd)
+    }
+
+}
+
+impl<P : CanRecv> Socket<P> {
+
 /*****************************************************************************/
 /*                                                                           */
 /* recv                                                                      */
@@ -216,6 +281,123 @@ impl Socket {
         }
     }
 
+    /// Gets a file descriptor that is readable while a message can be received without blocking,
+    /// like the `NN_RCVFD` option of nanomsg. It lets an external event loop wait for the socket,
+    /// but must only be polled: reading from it or closing it would break the notifications.
+    /// The same descriptor is returned on each call, and stays valid as long as the socket.
+    #[cfg(unix)]
+    pub fn recv_fd(&mut self) -> io::Result<RawFd> {
+        if let Some(ref fd) = self.recv_fd {
+            return Ok(fd.as_raw_fd());
+        }
+
+        let fd = Arc::new(try!(PollFd::new()));
+        let notified = fd.clone();
+
+        try!(self.send_request(Request::SetRecvNotifier(Box::new(move |ready| notified.set(ready)))));
+
+        let raw_fd = fd.as_raw_fd();
+
+        self.recv_fd = Some(fd);
+
+        Ok(raw_fd)
+    }
+
+}
+
+impl<P : CanReplyLater> Socket<P> {
+
+/*****************************************************************************/
+/*                                                                           */
+/* reply tokens                                                              */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Receives a request along with the token required to reply to it.
+    /// Unlike with `recv`, other requests can be received before this one is replied,
+    /// so the requests can be handed to worker threads, and the replies sent back in any order.  
+    /// Only `Rep` sockets support reply tokens, and not when they are raw.
+    pub fn recv_request(&mut self) -> io::Result<(Vec<u8>, ReplyToken)> {
+        self.recv_request_msg().map(|(msg, token)| (msg.into(), token))
+    }
+
+    /// Receives a request message along with the token required to reply to it.
+    pub fn recv_request_msg(&mut self) -> io::Result<(Message, ReplyToken)> {
+        self.call(Request::RecvRequest, |reply| self.on_recv_request_reply(reply))
+    }
+
+    fn on_recv_request_reply(&self, reply: Reply) -> io::Result<(Message, ReplyToken)> {
+        match reply {
+            Reply::RecvRequest(msg, token) => Ok((msg, token)),
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+    /// Sends a buffer as the reply to the request identified by the token.
+    /// Fails if the peer that sent the request is no longer connected.
+    pub fn send_reply(&mut self, token: ReplyToken, buffer: Vec<u8>) -> io::Result<()> {
+        self.send_reply_msg(token, Message::from_body(buffer))
+    }
+
+    /// Sends a message as the reply to the request identified by the token.
+    /// Fails if the peer that sent the request is no longer connected.
+    pub fn send_reply_msg(&mut self, token: ReplyToken, msg: Message) -> io::Result<()> {
+        let request = Request::SendReply(token, msg);
+
+        self.call(request, |reply| self.on_send_reply(reply))
+    }
+
+}
+
+impl Socket {
+
+/*****************************************************************************/
+/*                                                                           */
+/* async                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Turns the socket into an [AsyncSocket](struct.AsyncSocket.html),
+    /// whose send and recv operations return futures instead of blocking the calling thread.
+    pub fn into_async(self) -> io::Result<async_socket::AsyncSocket> {
+        async_socket::AsyncSocket::new(self)
+    }
+
+}
+
+impl<P : CanCreateContext> Socket<P> {
+
+/*****************************************************************************/
+/*                                                                           */
+/* contexts                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Creates a context, an independent state machine sharing the endpoints of the socket.
+    /// Each context can have its own request in progress, which lets several threads
+    /// use the same set of connections concurrently, without opening a socket per thread.
+    /// Only `Req` and `Rep` sockets support contexts, and not when they are raw.
+    pub fn create_context(&mut self) -> io::Result<context::Context> {
+        self.call(Request::CreateContext, |reply| self.on_create_context_reply(reply))
+    }
+
+    fn on_create_context_reply(&self, reply: Reply) -> io::Result<context::Context> {
+        match reply {
+            Reply::ContextCreated(id, rx) => {
+                let request_tx = self.request_sender.context_sender(id);
+
+                Ok(context::Context::new(request_tx, rx))
+            },
+            Reply::Err(e) => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+}
+
+impl<P> Socket<P> {
+
 /*****************************************************************************/
 /*                                                                           */
 /* options                                                                   */
@@ -280,6 +462,66 @@ impl Socket {
         }
     }
 
+    /// Gets the current value of a socket option.
+    /// See [ConfigOptionKind](core/config/enum.ConfigOptionKind.html) to get the list of options.
+    pub fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        let request = Request::GetOption(kind);
+
+        self.call(request, |reply| self.on_get_option_reply(reply))
+    }
+
+    fn on_get_option_reply(&self, reply: Reply) -> io::Result<ConfigOption> {
+        match reply {
+            Reply::GetOption(opt) => Ok(opt),
+            Reply::Err(e)         => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* statistics                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Gets the counters describing the activity of the socket since its creation, 
+    /// such as the number of messages sent or of connections broken.  
+    /// See [Statistics](struct.Statistics.html) to get the list of counters.
+    pub fn statistics(&self) -> io::Result<Statistics> {
+        self.call(Request::GetStatistics, |reply| self.on_statistics_reply(reply))
+    }
+
+    fn on_statistics_reply(&self, reply: Reply) -> io::Result<Statistics> {
+        match reply {
+            Reply::Statistics(stats) => Ok(stats),
+            Reply::Err(e)            => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
+/*****************************************************************************/
+/*                                                                           */
+/* monitor                                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+    /// Gets a channel receiving the lifecycle events of the socket endpoints, 
+    /// such as connections being established, accepted or lost.  
+    /// The events refer to the [endpoint ids](struct.Endpoint.html#method.id), 
+    /// and the pipes accepted by a bound endpoint have their own ids.  
+    /// A socket has at most one monitor, calling this method again disconnects the previous one.
+    pub fn monitor(&mut self) -> io::Result<mpsc::Receiver<SocketEvent>> {
+        self.call(Request::Monitor, |reply| self.on_monitor_reply(reply))
+    }
+
+    fn on_monitor_reply(&self, reply: Reply) -> io::Result<mpsc::Receiver<SocketEvent>> {
+        match reply {
+            Reply::Monitor(rx) => Ok(rx),
+            Reply::Err(e)      => Err(e),
+            _ => self.unexpected_reply()
+        }
+    }
+
 /*****************************************************************************/
 /*                                                                           */
 /* backend                                                                   */
@@ -294,7 +536,8 @@ impl Socket {
         self.send_request(request).and_then(|_| self.recv_reply())
     }
 
-    fn send_request(&self, request: Request) -> io::Result<()> {
+    #[doc(hidden)]
+    pub fn send_request(&self, request: Request) -> io::Result<()> {
         self.request_sender.send(request)
     }
 
@@ -302,14 +545,79 @@ impl Socket {
         self.reply_receiver.receive()
     }
 
+    #[doc(hidden)]
+    pub fn try_recv_reply(&self) -> io::Result<Option<Reply>> {
+        self.reply_receiver.try_receive()
+    }
+
     fn unexpected_reply<T>(&self) -> io::Result<T> {
         Err(other_io_error("unexpected reply"))
     }
 }
 
-impl Drop for Socket {
+impl Socket<Sub> {
+
+    /// Subscribes to the messages starting with the specified topic.
+    /// A single `Sub` socket can handle multiple subscriptions.
+    pub fn subscribe(&mut self, topic: &[u8]) -> io::Result<()> {
+        self.set_option(ConfigOption::SubscribeTopic(topic.to_vec()))
+    }
+
+    /// Unsubscribes from the specified topic.
+    pub fn unsubscribe(&mut self, topic: &[u8]) -> io::Result<()> {
+        self.set_option(ConfigOption::UnsubscribeTopic(topic.to_vec()))
+    }
+}
+
+impl<P> Drop for Socket<P> {
     fn drop(&mut self) {
         let _ = self.send_request(Request::Close);
-        let _ = self.recv_reply();
+
+        // the replies to the requests of dropped futures come before the channel closes along with the socket
+        while self.recv_reply().is_ok() {}
+    }
+}
+
+/// Replaces the host and port of a bound url by the socket address the system picked,
+/// keeping the scheme, any `iface;` prefix and the path. Urls of transports not bound to
+/// a socket address are left untouched.
+fn bound_url(url: &str, local_addr: &str) -> String {
+    let addr_start = match url.find("://") {
+        Some(index) => index + 3,
+        None => return From::from(url)
+    };
+
+    if local_addr.parse::<SocketAddr>().is_err() {
+        return From::from(url);
+    }
+
+    let host_start = url[addr_start..].find(';').map_or(addr_start, |index| addr_start + index + 1);
+    let host_end = url[host_start..].find('/').map_or(url.len(), |index| host_start + index);
+
+    format!("{}{}{}", &url[..host_start], local_addr, &url[host_end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bound_url;
+
+    #[test]
+    fn bound_url_has_the_picked_port() {
+        assert_eq!("tcp://127.0.0.1:5454", bound_url("tcp://127.0.0.1:0", "127.0.0.1:5454"));
+        assert_eq!("tcp://0.0.0.0:5454", bound_url("tcp://*:0", "0.0.0.0:5454"));
+        assert_eq!("tcp://[::1]:5454", bound_url("tcp://[::1]:0", "[::1]:5454"));
+    }
+
+    #[test]
+    fn bound_url_keeps_the_path_and_the_interface() {
+        assert_eq!("ws://127.0.0.1:5454/chat", bound_url("ws://127.0.0.1:0/chat", "127.0.0.1:5454"));
+        assert_eq!("ws://127.0.0.1:5454/", bound_url("ws://127.0.0.1:0/", "127.0.0.1:5454"));
+        assert_eq!("tcp://eth0;127.0.0.1:5454", bound_url("tcp://eth0;127.0.0.1:0", "127.0.0.1:5454"));
+    }
+
+    #[test]
+    fn bound_url_is_kept_when_not_bound_to_a_socket_address() {
+        assert_eq!("ipc:///tmp/test.ipc", bound_url("ipc:///tmp/test.ipc", "/tmp/test.ipc"));
+        assert_eq!("inproc://test", bound_url("inproc://test", "test"));
     }
 }
@@ -29,6 +29,18 @@ pub fn timedout_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error
     io::Error::new(io::ErrorKind::TimedOut, msg)
 }
 
+pub fn addr_in_use_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::AddrInUse, msg)
+}
+
+pub fn connection_refused_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::ConnectionRefused, msg)
+}
+
+pub fn connection_reset_io_error<E>(msg: E) -> io::Error where E: Into<Box<error::Error + Send + Sync>> {
+    io::Error::new(io::ErrorKind::ConnectionReset, msg)
+}
+
 pub fn from_send_error<T>(send_error: mio::channel::SendError<T>) -> io::Error {
     match send_error {
         mio::channel::SendError::Io(e) => e,
@@ -10,7 +10,7 @@
 //! # Goals
 //!
 //! * Support for all of nanomsg's protocols.
-//! * Support for TCP and IPC transports.
+//! * Support for TCP, IPC, inproc and WebSocket transports, TCP optionally secured with TLS.
 //! * Idiomatic rust API first, mimic the original C API second.
 //! * Extensibility: allow user code to define additional protocols and transports
 //!
@@ -66,7 +66,17 @@ extern crate time;
 extern crate byteorder;
 extern crate mio;
 extern crate mio_uds;
-
+extern crate iovec;
+extern crate rand;
+extern crate sha1;
+extern crate base64;
+extern crate futures;
+extern crate net2;
+#[cfg(feature = "tls")]
+extern crate openssl;
+
+#[cfg(unix)]
+extern crate libc;
 #[cfg(windows)]
 extern crate mio_named_pipes;
 #[cfg(windows)]
@@ -88,17 +98,27 @@ mod io_error;
 
 pub use facade::session::SessionBuilder;
 pub use facade::session::Session;
-pub use facade::socket::Socket;
+pub use facade::socket::{Socket, Dynamic};
 pub use facade::device::Device;
 pub use facade::probe::Probe;
 pub use facade::endpoint::Endpoint;
+pub use facade::context::Context;
+pub use facade::async_socket::AsyncSocket;
 pub use core::Message;
+pub use core::ReplyToken;
+pub use core::Statistics;
+pub use core::SocketEvent;
+pub use core::EndpointId;
 pub use core::PollReq;
 pub use core::PollRes;
-pub use core::config::ConfigOption;
+pub use core::config::{ConfigOption, ConfigOptionKind, SendBuffer, SendBufferPolicy};
 
 pub use transport::tcp::Tcp;
 pub use transport::ipc::Ipc;
+pub use transport::inproc::Inproc;
+pub use transport::ws::Ws;
+#[cfg(feature = "tls")]
+pub use transport::tcp::tls::{TlsTcp, TlsTcpBuilder};
 
 pub use proto::pair::Pair;
 pub use proto::publ::Pub;
@@ -19,13 +19,47 @@ pub mod bus;
 
 mod pipes;
 mod priolist;
+mod trie;
 mod policy;
 
-use core::Scheduled;
+use core::{Message, Scheduled};
 
 #[doc(hidden)]
 pub type Timeout = Option<Scheduled>;
 
+/// Implemented by the protocols whose sockets can send messages.
+pub trait CanSend {}
+
+/// Implemented by the protocols whose sockets can receive messages.
+pub trait CanRecv {}
+
+/// Implemented by the protocols whose sockets can receive requests and reply to them later,
+/// see [recv_request](../struct.Socket.html#method.recv_request).
+pub trait CanReplyLater : CanSend + CanRecv {}
+
+/// Implemented by the protocols whose sockets can have [contexts](../struct.Context.html).
+pub trait CanCreateContext {}
+
+impl CanSend for pair::Pair {}
+impl CanRecv for pair::Pair {}
+impl CanSend for push::Push {}
+impl CanRecv for pull::Pull {}
+impl CanSend for req::Req {}
+impl CanRecv for req::Req {}
+impl CanCreateContext for req::Req {}
+impl CanSend for rep::Rep {}
+impl CanRecv for rep::Rep {}
+impl CanReplyLater for rep::Rep {}
+impl CanCreateContext for rep::Rep {}
+impl CanSend for publ::Pub {}
+impl CanRecv for sub::Sub {}
+impl CanSend for surv::Surveyor {}
+impl CanRecv for surv::Surveyor {}
+impl CanSend for resp::Respondent {}
+impl CanRecv for resp::Respondent {}
+impl CanSend for bus::Bus {}
+impl CanRecv for bus::Bus {}
+
 /// **One-to-one protocol**   
 ///   
 /// Pair protocol is the simplest and least scalable scalability protocol. 
@@ -57,11 +91,13 @@ pub const PAIR:       u16 = (    16)    ;
 /// in the incomming message, up to the size of the topic.  
 /// Subscribing via [`Socket::set_option`](struct.Socket.html#method.set_option) and [`ConfigOption::Subscribe`](../enum.ConfigOption.html#variant.Subscribe)
 /// Will match any message with intial 5 bytes being "Hello", for example, message "Hello, World!" will match.
+/// Topics that are not text can be given with [`ConfigOption::SubscribeTopic`](../enum.ConfigOption.html#variant.SubscribeTopic).
 /// Topic with zero length matches any message.
 /// If the socket is subscribed to multiple topics, 
 /// message matching any of them will be delivered to the user.
 /// Since the filtering is performed on the Subscriber side, 
-/// all the messages from Publisher will be sent over the transport layer.
+/// all the messages from Publisher will be sent over the transport layer,
+/// unless both sides enable [`ConfigOption::SubscriptionForwarding`](../enum.ConfigOption.html#variant.SubscriptionForwarding).
 /// The entire message, including the topic, is delivered to the user.  
 ///   
 /// This socket is used to distribute messages to multiple destinations. Receive operation is not defined.
@@ -147,3 +183,43 @@ pub const RESPONDENT: u16 = (6 * 16) + 3;
 /// Sent messages are distributed to all nodes in the topology. 
 /// Incoming messages from all other nodes in the topology are fair-queued in the socket.
 pub const BUS:        u16 = (7 * 16)    ;
+
+/// Handshake flag of the `Pub` and `Sub` sockets that forward the subscriptions.
+/// When both ends of a pipe send it, the `Sub` socket sends a message to the `Pub` socket
+/// each time it subscribes or unsubscribes, made of `SUBSCRIBE` or `UNSUBSCRIBE` followed by the topic.
+const SUB_FORWARDING: u8 = 1;
+const SUBSCRIBE: u8 = 1;
+const UNSUBSCRIBE: u8 = 0;
+
+/// Takes the next 4 bytes hop off the routing information of a received message.
+/// Stream based pipes deliver that information at the beginning of the body,
+/// while inproc pipes hand it over in the header, as the sender built it.
+fn pop_hop(raw_msg: &mut Message) -> Option<[u8; 4]> {
+    let mut hop: [u8; 4] = [0; 4];
+    let source = if raw_msg.header.is_empty() {
+        &mut raw_msg.body
+    } else {
+        &mut raw_msg.header
+    };
+
+    if source.len() < 4 {
+        return None;
+    }
+
+    hop.copy_from_slice(&source[0..4]);
+    source.drain(0..4);
+
+    Some(hop)
+}
+
+/// Gets the payload left in a received message once its routing information has been popped.
+fn into_payload(raw_msg: Message) -> Vec<u8> {
+    let (mut header, body) = raw_msg.split();
+
+    if header.is_empty() {
+        body
+    } else {
+        header.extend_from_slice(&body);
+        header
+    }
+}
@@ -36,6 +36,10 @@ impl PipeCollection {
         self.pipes.remove(id)
     }
 
+    pub fn contains(&self, id: &EndpointId) -> bool {
+        self.pipes.contains_key(id)
+    }
+
     pub fn get_mut(&mut self, id: &EndpointId) -> Option<&mut Pipe> {
         self.pipes.get_mut(id)
     }
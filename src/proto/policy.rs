@@ -35,6 +35,20 @@ pub mod broadcast {
         }
         bc.insert(except);
     }
+    pub fn send_to_matching<F>(
+        bc: &mut HashSet<EndpointId>, 
+        pipes: &mut PipeCollection,
+        ctx: &mut Context, 
+        msg: Rc<Message>, 
+        matches: F) where F : Fn(&EndpointId) -> bool {
+
+        let ids: Vec<EndpointId> = bc.iter().filter(|x| matches(x)).cloned().collect();
+
+        for id in ids {
+            bc.remove(&id);
+            pipes.get_mut(&id).map(|pipe| pipe.send(ctx, msg.clone()));
+        }
+    }
 }
 
 pub mod fair_queue {
@@ -4,23 +4,28 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
+use std::io;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, EndpointId, Message};
 use core::socket::{Protocol, Reply};
 use core::endpoint::Pipe;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::context::{Context, Event};
 use super::pipes::PipeCollection;
-use super::{Timeout, PUB, SUB};
+use super::trie::Trie;
+use super::{Timeout, PUB, SUB, SUB_FORWARDING, SUBSCRIBE, UNSUBSCRIBE};
 use super::policy::broadcast;
 use io_error::*;
 
 pub struct Pub {
     reply_tx: Sender<Reply>,
     pipes: PipeCollection,
-    bc: HashSet<EndpointId>
+    bc: HashSet<EndpointId>,
+    forwarding: bool,
+    filters: HashMap<EndpointId, Trie, BuildIdHasher>
 }
 
 /*****************************************************************************/
@@ -34,7 +39,9 @@ impl From<Sender<Reply>> for Pub {
         Pub {
             reply_tx: tx,
             pipes: PipeCollection::new(),
-            bc: HashSet::new()
+            bc: HashSet::new(),
+            forwarding: false,
+            filters: HashMap::default()
         }
     }
 }
@@ -50,9 +57,16 @@ impl Protocol for Pub {
     fn peer_id(&self) -> u16 { SUB }
 
     fn add_pipe(&mut self, _: &mut Context, eid: EndpointId, pipe: Pipe) {
+        let flags = pipe.get_proto_flags() & pipe.get_peer_proto_flags();
+
+        if flags & SUB_FORWARDING != 0 {
+            self.filters.insert(eid, Trie::new());
+        }
+
         self.pipes.insert(eid, pipe);
     }
     fn remove_pipe(&mut self, ctx: &mut Context, eid: EndpointId) -> Option<Pipe> {
+        self.filters.remove(&eid);
         self.bc.remove(&eid);
         if self.bc.is_empty() {
             ctx.raise(Event::CanSend(false));
@@ -61,9 +75,13 @@ impl Protocol for Pub {
     }
     fn send(&mut self, ctx: &mut Context, msg: Message, timeout: Timeout) {
         let msg = Rc::new(msg);
+        let filters = &self.filters;
+        let is_wanted_by = |eid: &EndpointId| filters.get(eid).map_or(true, |f| f.matches(msg.get_body()));
 
-        broadcast::send_to_all(&mut self.bc, &mut self.pipes, ctx, msg);
-        ctx.raise(Event::CanSend(false));
+        broadcast::send_to_matching(&mut self.bc, &mut self.pipes, ctx, msg.clone(), is_wanted_by);
+        if self.bc.is_empty() {
+            ctx.raise(Event::CanSend(false));
+        }
 
         let _ = self.reply_tx.send(Reply::Send);
         if let Some(sched) = timeout {
@@ -87,11 +105,23 @@ impl Protocol for Pub {
             ctx.cancel(sched);
         }
     }
-    fn on_recv_ack(&mut self, _: &mut Context, _: EndpointId, _: Message) {
+    fn on_recv_ack(&mut self, _: &mut Context, eid: EndpointId, msg: Message) {
+        if let Some(filter) = self.filters.get_mut(&eid) {
+            let body = msg.get_body();
+
+            match body.first() {
+                Some(&SUBSCRIBE)   => { filter.insert(&body[1..]); },
+                Some(&UNSUBSCRIBE) => { filter.remove(&body[1..]); },
+                _ => {}
+            }
+        }
     }
     fn on_recv_timeout(&mut self, _: &mut Context) {
     }
-    fn on_recv_ready(&mut self, _: &mut Context, _: EndpointId) {
+    fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if self.filters.contains_key(&eid) {
+            self.pipes.recv_from(ctx, eid);
+        }
     }
     fn is_send_ready(&self) -> bool {
         !self.bc.is_empty()
@@ -99,7 +129,116 @@ impl Protocol for Pub {
     fn is_recv_ready(&self) -> bool {
         false
     }
+    fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
+        match opt {
+            ConfigOption::SubscriptionForwarding(x) => Ok(self.forwarding = x),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::SubscriptionForwarding => Ok(ConfigOption::SubscriptionForwarding(self.forwarding)),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
+    fn proto_flags(&self) -> u8 {
+        if self.forwarding { SUB_FORWARDING } else { 0 }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.pipes.close_all(ctx)
     }
 }
+
+/*****************************************************************************/
+/*                                                                           */
+/* tests                                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc;
+
+    use core::{EndpointId, Message};
+    use core::socket::Protocol;
+    use core::context::{Event};
+    use core::config::ConfigOption;
+    use core::tests::*;
+
+    use super::*;
+
+    #[test]
+    fn send_is_filtered_on_the_subscriptions_forwarded_by_the_peer() {
+        let (tx, _) = mpsc::channel();
+        let mut publ = Pub::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let plain = EndpointId::from(1);
+        let forwarding = EndpointId::from(2);
+
+        let _ = publ.set_option(ConfigOption::SubscriptionForwarding(true));
+        publ.add_pipe(&mut ctx, plain, new_test_pipe(plain));
+        publ.add_pipe(&mut ctx, forwarding, new_test_pipe_with_flags(forwarding, SUB_FORWARDING));
+        publ.on_recv_ready(&mut ctx, forwarding);
+        publ.on_recv_ack(&mut ctx, forwarding, Message::from_body(vec![SUBSCRIBE, b'A']));
+        publ.on_send_ready(&mut ctx, plain);
+        publ.on_send_ready(&mut ctx, forwarding);
+
+        publ.send(&mut ctx, Message::from_body(b"B".to_vec()), None);
+
+        ctx_sensor.borrow().assert_one_send_to(plain);
+        ctx_sensor.borrow().assert_send_to(forwarding, 0);
+        assert!(publ.is_send_ready());
+
+        publ.send(&mut ctx, Message::from_body(b"AB".to_vec()), None);
+
+        let sensor = ctx_sensor.borrow();
+        let raised_evts = sensor.get_raised_events();
+
+        sensor.assert_one_recv_from(forwarding);
+        sensor.assert_send_to(plain, 1);
+        sensor.assert_send_to(forwarding, 1);
+        assert_eq!(Event::CanSend(false), raised_evts[raised_evts.len() - 1]);
+    }
+
+    #[test]
+    fn unsubscribed_topics_are_no_longer_sent() {
+        let (tx, _) = mpsc::channel();
+        let mut publ = Pub::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+
+        let _ = publ.set_option(ConfigOption::SubscriptionForwarding(true));
+        publ.add_pipe(&mut ctx, eid, new_test_pipe_with_flags(eid, SUB_FORWARDING));
+        publ.on_recv_ack(&mut ctx, eid, Message::from_body(vec![SUBSCRIBE, b'A']));
+        publ.on_recv_ack(&mut ctx, eid, Message::from_body(vec![UNSUBSCRIBE, b'A']));
+        publ.on_send_ready(&mut ctx, eid);
+        publ.send(&mut ctx, Message::from_body(b"A".to_vec()), None);
+
+        ctx_sensor.borrow().assert_no_send_call();
+    }
+
+    #[test]
+    fn pipes_are_not_filtered_unless_both_sides_agree() {
+        let (tx, _) = mpsc::channel();
+        let mut publ = Pub::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let mut pipe = new_test_pipe(eid);
+
+        pipe.set_peer_proto_flags(SUB_FORWARDING);
+        publ.add_pipe(&mut ctx, eid, pipe);
+        publ.on_recv_ready(&mut ctx, eid);
+        publ.on_send_ready(&mut ctx, eid);
+        publ.send(&mut ctx, Message::from_body(b"A".to_vec()), None);
+
+        let sensor = ctx_sensor.borrow();
+        sensor.assert_no_recv_call();
+        sensor.assert_one_send_to(eid);
+    }
+}
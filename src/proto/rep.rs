@@ -4,25 +4,34 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
+use std::mem;
 use std::sync::mpsc::Sender;
+use std::io;
 
 use byteorder::*;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, EndpointId, ContextId, Message, ReplyToken};
 use core::socket::{Protocol, Reply};
 use core::endpoint::Pipe;
-use core::context::Context;
+use core::context::{Context, Schedulable};
 use super::priolist::Priolist;
 use super::pipes::PipeCollection;
-use super::{Timeout, REQ, REP};
+use super::{Timeout, REQ, REP, pop_hop, into_payload};
 use super::policy::fair_queue;
 use io_error::*;
 
 pub struct Rep {
     inner: Inner,
-    state: Option<State>
+    state: Option<State>,
+    contexts: HashMap<ContextId, RepContext, BuildIdHasher>
+}
+
+/// State machine of a context, swapped with the one of the socket while the context is being operated.
+struct RepContext {
+    state: Option<State>,
+    exchange: Exchange
 }
 
 enum State {
@@ -35,15 +44,25 @@ enum State {
 }
 
 struct Inner {
-    reply_tx: Sender<Reply>,
+    exchange: Exchange,
     pipes: PipeCollection,
     fq: Priolist,
     sd: HashSet<EndpointId>,
+    /// For each pipe, the exchanges of the messages it has not acknowledged yet, in the order they were sent.
+    unacked: HashMap<EndpointId, VecDeque<Option<ContextId>>>,
     ttl: u8,
-    backtrace: Vec<u8>,
     is_device_item: bool
 }
 
+/// What belongs to a single request/reply exchange: where to send the replies to the facade,
+/// and the backtrace of the request being processed, unless it was handed to the facade in a reply token.
+struct Exchange {
+    reply_tx: Sender<Reply>,
+    context_id: Option<ContextId>,
+    backtrace: Vec<u8>,
+    with_token: bool
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Rep                                                                      */
@@ -64,10 +83,49 @@ impl Rep {
 
             self.state = Some(new_state);
 
+            // the readiness of the socket is checked by apply_on_context
+            if self.inner.exchange.context_id.is_none() {
+                ctx.check_send_ready_change(was_send_ready, is_send_ready);
+                ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+            }
+
+            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+        }
+    }
+
+    fn apply_on_context<F>(&mut self, ctx: &mut Context, cid: ContextId, f: F) where F : FnOnce(&mut Rep, &mut Context) {
+        if let Some(mut context) = self.contexts.remove(&cid) {
+            let was_send_ready = self.is_send_ready();
+            let was_recv_ready = self.is_recv_ready();
+
+            self.swap_context(&mut context);
+            f(self, ctx);
+            self.swap_context(&mut context);
+            self.contexts.insert(cid, context);
+
+            let is_send_ready = self.is_send_ready();
+            let is_recv_ready = self.is_recv_ready();
+
             ctx.check_send_ready_change(was_send_ready, is_send_ready);
             ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+        }
+    }
 
-            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+    fn swap_context(&mut self, context: &mut RepContext) {
+        mem::swap(&mut self.state, &mut context.state);
+        mem::swap(&mut self.inner.exchange, &mut context.exchange);
+    }
+
+    fn context_ids(&self) -> Vec<ContextId> {
+        self.contexts.keys().cloned().collect()
+    }
+
+    /// Pipe events can concern any of the exchanges, so they are given to the socket and all its contexts.
+    fn apply_on_all<F>(&mut self, ctx: &mut Context, transition: F) where F : Fn(State, &mut Context, &mut Inner) -> State {
+        self.apply(ctx, &transition);
+
+        for cid in self.context_ids() {
+            self.apply_on_context(ctx, cid, |rep, ctx| rep.apply(ctx, &transition));
         }
     }
 
@@ -77,7 +135,8 @@ impl From<Sender<Reply>> for Rep {
     fn from(tx: Sender<Reply>) -> Rep {
         Rep {
             inner: Inner::new(tx),
-            state: Some(State::Idle)
+            state: Some(State::Idle),
+            contexts: HashMap::default()
         }
     }
 }
@@ -103,7 +162,7 @@ impl Protocol for Rep {
         let is_recv_ready = self.is_recv_ready();
 
         if pipe.is_some() {
-            self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            self.apply_on_all(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
         }
 
         ctx.check_send_ready_change(was_send_ready, is_send_ready);
@@ -118,23 +177,38 @@ impl Protocol for Rep {
             self.inner.on_send_malformed(ctx, timeout);
         }
     }
+    /// A pipe acknowledges the messages in the order they were sent,
+    /// so the ack is given to the exchange that sent the oldest message not acknowledged yet.
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.inner.clear_backtrace();
-        
-        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+        match self.inner.take_unacked(eid) {
+            Some(None) => self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid)),
+            Some(Some(cid)) => self.apply_on_context(ctx, cid, |rep, ctx| {
+                rep.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+            }),
+            None => {}
+        }
     }
     fn on_send_timeout(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+        self.apply_on_all(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
     }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
+        self.inner.exchange.with_token = false;
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
         if let Some(msg) = self.inner.raw_msg_to_msg(raw_msg, eid) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
+            let mut request = Some(msg);
+
+            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, &mut request));
+
+            for cid in self.context_ids() {
+                self.apply_on_context(ctx, cid, |rep, ctx| {
+                    rep.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, &mut request))
+                });
+            }
         } else {
             self.inner.on_recv_ack_malformed(ctx)
         }
@@ -143,11 +217,43 @@ impl Protocol for Rep {
         self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.apply_on_all(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+    }
+    fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
+        match task {
+            Schedulable::ContextSendTimeout(cid) => self.apply_on_context(ctx, cid, |rep, ctx| rep.on_send_timeout(ctx)),
+            Schedulable::ContextRecvTimeout(cid) => self.apply_on_context(ctx, cid, |rep, ctx| rep.on_recv_timeout(ctx)),
+            _ => {}
+        }
     }
     fn on_device_plugged(&mut self, _: &mut Context) {
         self.inner.is_device_item = true;
     }
+    fn recv_request(&mut self, ctx: &mut Context, timeout: Timeout) -> io::Result<()> {
+        if self.inner.is_device_item {
+            return Err(invalid_input_io_error("reply tokens not supported by raw sockets"));
+        }
+
+        self.inner.exchange.with_token = true;
+        self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout));
+
+        Ok(())
+    }
+    fn send_reply(&mut self, ctx: &mut Context, token: ReplyToken, msg: Message, timeout: Timeout) -> io::Result<()> {
+        if self.inner.is_device_item {
+            return Err(invalid_input_io_error("reply tokens not supported by raw sockets"));
+        }
+
+        let (raw_msg, eid) = try!(route(token.into_backtrace(), msg.body).ok_or_else(|| invalid_input_io_error("invalid reply token")));
+
+        if self.inner.has_pipe(&eid) == false {
+            return Err(connection_reset_io_error("peer is no longer connected"));
+        }
+
+        self.apply(ctx, |s, ctx, inner| s.send_reply(ctx, inner, Rc::new(raw_msg), timeout, eid));
+
+        Ok(())
+    }
     fn is_send_ready(&self) -> bool {
         if let Some(ref state) = self.state {
             state.is_send_ready(&self.inner)
@@ -162,7 +268,31 @@ impl Protocol for Rep {
             false
         }
     }
+    fn create_context(&mut self, cid: ContextId, tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.is_device_item {
+            return Err(invalid_input_io_error("contexts not supported by raw sockets"));
+        }
+
+        let context = RepContext {
+            state: Some(State::Idle),
+            exchange: Exchange::new(tx, Some(cid))
+        };
+
+        self.contexts.insert(cid, context);
+
+        Ok(())
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.apply_on_context(ctx, cid, |rep, ctx| rep.send(ctx, msg, timeout))
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.apply_on_context(ctx, cid, |rep, ctx| rep.recv(ctx, timeout))
+    }
+    fn close_context(&mut self, _: &mut Context, cid: ContextId) {
+        self.contexts.remove(&cid);
+    }
     fn close(&mut self, ctx: &mut Context) {
+        self.contexts.clear();
         self.inner.close(ctx)
     }
 }
@@ -196,6 +326,22 @@ impl State {
                     State::Receiving(id, timeout)
                 }
             },
+            State::Sending(id, msg, timeout) => {
+                if id == eid {
+                    inner.on_send_to_removed_pipe(ctx, timeout);
+                    State::Idle
+                } else {
+                    State::Sending(id, msg, timeout)
+                }
+            },
+            State::SendOnHold(id, msg, timeout) => {
+                if id == eid {
+                    inner.on_send_to_removed_pipe(ctx, timeout);
+                    State::Idle
+                } else {
+                    State::SendOnHold(id, msg, timeout)
+                }
+            },
             any => any
         }
     }
@@ -227,6 +373,13 @@ impl State {
             State::Idle
         }
     }
+    fn send_reply(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout, eid: EndpointId) -> State {
+        if inner.is_send_ready_to(&eid) {
+            State::Idle.send_reply_to(ctx, inner, msg, timeout, eid)
+        } else {
+            State::SendOnHold(eid, msg, timeout)
+        }
+    }
     fn send_reply_to(self, ctx: &mut Context, inner: &mut Inner, msg: Rc<Message>, timeout: Timeout, eid: EndpointId) -> State {
         if inner.send_to(ctx, msg.clone(), eid) {
             State::Sending(eid, msg, timeout)
@@ -257,7 +410,8 @@ impl State {
 
         match self {
             State::SendOnHold(id, msg, timeout) => {
-                if id == eid {
+                // another exchange may have taken the pipe already
+                if id == eid && inner.is_send_ready_to(&eid) {
                     State::Idle.send_reply_to(ctx, inner, msg, timeout, eid)
                 } else {
                     State::SendOnHold(id, msg, timeout)
@@ -288,18 +442,22 @@ impl State {
             |   | State::RecvOnHold(timeout),
             |eid| State::Receiving(eid, timeout))
     }
-    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message) -> State {
+    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, request: &mut Option<Message>) -> State {
         match self {
             State::Receiving(id, timeout) => {
-                if id == eid {
-                    inner.on_recv_ack(ctx, timeout, msg);
-                    if inner.is_device_item {
-                        State::Idle
-                    } else {
-                        State::Active(eid)
+                match request.take() {
+                    Some(msg) if id == eid => {
+                        inner.on_recv_ack(ctx, timeout, msg);
+                        if inner.is_device_item || inner.exchange.with_token {
+                            State::Idle
+                        } else {
+                            State::Active(eid)
+                        }
+                    },
+                    other => {
+                        *request = other;
+                        State::Receiving(id, timeout)
                     }
-                } else {
-                    State::Receiving(id, timeout)
                 }
             },
             any => any
@@ -332,12 +490,12 @@ impl State {
 impl Inner {
     fn new(tx: Sender<Reply>) -> Inner {
         Inner {
-            reply_tx: tx,
+            exchange: Exchange::new(tx, None),
             pipes:PipeCollection::new(),
             fq: Priolist::new(),
             sd: HashSet::new(),
+            unacked: HashMap::new(),
             ttl: 8,
-            backtrace: Vec::new(),
             is_device_item: false
         }
     }
@@ -348,35 +506,54 @@ impl Inner {
     fn remove_pipe(&mut self, eid: EndpointId) -> Option<Pipe> {
         self.fq.remove(&eid);
         self.sd.remove(&eid);
+        self.unacked.remove(&eid);
         self.pipes.remove(&eid)
     }
     fn on_send_malformed(&mut self, ctx: &mut Context, timeout: Timeout) {
         let error = invalid_data_io_error("Sending without eid");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
         if let Some(sched) = timeout {
             ctx.cancel(sched);
         }
     }
     fn send_to(&mut self, ctx: &mut Context, msg: Rc<Message>, eid: EndpointId) -> bool {
         self.sd.remove(&eid);
-        self.pipes.send_to(ctx, msg, eid).is_some()
+
+        if self.pipes.send_to(ctx, msg, eid).is_some() {
+            self.unacked.entry(eid).or_insert_with(VecDeque::new).push_back(self.exchange.context_id);
+            true
+        } else {
+            false
+        }
+    }
+    fn take_unacked(&mut self, eid: EndpointId) -> Option<Option<ContextId>> {
+        self.unacked.get_mut(&eid).and_then(|exchanges| exchanges.pop_front())
     }
-    fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout) {
-        let _ = self.reply_tx.send(Reply::Send);
+    fn on_send_ack(&mut self, ctx: &mut Context, timeout: Timeout) {
+        self.clear_backtrace();
+
+        let _ = self.exchange.reply_tx.send(Reply::Send);
         if let Some(sched) = timeout {
             ctx.cancel(sched);
         }
     }
     fn send_when_inactive(&mut self, ctx: &mut Context, timeout: Timeout) {
         let error = other_io_error("Can't send: no active request");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
+        if let Some(sched) = timeout {
+            ctx.cancel(sched);
+        }
+    }
+    fn on_send_to_removed_pipe(&self, ctx: &mut Context, timeout: Timeout) {
+        let error = connection_reset_io_error("peer is no longer connected");
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
         if let Some(sched) = timeout {
             ctx.cancel(sched);
         }
     }
     fn on_send_timeout(&self) {
         let error = timedout_io_error("Send timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
     }
     fn on_send_ready(&mut self, eid: EndpointId) {
         self.sd.insert(eid);
@@ -387,6 +564,9 @@ impl Inner {
     fn is_send_ready_to(&self, eid: &EndpointId) -> bool {
         self.sd.contains(eid)
     }
+    fn has_pipe(&self, eid: &EndpointId) -> bool {
+        self.pipes.contains(eid)
+    }
 
     fn recv(&mut self, ctx: &mut Context) -> Option<EndpointId> {
         fair_queue::recv(&mut self.fq, &mut self.pipes, ctx)
@@ -395,36 +575,40 @@ impl Inner {
         self.fq.activate(&eid)
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, timeout: Timeout, mut msg: Message) {
-        if !self.is_device_item {
+        if self.is_device_item {
+            let _ = self.exchange.reply_tx.send(Reply::Recv(msg));
+        } else if self.exchange.with_token {
+            let (header, body) = msg.split();
+            let _ = self.exchange.reply_tx.send(Reply::RecvRequest(Message::from_body(body), ReplyToken::new(header)));
+        } else {
             self.set_backtrace(&msg.header);
             msg.header.clear();
+            let _ = self.exchange.reply_tx.send(Reply::Recv(msg));
         }
-        let _ = self.reply_tx.send(Reply::Recv(msg));
         if let Some(sched) = timeout {
             ctx.cancel(sched);
         }
     }
     fn on_recv_timeout(&self) {
         let error = timedout_io_error("Recv timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
     }
     fn on_recv_ack_malformed(&self, _: &mut Context) {
         let error = invalid_data_io_error("Received request without id");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
     }
     fn is_recv_ready(&self) -> bool {
         self.fq.peek()
     }
  
-    fn raw_msg_to_msg(&self, raw_msg: Message, eid: EndpointId) -> Option<Message> {
-        let (mut header, mut body) = raw_msg.split();
+    fn raw_msg_to_msg(&self, mut raw_msg: Message, eid: EndpointId) -> Option<Message> {
+        let mut header = Vec::with_capacity(4 + raw_msg.get_header().len());
         let mut hops = 0;
         let mut eid_bytes: [u8; 4] = [0; 4];
         let eid_usize: usize = eid.into();
 
         BigEndian::write_u32(&mut eid_bytes[0..4], eid_usize as u32);
 
-        header.reserve(4);
         header.extend_from_slice(&eid_bytes[..]);
 
         loop {
@@ -433,19 +617,16 @@ impl Inner {
             }
             hops += 1;
 
-            if body.len() < 4 {
-                return None;
-            }
+            let hop = match pop_hop(&mut raw_msg) {
+                Some(hop) => hop,
+                None => return None
+            };
 
-            let tail = body.split_off(4);
-            header.reserve(4);
-            header.extend_from_slice(&body);
+            header.extend_from_slice(&hop);
 
-            let position = header.len() - 4;
-            if header[position] & 0x80 != 0 {
-                return Some(Message::from_header_and_body(header, tail));
+            if hop[0] & 0x80 != 0 {
+                return Some(Message::from_header_and_body(header, into_payload(raw_msg)));
             }
-            body = tail;
         }
     }
     fn msg_to_raw_msg(&self, msg: Message) -> Option<(Message, EndpointId)> {
@@ -458,31 +639,54 @@ impl Inner {
             header.extend_from_slice(backtrace);
         }
 
-        if header.len() < 4 {
-            return None;
-        }
-
-        let tail = header.split_off(4);
-        let eid_u32 = BigEndian::read_u32(&header);
-        let eid = EndpointId::from(eid_u32 as usize);
-        
-        Some((Message::from_header_and_body(tail, body), eid))
+        route(header, body)
     }
     fn set_backtrace(&mut self, bt: &[u8]) {
-        self.backtrace.clear();
-        self.backtrace.extend_from_slice(bt);
+        self.exchange.backtrace.clear();
+        self.exchange.backtrace.extend_from_slice(bt);
     }
     fn get_backtrace(&self) -> &[u8] {
-        &self.backtrace
+        &self.exchange.backtrace
     }
     fn clear_backtrace(&mut self) {
-        self.backtrace.clear();
+        self.exchange.backtrace.clear();
     }
     fn close(&mut self, ctx: &mut Context) {
+        self.unacked.clear();
         self.pipes.close_all(ctx)
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* Exchange                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Exchange {
+    fn new(tx: Sender<Reply>, cid: Option<ContextId>) -> Exchange {
+        Exchange {
+            reply_tx: tx,
+            context_id: cid,
+            backtrace: Vec::new(),
+            with_token: false
+        }
+    }
+}
+
+/// Removes the id of the pipe to send the message to, from the head of the backtrace.
+fn route(mut header: Vec<u8>, body: Vec<u8>) -> Option<(Message, EndpointId)> {
+    if header.len() < 4 {
+        return None;
+    }
+
+    let tail = header.split_off(4);
+    let eid_u32 = BigEndian::read_u32(&header);
+    let eid = EndpointId::from(eid_u32 as usize);
+
+    Some((Message::from_header_and_body(tail, body), eid))
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* tests                                                                     */
@@ -495,10 +699,11 @@ mod tests {
     use std::cell::RefCell;
     use std::rc::Rc;
     use std::sync::mpsc;
+    use std::io;
 
     use byteorder::*;
 
-    use core::{EndpointId, Message, Scheduled};
+    use core::{EndpointId, ContextId, Message, ReplyToken, Scheduled};
     use core::socket::{Protocol, Reply};
     use core::context::{Event};
     use core::tests::*;
@@ -538,6 +743,34 @@ mod tests {
         sensor.assert_one_cancellation(timeout);
     }
 
+    #[test]
+    fn when_recv_succeed_with_backtrace_in_header_the_body_is_left_untouched() {
+        let (tx, rx) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+        let request_id = 666 | 0x80000000;
+        let mut header: Vec<u8> = vec![0, 0, 0, 0];
+
+        BigEndian::write_u32(&mut header[0..4], request_id);
+
+        let msg = Message::from_header_and_body(header, vec![4, 2, 1]);
+
+        rep.add_pipe(&mut ctx, eid, pipe);
+        rep.on_recv_ready(&mut ctx, eid);
+        rep.recv(&mut ctx, None);
+        rep.on_recv_ack(&mut ctx, eid, msg);
+
+        let reply = rx.recv().expect("facade should have been sent a reply !");
+        match reply {
+            Reply::Recv(msg) => assert_eq!(&[4, 2, 1], msg.get_body()),
+            _ => panic!("facade should have been sent the request")
+        }
+        assert_eq!(&[0, 0, 0, 0, 0x80, 0, 2, 154], rep.inner.get_backtrace());
+    }
+
     #[test]
     fn send_before_recv_notifies_an_error() {
         let (tx, rx) = mpsc::channel();
@@ -844,4 +1077,199 @@ mod tests {
 
         ctx_sensor.borrow().assert_no_send_call();
     }
+
+    #[test]
+    fn contexts_reply_to_the_pipe_their_own_request_came_from() {
+        let (tx, _) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let (eid_a, eid_b) = (EndpointId::from(1), EndpointId::from(2));
+        let (cid_a, cid_b) = (ContextId::from(1), ContextId::from(2));
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+        let new_request = |request_id: u32| {
+            let mut body: Vec<u8> = vec![0, 0, 0, 0, 4, 2, 1];
+
+            BigEndian::write_u32(&mut body[0..4], request_id | 0x80000000);
+
+            Message::from_body(body)
+        };
+
+        rep.create_context(cid_a, tx_a).unwrap();
+        rep.create_context(cid_b, tx_b).unwrap();
+        rep.add_pipe(&mut ctx, eid_a, new_test_pipe(eid_a));
+        rep.add_pipe(&mut ctx, eid_b, new_test_pipe(eid_b));
+        rep.on_recv_ready(&mut ctx, eid_a);
+        rep.on_recv_ready(&mut ctx, eid_b);
+        rep.on_send_ready(&mut ctx, eid_a);
+        rep.on_send_ready(&mut ctx, eid_b);
+
+        rep.context_recv(&mut ctx, cid_a, None);
+        rep.context_recv(&mut ctx, cid_b, None);
+        rep.on_recv_ack(&mut ctx, eid_b, new_request(2));
+        rep.on_recv_ack(&mut ctx, eid_a, new_request(1));
+        let _ = rx_a.try_recv().expect("context A should have been sent a reply !");
+        let _ = rx_b.try_recv().expect("context B should have been sent a reply !");
+
+        rep.context_send(&mut ctx, cid_b, Message::new(), None);
+        rep.on_send_ack(&mut ctx, eid_b);
+        rep.context_send(&mut ctx, cid_a, Message::new(), None);
+        rep.on_send_ack(&mut ctx, eid_a);
+
+        let sensor = ctx_sensor.borrow();
+        let send_calls = sensor.get_send_calls();
+
+        assert_eq!(2, send_calls.len());
+        assert_eq!(eid_b, send_calls[0].0);
+        assert_eq!(2 | 0x80000000, BigEndian::read_u32(send_calls[0].1.get_header()));
+        assert_eq!(eid_a, send_calls[1].0);
+        assert_eq!(1 | 0x80000000, BigEndian::read_u32(send_calls[1].1.get_header()));
+    }
+
+    fn new_request(request_id: u32) -> Message {
+        let mut body: Vec<u8> = vec![0, 0, 0, 0, 4, 2, 1];
+
+        BigEndian::write_u32(&mut body[0..4], request_id | 0x80000000);
+
+        Message::from_body(body)
+    }
+
+    fn recv_request_with_token(rep: &mut Rep, ctx: &mut TestContext, rx: &mpsc::Receiver<Reply>, eid: EndpointId, request_id: u32) -> ReplyToken {
+        rep.on_recv_ready(ctx, eid);
+        rep.recv_request(ctx, None).unwrap();
+        rep.on_recv_ack(ctx, eid, new_request(request_id));
+
+        match rx.try_recv().expect("facade should have been sent a reply !") {
+            Reply::RecvRequest(msg, token) => {
+                assert_eq!(0, msg.get_header().len());
+                assert_eq!(3, msg.get_body().len());
+                token
+            },
+            _ => panic!("facade should have been sent a request and a token !")
+        }
+    }
+
+    #[test]
+    fn reply_tokens_let_requests_be_replied_in_any_order() {
+        let (tx, rx) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let (eid_a, eid_b) = (EndpointId::from(1), EndpointId::from(2));
+
+        rep.add_pipe(&mut ctx, eid_a, new_test_pipe(eid_a));
+        rep.add_pipe(&mut ctx, eid_b, new_test_pipe(eid_b));
+        rep.on_send_ready(&mut ctx, eid_a);
+        rep.on_send_ready(&mut ctx, eid_b);
+
+        let token_a = recv_request_with_token(&mut rep, &mut ctx, &rx, eid_a, 1);
+        let token_b = recv_request_with_token(&mut rep, &mut ctx, &rx, eid_b, 2);
+
+        rep.send_reply(&mut ctx, token_b, Message::new(), None).unwrap();
+        rep.on_send_ack(&mut ctx, eid_b);
+        rep.send_reply(&mut ctx, token_a, Message::new(), None).unwrap();
+        rep.on_send_ack(&mut ctx, eid_a);
+
+        for _ in 0..2 {
+            match rx.try_recv().expect("facade should have been sent a reply !") {
+                Reply::Send => {},
+                _ => panic!("reply should have been sent !")
+            }
+        }
+
+        let sensor = ctx_sensor.borrow();
+        let send_calls = sensor.get_send_calls();
+
+        assert_eq!(2, send_calls.len());
+        assert_eq!(eid_b, send_calls[0].0);
+        assert_eq!(2 | 0x80000000, BigEndian::read_u32(send_calls[0].1.get_header()));
+        assert_eq!(eid_a, send_calls[1].0);
+        assert_eq!(1 | 0x80000000, BigEndian::read_u32(send_calls[1].1.get_header()));
+    }
+
+    #[test]
+    fn reply_token_of_a_removed_pipe_is_rejected() {
+        let (tx, rx) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+
+        rep.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+
+        let token = recv_request_with_token(&mut rep, &mut ctx, &rx, eid, 1);
+
+        rep.remove_pipe(&mut ctx, eid);
+
+        let err = rep.send_reply(&mut ctx, token, Message::new(), None).unwrap_err();
+
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+        ctx_sensor.borrow().assert_no_send_call();
+    }
+
+    #[test]
+    fn when_pipe_is_removed_while_reply_is_on_hold_an_error_is_notified_and_timeout_is_cancelled() {
+        let (tx, rx) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let timeout = Scheduled::from(1);
+
+        rep.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+
+        let token = recv_request_with_token(&mut rep, &mut ctx, &rx, eid, 1);
+
+        rep.send_reply(&mut ctx, token, Message::new(), Some(timeout)).unwrap();
+        rep.remove_pipe(&mut ctx, eid);
+
+        let is_reply_err = match rx.try_recv().expect("facade should have been sent a reply !") {
+            Reply::Err(e) => e.kind() == io::ErrorKind::ConnectionReset,
+            _ => false
+        };
+        assert!(is_reply_err);
+
+        let sensor = ctx_sensor.borrow();
+        sensor.assert_no_send_call();
+        sensor.assert_one_cancellation(timeout);
+    }
+
+    #[test]
+    fn contexts_replying_to_the_same_pipe_are_acknowledged_in_send_order() {
+        let (tx, _) = mpsc::channel();
+        let mut rep = Rep::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(1);
+        let (cid_a, cid_b) = (ContextId::from(1), ContextId::from(2));
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+
+        rep.create_context(cid_a, tx_a).unwrap();
+        rep.create_context(cid_b, tx_b).unwrap();
+        rep.add_pipe(&mut ctx, eid, new_test_pipe(eid));
+
+        rep.on_recv_ready(&mut ctx, eid);
+        rep.context_recv(&mut ctx, cid_a, None);
+        rep.on_recv_ack(&mut ctx, eid, new_request(1));
+        rep.on_recv_ready(&mut ctx, eid);
+        rep.context_recv(&mut ctx, cid_b, None);
+        rep.on_recv_ack(&mut ctx, eid, new_request(2));
+        let _ = rx_a.try_recv().expect("context A should have been sent a request !");
+        let _ = rx_b.try_recv().expect("context B should have been sent a request !");
+
+        rep.on_send_ready(&mut ctx, eid);
+        rep.context_send(&mut ctx, cid_a, Message::new(), None);
+        rep.on_send_ready(&mut ctx, eid);
+        rep.context_send(&mut ctx, cid_b, Message::new(), None);
+
+        rep.on_send_ack(&mut ctx, eid);
+        let _ = rx_a.try_recv().expect("context A should have been sent a reply !");
+        assert!(rx_b.try_recv().is_err());
+
+        rep.on_send_ack(&mut ctx, eid);
+        assert!(rx_a.try_recv().is_err());
+        let _ = rx_b.try_recv().expect("context B should have been sent a reply !");
+    }
 }
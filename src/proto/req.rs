@@ -4,8 +4,9 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
+use std::mem;
 use std::sync::mpsc::Sender;
 use std::io;
 use std::time::Duration;
@@ -14,20 +15,27 @@ use time;
 
 use byteorder::*;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, EndpointId, ContextId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::{Context, Schedulable};
 use super::priolist::Priolist;
 use super::pipes::PipeCollection;
-use super::{Timeout, REQ, REP};
+use super::{Timeout, REQ, REP, pop_hop, into_payload};
 use super::policy::{load_balancing, fair_queue};
 use io_error::*;
 
 pub struct Req {
     inner: Inner,
-    state: Option<State>
+    state: Option<State>,
+    contexts: HashMap<ContextId, ReqContext, BuildIdHasher>
+}
+
+/// State machine of a context, swapped with the one of the socket while the context is being operated.
+struct ReqContext {
+    state: Option<State>,
+    exchange: Exchange
 }
 
 enum State {
@@ -40,16 +48,28 @@ enum State {
 }
 
 struct Inner {
-    reply_tx: Sender<Reply>,
+    exchange: Exchange,
     pipes: PipeCollection,
     lb: Priolist,
     fq: Priolist,
     rv: HashSet<EndpointId>,
+    reading: HashSet<EndpointId>,
+    /// For each pipe, the exchanges of the messages it has not acknowledged yet, in the order they were sent.
+    unacked: HashMap<EndpointId, VecDeque<Option<ContextId>>>,
     req_id_seq: u32,
     is_device_item: bool,
     resend_ivl: Duration
 }
 
+/// What belongs to a single request/reply exchange: where to send the replies to the facade,
+/// the id of the current request, and the reply received before it was asked for.
+struct Exchange {
+    reply_tx: Sender<Reply>,
+    context_id: Option<ContextId>,
+    req_id: u32,
+    reply: Option<Message>
+}
+
 struct PendingRequest {
     req: Rc<Message>,
     retry_timeout: Timeout
@@ -75,10 +95,49 @@ impl Req {
 
             self.state = Some(new_state);
 
+            // the readiness of the socket is checked by apply_on_context
+            if self.inner.exchange.context_id.is_none() {
+                ctx.check_send_ready_change(was_send_ready, is_send_ready);
+                ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+            }
+
+            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+        }
+    }
+
+    fn apply_on_context<F>(&mut self, ctx: &mut Context, cid: ContextId, f: F) where F : FnOnce(&mut Req, &mut Context) {
+        if let Some(mut context) = self.contexts.remove(&cid) {
+            let was_send_ready = self.is_send_ready();
+            let was_recv_ready = self.is_recv_ready();
+
+            self.swap_context(&mut context);
+            f(self, ctx);
+            self.swap_context(&mut context);
+            self.contexts.insert(cid, context);
+
+            let is_send_ready = self.is_send_ready();
+            let is_recv_ready = self.is_recv_ready();
+
             ctx.check_send_ready_change(was_send_ready, is_send_ready);
             ctx.check_recv_ready_change(was_recv_ready, is_recv_ready);
+        }
+    }
 
-            #[cfg(debug_assertions)] debug!("[{:?}] switch from {} to {}", ctx, old_name, new_name);
+    fn swap_context(&mut self, context: &mut ReqContext) {
+        mem::swap(&mut self.state, &mut context.state);
+        mem::swap(&mut self.inner.exchange, &mut context.exchange);
+    }
+
+    fn context_ids(&self) -> Vec<ContextId> {
+        self.contexts.keys().cloned().collect()
+    }
+
+    /// Pipe events can concern any of the exchanges, so they are given to the socket and all its contexts.
+    fn apply_on_all<F>(&mut self, ctx: &mut Context, transition: F) where F : Fn(State, &mut Context, &mut Inner) -> State {
+        self.apply(ctx, &transition);
+
+        for cid in self.context_ids() {
+            self.apply_on_context(ctx, cid, |req, ctx| req.apply(ctx, &transition));
         }
     }
 
@@ -88,7 +147,8 @@ impl From<Sender<Reply>> for Req {
     fn from(tx: Sender<Reply>) -> Req {
         Req {
             inner: Inner::new(tx),
-            state: Some(State::Idle)
+            state: Some(State::Idle),
+            contexts: HashMap::default()
         }
     }
 }
@@ -114,7 +174,7 @@ impl Protocol for Req {
         let is_recv_ready = self.is_recv_ready();
 
         if pipe.is_some() {
-            self.apply(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
+            self.apply_on_all(ctx, |s, ctx, inner| s.on_pipe_removed(ctx, inner, eid));
         }
 
         ctx.check_send_ready_change(was_send_ready, is_send_ready);
@@ -127,21 +187,39 @@ impl Protocol for Req {
 
         self.apply(ctx, |s, ctx, inner| s.send(ctx, inner, Rc::new(raw_msg), timeout, false))
     }
+    /// A pipe acknowledges the messages in the order they were sent,
+    /// so the ack is given to the exchange that sent the oldest message not acknowledged yet.
     fn on_send_ack(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+        match self.inner.take_unacked(eid) {
+            Some(None) => self.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid)),
+            Some(Some(cid)) => self.apply_on_context(ctx, cid, |req, ctx| {
+                req.apply(ctx, |s, ctx, inner| s.on_send_ack(ctx, inner, eid))
+            }),
+            None => {}
+        }
     }
     fn on_send_timeout(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx, inner| s.on_send_timeout(ctx, inner))
     }
     fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
+        self.apply_on_all(ctx, |s, ctx, inner| s.on_send_ready(ctx, inner, eid))
     }
     fn recv(&mut self, ctx: &mut Context, timeout: Timeout) {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, raw_msg: Message) {
+        self.inner.on_reply_read(eid);
+
         if let Some((msg, req_id)) = self.inner.raw_msg_to_msg(raw_msg) {
-            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg, req_id))
+            let mut reply = Some(msg);
+
+            self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, &mut reply, req_id));
+
+            for cid in self.context_ids() {
+                self.apply_on_context(ctx, cid, |req, ctx| {
+                    req.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, &mut reply, req_id))
+                });
+            }
         } else {
             self.inner.on_recv_ack_malformed(ctx)
         }
@@ -150,7 +228,7 @@ impl Protocol for Req {
         self.apply(ctx, |s, ctx, inner| s.on_recv_timeout(ctx, inner))
     }
     fn on_recv_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
-        self.apply(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
+        self.apply_on_all(ctx, |s, ctx, inner| s.on_recv_ready(ctx, inner, eid))
     }
     fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
         match opt {
@@ -158,9 +236,23 @@ impl Protocol for Req {
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::ReqResendIvl => Ok(ConfigOption::ReqResendIvl(self.inner.resend_ivl)),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
-        if let Schedulable::ReqResend = task {
-            self.apply(ctx, |s, ctx, inner| s.on_retry_timeout(ctx, inner))
+        match task {
+            Schedulable::ReqResend => {
+                self.apply(ctx, |s, ctx, inner| s.on_retry_timeout(ctx, inner))
+            },
+            Schedulable::ReqContextResend(cid) => self.apply_on_context(ctx, cid, |req, ctx| {
+                req.apply(ctx, |s, ctx, inner| s.on_retry_timeout(ctx, inner))
+            }),
+            Schedulable::ContextSendTimeout(cid) => self.apply_on_context(ctx, cid, |req, ctx| req.on_send_timeout(ctx)),
+            Schedulable::ContextRecvTimeout(cid) => self.apply_on_context(ctx, cid, |req, ctx| req.on_recv_timeout(ctx)),
+            _ => {}
         }
     }
     fn on_device_plugged(&mut self, _: &mut Context) {
@@ -180,7 +272,35 @@ impl Protocol for Req {
             false
         }
     }
+    fn create_context(&mut self, cid: ContextId, tx: Sender<Reply>) -> io::Result<()> {
+        if self.inner.is_device_item {
+            return Err(invalid_input_io_error("contexts not supported by raw sockets"));
+        }
+
+        let context = ReqContext {
+            state: Some(State::Idle),
+            exchange: Exchange::new(tx, Some(cid), self.inner.cur_req_id())
+        };
+
+        self.contexts.insert(cid, context);
+
+        Ok(())
+    }
+    fn context_send(&mut self, ctx: &mut Context, cid: ContextId, msg: Message, timeout: Timeout) {
+        self.apply_on_context(ctx, cid, |req, ctx| req.send(ctx, msg, timeout))
+    }
+    fn context_recv(&mut self, ctx: &mut Context, cid: ContextId, timeout: Timeout) {
+        self.apply_on_context(ctx, cid, |req, ctx| req.recv(ctx, timeout))
+    }
+    fn close_context(&mut self, ctx: &mut Context, cid: ContextId) {
+        if let Some(context) = self.contexts.remove(&cid) {
+            if let Some(State::Active(_, p)) = context.state {
+                self.inner.cancel(ctx, p);
+            }
+        }
+    }
     fn close(&mut self, ctx: &mut Context) {
+        self.contexts.clear();
         self.inner.close(ctx)
     }
 }
@@ -292,7 +412,13 @@ impl State {
                 |   | State::RecvOnHold(None, None, timeout),
                 |eid| State::Receiving(eid, None, timeout))
         } else if let State::Active(eid, p) = self {
-            State::Idle.recv_reply_for(ctx, inner, timeout, eid, p)
+            match inner.take_reply() {
+                Some(msg) => {
+                    inner.on_recv_ack(ctx, timeout, msg, p.retry_timeout);
+                    State::Idle
+                },
+                None => State::Idle.recv_reply_for(ctx, inner, timeout, eid, p)
+            }
         } else {
             inner.recv_when_inactive(ctx, timeout);
 
@@ -306,27 +432,43 @@ impl State {
             State::RecvOnHold(Some(eid), Some(p), timeout)
         }
     }
-    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message, req_id: u32) -> State {
+    /// The reply is taken by the exchange whose request id it matches,
+    /// the other exchanges waiting for a reply from the same pipe keep reading it.
+    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, reply: &mut Option<Message>, req_id: u32) -> State {
         match self {
             State::Receiving(id, None, timeout) => {
-                if eid == id {
-                    inner.on_recv_ack(ctx, timeout, msg, None);
-                    State::Idle
-                } else {
-                    State::Receiving(id, None, timeout)
+                match reply.take() {
+                    Some(msg) if eid == id => {
+                        inner.on_recv_ack(ctx, timeout, msg, None);
+                        State::Idle
+                    },
+                    other => {
+                        *reply = other;
+                        State::Receiving(id, None, timeout)
+                    }
                 }
             },
             State::Receiving(id, Some(p), timeout) => {
-                if eid == id {
-                    if inner.cur_req_id() == req_id {
+                if eid != id {
+                    return State::Receiving(id, Some(p), timeout);
+                }
+                if inner.cur_req_id() == req_id {
+                    if let Some(msg) = reply.take() {
                         inner.on_recv_ack(ctx, timeout, msg, p.retry_timeout);
-                        State::Idle
-                    } else {
-                        State::Idle.recv_reply_for(ctx, inner, timeout, id, p)
+                        return State::Idle;
                     }
-                } else {
-                    State::Receiving(id, Some(p), timeout)
                 }
+
+                State::Idle.recv_reply_for(ctx, inner, timeout, id, p)
+            },
+            State::Active(id, p) => {
+                if inner.cur_req_id() == req_id {
+                    if let Some(msg) = reply.take() {
+                        inner.set_reply(msg);
+                    }
+                }
+
+                State::Active(id, p)
             },
             any => any
         }
@@ -384,13 +526,17 @@ impl State {
 
 impl Inner {
     fn new(tx: Sender<Reply>) -> Inner {
+        let req_id_seq = time::get_time().nsec as u32;
+
         Inner {
-            reply_tx: tx,
+            exchange: Exchange::new(tx, None, req_id_seq | 0x80000000),
             pipes: PipeCollection::new(),
             lb: Priolist::new(),
             fq: Priolist::new(),
             rv: HashSet::new(),
-            req_id_seq: time::get_time().nsec as u32,
+            reading: HashSet::new(),
+            unacked: HashMap::new(),
+            req_id_seq: req_id_seq,
             is_device_item: false,
             resend_ivl: Duration::from_secs(60)
         }
@@ -404,17 +550,28 @@ impl Inner {
         self.lb.remove(&eid);
         self.fq.remove(&eid);
         self.rv.remove(&eid);
+        self.reading.remove(&eid);
+        self.unacked.remove(&eid);
         self.pipes.remove(&eid)
     }
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>) -> Option<EndpointId> {
-        load_balancing::send(&mut self.lb, &mut self.pipes, ctx, msg)
+        let sent = load_balancing::send(&mut self.lb, &mut self.pipes, ctx, msg);
+
+        if let Some(eid) = sent {
+            self.unacked.entry(eid).or_insert_with(VecDeque::new).push_back(self.exchange.context_id);
+        }
+
+        sent
+    }
+    fn take_unacked(&mut self, eid: EndpointId) -> Option<Option<ContextId>> {
+        self.unacked.get_mut(&eid).and_then(|exchanges| exchanges.pop_front())
     }
     fn on_send_ready(&mut self, eid: EndpointId) {
         self.lb.activate(&eid)
     }
     fn on_send_ack(&self, ctx: &mut Context, timeout: Timeout, retry: bool) -> Timeout {
         if !retry {
-            let _ = self.reply_tx.send(Reply::Send);
+            let _ = self.exchange.reply_tx.send(Reply::Send);
         }
         if let Some(sched) = timeout {
             ctx.cancel(sched);
@@ -422,12 +579,12 @@ impl Inner {
         if self.is_device_item {
             None
         } else {
-            ctx.schedule(Schedulable::ReqResend, self.resend_ivl).ok()
+            ctx.schedule(self.exchange.resend_task(), self.resend_ivl).ok()
         }
     }
     fn on_send_timeout(&self) {
         let error = timedout_io_error("Send timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
     }
     fn is_send_ready(&self) -> bool {
         self.lb.peek()
@@ -441,19 +598,41 @@ impl Inner {
     fn recv(&mut self, ctx: &mut Context) -> Option<EndpointId> {
         fair_queue::recv(&mut self.fq, &mut self.pipes, ctx)
     }
+    /// Several exchanges can wait for a reply from the same pipe,
+    /// but only one recv operation can be in progress on a pipe.
     fn recv_reply_from(&mut self, ctx: &mut Context, eid: EndpointId) -> bool {
         self.rv.remove(&eid);
-        self.pipes.get_mut(&eid).map(|pipe| pipe.recv(ctx)).is_some()
+
+        if self.reading.contains(&eid) {
+            return true;
+        }
+
+        let is_reading = self.pipes.get_mut(&eid).map(|pipe| pipe.recv(ctx)).is_some();
+
+        if is_reading {
+            self.reading.insert(eid);
+        }
+
+        is_reading
+    }
+    fn on_reply_read(&mut self, eid: EndpointId) {
+        self.reading.remove(&eid);
+    }
+    fn set_reply(&mut self, msg: Message) {
+        self.exchange.reply = Some(msg);
+    }
+    fn take_reply(&mut self) -> Option<Message> {
+        self.exchange.reply.take()
     }
     fn recv_when_inactive(&mut self, ctx: &mut Context, timeout: Timeout) {
         let error = other_io_error("Can't recv: no active request");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
         if let Some(sched) = timeout {
             ctx.cancel(sched);
         }
     }
     fn on_recv_ack(&self, ctx: &mut Context, timeout: Timeout, msg: Message, retry_timeout: Timeout) {
-        let _ = self.reply_tx.send(Reply::Recv(msg));
+        let _ = self.exchange.reply_tx.send(Reply::Recv(msg));
         if let Some(sched) = timeout {
             ctx.cancel(sched);
         }
@@ -463,14 +642,14 @@ impl Inner {
     }
     fn on_recv_timeout(&self, ctx: &mut Context, retry_timeout: Timeout) {
         let error = timedout_io_error("Recv timed out");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
         if let Some(sched) = retry_timeout {
             ctx.cancel(sched);
         }
     }
     fn on_recv_ack_malformed(&self, _: &mut Context) {
         let error = invalid_data_io_error("Received reply without req id");
-        let _ = self.reply_tx.send(Reply::Err(error));
+        let _ = self.exchange.reply_tx.send(Reply::Err(error));
     }
     fn on_recv_ready(&mut self, eid: EndpointId) {
         self.fq.activate(&eid);
@@ -487,7 +666,12 @@ impl Inner {
         if self.is_device_item {
             msg
         } else {
-            encode(msg, self.next_req_id())
+            let req_id = self.next_req_id();
+
+            self.exchange.req_id = req_id;
+            self.exchange.reply = None;
+
+            encode(msg, req_id)
         }
     }
 
@@ -501,7 +685,7 @@ impl Inner {
     }
 
     fn cur_req_id(&self) -> u32 {
-        self.req_id_seq | 0x80000000
+        self.exchange.req_id
     }
 
     fn next_req_id(&mut self) -> u32 {
@@ -512,10 +696,34 @@ impl Inner {
         self.resend_ivl = ivl;
     }
     fn close(&mut self, ctx: &mut Context) {
+        self.unacked.clear();
         self.pipes.close_all(ctx)
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* Exchange                                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Exchange {
+    fn new(tx: Sender<Reply>, cid: Option<ContextId>, req_id: u32) -> Exchange {
+        Exchange {
+            reply_tx: tx,
+            context_id: cid,
+            req_id: req_id,
+            reply: None
+        }
+    }
+    fn resend_task(&self) -> Schedulable {
+        match self.context_id {
+            Some(cid) => Schedulable::ReqContextResend(cid),
+            None => Schedulable::ReqResend
+        }
+    }
+}
+
 fn encode(msg: Message, req_id: u32) -> Message {
     let mut raw_msg = msg;
     let mut req_id_bytes: [u8; 4] = [0; 4];
@@ -527,22 +735,14 @@ fn encode(msg: Message, req_id: u32) -> Message {
     raw_msg
 }
 
-fn decode(raw_msg: Message) -> Option<(Message, u32)> {
-    if raw_msg.get_body().len() < 4 {
-        return None;
-    }
-
-    let (mut header, mut payload) = raw_msg.split();
-    let body = payload.split_off(4);
-    let req_id = BigEndian::read_u32(&payload);
-
-    if header.is_empty() {
-        header = payload;
-    } else {
-        header.extend_from_slice(&payload);
-    }
+fn decode(mut raw_msg: Message) -> Option<(Message, u32)> {
+    let req_id_bytes = match pop_hop(&mut raw_msg) {
+        Some(hop) => hop,
+        None => return None
+    };
+    let req_id = BigEndian::read_u32(&req_id_bytes);
 
-    Some((Message::from_header_and_body(header, body), req_id))
+    Some((Message::from_header_and_body(req_id_bytes.to_vec(), into_payload(raw_msg)), req_id))
 }
 
 /*****************************************************************************/
@@ -560,7 +760,7 @@ mod tests {
 
     use byteorder::*;
 
-    use core::{EndpointId, Message, Scheduled};
+    use core::{EndpointId, ContextId, Message, Scheduled};
     use core::socket::{Protocol, Reply};
     use core::context::{Event};
     use core::tests::*;
@@ -978,4 +1178,182 @@ mod tests {
         assert_eq!(4, app_msg.get_header().len());
         assert_eq!(2, app_msg.get_body().len());
      }
+
+    fn sent_request_id(sensor: &TestContextSensor, index: usize) -> u32 {
+        let &(_, ref raw_msg) = &sensor.get_send_calls()[index];
+
+        BigEndian::read_u32(raw_msg.get_header())
+    }
+
+    #[test]
+    fn when_in_regular_mode_recv_will_accept_msg_with_request_id_in_header() {
+        let (tx, rx) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+
+        req.add_pipe(&mut ctx, eid, pipe);
+        req.on_send_ready(&mut ctx, eid);
+        req.send(&mut ctx, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        let _ = rx.try_recv().expect("facade should have been sent a reply !");
+
+        let good_request_id = (req.inner.req_id_seq) | 0x80000000;
+        let mut header: Vec<u8> = vec![0, 0, 0, 0];
+
+        BigEndian::write_u32(&mut header[0..4], good_request_id);
+
+        let msg = Message::from_header_and_body(header, vec![4, 2, 1]);
+        req.on_recv_ready(&mut ctx, eid);
+        req.recv(&mut ctx, None);
+        req.on_recv_ack(&mut ctx, eid, msg);
+
+        let reply = rx.try_recv().expect("facade should have been sent a reply !");
+        match reply {
+            Reply::Recv(msg) => assert_eq!(&[4, 2, 1], msg.get_body()),
+            _ => panic!("facade should have been sent the reply")
+        }
+    }
+
+    fn new_reply(request_id: u32) -> Message {
+        let mut body: Vec<u8> = vec![0, 0, 0, 0, 4, 2, 1];
+
+        BigEndian::write_u32(&mut body[0..4], request_id);
+
+        Message::from_body(body)
+    }
+
+    #[test]
+    fn when_in_raw_mode_contexts_cannot_be_created() {
+        let (tx, _) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let (context_tx, _) = mpsc::channel();
+
+        req.on_device_plugged(&mut ctx);
+
+        assert!(req.create_context(ContextId::from(1), context_tx).is_err());
+    }
+
+    #[test]
+    fn contexts_sharing_a_pipe_receive_the_reply_to_their_own_request() {
+        let (tx, _) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+        let (cid_a, cid_b) = (ContextId::from(1), ContextId::from(2));
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+
+        req.create_context(cid_a, tx_a).unwrap();
+        req.create_context(cid_b, tx_b).unwrap();
+        req.add_pipe(&mut ctx, eid, pipe);
+
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid_a, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid_b, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        let _ = rx_a.try_recv().expect("context A should have been sent a reply !");
+        let _ = rx_b.try_recv().expect("context B should have been sent a reply !");
+
+        let request_id_a = sent_request_id(&ctx_sensor.borrow(), 0);
+        let request_id_b = sent_request_id(&ctx_sensor.borrow(), 1);
+        assert!(request_id_a != request_id_b);
+
+        req.on_recv_ready(&mut ctx, eid);
+        req.context_recv(&mut ctx, cid_a, None);
+        req.context_recv(&mut ctx, cid_b, None);
+        ctx_sensor.borrow().assert_recv_from(eid, 1);
+
+        req.on_recv_ack(&mut ctx, eid, new_reply(request_id_b));
+        assert!(rx_a.try_recv().is_err());
+        match rx_b.try_recv().expect("context B should have been sent a reply !") {
+            Reply::Recv(msg) => assert_eq!(request_id_b, BigEndian::read_u32(msg.get_header())),
+            _ => panic!("context B should have received its reply !")
+        }
+
+        req.on_recv_ack(&mut ctx, eid, new_reply(request_id_a));
+        match rx_a.try_recv().expect("context A should have been sent a reply !") {
+            Reply::Recv(msg) => assert_eq!(request_id_a, BigEndian::read_u32(msg.get_header())),
+            _ => panic!("context A should have received its reply !")
+        }
+        ctx_sensor.borrow().assert_recv_from(eid, 2);
+    }
+
+    #[test]
+    fn when_reply_comes_before_context_recv_it_is_kept_for_the_context() {
+        let (tx, rx) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+        let cid = ContextId::from(1);
+        let (context_tx, context_rx) = mpsc::channel();
+
+        req.create_context(cid, context_tx).unwrap();
+        req.add_pipe(&mut ctx, eid, pipe);
+
+        req.on_send_ready(&mut ctx, eid);
+        req.send(&mut ctx, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid, Message::new(), None);
+        req.on_send_ack(&mut ctx, eid);
+        let _ = rx.try_recv().expect("facade should have been sent a reply !");
+        let _ = context_rx.try_recv().expect("context should have been sent a reply !");
+
+        let context_request_id = sent_request_id(&ctx_sensor.borrow(), 1);
+
+        req.on_recv_ready(&mut ctx, eid);
+        req.recv(&mut ctx, None);
+        req.on_recv_ack(&mut ctx, eid, new_reply(context_request_id));
+        assert!(rx.try_recv().is_err());
+        assert!(context_rx.try_recv().is_err());
+
+        req.context_recv(&mut ctx, cid, None);
+        let reply = context_rx.try_recv().expect("context should have been sent a reply !");
+        let is_reply_ok = match reply {
+            Reply::Recv(_) => true,
+            _ => false
+        };
+        assert!(is_reply_ok);
+    }
+
+    #[test]
+    fn contexts_sending_to_the_same_pipe_are_acknowledged_in_send_order() {
+        let (tx, _) = mpsc::channel();
+        let mut req = Req::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+        let (cid_a, cid_b) = (ContextId::from(1), ContextId::from(2));
+        let (tx_a, rx_a) = mpsc::channel();
+        let (tx_b, rx_b) = mpsc::channel();
+
+        req.create_context(cid_a, tx_a).unwrap();
+        req.create_context(cid_b, tx_b).unwrap();
+        req.add_pipe(&mut ctx, eid, pipe);
+
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid_a, Message::new(), None);
+        req.on_send_ready(&mut ctx, eid);
+        req.context_send(&mut ctx, cid_b, Message::new(), None);
+
+        req.on_send_ack(&mut ctx, eid);
+        let _ = rx_a.try_recv().expect("context A should have been sent a reply !");
+        assert!(rx_b.try_recv().is_err());
+
+        req.on_send_ack(&mut ctx, eid);
+        assert!(rx_a.try_recv().is_err());
+        let _ = rx_b.try_recv().expect("context B should have been sent a reply !");
+    }
 }
\ No newline at end of file
@@ -16,7 +16,7 @@ use core::endpoint::Pipe;
 use core::context::{Context, Event};
 use super::priolist::Priolist;
 use super::pipes::PipeCollection;
-use super::{Timeout, SURVEYOR, RESPONDENT};
+use super::{Timeout, SURVEYOR, RESPONDENT, pop_hop, into_payload};
 use super::policy::fair_queue;
 use io_error::*;
 
@@ -420,15 +420,14 @@ impl Inner {
         self.fq.peek()
     }
 
-    fn raw_msg_to_msg(&self, raw_msg: Message, eid: EndpointId) -> Option<Message> {
-        let (mut header, mut body) = raw_msg.split();
+    fn raw_msg_to_msg(&self, mut raw_msg: Message, eid: EndpointId) -> Option<Message> {
+        let mut header = Vec::with_capacity(4 + raw_msg.get_header().len());
         let mut hops = 0;
         let mut eid_bytes: [u8; 4] = [0; 4];
         let eid_usize: usize = eid.into();
 
         BigEndian::write_u32(&mut eid_bytes[0..4], eid_usize as u32);
 
-        header.reserve(4);
         header.extend_from_slice(&eid_bytes[..]);
 
         loop {
@@ -437,19 +436,16 @@ impl Inner {
             }
             hops += 1;
 
-            if body.len() < 4 {
-                return None;
-            }
+            let hop = match pop_hop(&mut raw_msg) {
+                Some(hop) => hop,
+                None => return None
+            };
 
-            let tail = body.split_off(4);
-            header.reserve(4);
-            header.extend_from_slice(&body);
+            header.extend_from_slice(&hop);
 
-            let position = header.len() - 4;
-            if header[position] & 0x80 != 0 {
-                return Some(Message::from_header_and_body(header, tail));
+            if hop[0] & 0x80 != 0 {
+                return Some(Message::from_header_and_body(header, into_payload(raw_msg)));
             }
-            body = tail;
         }
     }
     fn msg_to_raw_msg(&self, msg: Message) -> Option<(Message, EndpointId)> {
@@ -4,19 +4,20 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 use std::io;
 
-use core::{EndpointId, Message};
+use core::{BuildIdHasher, EndpointId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::Context;
 use super::priolist::Priolist;
+use super::trie::Trie;
 use super::pipes::PipeCollection;
-use super::{Timeout, PUB, SUB};
+use super::{Timeout, PUB, SUB, SUB_FORWARDING, SUBSCRIBE, UNSUBSCRIBE};
 use super::policy::fair_queue;
 use io_error::*;
 
@@ -35,7 +36,17 @@ struct Inner {
     reply_tx: Sender<Reply>,
     pipes: PipeCollection,
     fq: Priolist,
-    subscriptions: HashSet<Vec<u8>>
+    subscriptions: Trie,
+    forwarding: bool,
+    upstreams: HashMap<EndpointId, Upstream, BuildIdHasher>
+}
+
+/// A pipe whose peer wants to be told about the subscriptions.
+/// Only one message can be sent at a time, the next ones wait for the pipe to be ready again.
+#[derive(Default)]
+struct Upstream {
+    pending: VecDeque<Rc<Message>>,
+    can_send: bool
 }
 
 /*****************************************************************************/
@@ -71,7 +82,9 @@ impl From<Sender<Reply>> for Sub {
                 reply_tx: tx,
                 pipes: PipeCollection::new(),
                 fq: Priolist::new(),
-                subscriptions: HashSet::new()
+                subscriptions: Trie::new(),
+                forwarding: false,
+                upstreams: HashMap::default()
             },
             state: Some(State::Idle)
         }
@@ -120,6 +133,9 @@ impl Protocol for Sub {
         self.apply(ctx, |s, ctx, inner| s.recv(ctx, inner, timeout))
     }
     fn on_recv_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Message) {
+        self.on_recv_shared_ack(ctx, eid, Rc::new(msg))
+    }
+    fn on_recv_shared_ack(&mut self, ctx: &mut Context, eid: EndpointId, msg: Rc<Message>) {
         self.apply(ctx, |s, ctx, inner| s.on_recv_ack(ctx, inner, eid, msg))
     }
     fn on_recv_timeout(&mut self, ctx: &mut Context) {
@@ -136,11 +152,27 @@ impl Protocol for Sub {
     }
     fn set_option(&mut self, opt: ConfigOption) -> io::Result<()> {
         match opt {
-            ConfigOption::Subscribe(x)   => Ok(self.inner.subscribe(x)),
-            ConfigOption::Unsubscribe(x) => Ok(self.inner.unsubscribe(x)),
+            ConfigOption::Subscribe(x)              => Ok(self.inner.subscribe(x.as_bytes())),
+            ConfigOption::Unsubscribe(x)            => Ok(self.inner.unsubscribe(x.as_bytes())),
+            ConfigOption::SubscribeTopic(x)         => Ok(self.inner.subscribe(&x)),
+            ConfigOption::UnsubscribeTopic(x)       => Ok(self.inner.unsubscribe(&x)),
+            ConfigOption::SubscriptionForwarding(x) => Ok(self.inner.forwarding = x),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
+    fn on_option_set(&mut self, ctx: &mut Context) {
+        self.inner.flush(ctx)
+    }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::Subscriptions          => Ok(ConfigOption::Subscriptions(self.inner.subscriptions())),
+            ConfigOptionKind::SubscriptionForwarding => Ok(ConfigOption::SubscriptionForwarding(self.inner.forwarding)),
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn proto_flags(&self) -> u8 {
+        if self.inner.forwarding { SUB_FORWARDING } else { 0 }
+    }
     fn close(&mut self, ctx: &mut Context) {
         self.inner.close(ctx)
     }
@@ -192,7 +224,8 @@ impl State {
     fn on_send_timeout(self, _: &mut Context, _: &mut Inner) -> State {
         self
     }
-    fn on_send_ready(self, _: &mut Context, _: &mut Inner, _: EndpointId) -> State {
+    fn on_send_ready(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId) -> State {
+        inner.on_send_ready(ctx, eid);
         self
     }
 
@@ -207,12 +240,12 @@ impl State {
             |   | State::RecvOnHold(timeout),
             |eid| State::Receiving(eid, timeout))
     }
-    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Message) -> State {
+    fn on_recv_ack(self, ctx: &mut Context, inner: &mut Inner, eid: EndpointId, msg: Rc<Message>) -> State {
         match self {
             State::Receiving(id, timeout) => {
                 if id == eid {
                     if inner.accept(&msg) {
-                        inner.on_recv_ack(ctx, timeout, msg);
+                        inner.on_recv_ack(ctx, timeout, Message::from_rc(msg));
                         State::Idle
                     } else {
                         State::Idle.recv(ctx, inner, timeout)
@@ -247,13 +280,50 @@ impl State {
 
 impl Inner {
     fn add_pipe(&mut self, eid: EndpointId, pipe: Pipe) {
+        let flags = pipe.get_proto_flags() & pipe.get_peer_proto_flags();
+
+        if flags & SUB_FORWARDING != 0 {
+            let mut upstream = Upstream::default();
+
+            for topic in self.subscriptions.keys() {
+                upstream.pending.push_back(Rc::new(subscription_msg(SUBSCRIBE, &topic)));
+            }
+
+            self.upstreams.insert(eid, upstream);
+        }
+
         self.fq.insert(eid, pipe.get_recv_priority());
         self.pipes.insert(eid, pipe);
     }
     fn remove_pipe(&mut self, eid: EndpointId) -> Option<Pipe> {
+        self.upstreams.remove(&eid);
         self.fq.remove(&eid);
         self.pipes.remove(&eid)
     }
+    fn on_send_ready(&mut self, ctx: &mut Context, eid: EndpointId) {
+        if let Some(upstream) = self.upstreams.get_mut(&eid) {
+            upstream.can_send = true;
+        }
+
+        self.flush(ctx);
+    }
+    fn forward(&mut self, action: u8, topic: &[u8]) {
+        let msg = Rc::new(subscription_msg(action, topic));
+
+        for upstream in self.upstreams.values_mut() {
+            upstream.pending.push_back(msg.clone());
+        }
+    }
+    fn flush(&mut self, ctx: &mut Context) {
+        for (eid, upstream) in &mut self.upstreams {
+            if upstream.can_send {
+                if let Some(msg) = upstream.pending.pop_front() {
+                    upstream.can_send = false;
+                    self.pipes.send_to(ctx, msg, *eid);
+                }
+            }
+        }
+    }
     fn send(&mut self, ctx: &mut Context, timeout: Timeout) {
         let error = other_io_error("Send is not supported by pull protocol");
         let _ = self.reply_tx.send(Reply::Err(error));
@@ -282,24 +352,39 @@ impl Inner {
         self.fq.peek()
     }
 
-    fn subscribe(&mut self, subscription :String) {
-        self.subscriptions.insert(subscription.into_bytes());
+    fn subscribe(&mut self, topic: &[u8]) {
+        if self.subscriptions.insert(topic) {
+            self.forward(SUBSCRIBE, topic);
+        }
+    }
+
+    fn unsubscribe(&mut self, topic: &[u8]) {
+        if self.subscriptions.remove(topic) {
+            self.forward(UNSUBSCRIBE, topic);
+        }
     }
 
-    fn unsubscribe(&mut self, subscription :String) {
-        self.subscriptions.remove(&subscription.into_bytes());
+    fn subscriptions(&self) -> Vec<Vec<u8>> {
+        self.subscriptions.keys()
     }
 
     fn accept(&self, msg: &Message) -> bool {
-        let payload = msg.get_body();
-        
-        self.subscriptions.iter().any(|s| payload.starts_with(s))
+        self.subscriptions.matches(msg.get_body())
     }
     fn close(&mut self, ctx: &mut Context) {
         self.pipes.close_all(ctx)
     }
 }
 
+fn subscription_msg(action: u8, topic: &[u8]) -> Message {
+    let mut body = Vec::with_capacity(1 + topic.len());
+
+    body.push(action);
+    body.extend_from_slice(topic);
+
+    Message::from_body(body)
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* tests                                                                     */
@@ -316,7 +401,7 @@ mod tests {
     use core::{EndpointId, Message, Scheduled};
     use core::socket::{Protocol, Reply};
     use core::context::{Event};
-    use core::config::ConfigOption;
+    use core::config::{ConfigOption, ConfigOptionKind};
     use core::tests::*;
 
     use super::*;
@@ -376,6 +461,119 @@ mod tests {
         assert_eq!(Event::CanRecv(true), raised_evts[2]);
     }
 
+    #[test]
+    fn subscriptions_can_be_read_back() {
+        let (tx, _) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("A")));
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("B")));
+        let _ = sub.set_option(ConfigOption::Unsubscribe(String::from("A")));
+
+        let opt = sub.get_option(ConfigOptionKind::Subscriptions).unwrap();
+
+        assert_eq!(ConfigOption::Subscriptions(vec![b"B".to_vec()]), opt);
+        assert!(sub.get_option(ConfigOptionKind::ReqResendIvl).is_err());
+    }
+
+    #[test]
+    fn messages_are_filtered_on_binary_topics() {
+        let (tx, rx) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let _ = sub.set_option(ConfigOption::SubscribeTopic(vec![255, 0]));
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+
+        sub.add_pipe(&mut ctx, eid, pipe);
+        sub.on_recv_ready(&mut ctx, eid);
+        sub.recv(&mut ctx, None);
+        sub.on_recv_ack(&mut ctx, eid, Message::from_body(vec![255, 1, 0]));
+
+        assert!(rx.try_recv().is_err());
+
+        sub.on_recv_ready(&mut ctx, eid);
+        sub.on_recv_ack(&mut ctx, eid, Message::from_body(vec![255, 0, 1]));
+
+        let reply = rx.recv().expect("facade should have been sent a reply !");
+        let body = match reply {
+            Reply::Recv(msg) => msg.get_body().to_vec(),
+            _ => Vec::new()
+        };
+        assert_eq!(vec![255, 0, 1], body);
+    }
+
+    #[test]
+    fn subscriptions_are_forwarded_when_both_sides_agree() {
+        let (tx, _) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let plain = EndpointId::from(0);
+        let forwarding = EndpointId::from(1);
+
+        let _ = sub.set_option(ConfigOption::SubscriptionForwarding(true));
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("A")));
+        sub.add_pipe(&mut ctx, plain, new_test_pipe(plain));
+        sub.add_pipe(&mut ctx, forwarding, new_test_pipe_with_flags(forwarding, SUB_FORWARDING));
+        sub.on_send_ready(&mut ctx, plain);
+        sub.on_send_ready(&mut ctx, forwarding);
+
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("B")));
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("B")));
+        sub.on_send_ready(&mut ctx, forwarding);
+        let _ = sub.set_option(ConfigOption::Unsubscribe(String::from("A")));
+        sub.on_send_ready(&mut ctx, forwarding);
+
+        let sensor = ctx_sensor.borrow();
+        let bodies: Vec<Vec<u8>> = sensor.get_send_calls().iter().map(|&(_, ref msg)| msg.get_body().to_vec()).collect();
+
+        sensor.assert_send_to(plain, 0);
+        sensor.assert_send_to(forwarding, 3);
+        assert_eq!(vec![b"\x01A".to_vec(), b"\x01B".to_vec(), b"\x00A".to_vec()], bodies);
+    }
+
+    #[test]
+    fn subscriptions_wait_for_the_pipe_to_be_ready() {
+        let (tx, _) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+
+        let _ = sub.set_option(ConfigOption::SubscriptionForwarding(true));
+        sub.add_pipe(&mut ctx, eid, new_test_pipe_with_flags(eid, SUB_FORWARDING));
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("A")));
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("B")));
+
+        ctx_sensor.borrow().assert_no_send_call();
+
+        sub.on_send_ready(&mut ctx, eid);
+        ctx_sensor.borrow().assert_one_send_to(eid);
+
+        sub.on_send_ready(&mut ctx, eid);
+        ctx_sensor.borrow().assert_send_to(eid, 2);
+    }
+
+    #[test]
+    fn subscriptions_are_sent_once_set_when_the_pipe_is_idle() {
+        let (tx, _) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+
+        let _ = sub.set_option(ConfigOption::SubscriptionForwarding(true));
+        sub.add_pipe(&mut ctx, eid, new_test_pipe_with_flags(eid, SUB_FORWARDING));
+        sub.on_send_ready(&mut ctx, eid);
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("A")));
+
+        ctx_sensor.borrow().assert_no_send_call();
+
+        sub.on_option_set(&mut ctx);
+        ctx_sensor.borrow().assert_one_send_to(eid);
+    }
+
     /*//#[test]
     fn when_recv_ack_event_is_raised_if_there_is_another_pipe_ready() {
         let (tx, _) = mpsc::channel();
@@ -423,4 +621,35 @@ mod tests {
         assert_eq!(Event::CanRecv(true), raised_evts[0]);
         assert_eq!(Event::CanRecv(false), raised_evts[1]);
     }*/
+    #[test]
+    fn messages_shared_with_other_pipes_are_filtered_too() {
+        let (tx, rx) = mpsc::channel();
+        let mut sub = Sub::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let _ = sub.set_option(ConfigOption::Subscribe(String::from("A")));
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+        let rejected = Rc::new(Message::from_body(vec![66]));
+        let accepted = Rc::new(Message::from_body(vec![65, 66]));
+
+        sub.add_pipe(&mut ctx, eid, pipe);
+        sub.on_recv_ready(&mut ctx, eid);
+        sub.recv(&mut ctx, None);
+        sub.on_recv_shared_ack(&mut ctx, eid, rejected.clone());
+
+        assert!(rx.try_recv().is_err());
+        assert_eq!(1, Rc::strong_count(&rejected));
+
+        sub.on_recv_ready(&mut ctx, eid);
+        sub.on_recv_shared_ack(&mut ctx, eid, accepted.clone());
+
+        let reply = rx.recv().expect("facade should have been sent a reply !");
+        let body = match reply {
+            Reply::Recv(msg) => msg.get_body().to_vec(),
+            _ => Vec::new()
+        };
+        assert_eq!(vec![65, 66], body);
+        assert_eq!(&[65, 66], accepted.get_body());
+    }
 }
\ No newline at end of file
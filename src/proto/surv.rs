@@ -16,12 +16,12 @@ use byteorder::*;
 
 use core::{EndpointId, Message};
 use core::socket::{Protocol, Reply};
-use core::config::ConfigOption;
+use core::config::{ConfigOption, ConfigOptionKind};
 use core::endpoint::Pipe;
 use core::context::{Context, Schedulable};
 use super::priolist::Priolist;
 use super::pipes::PipeCollection;
-use super::{Timeout, SURVEYOR, RESPONDENT};
+use super::{Timeout, SURVEYOR, RESPONDENT, pop_hop, into_payload};
 use super::policy::{broadcast, fair_queue};
 use io_error::*;
 
@@ -155,6 +155,12 @@ impl Protocol for Surveyor {
             _ => Err(invalid_input_io_error("option not supported"))
         }
     }
+    fn get_option(&self, kind: ConfigOptionKind) -> io::Result<ConfigOption> {
+        match kind {
+            ConfigOptionKind::SurveyDeadline => Ok(ConfigOption::SurveyDeadline(self.inner.deadline)),
+            _ => Err(invalid_input_io_error("option not supported"))
+        }
+    }
     fn on_timer_tick(&mut self, ctx: &mut Context, task: Schedulable) {
         if let Schedulable::SurveyCancel = task {
             self.apply(ctx, |s, ctx, inner| s.on_survey_timeout(ctx, inner))
@@ -461,22 +467,14 @@ fn encode(msg: Message, survey_id: u32) -> Message {
     raw_msg
 }
 
-fn decode(raw_msg: Message) -> Option<(Message, u32)> {
-    if raw_msg.get_body().len() < 4 {
-        return None;
-    }
-
-    let (mut header, mut payload) = raw_msg.split();
-    let body = payload.split_off(4);
-    let survey_id = BigEndian::read_u32(&payload);
-
-    if header.is_empty() {
-        header = payload;
-    } else {
-        header.extend_from_slice(&payload);
-    }
+fn decode(mut raw_msg: Message) -> Option<(Message, u32)> {
+    let survey_id_bytes = match pop_hop(&mut raw_msg) {
+        Some(hop) => hop,
+        None => return None
+    };
+    let survey_id = BigEndian::read_u32(&survey_id_bytes);
 
-    Some((Message::from_header_and_body(header, body), survey_id))
+    Some((Message::from_header_and_body(survey_id_bytes.to_vec(), into_payload(raw_msg)), survey_id))
 }
 
 /*****************************************************************************/
@@ -778,5 +776,36 @@ mod tests {
         assert_eq!(3, app_msg.get_body().len());
     }
 
+    #[test]
+    fn when_in_raw_mode_recv_keeps_the_survey_id_found_in_the_header() {
+        let (tx, rx) = mpsc::channel();
+        let mut surv = Surveyor::from(tx);
+        let ctx_sensor = Rc::new(RefCell::new(TestContextSensor::default()));
+        let mut ctx = TestContext::with_sensor(ctx_sensor.clone());
+        let eid = EndpointId::from(0);
+        let pipe = new_test_pipe(eid);
+
+        let any_survey_id = 666 | 0x80000000;
+        let mut header: Vec<u8> = vec![0, 0, 0, 0];
+
+        BigEndian::write_u32(&mut header[0..4], any_survey_id);
+
+        let msg = Message::from_header_and_body(header, vec![4, 2, 1]);
+        surv.on_device_plugged(&mut ctx);
+        surv.add_pipe(&mut ctx, eid, pipe);
+        surv.on_recv_ready(&mut ctx, eid);
+        surv.recv(&mut ctx, None);
+        surv.on_recv_ack(&mut ctx, eid, msg);
+
+        let reply = rx.try_recv().expect("facade should have been sent a reply !");
+        let reply_msg = match reply {
+            Reply::Recv(msg) => Some(msg),
+            _ => None
+        };
+        let app_msg = reply_msg.expect("facade should have been sent a Recv reply !");
+        assert_eq!(&[0x80, 0, 2, 154], app_msg.get_header());
+        assert_eq!(&[4, 2, 1], app_msg.get_body());
+    }
+
     // test CanRecv events ...
 }
\ No newline at end of file
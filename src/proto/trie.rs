@@ -0,0 +1,233 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// Set of byte strings telling whether one of them is a prefix of a given input.
+/// Used by `Sub` to filter the incoming messages by topic, the cost of a lookup
+/// depends on the length of the message rather than on the number of subscriptions.
+/// The children of a node are kept sorted by byte so they can be found with a binary search.
+#[derive(Default)]
+pub struct Trie {
+    root: Node
+}
+
+#[derive(Default)]
+struct Node {
+    is_key: bool,
+    children: Vec<(u8, Node)>
+}
+
+impl Trie {
+    pub fn new() -> Trie {
+        Trie::default()
+    }
+
+    /// Adds the key, returns false if it was already there.
+    pub fn insert(&mut self, key: &[u8]) -> bool {
+        let mut node = &mut self.root;
+
+        for &b in key {
+            node = node.child_or_insert(b);
+        }
+
+        if node.is_key {
+            false
+        } else {
+            node.is_key = true;
+            true
+        }
+    }
+
+    /// Removes the key and the nodes only used by it, returns false if it was not there.
+    pub fn remove(&mut self, key: &[u8]) -> bool {
+        self.root.remove(key)
+    }
+
+    /// Tells whether one of the keys is a prefix of the input, the empty key matching everything.
+    pub fn matches(&self, input: &[u8]) -> bool {
+        let mut node = &self.root;
+
+        for &b in input {
+            if node.is_key {
+                return true;
+            }
+
+            node = match node.child(b) {
+                Some(child) => child,
+                None => return false
+            };
+        }
+
+        node.is_key
+    }
+
+    /// Returns all the keys, in ascending order.
+    pub fn keys(&self) -> Vec<Vec<u8>> {
+        let mut keys = Vec::new();
+        let mut prefix = Vec::new();
+
+        self.root.collect(&mut prefix, &mut keys);
+
+        keys
+    }
+}
+
+impl Node {
+    fn child(&self, b: u8) -> Option<&Node> {
+        self.children.
+            binary_search_by_key(&b, |&(x, _)| x).
+            ok().
+            map(|index| &self.children[index].1)
+    }
+
+    fn child_or_insert(&mut self, b: u8) -> &mut Node {
+        let index = match self.children.binary_search_by_key(&b, |&(x, _)| x) {
+            Ok(index) => index,
+            Err(index) => {
+                self.children.insert(index, (b, Node::default()));
+                index
+            }
+        };
+
+        &mut self.children[index].1
+    }
+
+    fn remove(&mut self, key: &[u8]) -> bool {
+        if key.is_empty() {
+            let removed = self.is_key;
+
+            self.is_key = false;
+
+            return removed;
+        }
+
+        let index = match self.children.binary_search_by_key(&key[0], |&(x, _)| x) {
+            Ok(index) => index,
+            Err(_) => return false
+        };
+        let removed = self.children[index].1.remove(&key[1..]);
+
+        if removed && self.children[index].1.is_useless() {
+            self.children.remove(index);
+        }
+
+        removed
+    }
+
+    fn is_useless(&self) -> bool {
+        self.is_key == false && self.children.is_empty()
+    }
+
+    fn collect(&self, prefix: &mut Vec<u8>, keys: &mut Vec<Vec<u8>>) {
+        if self.is_key {
+            keys.push(prefix.clone());
+        }
+
+        for &(b, ref child) in &self.children {
+            prefix.push(b);
+            child.collect(prefix, keys);
+            prefix.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Trie;
+
+    #[test]
+    fn empty_trie_matches_nothing() {
+        let trie = Trie::new();
+
+        assert!(trie.matches(b"") == false);
+        assert!(trie.matches(b"abc") == false);
+    }
+
+    #[test]
+    fn empty_key_matches_everything() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"");
+
+        assert!(trie.matches(b""));
+        assert!(trie.matches(b"abc"));
+    }
+
+    #[test]
+    fn key_matches_input_it_prefixes() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"ab");
+
+        assert!(trie.matches(b"ab"));
+        assert!(trie.matches(b"abc"));
+        assert!(trie.matches(b"a") == false);
+        assert!(trie.matches(b"ac") == false);
+        assert!(trie.matches(b"ba") == false);
+    }
+
+    #[test]
+    fn keys_can_be_any_bytes() {
+        let mut trie = Trie::new();
+
+        trie.insert(&[0xFF, 0x00]);
+
+        assert!(trie.matches(&[0xFF, 0x00, 0x01]));
+        assert!(trie.matches(&[0xFF, 0x01]) == false);
+    }
+
+    #[test]
+    fn insert_and_remove_tell_whether_the_key_was_there() {
+        let mut trie = Trie::new();
+
+        assert!(trie.insert(b"abc"));
+        assert!(trie.insert(b"abc") == false);
+        assert!(trie.remove(b"ab") == false);
+        assert!(trie.remove(b"abc"));
+        assert!(trie.remove(b"abc") == false);
+    }
+
+    #[test]
+    fn remove_keeps_longer_and_shorter_keys() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"a");
+        trie.insert(b"ab");
+        trie.insert(b"abc");
+        trie.remove(b"ab");
+
+        assert!(trie.matches(b"a"));
+        assert!(trie.matches(b"abc"));
+        assert_eq!(vec![b"a".to_vec(), b"abc".to_vec()], trie.keys());
+    }
+
+    #[test]
+    fn remove_prunes_unused_nodes() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"abc");
+        trie.insert(b"abd");
+        trie.remove(b"abc");
+
+        assert_eq!(1, trie.root.children[0].1.children[0].1.children.len());
+
+        trie.remove(b"abd");
+
+        assert!(trie.root.children.is_empty());
+    }
+
+    #[test]
+    fn keys_are_listed_in_order() {
+        let mut trie = Trie::new();
+
+        trie.insert(b"b");
+        trie.insert(b"");
+        trie.insert(b"ba");
+        trie.insert(b"a");
+
+        assert_eq!(vec![b"".to_vec(), b"a".to_vec(), b"b".to_vec(), b"ba".to_vec()], trie.keys());
+    }
+}
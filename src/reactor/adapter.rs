@@ -17,12 +17,14 @@ use core::context;
 use core::device;
 use core::probe;
 use core::network::Network;
-use core::{BuildIdHasher, SocketId, EndpointId, DeviceId, ProbeId, Message, EndpointTmpl, Scheduled};
+use core::config::{SendBuffer, SendBufferPolicy};
+use core::{BuildIdHasher, SocketId, EndpointId, DeviceId, ProbeId, Message, EndpointTmpl, EndpointDesc, Scheduled};
 use transport::{Transport, Destination};
 use transport::endpoint::*;
 use transport::pipe;
 use transport::acceptor;
 use super::bus::EventLoopBus;
+use super::send_queue::{SendQueue, Push};
 use super::{Signal, Task};
 use sequence::Sequence;
 use io_error::*;
@@ -64,13 +66,16 @@ pub struct ProbeEventLoopContext<'a> {
 pub struct PipeController {
     socket_id: SocketId,
     endpoint_id: EndpointId,
-    pipe: Box<pipe::Pipe>
+    pipe: Box<pipe::Pipe>,
+    send_queue: SendQueue
 }
 
 pub struct AcceptorController {
     socket_id: SocketId,
     endpoint_id: EndpointId,
-    acceptor: Box<acceptor::Acceptor>
+    acceptor: Box<acceptor::Acceptor>,
+    send_buffer: SendBuffer,
+    send_buffer_policy: SendBufferPolicy
 }
 
 pub struct EndpointCollection {
@@ -114,13 +119,45 @@ impl PipeController {
         let mut ctx = self.create_context(registrar, signal_bus);
 
         match cmd {
-            pipe::Command::Open      => self.pipe.open(&mut ctx),
-            pipe::Command::Close     => self.pipe.close(&mut ctx),
-            pipe::Command::Send(msg) => self.pipe.send(&mut ctx, msg),
-            pipe::Command::Recv      => self.pipe.recv(&mut ctx)
+            pipe::Command::Open          => self.pipe.open(&mut ctx),
+            pipe::Command::Close         => self.pipe.close(&mut ctx),
+            pipe::Command::Send(msg)     => self.pipe.send(&mut ctx, msg),
+            pipe::Command::SendAll(msgs) => self.pipe.send_all(&mut ctx, msgs),
+            pipe::Command::Recv          => self.pipe.recv(&mut ctx)
         }
     }
 
+    /// Gives the message to the transport if it is idle, buffers it otherwise.
+    pub fn send(&mut self, registrar: &mut Registrar, signal_bus: &mut EventLoopBus<Signal>, msg: Rc<Message>) -> Push {
+        let push = self.send_queue.push(msg);
+
+        if let Push::Write(ref msg) = push {
+            self.process(registrar, signal_bus, pipe::Command::Send(msg.clone()));
+        }
+
+        push
+    }
+
+    /// The transport can send, it is given all the buffered messages at once.
+    pub fn on_writable(&mut self, registrar: &mut Registrar, signal_bus: &mut EventLoopBus<Signal>) {
+        let msgs = self.send_queue.on_writable();
+
+        if msgs.is_empty() == false {
+            self.process(registrar, signal_bus, pipe::Command::SendAll(msgs));
+        }
+    }
+
+    /// Returns the size of the message that has just been sent by the transport,
+    /// and whether it was acknowledged to the socket when it was buffered.
+    pub fn on_sent(&mut self) -> (usize, bool) {
+        self.send_queue.on_written()
+    }
+
+    /// Tells whether the socket should be notified that it can send to this pipe again.
+    pub fn take_send_ready(&mut self) -> bool {
+        self.send_queue.take_ready()
+    }
+
     fn create_context<'a, 'b>(&self, registrar: &'b mut Registrar, signal_bus: &'a mut EventLoopBus<Signal>) -> EndpointEventLoopContext<'a, 'b> {
         EndpointEventLoopContext {
             socket_id: self.socket_id,
@@ -177,19 +214,30 @@ impl EndpointCollection {
         self.pipes.get_mut(&eid)
     }
 
-    pub fn insert_pipe(&mut self, sid: SocketId, pipe: Box<pipe::Pipe>) -> EndpointId {
+    fn insert_pipe(&mut self, sid: SocketId, pipe: Box<pipe::Pipe>, send_queue: SendQueue) -> EndpointId {
         let eid = EndpointId::from(self.ids.next());
         
-        self.insert_pipe_controller(sid, eid, pipe);
+        self.insert_pipe_controller(sid, eid, pipe, send_queue);
 
         eid
     }
 
-    fn insert_pipe_controller(&mut self, sid: SocketId, eid: EndpointId, pipe: Box<pipe::Pipe>) {
+    /// Inserts a pipe created by an acceptor, its messages are buffered like the acceptor was told to.
+    pub fn insert_accepted_pipe(&mut self, sid: SocketId, aid: EndpointId, pipe: Box<pipe::Pipe>) -> EndpointId {
+        let send_queue = match self.acceptors.get(&aid) {
+            Some(acceptor) => SendQueue::new(acceptor.send_buffer, acceptor.send_buffer_policy),
+            None => SendQueue::new(SendBuffer::Messages(0), SendBufferPolicy::Block)
+        };
+
+        self.insert_pipe(sid, pipe, send_queue)
+    }
+
+    fn insert_pipe_controller(&mut self, sid: SocketId, eid: EndpointId, pipe: Box<pipe::Pipe>, send_queue: SendQueue) {
         let controller = PipeController {
             socket_id: sid,
             endpoint_id: eid,
-            pipe: pipe
+            pipe: pipe,
+            send_queue: send_queue
         };
 
         self.pipes.insert(eid, controller);
@@ -199,27 +247,60 @@ impl EndpointCollection {
         self.pipes.remove(&eid);
     }
 
+    /// Keeps track of the messages given to a pipe that have not been sent yet,
+    /// so a closing socket can wait for them, and of their size, for the socket statistics.
+    fn on_pipe_send(&mut self, eid: EndpointId, len: usize) {
+        if let Some(pipe) = self.pipes.get_mut(&eid) {
+            pipe.send_queue.on_send(len);
+        }
+    }
+
+    /// Messages still pending when a pipe is closed will never be sent.
+    fn on_pipe_close(&mut self, eid: EndpointId) {
+        if let Some(pipe) = self.pipes.get_mut(&eid) {
+            pipe.send_queue.clear();
+        }
+    }
+
+    fn has_pending_send(&self, sid: SocketId) -> bool {
+        self.pipes.values().any(|pipe| pipe.socket_id == sid && pipe.send_queue.has_pending())
+    }
+
+    fn get_remote_addr(&self, eid: EndpointId) -> Option<String> {
+        self.pipes.get(&eid).and_then(|pipe| pipe.pipe.remote_addr())
+    }
+
     pub fn get_acceptor_mut(&mut self, eid: EndpointId) -> Option<&mut AcceptorController> {
         self.acceptors.get_mut(&eid)
     }
 
-    fn insert_acceptor(&mut self, sid: SocketId, acceptor: Box<acceptor::Acceptor>) -> EndpointId {
+    fn insert_acceptor(&mut self, sid: SocketId, acceptor: Box<acceptor::Acceptor>, desc: &EndpointDesc) -> EndpointId {
         let eid = EndpointId::from(self.ids.next());
 
-        self.insert_acceptor_controller(sid, eid, acceptor);
+        self.insert_acceptor_controller(sid, eid, acceptor, desc);
 
         eid
     }
 
-    fn insert_acceptor_controller(&mut self, sid: SocketId, eid: EndpointId, acceptor: Box<acceptor::Acceptor>) {
+    fn insert_acceptor_controller(&mut self, sid: SocketId, eid: EndpointId, acceptor: Box<acceptor::Acceptor>, desc: &EndpointDesc) {
         let controller = AcceptorController {
             socket_id: sid,
             endpoint_id: eid,
-            acceptor: acceptor
+            acceptor: acceptor,
+            send_buffer: desc.send_buffer,
+            send_buffer_policy: desc.send_buffer_policy
         };
 
         self.acceptors.insert(eid, controller);
     }
+
+    fn get_local_addr(&self, eid: EndpointId) -> Option<String> {
+        self.acceptors.get(&eid).and_then(|acceptor| acceptor.acceptor.local_addr())
+    }
+}
+
+fn create_send_queue(desc: &EndpointDesc) -> SendQueue {
+    SendQueue::new(desc.send_buffer, desc.send_buffer_policy)
 }
 
 impl Schedule {
@@ -300,8 +381,10 @@ impl<'a> SocketEventLoopContext<'a> {
         let dest = Destination {
             addr: addr,
             pids: tmpl.pids,
+            proto_flags: tmpl.spec.desc.proto_flags,
             tcp_no_delay: tmpl.spec.desc.tcp_no_delay,
             recv_max_size: tmpl.spec.desc.recv_max_size,
+            recv_buffer: tmpl.spec.desc.recv_buffer
         };
 
         transport.connect(&dest)
@@ -320,8 +403,10 @@ impl<'a> SocketEventLoopContext<'a> {
         let dest = Destination {
             addr: addr,
             pids: tmpl.pids,
+            proto_flags: tmpl.spec.desc.proto_flags,
             tcp_no_delay: tmpl.spec.desc.tcp_no_delay,
             recv_max_size: tmpl.spec.desc.recv_max_size,
+            recv_buffer: tmpl.spec.desc.recv_buffer
         };
 
         transport.bind(&dest)
@@ -332,25 +417,25 @@ impl<'a> Network for SocketEventLoopContext<'a> {
 
     fn connect(&mut self, sid: SocketId, tmpl: &EndpointTmpl) -> io::Result<EndpointId> {
         let pipe = try!(self.connect(tmpl));
-        let eid = self.endpoints.insert_pipe(sid, pipe);
+        let eid = self.endpoints.insert_pipe(sid, pipe, create_send_queue(&tmpl.spec.desc));
 
         Ok(eid)
     }
     fn bind(&mut self, sid: SocketId, tmpl: &EndpointTmpl) -> io::Result<EndpointId> {
         let acceptor = try!(self.bind(tmpl));
-        let eid = self.endpoints.insert_acceptor(sid, acceptor);
+        let eid = self.endpoints.insert_acceptor(sid, acceptor, &tmpl.spec.desc);
 
         Ok(eid)
     }
     fn reconnect(&mut self, sid: SocketId, eid: EndpointId, tmpl: &EndpointTmpl) -> io::Result<()> {
         let pipe = try!(self.connect(tmpl));
 
-        Ok(self.endpoints.insert_pipe_controller(sid, eid, pipe))
+        Ok(self.endpoints.insert_pipe_controller(sid, eid, pipe, create_send_queue(&tmpl.spec.desc)))
     }
     fn rebind(&mut self, sid: SocketId, eid: EndpointId, tmpl: &EndpointTmpl) -> io::Result<()> {
         let acceptor = try!(self.bind(tmpl));
 
-        Ok(self.endpoints.insert_acceptor_controller(sid, eid, acceptor))
+        Ok(self.endpoints.insert_acceptor_controller(sid, eid, acceptor, &tmpl.spec.desc))
     }
     fn open(&mut self, endpoint_id: EndpointId, remote: bool) {
         if remote {
@@ -361,17 +446,28 @@ impl<'a> Network for SocketEventLoopContext<'a> {
     }
     fn close(&mut self, endpoint_id: EndpointId, remote: bool) {
         if remote {
+            self.endpoints.on_pipe_close(endpoint_id);
             self.send_pipe_cmd(endpoint_id, pipe::Command::Close);
         } else {
             self.send_acceptor_cmd(endpoint_id, acceptor::Command::Close)
         }
     }
     fn send(&mut self, endpoint_id: EndpointId, msg: Rc<Message>) {
+        self.endpoints.on_pipe_send(endpoint_id, msg.len());
         self.send_pipe_cmd(endpoint_id, pipe::Command::Send(msg));
     }
     fn recv(&mut self, endpoint_id: EndpointId) {
         self.send_pipe_cmd(endpoint_id, pipe::Command::Recv);
     }
+    fn has_pending_send(&self, sid: SocketId) -> bool {
+        self.endpoints.has_pending_send(sid)
+    }
+    fn local_addr(&self, endpoint_id: EndpointId) -> Option<String> {
+        self.endpoints.get_local_addr(endpoint_id)
+    }
+    fn remote_addr(&self, endpoint_id: EndpointId) -> Option<String> {
+        self.endpoints.get_remote_addr(endpoint_id)
+    }
 
 }
 
@@ -6,6 +6,7 @@
 
 use std::collections::HashMap;
 use std::sync::mpsc::Sender;
+use std::rc::Rc;
 use std::io;
 use std::time::Duration;
 
@@ -13,11 +14,12 @@ use mio::{Token, Ready, PollOpt};
 use mio::timer::{Timer, Builder};
 use mio::channel::{Receiver};
 
-use core::{BuildIdHasher, SocketId, EndpointId, DeviceId, ProbeId, session, socket, context, endpoint, device, probe};
+use core::{BuildIdHasher, SocketId, EndpointId, DeviceId, ProbeId, Message, session, socket, context, endpoint, device, probe};
 use transport::{Transport, pipe, acceptor};
 use super::{Signal, Request, Task};
 use super::event_loop::{EventLoop, EventHandler};
 use super::bus::EventLoopBus;
+use super::send_queue::Push;
 use super::adapter::{
     EndpointCollection, 
     Schedule, 
@@ -25,6 +27,7 @@ use super::adapter::{
     DeviceEventLoopContext,
     ProbeEventLoopContext };
 use sequence::Sequence;
+use io_error::*;
 
 const CHANNEL_TOKEN: Token = Token(::std::usize::MAX - 1);
 const BUS_TOKEN: Token     = Token(::std::usize::MAX - 2);
@@ -100,9 +103,15 @@ impl Dispatcher {
 /*                                                                           */
 /*****************************************************************************/
 
+    /// The signals raised by a request are processed before the next request,
+    /// so the readiness of the sockets is up to date when the next request comes in.
+    /// Otherwise a burst of requests would find the pipes still busy with the first one,
+    /// a `Pub` socket drops the messages to these pipes even though their send buffer has room.
+    /// This applies to every socket, the pipes without send buffer are just ready less often.
     fn process_channel(&mut self, el: &mut EventLoop) {
         while let Ok(req) = self.channel.try_recv() {
             self.process_request(el, req);
+            self.process_bus(el);
         }
     }
     fn process_bus(&mut self, el: &mut EventLoop) {
@@ -132,7 +141,7 @@ impl Dispatcher {
     }
     fn process_signal(&mut self, el: &mut EventLoop, signal: Signal) {
         match signal {
-            Signal::PipeCmd(_, eid, cmd)       => self.process_pipe_cmd(el, eid, cmd),
+            Signal::PipeCmd(sid, eid, cmd)     => self.process_pipe_cmd(el, sid, eid, cmd),
             Signal::AcceptorCmd(_, eid, cmd)   => self.process_acceptor_cmd(el, eid, cmd),
             Signal::SocketCmd(sid, cmd)        => self.process_socket_cmd(el, sid, cmd),
             Signal::PipeEvt(sid, eid, evt)     => self.process_pipe_evt(el, sid, eid, evt),
@@ -159,6 +168,7 @@ impl Dispatcher {
             context::Schedulable::Rebind(eid, spec)    => self.apply_on_socket(sid, |socket, ctx| socket.rebind(ctx, eid, spec)),
             context::Schedulable::SendTimeout          => self.apply_on_socket(sid, |socket, ctx| socket.on_send_timeout(ctx)),
             context::Schedulable::RecvTimeout          => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_timeout(ctx)),
+            context::Schedulable::Linger               => self.apply_on_socket(sid, |socket, ctx| socket.on_linger_timeout(ctx)),
             other                                      => self.apply_on_socket(sid, |socket, ctx| socket.on_timer_tick(ctx, other))
         }
     }
@@ -199,7 +209,13 @@ impl Dispatcher {
 /*****************************************************************************/
     fn process_session_request(&mut self, el: &mut EventLoop, request: session::Request) {
         match request {
-            session::Request::CreateSocket(ctor) => self.sockets.add_socket(ctor),
+            session::Request::CreateSocket(ctor, raw) => {
+                let id = self.sockets.add_socket(ctor);
+
+                if raw {
+                    self.apply_on_socket(id, |socket, ctx| socket.on_device_plugged(ctx));
+                }
+            },
             session::Request::CreateDevice(l, r) => {
                 self.apply_on_socket(l, |socket, ctx| socket.on_device_plugged(ctx));
                 self.apply_on_socket(r, |socket, ctx| socket.on_device_plugged(ctx));
@@ -211,24 +227,49 @@ impl Dispatcher {
     }
     fn process_socket_request(&mut self, _: &mut EventLoop, id: SocketId, request: socket::Request) {
         match request {
-            socket::Request::Connect(url)     => self.apply_on_socket(id, |socket, ctx| socket.connect(ctx, url)),
-            socket::Request::Bind(url)        => self.apply_on_socket(id, |socket, ctx| socket.bind(ctx, url)),
-            socket::Request::Send(msg, false) => self.apply_on_socket(id, |socket, ctx| socket.send(ctx, msg)),
-            socket::Request::Send(msg, true)  => self.apply_on_socket(id, |socket, ctx| socket.try_send(ctx, msg)),
-            socket::Request::Recv(false)      => self.apply_on_socket(id, |socket, ctx| socket.recv(ctx)),
-            socket::Request::Recv(true)       => self.apply_on_socket(id, |socket, ctx| socket.try_recv(ctx)),
-            socket::Request::SetOption(x)     => self.apply_on_socket(id, |socket, ctx| socket.set_option(ctx, x)),
-            socket::Request::Close            => self.apply_on_socket(id, |socket, ctx| socket.close(ctx)),
+            socket::Request::Connect(url)      => self.apply_on_socket(id, |socket, ctx| socket.connect(ctx, url)),
+            socket::Request::Bind(url)         => self.apply_on_socket(id, |socket, ctx| socket.bind(ctx, url)),
+            socket::Request::Send(msg, false)  => self.apply_on_socket(id, |socket, ctx| socket.send(ctx, msg)),
+            socket::Request::Send(msg, true)   => self.apply_on_socket(id, |socket, ctx| socket.try_send(ctx, msg)),
+            socket::Request::Recv(false)       => self.apply_on_socket(id, |socket, ctx| socket.recv(ctx)),
+            socket::Request::Recv(true)        => self.apply_on_socket(id, |socket, ctx| socket.try_recv(ctx)),
+            socket::Request::RecvRequest       => self.apply_on_socket(id, |socket, ctx| socket.recv_request(ctx)),
+            socket::Request::SendReply(t, m)   => self.apply_on_socket(id, |socket, ctx| socket.send_reply_with_token(ctx, t, m)),
+            socket::Request::SetOption(x)      => self.apply_on_socket(id, |socket, ctx| socket.set_option(ctx, x)),
+            socket::Request::GetOption(x)      => self.apply_on_socket(id, |socket, ctx| socket.get_option(ctx, x)),
+            socket::Request::CreateContext     => self.apply_on_socket(id, |socket, ctx| socket.create_context(ctx)),
+            socket::Request::ContextSend(c, m) => self.apply_on_socket(id, |socket, ctx| socket.context_send(ctx, c, m)),
+            socket::Request::ContextRecv(c)    => self.apply_on_socket(id, |socket, ctx| socket.context_recv(ctx, c)),
+            socket::Request::CloseContext(c)   => self.apply_on_socket(id, |socket, ctx| socket.close_context(ctx, c)),
+            socket::Request::SetWaker(w)       => self.apply_on_socket(id, |socket, ctx| socket.set_waker(ctx, w)),
+            socket::Request::SetRecvNotifier(n) => self.apply_on_socket(id, |socket, ctx| socket.set_recv_notifier(ctx, n)),
+            socket::Request::SetSendNotifier(n) => self.apply_on_socket(id, |socket, ctx| socket.set_send_notifier(ctx, n)),
+            socket::Request::GetStatistics     => self.apply_on_socket(id, |socket, ctx| socket.get_statistics(ctx)),
+            socket::Request::Monitor           => self.apply_on_socket(id, |socket, ctx| socket.monitor(ctx)),
+            socket::Request::Close             => self.apply_on_socket(id, |socket, ctx| socket.close(ctx)),
         }
     }
     fn process_endpoint_request(&mut self, _: &mut EventLoop, sid: SocketId, eid: EndpointId, request: endpoint::Request) {
-        let endpoint::Request::Close(remote) = request;
-
-        self.apply_on_socket(sid, |socket, ctx| if remote {
-            socket.close_pipe(ctx, eid)
-        } else {
-            socket.close_acceptor(ctx, eid)
-        });
+        match request {
+            endpoint::Request::Close(remote, reply_tx) => self.apply_on_socket(sid, |socket, ctx| {
+                let res = if remote {
+                    socket.close_pipe(ctx, eid)
+                } else {
+                    socket.close_acceptor(ctx, eid)
+                };
+                let reply = match res {
+                    Ok(_) => endpoint::Reply::Close,
+                    Err(e) => endpoint::Reply::Err(e)
+                };
+
+                let _ = reply_tx.send(reply);
+            }),
+            endpoint::Request::GetRemoteAddr(reply_tx) => self.apply_on_socket(sid, |socket, ctx| {
+                let reply = endpoint::Reply::RemoteAddr(socket.get_remote_addr(ctx, eid));
+
+                let _ = reply_tx.send(reply);
+            })
+        }
     }
     fn process_device_request(&mut self, _: &mut EventLoop, id: DeviceId, request: device::Request) {
         if let device::Request::Check = request { 
@@ -247,9 +288,37 @@ impl Dispatcher {
 /* process signal cmd                                                        */
 /*                                                                           */
 /*****************************************************************************/
-    fn process_pipe_cmd(&mut self, el: &mut EventLoop, eid: EndpointId, cmd: pipe::Command) {
-        if let Some(pipe) = self.endpoints.get_pipe_mut(eid) {
-            pipe.process(el, &mut self.bus, cmd);
+    fn process_pipe_cmd(&mut self, el: &mut EventLoop, sid: SocketId, eid: EndpointId, cmd: pipe::Command) {
+        match cmd {
+            pipe::Command::Send(msg) => self.process_pipe_send(el, sid, eid, msg),
+            cmd => if let Some(pipe) = self.endpoints.get_pipe_mut(eid) {
+                pipe.process(el, &mut self.bus, cmd);
+            }
+        }
+    }
+    fn process_pipe_send(&mut self, el: &mut EventLoop, sid: SocketId, eid: EndpointId, msg: Rc<Message>) {
+        let (push, ready) = match self.endpoints.get_pipe_mut(eid) {
+            Some(pipe) => {
+                let push = pipe.send(el, &mut self.bus, msg);
+                let ready = pipe.take_send_ready();
+
+                (push, ready)
+            },
+            None => return
+        };
+
+        match push {
+            Push::Write(_) => {},
+            Push::Ack      => self.apply_on_socket(sid, |socket, ctx| socket.on_send_buffered(ctx, eid)),
+            Push::Overflow => {
+                let err = other_io_error("send buffer overflow");
+
+                return self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_error(ctx, eid, err));
+            }
+        }
+
+        if ready {
+            self.apply_on_socket(sid, |socket, ctx| socket.on_send_ready(ctx, eid));
         }
     }
     fn process_acceptor_cmd(&mut self, el: &mut EventLoop, eid: EndpointId, cmd: acceptor::Command) {
@@ -268,26 +337,52 @@ impl Dispatcher {
 /* process signal evt                                                        */
 /*                                                                           */
 /*****************************************************************************/
-    fn process_pipe_evt(&mut self, _: &mut EventLoop, sid: SocketId, eid: EndpointId, evt: pipe::Event) {
+    fn process_pipe_evt(&mut self, el: &mut EventLoop, sid: SocketId, eid: EndpointId, evt: pipe::Event) {
         match evt {
-            pipe::Event::Opened        => self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_opened(ctx, eid)),
-            pipe::Event::CanSend       => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ready(ctx, eid)),
-            pipe::Event::Sent          => self.apply_on_socket(sid, |socket, ctx| socket.on_send_ack(ctx, eid)),
+            pipe::Event::Opened(flags) => self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_opened(ctx, eid, flags)),
+            pipe::Event::CanSend       => self.process_pipe_writable(el, sid, eid),
+            pipe::Event::Sent          => self.process_pipe_sent(sid, eid),
             pipe::Event::CanRecv       => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_ready(ctx, eid)),
             pipe::Event::Received(msg) => self.apply_on_socket(sid, |socket, ctx| socket.on_recv_ack(ctx, eid, msg)),
             pipe::Event::Error(err)    => self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_error(ctx, eid, err)),
             pipe::Event::Closed        => self.endpoints.remove_pipe(eid)
         }
     }
+    fn process_pipe_writable(&mut self, el: &mut EventLoop, sid: SocketId, eid: EndpointId) {
+        let ready = match self.endpoints.get_pipe_mut(eid) {
+            Some(pipe) => {
+                pipe.on_writable(el, &mut self.bus);
+                pipe.take_send_ready()
+            },
+            None => false
+        };
+
+        if ready {
+            self.apply_on_socket(sid, |socket, ctx| socket.on_send_ready(ctx, eid));
+        }
+    }
+    fn process_pipe_sent(&mut self, sid: SocketId, eid: EndpointId) {
+        let (len, buffered) = match self.endpoints.get_pipe_mut(eid) {
+            Some(pipe) => pipe.on_sent(),
+            None => (0, false)
+        };
+
+        if buffered {
+            self.apply_on_socket(sid, |socket, ctx| socket.on_send_flushed(ctx, len))
+        } else {
+            self.apply_on_socket(sid, |socket, ctx| socket.on_send_ack(ctx, eid, len))
+        }
+    }
     fn process_acceptor_evt(&mut self, _: &mut EventLoop, sid: SocketId, aid: EndpointId, evt: acceptor::Event) {
         match evt {
             // Maybe the controller should be removed from the endpoint collection
             acceptor::Event::Error(e) => self.apply_on_socket(sid, |socket, ctx| socket.on_acceptor_error(ctx, aid, e)),
             acceptor::Event::Accepted(pipes) => {
                 for pipe in pipes {
-                    let pipe_id = self.endpoints.insert_pipe(sid, pipe);
+                    let peer_addr = pipe.remote_addr();
+                    let pipe_id = self.endpoints.insert_accepted_pipe(sid, aid, pipe);
 
-                    self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_accepted(ctx, aid, pipe_id));
+                    self.apply_on_socket(sid, |socket, ctx| socket.on_pipe_accepted(ctx, aid, pipe_id, peer_addr));
                 }
             },
             _ => {}
@@ -296,12 +391,17 @@ impl Dispatcher {
     fn process_socket_evt(&mut self, _: &mut EventLoop, sid: SocketId, evt: context::Event) {
         match evt {
             context::Event::CanRecv(x) => {
+                if let Some(socket) = self.sockets.get_socket_mut(sid) {
+                    socket.on_can_recv(x);
+                }
                 self.apply_on_device_link(sid, |device| device.on_socket_can_recv(sid, x));
-                self.apply_on_probe_link(sid, |probe, ctx| probe.on_socket_can_recv(ctx, sid, x));
+                self.apply_on_probe_links(sid, |probe, ctx| probe.on_socket_can_recv(ctx, sid, x));
             },
             context::Event::CanSend(x) => {
-                self.apply_on_probe_link(sid, |probe, ctx| probe.on_socket_can_send(ctx, sid, x));
-
+                if let Some(socket) = self.sockets.get_socket_mut(sid) {
+                    socket.on_can_send(x);
+                }
+                self.apply_on_probe_links(sid, |probe, ctx| probe.on_socket_can_send(ctx, sid, x));
             },
             context::Event::Closed => self.sockets.remove_socket(sid)
         }
@@ -318,6 +418,7 @@ impl Dispatcher {
                 &mut self.timer);
 
             f(socket, &mut ctx);
+            socket.wake();
         }
     }
 
@@ -348,15 +449,10 @@ impl Dispatcher {
         }
     }
 
-    fn apply_on_probe_link<F>(&mut self, id: SocketId, f: F) 
-    where F : FnOnce(&mut probe::Probe, &mut ProbeEventLoopContext) {
-        if let Some((pid, probe)) = self.sockets.find_probe_mut(id) {
-            let mut ctx = ProbeEventLoopContext::new(
-                *pid,
-                &mut self.bus,
-                &mut self.schedule,
-                &mut self.timer);
-            f(probe, &mut ctx);
+    fn apply_on_probe_links<F>(&mut self, id: SocketId, f: F) 
+    where F : Fn(&mut probe::Probe, &mut ProbeEventLoopContext) {
+        for pid in self.sockets.find_probe_ids(id) {
+            self.apply_on_probe(pid, |probe, ctx| f(probe, ctx));
         }
     }
 }
@@ -8,6 +8,7 @@ pub mod dispatcher;
 mod event_loop;
 mod bus;
 mod adapter;
+mod send_queue;
 
 use core::{SocketId, EndpointId, DeviceId, ProbeId, context, session, socket, endpoint, device, probe};
 use transport::{pipe, acceptor};
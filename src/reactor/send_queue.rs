@@ -0,0 +1,342 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// Outbound messages of a pipe, between the socket and the transport.
+/// The messages sent by the socket while the transport is busy are buffered up to the high-water mark,
+/// and the socket is told the pipe can send as long as there is room.
+/// Buffered messages are acknowledged to the socket right away, the transport is given all of them
+/// at once when it can send again, so it can write them together.
+
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+use core::Message;
+use core::config::{SendBuffer, SendBufferPolicy};
+
+pub struct SendQueue {
+    limit: SendBuffer,
+    policy: SendBufferPolicy,
+    /// Size of the messages sent by the socket that are not sent by the transport yet, in order.
+    pending: VecDeque<usize>,
+    buffered: VecDeque<Rc<Message>>,
+    buffered_len: usize,
+    /// The transport can take a message right away.
+    writable: bool,
+    /// For each message the transport is sending, in order,
+    /// whether it was already acknowledged when it was buffered.
+    in_flight: VecDeque<bool>,
+    /// The socket has been told it can send to the pipe.
+    ready: bool
+}
+
+/// What to do with a message sent by the socket.
+pub enum Push {
+    /// The transport is idle, the message must be given to it.
+    Write(Rc<Message>),
+    /// The message was buffered, or dropped because of the policy, and must be acknowledged.
+    Ack,
+    /// The buffer is full and the pipe must be closed.
+    Overflow
+}
+
+impl SendQueue {
+    pub fn new(limit: SendBuffer, policy: SendBufferPolicy) -> SendQueue {
+        SendQueue {
+            limit: limit,
+            policy: policy,
+            pending: VecDeque::new(),
+            buffered: VecDeque::new(),
+            buffered_len: 0,
+            writable: false,
+            in_flight: VecDeque::new(),
+            ready: false
+        }
+    }
+
+    /// The socket is sending a message of the specified size, it is pending until the transport is done with it.
+    pub fn on_send(&mut self, len: usize) {
+        self.ready = false;
+        self.pending.push_back(len);
+    }
+
+    pub fn push(&mut self, msg: Rc<Message>) -> Push {
+        if self.writable {
+            self.writable = false;
+            self.in_flight.push_back(false);
+            return Push::Write(msg);
+        }
+
+        // a blocking pipe is only ready while there is room, the socket must not send past the limit
+        debug_assert!(self.policy != SendBufferPolicy::Block || self.is_full() == false);
+
+        if self.is_full() == false {
+            self.buffer(msg);
+            return Push::Ack;
+        }
+
+        match self.policy {
+            SendBufferPolicy::DropOldest => {
+                while self.is_full() && self.drop_oldest() {}
+
+                if self.is_full() {
+                    self.drop_newest();
+                } else {
+                    self.buffer(msg);
+                }
+                Push::Ack
+            },
+            SendBufferPolicy::Disconnect => {
+                self.drop_newest();
+                Push::Overflow
+            },
+            _ => {
+                self.drop_newest();
+                Push::Ack
+            }
+        }
+    }
+
+    /// The transport can send, returns the buffered messages it must be given, if any.
+    pub fn on_writable(&mut self) -> Vec<Rc<Message>> {
+        if self.buffered.is_empty() {
+            self.writable = true;
+            return Vec::new();
+        }
+
+        self.buffered_len = 0;
+        self.in_flight.extend(self.buffered.iter().map(|_| true));
+        self.buffered.drain(..).collect()
+    }
+
+    /// The transport has sent a message, returns its size,
+    /// and whether it was acknowledged when it was buffered.
+    pub fn on_written(&mut self) -> (usize, bool) {
+        let len = self.pending.pop_front().unwrap_or(0);
+        let flushed = self.in_flight.pop_front().unwrap_or(false);
+
+        (len, flushed)
+    }
+
+    /// Tells whether the socket should be told it can send to the pipe, only once until it does.
+    pub fn take_ready(&mut self) -> bool {
+        if self.ready || self.can_take() == false {
+            return false;
+        }
+
+        self.ready = true;
+        true
+    }
+
+    /// Messages still pending when the pipe is closed will never be sent.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.in_flight.clear();
+        self.buffered.clear();
+        self.buffered_len = 0;
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_empty() == false
+    }
+
+    fn can_take(&self) -> bool {
+        self.writable || self.policy != SendBufferPolicy::Block || self.is_full() == false
+    }
+
+    fn is_full(&self) -> bool {
+        match self.limit {
+            SendBuffer::Messages(x) => self.buffered.len() >= x,
+            SendBuffer::Bytes(x)    => self.buffered_len >= x
+        }
+    }
+
+    fn buffer(&mut self, msg: Rc<Message>) {
+        self.buffered_len += msg.len();
+        self.buffered.push_back(msg);
+    }
+
+    /// The pending sizes are ordered like the messages: the ones being written, the buffered ones, and the newest.
+    fn drop_newest(&mut self) {
+        let index = self.in_flight.len() + self.buffered.len();
+
+        self.pending.remove(index);
+    }
+
+    fn drop_oldest(&mut self) -> bool {
+        match self.buffered.pop_front() {
+            Some(msg) => {
+                self.buffered_len -= msg.len();
+                self.pending.remove(self.in_flight.len());
+                true
+            },
+            None => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use core::Message;
+    use core::config::{SendBuffer, SendBufferPolicy};
+
+    use super::*;
+
+    fn send(queue: &mut SendQueue, body: &[u8]) -> Push {
+        let msg = Message::from_body(body.to_vec());
+
+        queue.on_send(msg.len());
+        queue.push(Rc::new(msg))
+    }
+
+    fn is_write(push: Push) -> bool {
+        match push {
+            Push::Write(_) => true,
+            _ => false
+        }
+    }
+
+    fn is_ack(push: Push) -> bool {
+        match push {
+            Push::Ack => true,
+            _ => false
+        }
+    }
+
+    #[test]
+    fn without_buffer_the_pipe_is_ready_when_the_transport_is() {
+        let mut queue = SendQueue::new(SendBuffer::Messages(0), SendBufferPolicy::Block);
+
+        assert!(queue.take_ready() == false);
+        assert!(queue.on_writable().is_empty());
+        assert!(queue.take_ready());
+        assert!(queue.take_ready() == false);
+        assert!(is_write(send(&mut queue, b"A")));
+        assert!(queue.take_ready() == false);
+        assert_eq!((1, false), queue.on_written());
+        assert!(queue.has_pending() == false);
+    }
+
+    #[test]
+    fn buffered_messages_are_written_in_order_once_acknowledged() {
+        let mut queue = SendQueue::new(SendBuffer::Messages(2), SendBufferPolicy::Block);
+
+        queue.on_writable();
+        assert!(is_write(send(&mut queue, b"A")));
+        assert!(queue.take_ready());
+        assert!(is_ack(send(&mut queue, b"BB")));
+        assert!(queue.take_ready());
+        assert!(is_ack(send(&mut queue, b"CCC")));
+        assert!(queue.take_ready() == false);
+
+        assert_eq!((1, false), queue.on_written());
+
+        let msgs = queue.on_writable();
+
+        assert_eq!(2, msgs.len());
+        assert_eq!(b"BB", msgs[0].get_body());
+        assert_eq!(b"CCC", msgs[1].get_body());
+        assert!(queue.take_ready());
+        assert_eq!((2, true), queue.on_written());
+        assert_eq!((3, true), queue.on_written());
+        assert!(queue.on_writable().is_empty());
+        assert!(queue.has_pending() == false);
+    }
+
+    #[test]
+    fn buffer_can_be_limited_in_bytes() {
+        let mut queue = SendQueue::new(SendBuffer::Bytes(4), SendBufferPolicy::Block);
+
+        queue.on_writable();
+        send(&mut queue, b"A");
+        send(&mut queue, b"BBB");
+        assert!(queue.take_ready());
+        send(&mut queue, b"CC");
+        assert!(queue.take_ready() == false);
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_buffered_messages() {
+        let mut queue = SendQueue::new(SendBuffer::Messages(1), SendBufferPolicy::DropNewest);
+
+        queue.on_writable();
+        send(&mut queue, b"A");
+        send(&mut queue, b"BB");
+        assert!(is_ack(send(&mut queue, b"CCC")));
+        assert!(queue.take_ready());
+
+        assert_eq!((1, false), queue.on_written());
+        assert_eq!(b"BB", queue.on_writable()[0].get_body());
+        assert_eq!((2, true), queue.on_written());
+        assert!(queue.has_pending() == false);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room_for_the_newest() {
+        let mut queue = SendQueue::new(SendBuffer::Messages(1), SendBufferPolicy::DropOldest);
+
+        queue.on_writable();
+        send(&mut queue, b"A");
+        send(&mut queue, b"BB");
+        assert!(is_ack(send(&mut queue, b"CCC")));
+
+        assert_eq!((1, false), queue.on_written());
+        assert_eq!(b"CCC", queue.on_writable()[0].get_body());
+        assert_eq!((3, true), queue.on_written());
+        assert!(queue.has_pending() == false);
+    }
+
+    #[test]
+    fn disconnect_reports_the_overflow() {
+        let mut queue = SendQueue::new(SendBuffer::Messages(0), SendBufferPolicy::Disconnect);
+
+        queue.on_writable();
+        send(&mut queue, b"A");
+        assert!(queue.take_ready());
+
+        let overflow = match send(&mut queue, b"B") {
+            Push::Overflow => true,
+            _ => false
+        };
+
+        assert!(overflow);
+        assert_eq!((1, false), queue.on_written());
+        assert!(queue.has_pending() == false);
+    }
+
+    #[test]
+    fn messages_buffered_while_writing_are_acknowledged_in_order() {
+        let mut queue = SendQueue::new(SendBuffer::Messages(2), SendBufferPolicy::DropNewest);
+
+        queue.on_writable();
+        send(&mut queue, b"A");
+        send(&mut queue, b"BB");
+        assert_eq!(1, queue.on_writable().len());
+        send(&mut queue, b"CCC");
+        send(&mut queue, b"DDDD");
+        assert!(is_ack(send(&mut queue, b"EEEEE")));
+
+        assert_eq!((1, false), queue.on_written());
+        assert_eq!((2, true), queue.on_written());
+        assert_eq!(2, queue.on_writable().len());
+        assert_eq!((3, true), queue.on_written());
+        assert_eq!((4, true), queue.on_written());
+        assert!(queue.has_pending() == false);
+    }
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic]
+    fn block_does_not_take_messages_past_the_limit() {
+        let mut queue = SendQueue::new(SendBuffer::Messages(1), SendBufferPolicy::Block);
+
+        queue.on_writable();
+        send(&mut queue, b"A");
+        send(&mut queue, b"BB");
+        assert!(queue.take_ready() == false);
+        send(&mut queue, b"CCC");
+    }
+}
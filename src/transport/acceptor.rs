@@ -27,6 +27,9 @@ pub trait Acceptor {
     fn ready(&mut self, ctx: &mut Context, events: Ready);
     fn open(&mut self, ctx: &mut Context);
     fn close(&mut self, ctx: &mut Context);
+    /// Returns the address the acceptor is actually bound to,
+    /// which tells the port picked by the system when binding to port zero.
+    fn local_addr(&self) -> Option<String>;
 }
 
 pub trait Context : EndpointRegistrar {
@@ -4,6 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+use std::collections::VecDeque;
 use std::rc::Rc;
 use std::io::Result;
 
@@ -18,16 +19,28 @@ use io_error::*;
 
 pub struct Active<S> {
     stub: S,
+    peer_proto_flags: u8,
     writable: bool,
-    readable: bool
+    readable: bool,
+    /// The socket is waiting for the message being read.
+    receiving: bool,
+    /// Messages read ahead while the socket is not receiving, up to `recv_buffer` bytes.
+    received: VecDeque<Message>,
+    received_len: usize,
+    recv_buffer: usize
 }
 
 impl<S : AsyncPipeStub> Active<S> {
-    pub fn new(s: S) -> Active<S> {
+    pub fn new(s: S, peer_flags: u8, recv_buffer: usize) -> Active<S> {
         Active {
             stub: s,
+            peer_proto_flags: peer_flags,
             writable: false,
-            readable: false
+            readable: false,
+            receiving: false,
+            received: VecDeque::new(),
+            received_len: 0,
+            recv_buffer: recv_buffer
         }
     }
     
@@ -36,8 +49,8 @@ impl<S : AsyncPipeStub> Active<S> {
         ctx.raise(evt);
         ctx.reregister(self.stub.deref(), Ready::all(), PollOpt::edge());
     }
-    fn on_send_progress(&mut self, ctx: &mut Context, progress: Result<bool>) -> Result<()> {
-        progress.map(|sent| if sent { self.on_msg_sent(ctx) } )
+    fn on_send_progress(&mut self, ctx: &mut Context, progress: Result<usize>) -> Result<()> {
+        progress.map(|sent| for _ in 0..sent { self.on_msg_sent(ctx) } )
     }
     fn on_msg_sent(&mut self, ctx: &mut Context) {
         self.raise_and_resync_readiness(ctx, Event::Sent);
@@ -63,17 +76,55 @@ impl<S : AsyncPipeStub> Active<S> {
         progress.map(|recv| if let Some(msg) = recv { self.on_msg_received(ctx, msg) } )
     }
     fn on_msg_received(&mut self, ctx: &mut Context, msg: Message) {
-        self.raise_and_resync_readiness(ctx, Event::Received(msg));
+        self.receiving = false;
+        self.raise_and_resync_readiness(ctx, Event::Received(Rc::new(msg)));
+        self.raise_can_recv_if_buffered(ctx);
+    }
+    /// The input buffered by the stub will not make the socket readable, so it is reported right away.
+    fn raise_can_recv_if_buffered(&mut self, ctx: &mut Context) {
+        if self.readable == false && self.stub.has_buffered_input() {
+            self.readable = true;
+            ctx.raise(Event::CanRecv);
+        }
+    }
+    /// Continues the message the stub has started to read, if any, be it for the socket or ahead of it.
+    fn next_recv(&mut self) -> Result<Option<Message>> {
+        if self.stub.has_pending_recv() {
+            self.stub.resume_recv()
+        } else {
+            self.stub.start_recv()
+        }
+    }
+    fn read_ahead(&mut self) -> Result<()> {
+        while self.received_len < self.recv_buffer {
+            match try!(self.next_recv()) {
+                Some(msg) => {
+                    self.received_len += msg.len();
+                    self.received.push_back(msg);
+                },
+                None => break
+            }
+        }
+
+        Ok(())
     }
     fn readable_changed(&mut self, ctx: &mut Context, events: Ready) -> Result<()> {
         if events.is_readable() == false {
             return Ok(());
         }
-        if self.stub.has_pending_recv() {
-            let progress = self.stub.resume_recv();
+        if self.receiving {
+            let progress = self.next_recv();
 
             return self.on_recv_progress(ctx, progress);
         }
+
+        try!(self.stub.read_idle());
+        try!(self.read_ahead());
+
+        // with read-ahead, the socket is told it can receive only once a whole message is there
+        if self.recv_buffer > 0 && self.received.is_empty() {
+            return Ok(());
+        }
         if events.is_hup() == false && self.readable == false {
             self.readable = true;
             ctx.raise(Event::CanRecv);
@@ -97,15 +148,20 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
     fn name(&self) -> &'static str {"Active"}
 
     fn enter(&mut self, ctx: &mut Context) {
-        self.raise_and_resync_readiness(ctx, Event::Opened);
+        let peer_flags = self.peer_proto_flags;
+
+        self.raise_and_resync_readiness(ctx, Event::Opened(peer_flags));
     }
     fn close(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         ctx.deregister(self.stub.deref());
 
         box Dead
     }
-    fn send(mut self: Box<Self>, ctx: &mut Context, msg: Rc<Message>) -> Box<PipeState<S>> {
-        let progress = self.stub.start_send(msg);
+    fn send(self: Box<Self>, ctx: &mut Context, msg: Rc<Message>) -> Box<PipeState<S>> {
+        self.send_all(ctx, vec![msg])
+    }
+    fn send_all(mut self: Box<Self>, ctx: &mut Context, msgs: Vec<Rc<Message>>) -> Box<PipeState<S>> {
+        let progress = self.stub.start_send(msgs);
         let res = self.on_send_progress(ctx, progress);
 
         self.writable = false;
@@ -113,11 +169,26 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for Active<S> {
         no_transition_if_ok(self, ctx, res)
     }
     fn recv(mut self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
-        let progress = self.stub.start_recv();
-        let res = self.on_recv_progress(ctx, progress);
+        if let Some(msg) = self.received.pop_front() {
+            self.received_len -= msg.len();
+            self.raise_and_resync_readiness(ctx, Event::Received(Rc::new(msg)));
+            self.readable = self.received.is_empty() == false;
+
+            if self.readable {
+                ctx.raise(Event::CanRecv);
+            } else {
+                self.raise_can_recv_if_buffered(ctx);
+            }
+
+            return self;
+        }
 
+        self.receiving = true;
         self.readable = false;
 
+        let progress = self.next_recv();
+        let res = self.on_recv_progress(ctx, progress);
+
         no_transition_if_ok(self, ctx, res)
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
@@ -150,7 +221,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let mut state = box Active::new(stub);
+        let mut state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
 
         state.enter(&mut ctx);
@@ -169,7 +240,7 @@ mod tests {
         assert_eq!(1, ctx.get_raised_events().len());
         let evt = &ctx.get_raised_events()[0];
         let is_opened = match *evt {
-            pipe::Event::Opened => true,
+            pipe::Event::Opened(_) => true,
             _ => false,
         };
 
@@ -179,7 +250,7 @@ mod tests {
     #[test]
     fn close_should_deregister_and_cause_a_transition_to_dead() {
         let stub = TestStepStream::new();
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let new_state = state.close(&mut ctx);
 
@@ -195,7 +266,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let payload = vec!(66, 65, 67);
         let msg = Rc::new(Message::from_body(payload));
@@ -218,7 +289,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
 
         sensor.borrow_mut().set_start_send_result(Some(false));
@@ -245,12 +316,39 @@ mod tests {
         assert!(is_sent);
     }
 
+    #[test]
+    fn send_all_raises_an_event_per_message_sent() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let stub = TestStepStream::with_sensor(sensor.clone());
+        let state = box Active::new(stub, 0, 0);
+        let mut ctx = TestPipeContext::new();
+        let msgs = vec![
+            Rc::new(Message::from_body(vec!(65))),
+            Rc::new(Message::from_body(vec!(66))),
+            Rc::new(Message::from_body(vec!(67)))];
+
+        sensor.borrow_mut().set_start_send_result(Some(false));
+        let new_state = state.send_all(&mut ctx, msgs);
+
+        assert_eq!("Active", new_state.name());
+        assert_eq!(0, ctx.get_raised_events().len());
+
+        sensor.borrow_mut().set_resume_send_result(Some(true));
+        let events = mio::Ready::writable();
+        let new_state = new_state.ready(&mut ctx, events);
+
+        assert_eq!("Active", new_state.name());
+        assert_eq!(3, ctx.get_raised_events().len());
+        assert!(ctx.get_raised_events().iter().all(|evt| evt.name() == "Sent"));
+    }
+
     #[test]
     fn when_writable_should_raise_an_event() {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let events = mio::Ready::writable();
         let new_state = state.ready(&mut ctx, events);
@@ -271,7 +369,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let events = mio::Ready::writable();
         let new_state = state.ready(&mut ctx, events);
@@ -288,7 +386,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let events = mio::Ready::writable() | mio::Ready::hup();
         let new_state = state.ready(&mut ctx, events);
@@ -309,7 +407,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let payload = vec!(66, 65, 67);
         let msg = Message::from_body(payload);
@@ -328,12 +426,31 @@ mod tests {
         assert!(is_recv);
     }
 
+    #[test]
+    fn recv_with_buffered_input_left_should_raise_can_recv() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        let mut ctx = TestPipeContext::new();
+
+        stub.set_buffered_input(true);
+
+        let state = box Active::new(stub, 0, 0);
+
+        sensor.borrow_mut().set_start_recv_result(Some(Message::from_body(vec!(66, 65, 67))));
+        let new_state = state.recv(&mut ctx);
+        assert_eq!("Active", new_state.name());
+        assert_eq!(2, ctx.get_raised_events().len());
+        assert_eq!("Received", ctx.get_raised_events()[0].name());
+        assert_eq!("CanRecv", ctx.get_raised_events()[1].name());
+    }
+
     #[test]
     fn recv_with_postponed_success() {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let payload = vec!(66, 65, 67);
         let msg = Message::from_body(payload);
@@ -363,7 +480,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let events = mio::Ready::readable();
         let new_state = state.ready(&mut ctx, events);
@@ -384,7 +501,7 @@ mod tests {
         let sensor_srv = TestStepStreamSensor::new();
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
-        let state = box Active::new(stub);
+        let state = box Active::new(stub, 0, 0);
         let mut ctx = TestPipeContext::new();
         let events = mio::Ready::readable() | mio::Ready::hup();
         let new_state = state.ready(&mut ctx, events);
@@ -399,4 +516,85 @@ mod tests {
 
         assert!(is_error);
     }
+    #[test]
+    fn when_reading_ahead_readable_should_buffer_a_message_and_raise_an_event() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let stub = TestStepStream::with_sensor(sensor.clone());
+        let state = box Active::new(stub, 0, 1024);
+        let mut ctx = TestPipeContext::new();
+        let msg = Message::from_body(vec!(66, 65, 67));
+
+        sensor.borrow_mut().set_start_recv_result(Some(msg));
+        let new_state = state.ready(&mut ctx, mio::Ready::readable());
+        assert_eq!("Active", new_state.name());
+        assert_eq!(1, ctx.get_raised_events().len());
+
+        let is_can_recv = match ctx.get_raised_events()[0] {
+            pipe::Event::CanRecv => true,
+            _ => false,
+        };
+        assert!(is_can_recv);
+
+        let new_state = new_state.recv(&mut ctx);
+        assert_eq!("Active", new_state.name());
+        assert_eq!(2, ctx.get_raised_events().len());
+
+        let body = match ctx.get_raised_events()[1] {
+            pipe::Event::Received(ref msg) => msg.get_body().to_vec(),
+            _ => Vec::new(),
+        };
+        assert_eq!(vec!(66, 65, 67), body);
+    }
+
+    #[test]
+    fn when_reading_ahead_readable_should_not_raise_an_event_until_a_message_is_read() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let stub = TestStepStream::with_sensor(sensor.clone());
+        let state = box Active::new(stub, 0, 1024);
+        let mut ctx = TestPipeContext::new();
+        let msg = Message::from_body(vec!(66, 65, 67));
+
+        let new_state = state.ready(&mut ctx, mio::Ready::readable());
+        assert_eq!("Active", new_state.name());
+        assert_eq!(0, ctx.get_raised_events().len());
+
+        sensor.borrow_mut().set_resume_recv_result(Some(msg));
+        let new_state = new_state.ready(&mut ctx, mio::Ready::readable());
+        assert_eq!("Active", new_state.name());
+        assert_eq!(1, ctx.get_raised_events().len());
+
+        let is_can_recv = match ctx.get_raised_events()[0] {
+            pipe::Event::CanRecv => true,
+            _ => false,
+        };
+        assert!(is_can_recv);
+    }
+
+    #[test]
+    fn recv_should_resume_the_message_being_read_ahead() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let stub = TestStepStream::with_sensor(sensor.clone());
+        let state = box Active::new(stub, 0, 1024);
+        let mut ctx = TestPipeContext::new();
+        let msg = Message::from_body(vec!(66, 65, 67));
+
+        let new_state = state.ready(&mut ctx, mio::Ready::readable());
+        let new_state = new_state.recv(&mut ctx);
+        assert_eq!("Active", new_state.name());
+        assert_eq!(0, ctx.get_raised_events().len());
+
+        sensor.borrow_mut().set_resume_recv_result(Some(msg));
+        let new_state = new_state.ready(&mut ctx, mio::Ready::readable());
+        assert_eq!("Active", new_state.name());
+        assert_eq!(1, ctx.get_raised_events().len());
+
+        let is_recv = match ctx.get_raised_events()[0] {
+            pipe::Event::Received(_) => true,
+            _ => false,
+        };
+        assert!(is_recv);
+    }
 }
\ No newline at end of file
@@ -4,7 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
-use std::io::Result;
+use std::io::{Result, ErrorKind};
 
 use mio::{Ready, PollOpt};
 
@@ -16,27 +16,32 @@ use transport::pipe::Context;
 
 pub struct HandshakeTx<S : AsyncPipeStub + 'static> {
     stub: S,
-    proto_ids: (u16, u16)
+    proto_ids: (u16, u16),
+    proto_flags: u8,
+    recv_buffer: usize
 }
 
 impl<S : AsyncPipeStub> HandshakeTx<S> {
-    pub fn new(s: S, pids: (u16, u16)) -> HandshakeTx<S> {
+    pub fn new(s: S, pids: (u16, u16), flags: u8, recv_buffer: usize) -> HandshakeTx<S> {
         HandshakeTx { 
             stub: s,
-            proto_ids: pids
+            proto_ids: pids,
+            proto_flags: flags,
+            recv_buffer: recv_buffer
         }
     }
 
     fn send_handshake(&mut self) -> Result<()> {
         let pids = self.proto_ids;
+        let flags = self.proto_flags;
 
-        self.stub.send_handshake(pids)
+        self.stub.send_handshake(pids, flags)
     }
 }
 
 impl<S : AsyncPipeStub> Into<HandshakeRx<S>> for HandshakeTx<S> {
     fn into(self) -> HandshakeRx<S> {
-        HandshakeRx::new(self.stub, self.proto_ids)
+        HandshakeRx::new(self.stub, self.proto_ids, self.recv_buffer)
     }
 }
 
@@ -67,27 +72,34 @@ impl<S : AsyncPipeStub> PipeState<S> for HandshakeTx<S> {
 
 pub struct HandshakeRx<S> {
     stub: S,
-    proto_ids: (u16, u16)
+    proto_ids: (u16, u16),
+    peer_proto_flags: u8,
+    recv_buffer: usize
 }
 
 impl<S: AsyncPipeStub> HandshakeRx<S> {
-    pub fn new(s: S, pids: (u16, u16)) -> HandshakeRx<S> {
+    pub fn new(s: S, pids: (u16, u16), recv_buffer: usize) -> HandshakeRx<S> {
         HandshakeRx {
             stub: s,
-            proto_ids: pids
+            proto_ids: pids,
+            peer_proto_flags: 0,
+            recv_buffer: recv_buffer
         }
     }
 
     fn recv_handshake(&mut self) -> Result<()> {
         let pids = self.proto_ids;
+        let flags = try!(self.stub.recv_handshake(pids));
 
-        self.stub.recv_handshake(pids)
+        self.peer_proto_flags = flags;
+
+        Ok(())
     }
 }
 
 impl<S : AsyncPipeStub> Into<Active<S>> for HandshakeRx<S> {
     fn into(self) -> Active<S> {
-        Active::new(self.stub)
+        Active::new(self.stub, self.peer_proto_flags, self.recv_buffer)
     }
 }
 
@@ -105,9 +117,11 @@ impl<S : AsyncPipeStub + 'static> PipeState<S> for HandshakeRx<S> {
     }
     fn ready(mut self: Box<Self>, ctx: &mut Context, events: Ready) -> Box<PipeState<S>> {
         if events.is_readable() {
-            let res = self.recv_handshake();
-            
-            transition_if_ok::<HandshakeRx<S>, Active<S>, S>(self, ctx, res)
+            match self.recv_handshake() {
+                // the stub has not received the whole handshake yet
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => self,
+                res => transition_if_ok::<HandshakeRx<S>, Active<S>, S>(self, ctx, res)
+            }
         } else {
             self
         }
@@ -121,6 +135,7 @@ mod tests {
 
     use mio;
 
+    use transport::*;
     use transport::tests::*;
     use transport::async::state::*;
     use transport::async::tests::*;
@@ -129,7 +144,7 @@ mod tests {
     #[test]
     fn on_enter_tx_should_register() {
         let stub = TestStepStream::new();
-        let mut state = box HandshakeTx::new(stub, (4, 2));
+        let mut state = box HandshakeTx::new(stub, (4, 2), 0, 0);
         let mut ctx = TestPipeContext::new();
 
         state.enter(&mut ctx);
@@ -149,7 +164,7 @@ mod tests {
     #[test]
     fn tx_close_should_deregister_and_cause_a_transition_to_dead() {
         let stub = TestStepStream::new();
-        let state = box HandshakeTx::new(stub, (1, 1));
+        let state = box HandshakeTx::new(stub, (1, 1), 0, 0);
         let mut ctx = TestPipeContext::new();
         let new_state = state.close(&mut ctx);
 
@@ -166,13 +181,13 @@ mod tests {
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
         let pids = (4, 2);
-        let state = box HandshakeTx::new(stub, pids);
+        let state = box HandshakeTx::new(stub, pids, 1, 0);
         let mut ctx = TestPipeContext::new();
         let events = mio::Ready::writable();
         let new_state = state.ready(&mut ctx, events);
 
         assert_eq!(1, sensor.borrow().get_sent_handshakes().len());
-        assert_eq!(pids, sensor.borrow().get_sent_handshakes()[0]);
+        assert_eq!((pids, 1), sensor.borrow().get_sent_handshakes()[0]);
 
         assert_eq!("HandshakeRx", new_state.name());
     }
@@ -180,7 +195,7 @@ mod tests {
     #[test]
     fn on_enter_rx_should_reregister() {
         let stub = TestStepStream::new();
-        let mut state = box HandshakeRx::new(stub, (4, 2));
+        let mut state = box HandshakeRx::new(stub, (4, 2), 0);
         let mut ctx = TestPipeContext::new();
 
         state.enter(&mut ctx);
@@ -200,7 +215,7 @@ mod tests {
     #[test]
     fn rx_close_should_deregister_and_cause_a_transition_to_dead() {
         let stub = TestStepStream::new();
-        let state = box HandshakeRx::new(stub, (1, 1));
+        let state = box HandshakeRx::new(stub, (1, 1), 0);
         let mut ctx = TestPipeContext::new();
         let new_state = state.close(&mut ctx);
 
@@ -217,7 +232,7 @@ mod tests {
         let sensor = Rc::new(RefCell::new(sensor_srv));
         let stub = TestStepStream::with_sensor(sensor.clone());
         let pids = (6, 6);
-        let state = box HandshakeRx::new(stub, pids);
+        let state = box HandshakeRx::new(stub, pids, 0);
         let mut ctx = TestPipeContext::new();
         let events = mio::Ready::readable();
         let new_state = state.ready(&mut ctx, events);
@@ -225,4 +240,39 @@ mod tests {
         assert_eq!(1, sensor.borrow().get_received_handshakes());
         assert_eq!("Active", new_state.name());
     }
+
+    #[test]
+    fn the_flags_received_with_the_handshake_are_raised_when_opened() {
+        let mut stub = TestStepStream::new();
+        stub.set_recv_handshake_flags(1);
+        let state = box HandshakeRx::new(stub, (6, 6), 0);
+        let mut ctx = TestPipeContext::new();
+        let events = mio::Ready::readable();
+        let new_state = state.ready(&mut ctx, events);
+
+        assert_eq!("Active", new_state.name());
+        assert_eq!(1, ctx.get_raised_events().len());
+
+        let is_opened_with_flags = match ctx.get_raised_events()[0] {
+            pipe::Event::Opened(1) => true,
+            _ => false
+        };
+        assert!(is_opened_with_flags);
+    }
+
+    #[test]
+    fn readable_with_incomplete_handshake_should_not_cause_a_transition() {
+        let sensor_srv = TestStepStreamSensor::new();
+        let sensor = Rc::new(RefCell::new(sensor_srv));
+        let mut stub = TestStepStream::with_sensor(sensor.clone());
+        stub.set_recv_handshake_would_block(true);
+        let state = box HandshakeRx::new(stub, (6, 6), 0);
+        let mut ctx = TestPipeContext::new();
+        let events = mio::Ready::readable();
+        let new_state = state.ready(&mut ctx, events);
+
+        assert_eq!(1, sensor.borrow().get_received_handshakes());
+        assert_eq!(0, ctx.get_raised_events().len());
+        assert_eq!("HandshakeRx", new_state.name());
+    }
 }
@@ -11,21 +11,25 @@ use transport::pipe::Context;
 
 pub struct Initial<S : AsyncPipeStub> {
     stub: S,
-    proto_ids: (u16, u16)
+    proto_ids: (u16, u16),
+    proto_flags: u8,
+    recv_buffer: usize
 }
 
 impl<S : AsyncPipeStub> Initial<S> {
-    pub fn new(s: S, pids: (u16, u16)) -> Initial<S> {
+    pub fn new(s: S, pids: (u16, u16), flags: u8, recv_buffer: usize) -> Initial<S> {
         Initial {
             stub: s,
-            proto_ids: pids
+            proto_ids: pids,
+            proto_flags: flags,
+            recv_buffer: recv_buffer
         }
     }
 }
 
 impl<S : AsyncPipeStub> Into<HandshakeTx<S>> for Initial<S> {
     fn into(self) -> HandshakeTx<S> {
-        HandshakeTx::new(self.stub, self.proto_ids)
+        HandshakeTx::new(self.stub, self.proto_ids, self.proto_flags, self.recv_buffer)
     }
 }
 
@@ -49,7 +53,7 @@ mod tests {
     #[test]
     fn open_should_cause_transition_to_handshake() {
         let stub = TestStepStream::new();
-        let state = box Initial::new(stub, (1, 1));
+        let state = box Initial::new(stub, (1, 1), 0, 0);
         let mut ctx = TestPipeContext::new();
         let new_state = state.open(&mut ctx);
 
@@ -63,7 +67,7 @@ mod tests {
     #[test]
     fn close_should_cause_a_transition_to_dead() {
         let stub = TestStepStream::new();
-        let state = box Initial::new(stub, (1, 1));
+        let state = box Initial::new(stub, (1, 1), 0, 0);
         let mut ctx = TestPipeContext::new();
         let new_state = state.close(&mut ctx);
 
@@ -14,7 +14,7 @@ mod active;
 mod dead;
 
 #[cfg(test)]
-mod tests;
+pub mod tests;
 
 use std::rc::Rc;
 
@@ -29,15 +29,24 @@ use self::state::PipeState;
 
 pub struct AsyncPipe<S : AsyncPipeStub + 'static> {
 
-    state: Option<Box<PipeState<S>>>
+    state: Option<Box<PipeState<S>>>,
+    remote_addr: Option<String>
 
 }
 
 impl<S : AsyncPipeStub + 'static> AsyncPipe<S> {
-    pub fn new(stub: S, pids: (u16, u16)) -> AsyncPipe<S> {
-        let initial_state = box initial::Initial::new(stub, pids);
+    pub fn new(stub: S, pids: (u16, u16), flags: u8, recv_buffer: usize) -> AsyncPipe<S> {
+        let initial_state = box initial::Initial::new(stub, pids, flags, recv_buffer);
 
-        AsyncPipe { state: Some(initial_state) }
+        AsyncPipe {
+            state: Some(initial_state),
+            remote_addr: None
+        }
+    }
+
+    pub fn with_remote_addr(mut self, addr: String) -> AsyncPipe<S> {
+        self.remote_addr = Some(addr);
+        self
     }
 
     fn apply<F>(&mut self, ctx: &mut Context, transition: F) where F : FnOnce(Box<PipeState<S>>, &mut Context) -> Box<PipeState<S>> {
@@ -71,7 +80,15 @@ impl<S : AsyncPipeStub> pipe::Pipe for AsyncPipe<S> {
         self.apply(ctx, |s, ctx| s.send(ctx, msg))
     }
 
+    fn send_all(&mut self, ctx: &mut Context, msgs: Vec<Rc<Message>>) {
+        self.apply(ctx, |s, ctx| s.send_all(ctx, msgs))
+    }
+
     fn recv(&mut self, ctx: &mut Context) {
         self.apply(ctx, |s, ctx| s.recv(ctx))
     }
+
+    fn remote_addr(&self) -> Option<String> {
+        self.remote_addr.clone()
+    }
 }
@@ -29,6 +29,10 @@ pub trait PipeState<S : AsyncPipeStub + 'static> {
         error!("[{:?}] send while {}", ctx, self.name());
         box Dead
     }
+    fn send_all(self: Box<Self>, ctx: &mut Context, _: Vec<Rc<Message>>) -> Box<PipeState<S>> {
+        error!("[{:?}] send_all while {}", ctx, self.name());
+        box Dead
+    }
     fn recv(self: Box<Self>, ctx: &mut Context) -> Box<PipeState<S>> {
         error!("[{:?}] recv while {}", ctx, self.name());
         box Dead
@@ -11,6 +11,7 @@ use std::io::{Result, Read, Write, ErrorKind};
 use byteorder::{ BigEndian, ByteOrder };
 
 use mio::Evented;
+use iovec::IoVec;
 
 use core::Message;
 use io_error::*;
@@ -22,9 +23,11 @@ pub trait AsyncPipeStub : Sender + Receiver + Handshake + Deref<Target=Evented>
     fn registered(&mut self) {}
 }
 
+/// Both operations return how many messages were completely sent, in order.
 pub trait Sender {
-    fn start_send(&mut self, msg: Rc<Message>) -> Result<bool>;
-    fn resume_send(&mut self) -> Result<bool>;
+    /// Starts sending the messages, the stubs supporting it write several of them at once.
+    fn start_send(&mut self, msgs: Vec<Rc<Message>>) -> Result<usize>;
+    fn resume_send(&mut self) -> Result<usize>;
     fn has_pending_send(&self) -> bool;
 }
 
@@ -32,16 +35,24 @@ pub trait Receiver {
     fn start_recv(&mut self) -> Result<Option<Message>>;
     fn resume_recv(&mut self) -> Result<Option<Message>>;
     fn has_pending_recv(&self) -> bool;
+    /// Tells whether bytes already taken from the socket are waiting to be received,
+    /// the socket not becoming readable again for them.
+    fn has_buffered_input(&self) -> bool { false }
+    /// Called when the socket is readable while no message is being received,
+    /// so the stub can process the input it answers by itself, like the websocket pings.
+    fn read_idle(&mut self) -> Result<()> { Ok(()) }
 }
 
+/// The flags sent along with the protocol ids are returned by the peer in `recv_handshake`,
+/// transports that cannot carry them return zero.
 pub trait Handshake {
-    fn send_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
-    fn recv_handshake(&mut self, pids: (u16, u16)) -> Result<()>;
+    fn send_handshake(&mut self, pids: (u16, u16), flags: u8) -> Result<()>;
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> Result<u8>;
 }
 
-pub fn send_and_check_handshake<T:Write>(stream: &mut T, pids: (u16, u16)) -> Result<()> {
+pub fn send_and_check_handshake<T:Write>(stream: &mut T, pids: (u16, u16), flags: u8) -> Result<()> {
     let (proto_id, _) = pids;
-    let handshake = create_handshake(proto_id);
+    let handshake = create_handshake(proto_id, flags);
 
     match try!(stream.write(&handshake)) {
         8 => Ok(()),
@@ -49,25 +60,26 @@ pub fn send_and_check_handshake<T:Write>(stream: &mut T, pids: (u16, u16)) -> Re
     }
 }
 
-fn create_handshake(protocol_id: u16) -> [u8; 8] {
+fn create_handshake(protocol_id: u16, flags: u8) -> [u8; 8] {
     // handshake is Zero, 'S', 'P', Version, Proto[2], Rsvd[2]
-    let mut handshake = [0, 83, 80, 0, 0, 0, 0, 0];
+    // the first reserved byte holds the protocol flags, nanomsg ignores it
+    let mut handshake = [0, 83, 80, 0, 0, 0, flags, 0];
     BigEndian::write_u16(&mut handshake[4..6], protocol_id);
     handshake
 }
 
-pub fn recv_and_check_handshake<T:Read>(stream: &mut T, pids: (u16, u16)) -> Result<()> {
+pub fn recv_and_check_handshake<T:Read>(stream: &mut T, pids: (u16, u16)) -> Result<u8> {
     let mut handshake = [0u8; 8];
 
     stream.read(&mut handshake).and_then(|_| check_handshake(pids, &handshake))
 }
 
-fn check_handshake(pids: (u16, u16), handshake: &[u8; 8]) -> Result<()> {
+fn check_handshake(pids: (u16, u16), handshake: &[u8; 8]) -> Result<u8> {
     let (_, proto_id) = pids;
-    let expected_handshake = create_handshake(proto_id);
+    let expected_handshake = create_handshake(proto_id, 0);
 
-    if handshake == &expected_handshake {
-        Ok(())
+    if handshake[..6] == expected_handshake[..6] {
+        Ok(handshake[6])
     } else {
         Err(invalid_data_io_error("received bad handshake"))
     }
@@ -97,6 +109,27 @@ impl<T:Write> WriteBuffer for T {
 }
 
 
+/// Writes several buffers with a single system call when the stream supports vectored writes,
+/// returns how many bytes were written, which may end in the middle of a buffer.
+pub trait WriteBuffers {
+    fn write_buffers(&mut self, bufs: &[&[u8]]) -> Result<usize>;
+}
+
+/// Vectored write for the streams exposing mio's `write_bufs`, empty buffers are skipped.
+pub fn write_iovecs<F>(bufs: &[&[u8]], write_bufs: F) -> Result<usize> where F : FnOnce(&[&IoVec]) -> Result<usize> {
+    let iovecs: Vec<&IoVec> = bufs.iter().filter_map(|buf| IoVec::from_bytes(buf)).collect();
+
+    write_bufs(&iovecs)
+}
+
+/// Fallback for the streams without vectored writes, only the first buffer is written.
+pub fn write_first_buffer<T:Write>(stream: &mut T, bufs: &[&[u8]]) -> Result<usize> {
+    match bufs.first() {
+        Some(buf) => stream.write(buf),
+        None => Ok(0)
+    }
+}
+
 pub trait ReadBuffer {
     fn read_buffer(&mut self, buffer: &mut [u8]) -> Result<usize>;
 }
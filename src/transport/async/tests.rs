@@ -16,7 +16,7 @@ use transport::async::*;
 use io_error::*;
 
 pub struct TestStepStreamSensor {
-    sent_handshakes: Vec<(u16, u16)>,
+    sent_handshakes: Vec<((u16, u16), u8)>,
     received_handshakes: usize,
     start_send_result: Option<bool>,
     resume_send_result: Option<bool>,
@@ -36,11 +36,11 @@ impl TestStepStreamSensor {
         }
     }
 
-    pub fn get_sent_handshakes(&self) -> &[(u16, u16)] {
+    pub fn get_sent_handshakes(&self) -> &[((u16, u16), u8)] {
         &self.sent_handshakes
     }
 
-    fn push_sent_handshake(&mut self, sent_handshake: (u16, u16)) {
+    fn push_sent_handshake(&mut self, sent_handshake: ((u16, u16), u8)) {
         self.sent_handshakes.push(sent_handshake);
     }
 
@@ -89,8 +89,11 @@ pub struct TestStepStream {
     sensor: Rc<RefCell<TestStepStreamSensor>>,
     send_handshake_ok: bool,
     recv_handshake_ok: bool,
-    pending_send: bool,
-    pending_recv: bool
+    recv_handshake_would_block: bool,
+    recv_handshake_flags: u8,
+    pending_send: usize,
+    pending_recv: bool,
+    buffered_input: bool
 }
 
 impl TestStepStream {
@@ -103,10 +106,22 @@ impl TestStepStream {
             sensor: sensor,
             send_handshake_ok: true,
             recv_handshake_ok: true,
-            pending_send: false,
-            pending_recv: false
+            recv_handshake_would_block: false,
+            recv_handshake_flags: 0,
+            pending_send: 0,
+            pending_recv: false,
+            buffered_input: false
         }
     }
+    pub fn set_recv_handshake_would_block(&mut self, would_block: bool) {
+        self.recv_handshake_would_block = would_block;
+    }
+    pub fn set_recv_handshake_flags(&mut self, flags: u8) {
+        self.recv_handshake_flags = flags;
+    }
+    pub fn set_buffered_input(&mut self, buffered_input: bool) {
+        self.buffered_input = buffered_input;
+    }
 }
 
 impl stub::AsyncPipeStub for TestStepStream {
@@ -134,35 +149,36 @@ impl Deref for TestStepStream {
 }
 
 impl stub::Handshake for TestStepStream {
-    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
-        self.sensor.borrow_mut().push_sent_handshake(pids);
+    fn send_handshake(&mut self, pids: (u16, u16), flags: u8) -> io::Result<()> {
+        self.sensor.borrow_mut().push_sent_handshake((pids, flags));
         if self.send_handshake_ok { Ok(()) } else { Err(other_io_error("test")) }
     }
-    fn recv_handshake(&mut self, _: (u16, u16)) -> io::Result<()> {
+    fn recv_handshake(&mut self, _: (u16, u16)) -> io::Result<u8> {
         self.sensor.borrow_mut().push_received_handshake();
-        if self.recv_handshake_ok { Ok(()) } else { Err(other_io_error("test")) }
+        if self.recv_handshake_would_block { return Err(would_block_io_error("test")); }
+        if self.recv_handshake_ok { Ok(self.recv_handshake_flags) } else { Err(other_io_error("test")) }
     }
 }
 
 impl stub::Sender for TestStepStream {
-    fn start_send(&mut self, _: Rc<Message>) -> io::Result<bool> {
+    fn start_send(&mut self, msgs: Vec<Rc<Message>>) -> io::Result<usize> {
         match self.sensor.borrow_mut().take_start_send_result() {
-            Some(true) => { self.pending_send = false; Ok(true) },
-            Some(false) => { self.pending_send = true; Ok(false) },
+            Some(true) => { self.pending_send = 0; Ok(msgs.len()) },
+            Some(false) => { self.pending_send = msgs.len(); Ok(0) },
             None => Err(other_io_error("test"))
         }
     }
 
-    fn resume_send(&mut self) -> io::Result<bool> {
+    fn resume_send(&mut self) -> io::Result<usize> {
         match self.sensor.borrow_mut().take_resume_send_result() {
-            Some(true) => { self.pending_send = false; Ok(true) },
-            Some(false) => { self.pending_send = true; Ok(false) },
+            Some(true) => { let sent = self.pending_send; self.pending_send = 0; Ok(sent) },
+            Some(false) => Ok(0),
             None => Err(other_io_error("test"))
         }
     }
 
     fn has_pending_send(&self) -> bool {
-        self.pending_send
+        self.pending_send > 0
     }
 }
 
@@ -184,4 +200,68 @@ impl stub::Receiver for TestStepStream {
     fn has_pending_recv(&self) -> bool {
         self.pending_recv
     }
+
+    fn has_buffered_input(&self) -> bool {
+        self.buffered_input
+    }
+}
+
+/// Stream accepting `capacity` bytes in total, then returning would block until more room is given.
+pub struct TestWriteStream {
+    bytes: Vec<u8>,
+    writes: usize,
+    capacity: usize,
+    first_buffer_only: bool
+}
+
+impl TestWriteStream {
+    pub fn new(capacity: usize) -> TestWriteStream {
+        TestWriteStream {
+            bytes: Vec::new(),
+            writes: 0,
+            capacity: capacity,
+            first_buffer_only: false
+        }
+    }
+    /// Creates a stream writing at most the first buffer of each vectored write,
+    /// like the streams that have no vectored write.
+    pub fn first_buffer_only(capacity: usize) -> TestWriteStream {
+        TestWriteStream {
+            first_buffer_only: true,
+            ..TestWriteStream::new(capacity)
+        }
+    }
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+    }
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    pub fn get_writes(&self) -> usize {
+        self.writes
+    }
+}
+
+impl stub::WriteBuffers for TestWriteStream {
+    fn write_buffers(&mut self, bufs: &[&[u8]]) -> io::Result<usize> {
+        if self.capacity == 0 {
+            return Err(would_block_io_error("test"));
+        }
+
+        let bufs = if self.first_buffer_only { &bufs[..1] } else { bufs };
+        let mut written = 0;
+
+        self.writes += 1;
+
+        for buf in bufs {
+            let len = ::std::cmp::min(buf.len(), self.capacity - written);
+
+            self.bytes.extend_from_slice(&buf[..len]);
+            written += len;
+        }
+
+        self.capacity -= written;
+
+        Ok(written)
+    }
 }
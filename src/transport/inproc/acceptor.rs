@@ -0,0 +1,109 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::mem;
+
+use mio;
+use mio::{Registration, SetReadiness};
+
+use transport::pipe::Pipe;
+use transport::acceptor::*;
+
+/// The part of an acceptor that connecting pipes can reach through the listener registry.
+pub struct Listener {
+    proto_ids: (u16, u16),
+    proto_flags: u8,
+    backlog: RefCell<Vec<Box<Pipe>>>,
+    readiness: SetReadiness
+}
+
+pub struct InprocAcceptor {
+    addr: String,
+    registration: Registration,
+    listener: Rc<Listener>
+}
+
+impl Listener {
+    pub fn is_compatible(&self, pids: (u16, u16)) -> bool {
+        let (proto_id, peer_proto_id) = self.proto_ids;
+
+        pids == (peer_proto_id, proto_id)
+    }
+
+    pub fn get_proto_flags(&self) -> u8 {
+        self.proto_flags
+    }
+
+    pub fn push(&self, pipe: Box<Pipe>) {
+        self.backlog.borrow_mut().push(pipe);
+
+        let _ = self.readiness.set_readiness(mio::Ready::readable());
+    }
+
+    fn take_backlog(&self) -> Vec<Box<Pipe>> {
+        mem::replace(&mut *self.backlog.borrow_mut(), Vec::new())
+    }
+}
+
+impl InprocAcceptor {
+    pub fn new(addr: &str, pids: (u16, u16), flags: u8) -> InprocAcceptor {
+        let (registration, readiness) = Registration::new2();
+        let listener = Listener {
+            proto_ids: pids,
+            proto_flags: flags,
+            backlog: RefCell::new(Vec::new()),
+            readiness: readiness
+        };
+
+        InprocAcceptor {
+            addr: addr.to_owned(),
+            registration: registration,
+            listener: Rc::new(listener)
+        }
+    }
+
+    pub fn listener(&self) -> Rc<Listener> {
+        self.listener.clone()
+    }
+}
+
+impl Acceptor for InprocAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() == false {
+            return;
+        }
+
+        let pipes = self.listener.take_backlog();
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.registration, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.registration);
+        super::remove_listener(&self.addr, &self.listener);
+        self.listener.take_backlog();
+        ctx.raise(Event::Closed);
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        Some(self.addr.clone())
+    }
+}
+
+impl Drop for InprocAcceptor {
+    fn drop(&mut self) {
+        super::remove_listener(&self.addr, &self.listener);
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module provides a transport for sockets living in the same session.
+/// Messages are handed over from one pipe to its peer without being serialized.
+
+mod pipe;
+mod acceptor;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::io;
+
+use self::pipe::InprocPipe;
+use self::acceptor::{InprocAcceptor, Listener};
+use transport::{Transport, Destination};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use io_error::*;
+
+pub struct Inproc;
+
+// Each session runs its own I/O thread, so bound names are scoped to the session.
+thread_local! {
+    static LISTENERS: RefCell<HashMap<String, Rc<Listener>>> = RefCell::new(HashMap::new());
+}
+
+fn add_listener(addr: &str, listener: Rc<Listener>) -> io::Result<()> {
+    LISTENERS.with(|listeners| {
+        let mut listeners = listeners.borrow_mut();
+
+        if listeners.contains_key(addr) {
+            Err(addr_in_use_io_error(addr))
+        } else {
+            listeners.insert(addr.to_owned(), listener);
+            Ok(())
+        }
+    })
+}
+
+fn find_listener(addr: &str) -> Option<Rc<Listener>> {
+    LISTENERS.with(|listeners| listeners.borrow().get(addr).cloned())
+}
+
+fn remove_listener(addr: &str, listener: &Rc<Listener>) {
+    LISTENERS.with(|listeners| {
+        let mut listeners = listeners.borrow_mut();
+        let is_same = listeners.get(addr).map_or(false, |x| Rc::ptr_eq(x, listener));
+
+        if is_same {
+            listeners.remove(addr);
+        }
+    })
+}
+
+impl Transport for Inproc {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        if dest.addr.is_empty() {
+            return Err(invalid_input_io_error(dest.addr));
+        }
+
+        Ok(box InprocPipe::connect(dest.addr, dest.pids, dest.proto_flags))
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        if dest.addr.is_empty() {
+            return Err(invalid_input_io_error(dest.addr));
+        }
+
+        let acceptor = InprocAcceptor::new(dest.addr, dest.pids, dest.proto_flags);
+
+        try!(add_listener(dest.addr, acceptor.listener()));
+
+        Ok(box acceptor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use transport::*;
+    use super::*;
+
+    fn destination(addr: &str) -> Destination {
+        Destination {
+            addr: addr,
+            pids: (16, 16),
+            proto_flags: 0,
+            tcp_no_delay: false,
+            recv_max_size: 1024,
+            recv_buffer: 0
+        }
+    }
+
+    #[test]
+    fn bind_twice_to_the_same_name_should_fail() {
+        let transport = Inproc;
+        let acceptor = transport.bind(&destination("bind_twice")).unwrap();
+        let err = transport.bind(&destination("bind_twice")).err().unwrap();
+
+        assert_eq!(io::ErrorKind::AddrInUse, err.kind());
+
+        drop(acceptor);
+
+        assert!(transport.bind(&destination("bind_twice")).is_ok());
+    }
+
+    #[test]
+    fn connect_and_bind_should_reject_empty_names() {
+        let transport = Inproc;
+
+        assert_eq!(io::ErrorKind::InvalidInput, transport.connect(&destination("")).err().unwrap().kind());
+        assert_eq!(io::ErrorKind::InvalidInput, transport.bind(&destination("")).err().unwrap().kind());
+    }
+}
@@ -0,0 +1,361 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::io;
+
+use mio;
+use mio::{Registration, SetReadiness};
+
+use core::Message;
+use transport::pipe::*;
+use io_error::*;
+
+/// Maximum number of messages a pipe accepts in its mailbox before its peer stops being able to send.
+const MAILBOX_CAPACITY: usize = 64;
+
+/// Incoming messages of a pipe, shared with its peer that writes into it.
+struct Mailbox {
+    messages: RefCell<VecDeque<Rc<Message>>>,
+    readiness: SetReadiness,
+    closed: Cell<bool>
+}
+
+pub struct InprocPipe {
+    target: Option<(String, (u16, u16))>,
+    proto_flags: u8,
+    peer_proto_flags: u8,
+    registration: Registration,
+    local: Rc<Mailbox>,
+    remote: Option<Rc<Mailbox>>,
+    has_pending_recv: bool,
+    should_raise_can_send: bool,
+    should_raise_can_recv: bool,
+    is_dead: bool
+}
+
+impl Mailbox {
+    fn new() -> (Registration, Mailbox) {
+        let (registration, readiness) = Registration::new2();
+        let mailbox = Mailbox {
+            messages: RefCell::new(VecDeque::new()),
+            readiness: readiness,
+            closed: Cell::new(false)
+        };
+
+        (registration, mailbox)
+    }
+
+    fn push(&self, msg: Rc<Message>) {
+        self.messages.borrow_mut().push_back(msg);
+        self.notify();
+    }
+
+    fn pop(&self) -> Option<Rc<Message>> {
+        self.messages.borrow_mut().pop_front()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.messages.borrow().is_empty()
+    }
+
+    fn is_full(&self) -> bool {
+        self.messages.borrow().len() >= MAILBOX_CAPACITY
+    }
+
+    fn close(&self) {
+        self.closed.set(true);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    fn notify(&self) {
+        let _ = self.readiness.set_readiness(mio::Ready::readable());
+    }
+}
+
+impl InprocPipe {
+    fn new(registration: Registration, local: Rc<Mailbox>, remote: Option<Rc<Mailbox>>) -> InprocPipe {
+        InprocPipe {
+            target: None,
+            proto_flags: 0,
+            peer_proto_flags: 0,
+            registration: registration,
+            local: local,
+            remote: remote,
+            has_pending_recv: false,
+            should_raise_can_send: true,
+            should_raise_can_recv: true,
+            is_dead: false
+        }
+    }
+
+    pub fn connect(addr: &str, pids: (u16, u16), flags: u8) -> InprocPipe {
+        let (registration, local) = Mailbox::new();
+        let mut pipe = InprocPipe::new(registration, Rc::new(local), None);
+
+        pipe.target = Some((addr.to_owned(), pids));
+        pipe.proto_flags = flags;
+        pipe
+    }
+
+    fn link(&mut self) -> io::Result<()> {
+        let (addr, pids) = match self.target.take() {
+            Some(target) => target,
+            None => return Ok(())
+        };
+        let listener = match super::find_listener(&addr) {
+            Some(listener) => listener,
+            None => return Err(connection_refused_io_error(addr))
+        };
+
+        if listener.is_compatible(pids) == false {
+            return Err(invalid_data_io_error("incompatible protocol"));
+        }
+
+        let (registration, remote) = Mailbox::new();
+        let remote = Rc::new(remote);
+        let mut peer = InprocPipe::new(registration, remote.clone(), Some(self.local.clone()));
+
+        // both ends exchange their protocol flags, as a stream handshake would do
+        peer.peer_proto_flags = self.proto_flags;
+        self.peer_proto_flags = listener.get_proto_flags();
+        self.remote = Some(remote);
+        listener.push(box peer);
+
+        Ok(())
+    }
+
+    fn error(&mut self, ctx: &mut Context, err: io::Error) {
+        info!("[{:?}] error: {:?}", ctx, err);
+
+        self.is_dead = true;
+        self.local.close();
+        if let Some(ref remote) = self.remote {
+            remote.notify();
+        }
+        ctx.raise(Event::Error(err));
+    }
+
+    fn remote_changed(&mut self, ctx: &mut Context, remote: &Mailbox) {
+        if self.should_raise_can_send && remote.is_full() == false {
+            self.should_raise_can_send = false;
+            ctx.raise(Event::CanSend);
+        }
+    }
+
+    fn local_changed(&mut self, ctx: &mut Context, remote: &Mailbox) {
+        if self.has_pending_recv {
+            if let Some(msg) = self.local.pop() {
+                self.has_pending_recv = false;
+                self.local.notify();
+                remote.notify();
+                ctx.raise(Event::Received(msg));
+            }
+        } else if self.should_raise_can_recv && self.local.is_empty() == false {
+            self.should_raise_can_recv = false;
+            ctx.raise(Event::CanRecv);
+        }
+    }
+}
+
+impl Pipe for InprocPipe {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if self.is_dead || events.is_readable() == false {
+            return;
+        }
+
+        let remote = match self.remote {
+            Some(ref remote) => remote.clone(),
+            None => return
+        };
+
+        self.local_changed(ctx, &remote);
+
+        if remote.is_closed() {
+            if self.local.is_empty() {
+                self.error(ctx, connection_reset_io_error("peer closed"));
+            }
+        } else {
+            self.remote_changed(ctx, &remote);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        if let Err(e) = self.link() {
+            return self.error(ctx, e);
+        }
+
+        ctx.register(&self.registration, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened(self.peer_proto_flags));
+        self.local.notify();
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        if self.is_dead == false {
+            self.is_dead = true;
+            self.local.close();
+            if let Some(ref remote) = self.remote {
+                remote.notify();
+            }
+            ctx.deregister(&self.registration);
+        }
+
+        ctx.raise(Event::Closed);
+    }
+
+    fn send(&mut self, ctx: &mut Context, msg: Rc<Message>) {
+        if self.is_dead {
+            return;
+        }
+
+        let remote = match self.remote {
+            Some(ref remote) => remote.clone(),
+            None => return
+        };
+
+        if remote.is_closed() {
+            return self.error(ctx, connection_reset_io_error("peer closed"));
+        }
+
+        self.should_raise_can_send = true;
+        remote.push(msg);
+        ctx.raise(Event::Sent);
+
+        // let the next readiness notification tell whether there is room for another message
+        self.local.notify();
+    }
+
+    fn recv(&mut self, ctx: &mut Context) {
+        if self.is_dead {
+            return;
+        }
+
+        self.should_raise_can_recv = true;
+        self.has_pending_recv = true;
+
+        if let Some(ref remote) = self.remote {
+            let remote = remote.clone();
+
+            self.local_changed(ctx, &remote);
+        }
+    }
+
+    fn remote_addr(&self) -> Option<String> {
+        None
+    }
+}
+
+impl Drop for InprocPipe {
+    fn drop(&mut self) {
+        self.local.close();
+
+        if let Some(ref remote) = self.remote {
+            remote.notify();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use mio;
+
+    use core::Message;
+    use transport::tests::*;
+    use transport::pipe::*;
+    use super::*;
+
+    fn create_linked_pipes() -> (InprocPipe, InprocPipe) {
+        let (left_registration, left_mailbox) = Mailbox::new();
+        let (right_registration, right_mailbox) = Mailbox::new();
+        let left_mailbox = Rc::new(left_mailbox);
+        let right_mailbox = Rc::new(right_mailbox);
+        let left = InprocPipe::new(left_registration, left_mailbox.clone(), Some(right_mailbox.clone()));
+        let right = InprocPipe::new(right_registration, right_mailbox, Some(left_mailbox));
+
+        (left, right)
+    }
+
+    #[test]
+    fn sent_message_is_received_by_peer_with_header_kept_apart() {
+        let (mut left, mut right) = create_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+        let msg = Rc::new(Message::from_header_and_body(vec![1, 2], vec![65, 66, 67]));
+
+        left.send(&mut left_ctx, msg.clone());
+        right.recv(&mut right_ctx);
+
+        assert_eq!(1, left_ctx.get_raised_events().len());
+        assert_eq!(1, right_ctx.get_raised_events().len());
+
+        match right_ctx.get_raised_events()[0] {
+            Event::Received(ref received) => {
+                assert!(Rc::ptr_eq(&msg, received));
+                assert_eq!(&[1, 2], received.get_header());
+                assert_eq!(&[65, 66, 67], received.get_body());
+            },
+            _ => panic!("expected a received event")
+        }
+    }
+
+    #[test]
+    fn recv_without_message_is_completed_when_peer_sends() {
+        let (mut left, mut right) = create_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+
+        right.recv(&mut right_ctx);
+        assert_eq!(0, right_ctx.get_raised_events().len());
+
+        left.send(&mut left_ctx, Rc::new(Message::from_body(vec![65])));
+        right.ready(&mut right_ctx, mio::Ready::readable());
+
+        assert_eq!("Received", right_ctx.get_raised_events()[0].name());
+    }
+
+    #[test]
+    fn full_mailbox_prevents_can_send_until_peer_receives() {
+        let (mut left, mut right) = create_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+
+        for _ in 0..MAILBOX_CAPACITY {
+            left.send(&mut left_ctx, Rc::new(Message::new()));
+        }
+
+        left.ready(&mut left_ctx, mio::Ready::readable());
+        assert!(left_ctx.get_raised_events().iter().all(|evt| evt.name() == "Sent"));
+
+        right.recv(&mut right_ctx);
+        left.ready(&mut left_ctx, mio::Ready::readable());
+        assert_eq!("CanSend", left_ctx.get_raised_events().last().unwrap().name());
+    }
+
+    #[test]
+    fn peer_close_causes_an_error_once_mailbox_is_drained() {
+        let (mut left, mut right) = create_linked_pipes();
+        let mut left_ctx = TestPipeContext::new();
+        let mut right_ctx = TestPipeContext::new();
+
+        left.send(&mut left_ctx, Rc::new(Message::from_body(vec![65])));
+        left.close(&mut left_ctx);
+
+        right.ready(&mut right_ctx, mio::Ready::readable());
+        assert_eq!("CanRecv", right_ctx.get_raised_events()[0].name());
+
+        right.recv(&mut right_ctx);
+        right.ready(&mut right_ctx, mio::Ready::readable());
+        assert_eq!("Received", right_ctx.get_raised_events()[1].name());
+        assert_eq!("Error", right_ctx.get_raised_events()[2].name());
+    }
+}
@@ -4,6 +4,7 @@
 // or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
 // This file may not be copied, modified, or distributed except according to those terms.
 
+use std::collections::VecDeque;
 use std::rc::Rc;
 use std::io;
 
@@ -11,119 +12,108 @@ use byteorder::{ BigEndian, ByteOrder };
 
 use core::Message;
 use transport::async::stub::*;
-use io_error::*;
 
+/// Keeps the number of buffers given to a vectored write well below the usual `IOV_MAX`.
+const MAX_FRAMES_PER_WRITE: usize = 64;
+
+/// Sends messages framed with a type byte and their size, the frame header, protocol header and body
+/// of several messages being written with a single vectored write.
 pub struct SendOperation {
-    step: Option<SendOperationStep>
+    /// The messages not completely written yet, with their frame header.
+    frames: VecDeque<([u8; 9], Rc<Message>)>,
+    /// How many bytes of the first frame have already been written.
+    written: usize
 }
 
 impl SendOperation {
-    pub fn new(msg: Rc<Message>) -> SendOperation {
-        SendOperation { 
-            step: Some(SendOperationStep::TransportHdr(msg, 0))
-        }
-    }
-
-    pub fn run<T:io::Write>(&mut self, stream: &mut T) -> io::Result<bool> {
-        if let Some(step) = self.step.take() {
-            self.resume_at(stream, step)
-        } else {
-            Err(other_io_error("Cannot resume already finished send operation"))
+    pub fn new(msgs: Vec<Rc<Message>>) -> SendOperation {
+        SendOperation {
+            frames: msgs.into_iter().map(|msg| (transport_hdr(&msg), msg)).collect(),
+            written: 0
         }
     }
 
-    fn resume_at<T:io::Write>(&mut self, stream: &mut T, step: SendOperationStep) -> io::Result<bool> {
-        let mut cur_step = step;
-
-        loop {
-            let (passed, next_step) = try!(cur_step.advance(stream));
-
-            if next_step.is_terminal() {
-                return Ok(true);
-            }
-            if !passed {
-                self.step = Some(next_step);
-                return Ok(false)
+    /// Writes until the stream would block or every frame is written,
+    /// returns how many messages were completely written by this run.
+    pub fn run<T:WriteBuffers>(&mut self, stream: &mut T) -> io::Result<usize> {
+        let mut sent = 0;
+
+        // A short write does not mean the stream is full, some streams only write the first buffer
+        while self.frames.is_empty() == false {
+            let written = match stream.write_buffers(&self.buffers()) {
+                Ok(x) => x,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e)
+            };
+
+            if written == 0 {
+                break;
             }
 
-            cur_step = next_step;
+            sent += self.advance(written);
         }
-    }
-}
 
-enum SendOperationStep {
-    TransportHdr(Rc<Message>, usize),
-    ProtocolHdr(Rc<Message>, usize),
-    UsrPayload(Rc<Message>, usize),
-    Terminal
-}
+        Ok(sent)
+    }
 
-impl SendOperationStep {
-    /// Writes one of the buffers composing the message.
-    /// Returns whether the buffer was fully sent, and what is the next step.
-    fn advance<T:io::Write>(self, stream: &mut T) -> io::Result<(bool, SendOperationStep)> {
-        match self {
-            SendOperationStep::TransportHdr(msg, written) => write_transport_hdr(stream, msg, written),
-            SendOperationStep::ProtocolHdr(msg, written) => write_protocol_hdr(stream, msg, written),
-            SendOperationStep::UsrPayload(msg, written) => write_usr_payload(stream, msg, written),
-            SendOperationStep::Terminal => Err(other_io_error("Cannot advance terminal step of send operation"))
-        }
+    pub fn is_done(&self) -> bool {
+        self.frames.is_empty()
     }
 
-    fn is_terminal(&self) -> bool {
-        match *self {
-            SendOperationStep::Terminal => true,
-            _ => false,
+    /// The remaining parts of the frames, skipping what was already written and the empty parts.
+    fn buffers(&self) -> Vec<&[u8]> {
+        let mut bufs = Vec::new();
+        let mut skip = self.written;
+
+        for &(ref hdr, ref msg) in self.frames.iter().take(MAX_FRAMES_PER_WRITE) {
+            for buf in &[&hdr[..], msg.get_header(), msg.get_body()] {
+                if skip >= buf.len() {
+                    skip -= buf.len();
+                } else {
+                    bufs.push(&buf[skip..]);
+                    skip = 0;
+                }
+            }
         }
+
+        bufs
     }
-}
 
-fn write_transport_hdr<T:io::Write>(stream: &mut T, msg: Rc<Message>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
-    let msg_len = msg.len() as u64;
-    let mut buffer = [1u8; 9];
+    /// Removes the frames completely written, returns how many they are.
+    fn advance(&mut self, written: usize) -> usize {
+        let mut sent = 0;
+        let mut written = self.written + written;
 
-    BigEndian::write_u64(&mut buffer[1..], msg_len);
+        while let Some(frame_len) = self.frames.front().map(|&(ref hdr, ref msg)| hdr.len() + msg.len()) {
+            if written < frame_len {
+                break;
+            }
 
-    let sent = try!(stream.write_buffer(&buffer, &mut written));
-    if sent {
-        Ok((true, SendOperationStep::ProtocolHdr(msg, 0)))
-    } else {
-        Ok((false, SendOperationStep::TransportHdr(msg, written)))
-    }
-}
+            written -= frame_len;
+            sent += 1;
+            self.frames.pop_front();
+        }
 
-fn write_protocol_hdr<T:io::Write>(stream: &mut T, msg: Rc<Message>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
-    if msg.get_header().len() == 0 {
-        return Ok((true, SendOperationStep::UsrPayload(msg, 0)));
-    }
+        self.written = written;
 
-    let sent = try!(stream.write_buffer(msg.get_header(), &mut written));
-    if sent {
-        Ok((true, SendOperationStep::UsrPayload(msg, 0)))
-    } else {
-        Ok((false, SendOperationStep::ProtocolHdr(msg, written)))
+        sent
     }
 }
 
-fn write_usr_payload<T:io::Write>(stream: &mut T, msg: Rc<Message>, mut written: usize) -> io::Result<(bool, SendOperationStep)> {
-    if msg.get_body().len() == 0 {
-        return Ok((true, SendOperationStep::Terminal));
-    }
+fn transport_hdr(msg: &Message) -> [u8; 9] {
+    let mut buffer = [1u8; 9];
 
-    let sent = try!(stream.write_buffer(msg.get_body(), &mut written));
-    if sent {
-        Ok((true, SendOperationStep::Terminal))
-    } else {
-        Ok((false, SendOperationStep::UsrPayload(msg, written)))
-    }
+    BigEndian::write_u64(&mut buffer[1..], msg.len() as u64);
+
+    buffer
 }
 
 #[cfg(test)]
 mod tests {
-    use std::ops::Deref;
     use std::rc::Rc;
 
     use core::Message;
+    use transport::async::tests::TestWriteStream;
     use super::*;
 
     #[test]
@@ -131,12 +121,72 @@ mod tests {
         let header = vec!(1, 4, 3, 2);
         let payload = vec!(65, 66, 67, 69);
         let msg = Message::from_header_and_body(header, payload);
-        let mut operation = SendOperation::new(Rc::new(msg));
-        let mut stream = Vec::new();
+        let mut operation = SendOperation::new(vec![Rc::new(msg)]);
+        let mut stream = TestWriteStream::new(usize::max_value());
         let result = operation.run(&mut stream).expect("send should have succeeded");
         let expected_bytes = [1, 0, 0, 0, 0, 0, 0, 0, 8, 1, 4, 3, 2, 65, 66, 67, 69];
 
-        assert!(result);
-        assert_eq!(&expected_bytes, stream.deref());
+        assert_eq!(1, result);
+        assert!(operation.is_done());
+        assert_eq!(&expected_bytes, stream.get_bytes());
+        assert_eq!(1, stream.get_writes());
+    }
+
+    #[test]
+    fn send_several_messages_in_one_write() {
+        let msgs = vec![
+            Rc::new(Message::from_body(vec!(65, 66))),
+            Rc::new(Message::from_body(vec!())),
+            Rc::new(Message::from_header_and_body(vec!(1), vec!(67)))];
+        let mut operation = SendOperation::new(msgs);
+        let mut stream = TestWriteStream::new(usize::max_value());
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [
+            1, 0, 0, 0, 0, 0, 0, 0, 2, 65, 66,
+            1, 0, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 0, 0, 0, 0, 0, 0, 2, 1, 67];
+
+        assert_eq!(3, result);
+        assert_eq!(&expected_bytes[..], stream.get_bytes());
+        assert_eq!(1, stream.get_writes());
+    }
+
+    #[test]
+    fn send_keeps_writing_when_only_the_first_buffer_is_written() {
+        let msgs = vec![
+            Rc::new(Message::from_header_and_body(vec!(1), vec!(65, 66))),
+            Rc::new(Message::from_body(vec!(67)))];
+        let mut operation = SendOperation::new(msgs);
+        let mut stream = TestWriteStream::first_buffer_only(usize::max_value());
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [1, 0, 0, 0, 0, 0, 0, 0, 3, 1, 65, 66, 1, 0, 0, 0, 0, 0, 0, 0, 1, 67];
+
+        assert_eq!(2, result);
+        assert!(operation.is_done());
+        assert_eq!(&expected_bytes[..], stream.get_bytes());
+        assert_eq!(5, stream.get_writes());
+    }
+
+    #[test]
+    fn send_resumes_after_partial_writes() {
+        let msgs = vec![
+            Rc::new(Message::from_body(vec!(65, 66))),
+            Rc::new(Message::from_body(vec!(67)))];
+        let mut operation = SendOperation::new(msgs);
+        let mut stream = TestWriteStream::new(10);
+
+        assert_eq!(0, operation.run(&mut stream).unwrap());
+        assert!(operation.is_done() == false);
+
+        stream.set_capacity(1);
+        assert_eq!(1, operation.run(&mut stream).unwrap());
+
+        stream.set_capacity(100);
+        assert_eq!(1, operation.run(&mut stream).unwrap());
+        assert!(operation.is_done());
+
+        let expected_bytes = [1, 0, 0, 0, 0, 0, 0, 0, 2, 65, 66, 1, 0, 0, 0, 0, 0, 0, 0, 1, 67];
+
+        assert_eq!(&expected_bytes[..], stream.get_bytes());
     }
 }
\ No newline at end of file
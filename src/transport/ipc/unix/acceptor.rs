@@ -18,16 +18,20 @@ use super::stub::IpcPipeStub;
 pub struct IpcAcceptor {
     listener: UnixListener,
     proto_ids: (u16, u16),
-    recv_max_size: u64
+    proto_flags: u8,
+    recv_max_size: u64,
+    recv_buffer: usize
 }
 
 impl IpcAcceptor {
 
-    pub fn new(l: UnixListener, pids: (u16, u16), recv_max_size: u64) -> IpcAcceptor {
+    pub fn new(l: UnixListener, pids: (u16, u16), flags: u8, recv_max_size: u64, recv_buffer: usize) -> IpcAcceptor {
         IpcAcceptor {
             listener: l,
             proto_ids: pids,
-            recv_max_size: recv_max_size
+            proto_flags: flags,
+            recv_max_size: recv_max_size,
+            recv_buffer: recv_buffer
         }
     }
 
@@ -62,7 +66,7 @@ impl IpcAcceptor {
     fn create_pipe(&self, stream: UnixStream) -> Box<pipe::Pipe> {
         let stub = IpcPipeStub::new(stream, self.recv_max_size);
 
-        box AsyncPipe::new(stub, self.proto_ids)
+        box AsyncPipe::new(stub, self.proto_ids, self.proto_flags, self.recv_buffer)
     }
 }
 
@@ -82,4 +86,9 @@ impl acceptor::Acceptor for IpcAcceptor {
         ctx.deregister(&self.listener);
         ctx.raise(Event::Closed);
     }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr().ok().
+            and_then(|addr| addr.as_pathname().map(|path| path.to_string_lossy().into_owned()))
+    }
 }
\ No newline at end of file
@@ -27,15 +27,15 @@ impl Transport for Ipc {
         let filename = path::Path::new(dest.addr);
         let stream = try!(UnixStream::connect(filename));
         let stub = IpcPipeStub::new(stream, dest.recv_max_size);
-        let pipe = box AsyncPipe::new(stub, dest.pids);
+        let pipe = AsyncPipe::new(stub, dest.pids, dest.proto_flags, dest.recv_buffer).with_remote_addr(dest.addr.to_owned());
 
-        Ok(pipe)
+        Ok(box pipe)
     }
 
     fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
         let filename = path::Path::new(dest.addr);
         let listener = try!(UnixListener::bind(filename));
-        let acceptor = box IpcAcceptor::new(listener, dest.pids, dest.recv_max_size);
+        let acceptor = box IpcAcceptor::new(listener, dest.pids, dest.proto_flags, dest.recv_max_size, dest.recv_buffer);
 
         Ok(acceptor)
     }
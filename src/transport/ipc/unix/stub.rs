@@ -49,13 +49,14 @@ impl IpcPipeStub {
         }
     }
 
-    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
-        if try!(send_operation.run(&mut self.stream)) {
-            Ok(true)
-        } else {
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<usize> {
+        let sent = try!(send_operation.run(&mut self.stream));
+
+        if send_operation.is_done() == false {
             self.send_operation = Some(send_operation);
-            Ok(false)
         }
+
+        Ok(sent)
     }
 
     fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
@@ -75,6 +76,18 @@ impl Drop for IpcPipeStub {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* WriteBuffers for UnixStream                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+impl WriteBuffers for UnixStream {
+    fn write_buffers(&mut self, bufs: &[&[u8]]) -> io::Result<usize> {
+        write_iovecs(bufs, |iovecs| self.write_bufs(iovecs))
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Sender for IpcPipeStub                                                    */
@@ -82,13 +95,13 @@ impl Drop for IpcPipeStub {
 /*****************************************************************************/
 
 impl Sender for IpcPipeStub {
-    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
-        let send_operation = SendOperation::new(msg);
+    fn start_send(&mut self, msgs: Vec<Rc<Message>>) -> io::Result<usize> {
+        let send_operation = SendOperation::new(msgs);
 
         self.run_send_operation(send_operation)
     }
 
-    fn resume_send(&mut self) -> io::Result<bool> {
+    fn resume_send(&mut self) -> io::Result<usize> {
         if let Some(send_operation) = self.send_operation.take() {
             self.run_send_operation(send_operation)
         } else {
@@ -134,10 +147,10 @@ impl Receiver for IpcPipeStub {
 /*****************************************************************************/
 
 impl Handshake for IpcPipeStub {
-    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
-        send_and_check_handshake(&mut self.stream, pids)
+    fn send_handshake(&mut self, pids: (u16, u16), flags: u8) -> io::Result<()> {
+        send_and_check_handshake(&mut self.stream, pids, flags)
     }
-    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<u8> {
         recv_and_check_handshake(&mut self.stream, pids)
     }
 }
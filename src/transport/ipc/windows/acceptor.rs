@@ -16,16 +16,20 @@ use super::stub::IpcPipeStub;
 pub struct IpcAcceptor {
     addr: String,
     proto_ids: (u16, u16),
-    recv_max_size: u64
+    proto_flags: u8,
+    recv_max_size: u64,
+    recv_buffer: usize
 }
 
 impl IpcAcceptor {
 
-    pub fn new(a: String, pids: (u16, u16), recv_max_size: u64) -> IpcAcceptor {
+    pub fn new(a: String, pids: (u16, u16), flags: u8, recv_max_size: u64, recv_buffer: usize) -> IpcAcceptor {
         IpcAcceptor {
             addr: a,
             proto_ids: pids,
-            recv_max_size: recv_max_size
+            proto_flags: flags,
+            recv_max_size: recv_max_size,
+            recv_buffer: recv_buffer
         }
     }
 
@@ -51,7 +55,7 @@ impl IpcAcceptor {
     fn create_pipe(&self, named_pipe: NamedPipe) -> Box<pipe::Pipe> {
         let stub = IpcPipeStub::new_server(named_pipe, self.recv_max_size);
 
-        box AsyncPipe::new(stub, self.proto_ids)
+        box AsyncPipe::new(stub, self.proto_ids, self.proto_flags, self.recv_buffer)
     }
 }
 
@@ -69,4 +73,8 @@ impl acceptor::Acceptor for IpcAcceptor {
         // TODO find a way to drop the created pipe
         ctx.raise(Event::Closed);
     }
+
+    fn local_addr(&self) -> Option<String> {
+        Some(self.addr.clone())
+    }
 }
@@ -61,13 +61,14 @@ impl IpcPipeStub {
         }
     }
 
-    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<bool> {
-        if try!(send_operation.run(&mut self.named_pipe)) {
-            Ok(true)
-        } else {
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<usize> {
+        let sent = try!(send_operation.run(&mut self.named_pipe));
+
+        if send_operation.is_done() == false {
             self.send_operation = Some(send_operation);
-            Ok(false)
         }
+
+        Ok(sent)
     }
 
     fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
@@ -87,6 +88,18 @@ impl Drop for IpcPipeStub {
     }
 }
 
+/*****************************************************************************/
+/*                                                                           */
+/* WriteBuffers for NamedPipe                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+impl WriteBuffers for NamedPipe {
+    fn write_buffers(&mut self, bufs: &[&[u8]]) -> io::Result<usize> {
+        write_first_buffer(self, bufs)
+    }
+}
+
 /*****************************************************************************/
 /*                                                                           */
 /* Sender for IpcPipeStub                                                    */
@@ -94,13 +107,13 @@ impl Drop for IpcPipeStub {
 /*****************************************************************************/
 
 impl Sender for IpcPipeStub {
-    fn start_send(&mut self, msg: Rc<Message>) -> io::Result<bool> {
-        let send_operation = SendOperation::new(msg);
+    fn start_send(&mut self, msgs: Vec<Rc<Message>>) -> io::Result<usize> {
+        let send_operation = SendOperation::new(msgs);
 
         self.run_send_operation(send_operation)
     }
 
-    fn resume_send(&mut self) -> io::Result<bool> {
+    fn resume_send(&mut self) -> io::Result<usize> {
         if let Some(send_operation) = self.send_operation.take() {
             self.run_send_operation(send_operation)
         } else {
@@ -146,10 +159,10 @@ impl Receiver for IpcPipeStub {
 /*****************************************************************************/
 
 impl Handshake for IpcPipeStub {
-    fn send_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
-        send_and_check_handshake(&mut self.named_pipe, pids)
+    fn send_handshake(&mut self, pids: (u16, u16), flags: u8) -> io::Result<()> {
+        send_and_check_handshake(&mut self.named_pipe, pids, flags)
     }
-    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<()> {
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<u8> {
         recv_and_check_handshake(&mut self.named_pipe, pids)
     }
 }
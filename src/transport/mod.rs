@@ -7,6 +7,8 @@
 pub mod async;
 pub mod tcp;
 pub mod ipc;
+pub mod inproc;
+pub mod ws;
 pub mod endpoint;
 pub mod pipe;
 pub mod acceptor;
@@ -16,8 +18,33 @@ use std::io::Result;
 pub struct Destination<'a> {
     pub addr: &'a str,
     pub pids: (u16, u16),
+    pub proto_flags: u8,
     pub tcp_no_delay: bool,
-    pub recv_max_size: u64
+    pub recv_max_size: u64,
+    pub recv_buffer: usize
+}
+
+/// The part of a `Destination` the pipes are created with,
+/// owned so that the creation can wait for the address to be resolved.
+#[derive(Clone, Copy)]
+pub struct PipeSettings {
+    pub pids: (u16, u16),
+    pub proto_flags: u8,
+    pub tcp_no_delay: bool,
+    pub recv_max_size: u64,
+    pub recv_buffer: usize
+}
+
+impl<'a> Destination<'a> {
+    pub fn pipe_settings(&self) -> PipeSettings {
+        PipeSettings {
+            pids: self.pids,
+            proto_flags: self.proto_flags,
+            tcp_no_delay: self.tcp_no_delay,
+            recv_max_size: self.recv_max_size,
+            recv_buffer: self.recv_buffer
+        }
+    }
 }
 
 pub trait Transport {
@@ -17,16 +17,18 @@ pub enum Command {
     Open,
     Close,
     Send(Rc<Message>),
+    SendAll(Vec<Rc<Message>>),
     Recv
 }
 
 pub enum Event {
-    Opened,
+    /// The pipe is ready to exchange messages, with the protocol flags the peer sent in its handshake.
+    Opened(u8),
     Closed,
     CanSend,
     CanRecv,
     Sent,
-    Received(Message),
+    Received(Rc<Message>),
     Error(io::Error)
 }
 
@@ -35,7 +37,16 @@ pub trait Pipe {
     fn open(&mut self, ctx: &mut Context);
     fn close(&mut self, ctx: &mut Context);
     fn send(&mut self, ctx: &mut Context, msg: Rc<Message>);
+    /// Sends the messages in order, a `Sent` event being raised for each of them.
+    /// The transports able to write several messages at once override this.
+    fn send_all(&mut self, ctx: &mut Context, msgs: Vec<Rc<Message>>) {
+        for msg in msgs {
+            self.send(ctx, msg);
+        }
+    }
     fn recv(&mut self, ctx: &mut Context);
+    /// Returns the address of the peer when the transport knows it.
+    fn remote_addr(&self) -> Option<String>;
 }
 
 pub trait Context : EndpointRegistrar + fmt::Debug {
@@ -51,10 +62,11 @@ impl fmt::Debug for Command {
 impl Command {
     fn name(&self) -> &'static str {
         match *self {
-            Command::Open    => "Open",
-            Command::Close   => "Close",
-            Command::Send(_) => "Send",
-            Command::Recv    => "Recv"
+            Command::Open       => "Open",
+            Command::Close      => "Close",
+            Command::Send(_)    => "Send",
+            Command::SendAll(_) => "SendAll",
+            Command::Recv       => "Recv"
         }
     }
 }
@@ -68,7 +80,7 @@ impl fmt::Debug for Event {
 impl Event {
     pub fn name(&self) -> &'static str {
         match *self {
-            Event::Opened      => "Opened",
+            Event::Opened(_)   => "Opened",
             Event::Closed      => "Closed",
             Event::CanSend     => "CanSend",
             Event::CanRecv     => "CanRecv",
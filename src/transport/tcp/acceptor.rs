@@ -5,6 +5,7 @@
 // This file may not be copied, modified, or distributed except according to those terms.
 
 use std::io;
+use std::net::SocketAddr;
 
 use mio;
 use mio::tcp::{TcpListener, TcpStream};
@@ -17,8 +18,10 @@ use super::stub::TcpPipeStub;
 pub struct TcpAcceptor {
     listener: TcpListener,
     proto_ids: (u16, u16),
+    proto_flags: u8,
     no_delay: bool,
-    recv_max_size: u64
+    recv_max_size: u64,
+    recv_buffer: usize
 }
 
 impl TcpAcceptor {
@@ -27,8 +30,10 @@ impl TcpAcceptor {
         TcpAcceptor {
             listener: l,
             proto_ids: dest.pids,
+            proto_flags: dest.proto_flags,
             no_delay: dest.tcp_no_delay,
-            recv_max_size: dest.recv_max_size
+            recv_max_size: dest.recv_max_size,
+            recv_buffer: dest.recv_buffer
         }
     }
 
@@ -37,9 +42,9 @@ impl TcpAcceptor {
 
         loop {
             match self.listener.accept() {
-                Ok((stream, _)) => {
+                Ok((stream, addr)) => {
                     let _ = stream.set_nodelay(self.no_delay);
-                    let pipe = self.create_pipe(stream);
+                    let pipe = self.create_pipe(stream, addr);
 
                     pipes.push(pipe);
                 },
@@ -58,10 +63,12 @@ impl TcpAcceptor {
         }
     }
 
-    fn create_pipe(&self, stream: TcpStream) -> Box<pipe::Pipe> {
+    fn create_pipe(&self, stream: TcpStream, addr: SocketAddr) -> Box<pipe::Pipe> {
         let stub = TcpPipeStub::new(stream, self.recv_max_size);
 
-        box AsyncPipe::new(stub, self.proto_ids)
+        let pipe = AsyncPipe::new(stub, self.proto_ids, self.proto_flags, self.recv_buffer).with_remote_addr(addr.to_string());
+
+        box pipe
     }
 }
 
@@ -81,4 +88,8 @@ impl acceptor::Acceptor for TcpAcceptor {
         ctx.deregister(&self.listener);
         ctx.raise(Event::Closed);
     }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr().ok().map(|addr| addr.to_string())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,285 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module resolves the addresses of the tcp based transports, using the syntax of nanomsg.
+/// A bound address is `interface:port`, where the interface is `*`, an IP address or the name of a network interface.
+/// A connected address is `[interface;]host:port`, where the host is an IP address or a DNS name,
+/// and the optional interface is used as the source address of the connection.
+/// IPv6 addresses are enclosed in brackets, as in `[::1]:5454`.
+
+use std::str::FromStr;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+
+use mio::tcp::TcpStream;
+use net2::TcpBuilder;
+
+use io_error::*;
+
+/// Splits `interface;host:port` into the optional interface and the remote part.
+pub fn split_local(addr: &str) -> (Option<&str>, &str) {
+    match addr.find(';') {
+        Some(index) => (Some(&addr[..index]), &addr[index + 1..]),
+        None => (None, addr)
+    }
+}
+
+pub fn resolve_bind_addr(addr: &str) -> io::Result<SocketAddr> {
+    let (iface, port) = try!(split_port(addr));
+    let ip = try!(resolve_iface(iface));
+
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Tells whether the remote host of a connect address is a DNS name, whose lookup may block for a while.
+/// Fails right away when the address is malformed.
+pub fn needs_lookup(addr: &str) -> io::Result<bool> {
+    let (_, remote) = split_local(addr);
+    let (host, _) = try!(split_port(remote));
+
+    Ok(IpAddr::from_str(host).is_err())
+}
+
+/// Gets the remote host of a connect address, without the interface, the port and the IPv6 brackets.
+pub fn remote_host(addr: &str) -> io::Result<&str> {
+    let (_, remote) = split_local(addr);
+    let (host, _) = try!(split_port(remote));
+
+    Ok(host)
+}
+
+/// Resolves the optional source address and the remote address of a connection.
+/// DNS names are resolved each time this is called, so each reconnect attempt gets a fresh answer.
+pub fn resolve_connect_addr(addr: &str) -> io::Result<(Option<SocketAddr>, SocketAddr)> {
+    let (local, remote) = split_local(addr);
+    let (host, port) = try!(split_port(remote));
+    let remote_addr = try!(resolve_host(host, port));
+    let local_addr = match local {
+        Some(iface) => Some(SocketAddr::new(try!(resolve_iface(iface)), 0)),
+        None => None
+    };
+
+    Ok((local_addr, remote_addr))
+}
+
+pub fn connect(local_addr: Option<SocketAddr>, addr: &SocketAddr) -> io::Result<TcpStream> {
+    let local_addr = match local_addr {
+        Some(x) => x,
+        None => return TcpStream::connect(addr)
+    };
+    let builder = if addr.is_ipv4() {
+        try!(TcpBuilder::new_v4())
+    } else {
+        try!(TcpBuilder::new_v6())
+    };
+
+    try!(builder.bind(local_addr));
+
+    let stream = try!(builder.to_tcp_stream());
+
+    TcpStream::connect_stream(stream, addr)
+}
+
+fn split_port(addr: &str) -> io::Result<(&str, u16)> {
+    let index = match addr.rfind(':') {
+        Some(x) => x,
+        None => return Err(invalid_input_io_error(addr.to_owned()))
+    };
+    let (host, port) = (&addr[..index], &addr[index + 1..]);
+
+    match u16::from_str(port) {
+        Ok(port) => Ok((trim_brackets(host), port)),
+        Err(_) => Err(invalid_input_io_error(addr.to_owned()))
+    }
+}
+
+fn trim_brackets(host: &str) -> &str {
+    if host.starts_with('[') && host.ends_with(']') {
+        &host[1..host.len() - 1]
+    } else {
+        host
+    }
+}
+
+fn resolve_iface(iface: &str) -> io::Result<IpAddr> {
+    if iface == "*" {
+        return Ok(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
+    }
+
+    if let Ok(ip) = IpAddr::from_str(iface) {
+        return Ok(ip);
+    }
+
+    match find_iface_addr(iface) {
+        Some(ip) => Ok(ip),
+        None => Err(invalid_input_io_error(format!("unknown interface {}", iface)))
+    }
+}
+
+fn resolve_host(host: &str, port: u16) -> io::Result<SocketAddr> {
+    if let Ok(ip) = IpAddr::from_str(host) {
+        return Ok(SocketAddr::new(ip, port));
+    }
+
+    let ips: Vec<IpAddr> = try!((host, port).to_socket_addrs()).map(|addr| addr.ip()).collect();
+
+    match prefer_ipv4(&ips) {
+        Some(ip) => Ok(SocketAddr::new(ip, port)),
+        None => Err(invalid_input_io_error(format!("unknown host {}", host)))
+    }
+}
+
+/// Like nanomsg, IPv4 is preferred when a name has several addresses.
+fn prefer_ipv4(ips: &[IpAddr]) -> Option<IpAddr> {
+    ips.iter().find(|ip| ip.is_ipv4()).or_else(|| ips.first()).cloned()
+}
+
+#[cfg(unix)]
+fn find_iface_addr(name: &str) -> Option<IpAddr> {
+    use std::ffi::CStr;
+    use std::ptr;
+    use libc;
+
+    let mut ifaddrs = ptr::null_mut();
+    let mut found = Vec::new();
+
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        return None;
+    }
+
+    let mut cursor = ifaddrs;
+    while cursor.is_null() == false {
+        let ifaddr = unsafe { &*cursor };
+        let ifname = unsafe { CStr::from_ptr(ifaddr.ifa_name) };
+
+        if ifname.to_bytes() == name.as_bytes() && ifaddr.ifa_addr.is_null() == false {
+            if let Some(ip) = unsafe { from_sockaddr(ifaddr.ifa_addr) } {
+                found.push(ip);
+            }
+        }
+
+        cursor = ifaddr.ifa_next;
+    }
+
+    unsafe { libc::freeifaddrs(ifaddrs) };
+
+    prefer_ipv4(&found)
+}
+
+#[cfg(unix)]
+unsafe fn from_sockaddr(addr: *const ::libc::sockaddr) -> Option<IpAddr> {
+    use std::net::Ipv6Addr;
+    use libc;
+
+    match i32::from((*addr).sa_family) {
+        libc::AF_INET => {
+            let addr = &*(addr as *const libc::sockaddr_in);
+
+            Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr))))
+        },
+        libc::AF_INET6 => {
+            let addr = &*(addr as *const libc::sockaddr_in6);
+
+            Some(IpAddr::V6(Ipv6Addr::from(addr.sin6_addr.s6_addr)))
+        },
+        _ => None
+    }
+}
+
+#[cfg(not(unix))]
+fn find_iface_addr(_: &str) -> Option<IpAddr> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn socket_addr(addr: &str) -> SocketAddr {
+        SocketAddr::from_str(addr).unwrap()
+    }
+
+    #[test]
+    fn bind_addr_can_be_a_wildcard() {
+        assert_eq!(socket_addr("0.0.0.0:5454"), resolve_bind_addr("*:5454").unwrap());
+        assert_eq!(socket_addr("[::]:5454"), resolve_bind_addr("[::]:5454").unwrap());
+    }
+
+    #[test]
+    fn bind_addr_can_be_an_ip_address() {
+        assert_eq!(socket_addr("127.0.0.1:5454"), resolve_bind_addr("127.0.0.1:5454").unwrap());
+        assert_eq!(socket_addr("[::1]:5454"), resolve_bind_addr("[::1]:5454").unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn bind_addr_can_be_an_interface_name() {
+        let addr = resolve_bind_addr("lo:5454").unwrap();
+
+        assert!(addr.ip().is_loopback());
+        assert_eq!(5454, addr.port());
+    }
+
+    #[test]
+    fn bind_addr_with_unknown_interface_is_invalid() {
+        let err = resolve_bind_addr("nosuchiface0:5454").unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn bind_addr_without_port_is_invalid() {
+        assert_eq!(io::ErrorKind::InvalidInput, resolve_bind_addr("*").unwrap_err().kind());
+        assert_eq!(io::ErrorKind::InvalidInput, resolve_bind_addr("*:http").unwrap_err().kind());
+    }
+
+    #[test]
+    fn connect_addr_can_be_a_host_name() {
+        let (local, remote) = resolve_connect_addr("localhost:5454").unwrap();
+
+        assert_eq!(None, local);
+        assert!(remote.ip().is_loopback());
+        assert_eq!(5454, remote.port());
+    }
+
+    #[test]
+    fn only_host_names_need_a_lookup() {
+        assert!(needs_lookup("localhost:5454").unwrap());
+        assert!(needs_lookup("127.0.0.1;localhost:5454").unwrap());
+        assert_eq!(false, needs_lookup("10.0.0.1:5454").unwrap());
+        assert_eq!(false, needs_lookup("[::1]:5454").unwrap());
+        assert_eq!(io::ErrorKind::InvalidInput, needs_lookup("localhost").unwrap_err().kind());
+    }
+
+    #[test]
+    fn remote_host_has_no_interface_nor_port() {
+        assert_eq!("localhost", remote_host("localhost:5454").unwrap());
+        assert_eq!("example.com", remote_host("eth0;example.com:5454").unwrap());
+        assert_eq!("::1", remote_host("[::1]:5454").unwrap());
+        assert_eq!(io::ErrorKind::InvalidInput, remote_host("localhost").unwrap_err().kind());
+    }
+
+    #[test]
+    fn connect_addr_can_specify_the_local_interface() {
+        let (local, remote) = resolve_connect_addr("127.0.0.1;10.0.0.1:5454").unwrap();
+
+        assert_eq!(Some(socket_addr("127.0.0.1:0")), local);
+        assert_eq!(socket_addr("10.0.0.1:5454"), remote);
+    }
+
+    #[test]
+    fn ipv4_addresses_are_preferred() {
+        let v4 = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let v6 = IpAddr::from_str("::1").unwrap();
+
+        assert_eq!(Some(v4), prefer_ipv4(&[v6, v4]));
+        assert_eq!(Some(v6), prefer_ipv4(&[v6]));
+    }
+}
@@ -8,31 +8,36 @@ mod stub;
 mod send;
 mod recv;
 mod acceptor;
+pub mod addr;
+pub mod resolve;
+#[cfg(feature = "tls")]
+pub mod tls;
 
-use std::str::FromStr;
 use std::io;
 use std::net;
 
-use mio::tcp::{TcpListener, TcpStream};
+use mio::tcp::TcpListener;
 
 use self::stub::TcpPipeStub;
 use self::acceptor::TcpAcceptor;
-use transport::{Transport, Destination};
+use transport::{Transport, Destination, PipeSettings};
 use transport::pipe::Pipe;
 use transport::acceptor::Acceptor;
 use transport::async::AsyncPipe;
-use io_error::*;
 
+/// The tcp transport, using the address syntax of nanomsg.  
+/// Bind to `tcp://*:5454`, `tcp://127.0.0.1:5454`, `tcp://[::1]:5454` or `tcp://eth0:5454`.  
+/// Connect to `tcp://localhost:5454`, optionally from a given interface: `tcp://eth0;10.0.0.1:5454`.
 pub struct Tcp;
 
 impl Tcp {
-    fn connect(&self, addr: &net::SocketAddr, dest: &Destination) -> io::Result<Box<Pipe>> {
-        let stream = try!(TcpStream::connect(addr));
-        try!(stream.set_nodelay(dest.tcp_no_delay));
-        let stub = TcpPipeStub::new(stream, dest.recv_max_size);
-        let pipe = box AsyncPipe::new(stub, dest.pids);
+    fn connect(local_addr: Option<net::SocketAddr>, addr: &net::SocketAddr, settings: PipeSettings) -> io::Result<Box<Pipe>> {
+        let stream = try!(addr::connect(local_addr, addr));
+        try!(stream.set_nodelay(settings.tcp_no_delay));
+        let stub = TcpPipeStub::new(stream, settings.recv_max_size);
+        let pipe = AsyncPipe::new(stub, settings.pids, settings.proto_flags, settings.recv_buffer).with_remote_addr(addr.to_string());
 
-        Ok(pipe)
+        Ok(box pipe)
     }
     fn bind(&self, addr: &net::SocketAddr, dest: &Destination) -> io::Result<Box<Acceptor>> {
         let listener = try!(TcpListener::bind(addr));
@@ -44,16 +49,14 @@ impl Tcp {
 
 impl Transport for Tcp {
     fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
-        match net::SocketAddr::from_str(dest.addr) {
-            Ok(addr) => self.connect(&addr, dest),
-            Err(_) => Err(invalid_input_io_error(dest.addr))
-        }
+        let settings = dest.pipe_settings();
+
+        resolve::connect(dest.addr, move |local_addr, addr| Tcp::connect(local_addr, &addr, settings))
     }
 
     fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
-        match net::SocketAddr::from_str(dest.addr) {
-            Ok(addr) => self.bind(&addr, dest),
-            Err(_) => Err(invalid_input_io_error(dest.addr))
-        }
+        let addr = try!(addr::resolve_bind_addr(dest.addr));
+
+        self.bind(&addr, dest)
     }
 }
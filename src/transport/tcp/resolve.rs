@@ -0,0 +1,267 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module keeps DNS lookups away from the event loop thread.
+/// Resolving a name is a blocking call that can last as long as the resolver timeout,
+/// so it runs on a helper thread that wakes the pipe up once the addresses are known.
+
+use std::boxed::FnBox;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::rc::Rc;
+use std::io;
+use std::net::SocketAddr;
+
+use mio;
+use mio::{Registration, SetReadiness};
+
+use core::Message;
+use transport::pipe::*;
+use super::addr;
+
+/// Creates the pipe once the source and remote addresses of the connection are known.
+pub type Connector = Box<FnBox(Option<SocketAddr>, SocketAddr) -> io::Result<Box<Pipe>>>;
+
+type Resolution = io::Result<(Option<SocketAddr>, SocketAddr)>;
+
+/// Connects right away when the remote host is an IP address,
+/// otherwise returns a pipe that connects when the lookup of the name is done.
+pub fn connect<F>(addr: &str, connector: F) -> io::Result<Box<Pipe>>
+    where F : FnOnce(Option<SocketAddr>, SocketAddr) -> io::Result<Box<Pipe>> + 'static
+{
+    if try!(addr::needs_lookup(addr)) {
+        let pipe = ResolvingPipe::new(addr.to_owned(), box connector);
+
+        Ok(box pipe)
+    } else {
+        let (local_addr, remote_addr) = try!(addr::resolve_connect_addr(addr));
+
+        connector(local_addr, remote_addr)
+    }
+}
+
+/// Stands for the pipe while its address is being resolved, and then forwards everything to it.
+pub struct ResolvingPipe {
+    addr: String,
+    connector: Option<Connector>,
+    registration: Option<Registration>,
+    resolution: Arc<Mutex<Option<Resolution>>>,
+    pipe: Option<Box<Pipe>>
+}
+
+impl ResolvingPipe {
+    pub fn new(addr: String, connector: Connector) -> ResolvingPipe {
+        ResolvingPipe {
+            addr: addr,
+            connector: Some(connector),
+            registration: None,
+            resolution: Arc::new(Mutex::new(None)),
+            pipe: None
+        }
+    }
+
+    fn take_resolution(&self) -> Option<Resolution> {
+        match self.resolution.lock() {
+            Ok(mut resolution) => resolution.take(),
+            Err(_) => None
+        }
+    }
+
+    fn on_resolved(&mut self, ctx: &mut Context, resolution: Resolution) {
+        if let Some(registration) = self.registration.take() {
+            ctx.deregister(&registration);
+        }
+
+        let connector = match self.connector.take() {
+            Some(connector) => connector,
+            None => return
+        };
+        let pipe = resolution.and_then(|(local_addr, remote_addr)| connector.call_box((local_addr, remote_addr)));
+
+        match pipe {
+            Ok(mut pipe) => {
+                pipe.open(ctx);
+                self.pipe = Some(pipe);
+            },
+            Err(e) => ctx.raise(Event::Error(e))
+        }
+    }
+}
+
+fn resolve(addr: String, resolution: Arc<Mutex<Option<Resolution>>>, readiness: SetReadiness) {
+    let result = addr::resolve_connect_addr(&addr);
+
+    if let Ok(mut resolution) = resolution.lock() {
+        *resolution = Some(result);
+    }
+
+    let _ = readiness.set_readiness(mio::Ready::readable());
+}
+
+impl Pipe for ResolvingPipe {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if let Some(ref mut pipe) = self.pipe {
+            return pipe.ready(ctx, events);
+        }
+
+        if events.is_readable() {
+            if let Some(resolution) = self.take_resolution() {
+                self.on_resolved(ctx, resolution);
+            }
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        let (registration, readiness) = Registration::new2();
+        let addr = self.addr.clone();
+        let resolution = self.resolution.clone();
+
+        ctx.register(&registration, mio::Ready::readable(), mio::PollOpt::edge());
+        self.registration = Some(registration);
+
+        thread::spawn(move || resolve(addr, resolution, readiness));
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        if let Some(ref mut pipe) = self.pipe {
+            return pipe.close(ctx);
+        }
+
+        if let Some(registration) = self.registration.take() {
+            ctx.deregister(&registration);
+        }
+
+        self.connector = None;
+
+        ctx.raise(Event::Closed);
+    }
+
+    fn send(&mut self, ctx: &mut Context, msg: Rc<Message>) {
+        if let Some(ref mut pipe) = self.pipe {
+            pipe.send(ctx, msg);
+        }
+    }
+
+    fn send_all(&mut self, ctx: &mut Context, msgs: Vec<Rc<Message>>) {
+        if let Some(ref mut pipe) = self.pipe {
+            pipe.send_all(ctx, msgs);
+        }
+    }
+
+    fn recv(&mut self, ctx: &mut Context) {
+        if let Some(ref mut pipe) = self.pipe {
+            pipe.recv(ctx);
+        }
+    }
+
+    fn remote_addr(&self) -> Option<String> {
+        self.pipe.as_ref().and_then(|pipe| pipe.remote_addr())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::thread;
+    use std::time::Duration;
+    use std::net::SocketAddr;
+
+    use mio;
+
+    use core::Message;
+    use transport::tests::*;
+    use transport::pipe::*;
+    use super::*;
+
+    struct TestPipe;
+
+    impl Pipe for TestPipe {
+        fn ready(&mut self, _: &mut Context, _: mio::Ready) {}
+        fn open(&mut self, ctx: &mut Context) {
+            ctx.raise(Event::Opened(0));
+        }
+        fn close(&mut self, ctx: &mut Context) {
+            ctx.raise(Event::Closed);
+        }
+        fn send(&mut self, _: &mut Context, _: Rc<Message>) {}
+        fn recv(&mut self, _: &mut Context) {}
+        fn remote_addr(&self) -> Option<String> {
+            Some(String::from("test"))
+        }
+    }
+
+    fn recording_connector(connections: Rc<RefCell<Vec<SocketAddr>>>) -> Connector {
+        let connector = move |_: Option<SocketAddr>, addr: SocketAddr| {
+            connections.borrow_mut().push(addr);
+
+            Ok(box TestPipe as Box<Pipe>)
+        };
+
+        box connector
+    }
+
+    #[test]
+    fn ip_address_is_connected_right_away() {
+        let connections = Rc::new(RefCell::new(Vec::new()));
+        let recorder = connections.clone();
+        let pipe = connect("127.0.0.1:5454", move |_, addr| {
+            recorder.borrow_mut().push(addr);
+
+            Ok(box TestPipe as Box<Pipe>)
+        }).unwrap();
+
+        assert_eq!(1, connections.borrow().len());
+        assert_eq!(Some(String::from("test")), pipe.remote_addr());
+    }
+
+    #[test]
+    fn host_name_is_connected_once_resolved() {
+        let connections = Rc::new(RefCell::new(Vec::new()));
+        let mut pipe = ResolvingPipe::new(String::from("localhost:5454"), recording_connector(connections.clone()));
+        let mut ctx = TestPipeContext::new();
+
+        pipe.open(&mut ctx);
+
+        assert_eq!(1, ctx.get_registrations().len());
+        assert_eq!(0, connections.borrow().len());
+        assert_eq!(None, pipe.remote_addr());
+
+        for _ in 0..500 {
+            pipe.ready(&mut ctx, mio::Ready::readable());
+
+            if ctx.get_raised_events().is_empty() == false {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(1, connections.borrow().len());
+        assert!(connections.borrow()[0].ip().is_loopback());
+        assert_eq!(1, ctx.get_deregistrations());
+        assert_eq!(1, ctx.get_raised_events().len());
+        assert_eq!("Opened", ctx.get_raised_events()[0].name());
+        assert_eq!(Some(String::from("test")), pipe.remote_addr());
+    }
+
+    #[test]
+    fn close_before_resolution_does_not_connect() {
+        let connections = Rc::new(RefCell::new(Vec::new()));
+        let mut pipe = ResolvingPipe::new(String::from("localhost:5454"), recording_connector(connections.clone()));
+        let mut ctx = TestPipeContext::new();
+
+        pipe.open(&mut ctx);
+        pipe.close(&mut ctx);
+        thread::sleep(Duration::from_millis(100));
+        pipe.ready(&mut ctx, mio::Ready::readable());
+
+        assert_eq!(0, connections.borrow().len());
+        assert_eq!(1, ctx.get_deregistrations());
+        assert_eq!(1, ctx.get_raised_events().len());
+        assert_eq!("Closed", ctx.get_raised_events()[0].name());
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::net::SocketAddr;
+
+use mio;
+use mio::tcp::{TcpListener, TcpStream};
+use openssl::ssl::{Ssl, SslAcceptor};
+
+use transport::*;
+use transport::acceptor::*;
+use transport::async::AsyncPipe;
+use io_error::*;
+use super::stub::{TlsPipeStub, create_stream};
+
+pub struct TlsAcceptor {
+    listener: TcpListener,
+    ssl_acceptor: SslAcceptor,
+    proto_ids: (u16, u16),
+    proto_flags: u8,
+    no_delay: bool,
+    recv_max_size: u64,
+    recv_buffer: usize
+}
+
+impl TlsAcceptor {
+
+    pub fn new(l: TcpListener, ssl_acceptor: SslAcceptor, dest: &Destination) -> TlsAcceptor {
+        TlsAcceptor {
+            listener: l,
+            ssl_acceptor: ssl_acceptor,
+            proto_ids: dest.pids,
+            proto_flags: dest.proto_flags,
+            no_delay: dest.tcp_no_delay,
+            recv_max_size: dest.recv_max_size,
+            recv_buffer: dest.recv_buffer
+        }
+    }
+
+    fn accept(&mut self, ctx: &mut Context) {
+        let mut pipes = Vec::new();
+
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => {
+                    let _ = stream.set_nodelay(self.no_delay);
+
+                    match self.create_pipe(stream, addr) {
+                        Ok(pipe) => pipes.push(pipe),
+                        Err(e) => ctx.raise(Event::Error(e))
+                    }
+                },
+                Err(e) => {
+                    if e.kind() == io::ErrorKind::WouldBlock {
+                        break;
+                    } else {
+                        ctx.raise(Event::Error(e));
+                    }
+                }
+            }
+        }
+
+        if pipes.is_empty() == false {
+            ctx.raise(Event::Accepted(pipes));
+        }
+    }
+
+    fn create_pipe(&self, stream: TcpStream, addr: SocketAddr) -> io::Result<Box<pipe::Pipe>> {
+        let mut ssl = try!(Ssl::new(self.ssl_acceptor.context()).map_err(other_io_error));
+        ssl.set_accept_state();
+        let stream = try!(create_stream(ssl, stream));
+        let stub = TlsPipeStub::new(stream, self.recv_max_size);
+
+        let pipe = AsyncPipe::new(stub, self.proto_ids, self.proto_flags, self.recv_buffer).with_remote_addr(addr.to_string());
+
+        Ok(box pipe)
+    }
+}
+
+impl acceptor::Acceptor for TlsAcceptor {
+    fn ready(&mut self, ctx: &mut Context, events: mio::Ready) {
+        if events.is_readable() {
+            self.accept(ctx);
+        }
+    }
+
+    fn open(&mut self, ctx: &mut Context) {
+        ctx.register(&self.listener, mio::Ready::readable(), mio::PollOpt::edge());
+        ctx.raise(Event::Opened);
+    }
+
+    fn close(&mut self, ctx: &mut Context) {
+        ctx.deregister(&self.listener);
+        ctx.raise(Event::Closed);
+    }
+
+    fn local_addr(&self) -> Option<String> {
+        self.listener.local_addr().ok().map(|addr| addr.to_string())
+    }
+}
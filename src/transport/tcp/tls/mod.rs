@@ -0,0 +1,247 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module provides a transport running the TCP pipes inside a TLS session.
+/// The TLS handshake is performed before the SP handshake, which is sent encrypted.
+
+mod stub;
+mod acceptor;
+
+use std::path::{Path, PathBuf};
+use std::io;
+use std::net;
+
+use mio::tcp::TcpListener;
+use openssl::error::ErrorStack;
+use openssl::ssl::{Ssl, SslMethod, SslConnector, SslAcceptor, SslFiletype, SslVerifyMode};
+
+use self::stub::{TlsPipeStub, create_stream};
+use self::acceptor::TlsAcceptor;
+use transport::{Transport, Destination, PipeSettings};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use transport::tcp::{addr, resolve};
+use io_error::*;
+
+/// Configures the certificates used by the `tls+tcp` transport.
+/// The certificate chain and its private key are required to bind,
+/// when connecting they are only sent to servers requiring client authentication.
+#[derive(Default)]
+pub struct TlsTcpBuilder {
+    ca_file: Option<PathBuf>,
+    certificate_chain_file: Option<PathBuf>,
+    private_key_file: Option<PathBuf>,
+    require_client_auth: bool,
+    server_name: Option<String>,
+    skip_hostname_verification: bool
+}
+
+pub struct TlsTcp {
+    connector: SslConnector,
+    acceptor: Option<SslAcceptor>,
+    server_name: Option<String>,
+    skip_hostname_verification: bool
+}
+
+impl TlsTcpBuilder {
+    pub fn new() -> TlsTcpBuilder {
+        TlsTcpBuilder::default()
+    }
+
+    /// Sets the PEM file containing the trusted CA roots, used to verify the peer certificate.
+    /// When not set, the system default roots are used.
+    pub fn with_ca_file<P: AsRef<Path>>(mut self, path: P) -> TlsTcpBuilder {
+        self.ca_file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the PEM file containing the certificate chain presented to the peer.
+    pub fn with_certificate_chain_file<P: AsRef<Path>>(mut self, path: P) -> TlsTcpBuilder {
+        self.certificate_chain_file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the PEM file containing the private key of the certificate.
+    pub fn with_private_key_file<P: AsRef<Path>>(mut self, path: P) -> TlsTcpBuilder {
+        self.private_key_file = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// When set, bound endpoints reject the clients that do not present a valid certificate.
+    pub fn with_client_auth(mut self, required: bool) -> TlsTcpBuilder {
+        self.require_client_auth = required;
+        self
+    }
+
+    /// Sets the name expected in the server certificates, and sent to the servers with SNI.
+    /// When not set, the host of the connected address is expected, be it a DNS name or an IP address.
+    pub fn with_server_name(mut self, name: &str) -> TlsTcpBuilder {
+        self.server_name = Some(name.to_owned());
+        self
+    }
+
+    /// Accepts any server certificate signed by a trusted CA, whatever the name it was issued for.
+    /// This makes the connections vulnerable to man in the middle attacks, it should only be used for testing.
+    pub fn danger_skip_hostname_verification(mut self) -> TlsTcpBuilder {
+        self.skip_hostname_verification = true;
+        self
+    }
+
+    pub fn build(self) -> io::Result<TlsTcp> {
+        let connector = try!(self.build_connector().map_err(other_io_error));
+        let acceptor = if self.certificate_chain_file.is_some() {
+            Some(try!(self.build_acceptor().map_err(other_io_error)))
+        } else {
+            None
+        };
+
+        Ok(TlsTcp {
+            connector: connector,
+            acceptor: acceptor,
+            server_name: self.server_name,
+            skip_hostname_verification: self.skip_hostname_verification
+        })
+    }
+
+    fn build_connector(&self) -> Result<SslConnector, ErrorStack> {
+        let mut builder = try!(SslConnector::builder(SslMethod::tls()));
+
+        if let Some(ref path) = self.ca_file {
+            try!(builder.set_ca_file(path));
+        }
+        if let Some(ref path) = self.certificate_chain_file {
+            try!(builder.set_certificate_chain_file(path));
+        }
+        if let Some(ref path) = self.private_key_file {
+            try!(builder.set_private_key_file(path, SslFiletype::PEM));
+            try!(builder.check_private_key());
+        }
+
+        Ok(builder.build())
+    }
+
+    fn build_acceptor(&self) -> Result<SslAcceptor, ErrorStack> {
+        let mut builder = try!(SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()));
+
+        if let Some(ref path) = self.certificate_chain_file {
+            try!(builder.set_certificate_chain_file(path));
+        }
+        if let Some(ref path) = self.private_key_file {
+            try!(builder.set_private_key_file(path, SslFiletype::PEM));
+            try!(builder.check_private_key());
+        }
+        if let Some(ref path) = self.ca_file {
+            try!(builder.set_ca_file(path));
+        }
+        if self.require_client_auth {
+            builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+impl TlsTcp {
+    fn connect(ssl: Ssl, local_addr: Option<net::SocketAddr>, addr: &net::SocketAddr, settings: PipeSettings) -> io::Result<Box<Pipe>> {
+        let stream = try!(addr::connect(local_addr, addr));
+        try!(stream.set_nodelay(settings.tcp_no_delay));
+        let stream = try!(create_stream(ssl, stream));
+        let stub = TlsPipeStub::new(stream, settings.recv_max_size);
+        let pipe = AsyncPipe::new(stub, settings.pids, settings.proto_flags, settings.recv_buffer).with_remote_addr(addr.to_string());
+
+        Ok(box pipe)
+    }
+    fn create_client_ssl(&self, addr: &str) -> io::Result<Ssl> {
+        let config = try!(self.connector.configure().map_err(other_io_error));
+
+        if self.skip_hostname_verification {
+            return config.use_server_name_indication(false).verify_hostname(false).into_ssl("").map_err(other_io_error);
+        }
+
+        let host = match self.server_name {
+            Some(ref name) => name.as_str(),
+            None => try!(addr::remote_host(addr))
+        };
+
+        config.into_ssl(host).map_err(other_io_error)
+    }
+    fn bind(&self, addr: &net::SocketAddr, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let ssl_acceptor = match self.acceptor {
+            Some(ref ssl_acceptor) => ssl_acceptor.clone(),
+            None => return Err(invalid_input_io_error("no certificate configured"))
+        };
+        let listener = try!(TcpListener::bind(addr));
+        let acceptor = box TlsAcceptor::new(listener, ssl_acceptor, dest);
+
+        Ok(acceptor)
+    }
+}
+
+impl Transport for TlsTcp {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let mut ssl = try!(self.create_client_ssl(dest.addr));
+        let settings = dest.pipe_settings();
+
+        ssl.set_connect_state();
+
+        resolve::connect(dest.addr, move |local_addr, addr| TlsTcp::connect(ssl, local_addr, &addr, settings))
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let addr = try!(addr::resolve_bind_addr(dest.addr));
+
+        self.bind(&addr, dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use transport::*;
+    use super::*;
+
+    fn destination(addr: &str) -> Destination {
+        Destination {
+            addr: addr,
+            pids: (16, 16),
+            proto_flags: 0,
+            tcp_no_delay: false,
+            recv_max_size: 1024,
+            recv_buffer: 0
+        }
+    }
+
+    #[test]
+    fn build_with_missing_certificate_file_should_fail() {
+        let res = TlsTcpBuilder::new().
+            with_certificate_chain_file("test/certs/missing.pem").
+            with_private_key_file("test/certs/server.key").
+            build();
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn bind_without_certificate_should_fail() {
+        let transport = TlsTcpBuilder::new().build().unwrap();
+        let err = Transport::bind(&transport, &destination("127.0.0.1:5460")).err().unwrap();
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn bind_with_certificate_should_succeed() {
+        let transport = TlsTcpBuilder::new().
+            with_certificate_chain_file("test/certs/server.pem").
+            with_private_key_file("test/certs/server.key").
+            build().
+            unwrap();
+
+        assert!(Transport::bind(&transport, &destination("127.0.0.1:5461")).is_ok());
+    }
+}
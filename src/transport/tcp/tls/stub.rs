@@ -0,0 +1,315 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::ops::Deref;
+use std::rc::Rc;
+use std::io;
+#[cfg(windows)]
+use std::io::{Read, Write};
+
+use mio;
+use mio::tcp::{TcpStream, Shutdown};
+use openssl::ssl::{self, Ssl, SslStream, ErrorCode};
+
+use core::Message;
+use transport::tcp::send::SendOperation;
+use transport::tcp::recv::RecvOperation;
+use transport::async::stub::*;
+use io_error::*;
+
+/// Converts an error returned by the TLS session, an operation that needs
+/// the socket to be readable or writable again is reported as `WouldBlock`.
+fn tls_io_error(err: ssl::Error) -> io::Error {
+    match err.code() {
+        ErrorCode::WANT_READ | ErrorCode::WANT_WRITE => would_block_io_error("tls operation is incomplete"),
+        _ => err.into_io_error().unwrap_or_else(other_io_error)
+    }
+}
+
+/// Creates the TLS session of a pipe, without performing any I/O.
+pub fn create_stream(ssl: Ssl, stream: TcpStream) -> io::Result<SslStream<TcpStream>> {
+    SslStream::new(ssl, stream).map_err(other_io_error)
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* TlsPipeStub                                                               */
+/*                                                                           */
+/*****************************************************************************/
+
+/// OpenSSL read-ahead is left disabled so that records not yet decrypted stay in the socket.
+/// A peer may still pack several messages in a single record, the plain text left
+/// in the session once a message is received is then reported as buffered input,
+/// since the socket will not become readable for it.
+pub struct TlsPipeStub {
+    stream: SslStream<TcpStream>,
+    recv_max_size: u64,
+    is_established: bool,
+    is_handshake_sent: bool,
+    proto_flags: u8,
+    send_operation: Option<SendOperation>,
+    recv_operation: Option<RecvOperation>
+}
+
+impl Deref for TlsPipeStub {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        self.stream.get_ref()
+    }
+}
+
+impl TlsPipeStub {
+    pub fn new(stream: SslStream<TcpStream>, recv_max_size: u64) -> TlsPipeStub {
+        TlsPipeStub {
+            stream: stream,
+            recv_max_size: recv_max_size,
+            is_established: false,
+            is_handshake_sent: false,
+            proto_flags: 0,
+            send_operation: None,
+            recv_operation: None
+        }
+    }
+
+    fn run_tls_handshake(&mut self) -> io::Result<()> {
+        if self.is_established {
+            return Ok(());
+        }
+
+        try!(self.stream.do_handshake().map_err(tls_io_error));
+
+        self.is_established = true;
+        Ok(())
+    }
+
+    fn run_send_operation(&mut self, mut send_operation: SendOperation) -> io::Result<usize> {
+        let sent = try!(send_operation.run(&mut self.stream));
+
+        if send_operation.is_done() == false {
+            self.send_operation = Some(send_operation);
+        }
+
+        Ok(sent)
+    }
+
+    fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
+        match try!(recv_operation.run(&mut self.stream)) {
+            Some(msg) => Ok(Some(msg)),
+            None => {
+                self.recv_operation = Some(recv_operation);
+                Ok(None)
+            }
+        }
+    }
+}
+
+impl Drop for TlsPipeStub {
+    fn drop(&mut self) {
+        if self.is_established {
+            let _ = self.stream.shutdown();
+        }
+
+        let _ = self.stream.get_ref().shutdown(Shutdown::Both);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* WriteBuffers for SslStream<TcpStream>                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl WriteBuffers for SslStream<TcpStream> {
+    fn write_buffers(&mut self, bufs: &[&[u8]]) -> io::Result<usize> {
+        write_first_buffer(self, bufs)
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Sender for TlsPipeStub                                                    */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Sender for TlsPipeStub {
+    fn start_send(&mut self, msgs: Vec<Rc<Message>>) -> io::Result<usize> {
+        let send_operation = SendOperation::new(msgs);
+
+        self.run_send_operation(send_operation)
+    }
+
+    fn resume_send(&mut self) -> io::Result<usize> {
+        if let Some(send_operation) = self.send_operation.take() {
+            self.run_send_operation(send_operation)
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    fn has_pending_send(&self) -> bool {
+        self.send_operation.is_some()
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Receiver for TlsPipeStub                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Receiver for TlsPipeStub {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        let recv_operation = RecvOperation::new(self.recv_max_size);
+
+        self.run_recv_operation(recv_operation)
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(recv_operation) = self.recv_operation.take() {
+            self.run_recv_operation(recv_operation)
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.recv_operation.is_some()
+    }
+
+    fn has_buffered_input(&self) -> bool {
+        self.stream.ssl().pending() > 0
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Handshake for TlsPipeStub                                                 */
+/*                                                                           */
+/*****************************************************************************/
+
+/// The TLS handshake is started once the socket is writable, the connection
+/// being established at that point, and is then driven by readable events.
+/// The SP handshake is exchanged inside the TLS session once it is established,
+/// so the protocol flags are kept until then.
+impl Handshake for TlsPipeStub {
+    fn send_handshake(&mut self, _: (u16, u16), flags: u8) -> io::Result<()> {
+        self.proto_flags = flags;
+
+        match self.run_tls_handshake() {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            res => res
+        }
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<u8> {
+        try!(self.run_tls_handshake());
+
+        if self.is_handshake_sent == false {
+            try!(send_and_check_handshake(&mut self.stream, pids, self.proto_flags));
+            self.is_handshake_sent = true;
+        }
+
+        recv_and_check_handshake(&mut self.stream, pids)
+    }
+}
+
+impl AsyncPipeStub for TlsPipeStub {
+    #[cfg(windows)]
+    fn read_and_write_void(&mut self) {
+        let mut buffer: [u8; 0] = [0; 0];
+
+        let _ = self.stream.get_mut().read(&mut buffer);
+        let _ = self.stream.get_mut().write(&buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net;
+    use std::thread;
+    use std::time::Duration;
+
+    use mio::tcp::TcpStream;
+    use openssl::ssl::{Ssl, SslStream};
+
+    use transport::async::stub::Receiver;
+    use transport::tcp::tls::TlsTcpBuilder;
+    use super::*;
+
+    fn create_stream_pair() -> (SslStream<TcpStream>, SslStream<TcpStream>) {
+        let server = TlsTcpBuilder::new().
+            with_certificate_chain_file("test/certs/server.pem").
+            with_private_key_file("test/certs/server.key").
+            build().
+            unwrap();
+        let client = TlsTcpBuilder::new().
+            with_ca_file("test/certs/ca.pem").
+            with_server_name("localhost").
+            build().
+            unwrap();
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client_tcp = TcpStream::connect(&addr).unwrap();
+        let (server_tcp, _) = listener.accept().unwrap();
+        let server_tcp = TcpStream::from_stream(server_tcp).unwrap();
+        let mut server_ssl = Ssl::new(server.acceptor.unwrap().context()).unwrap();
+        let mut client_ssl = client.create_client_ssl(&addr.to_string()).unwrap();
+
+        server_ssl.set_accept_state();
+        client_ssl.set_connect_state();
+
+        let mut server_stream = create_stream(server_ssl, server_tcp).unwrap();
+        let mut client_stream = create_stream(client_ssl, client_tcp).unwrap();
+        let mut server_done = false;
+        let mut client_done = false;
+
+        for _ in 0..500 {
+            server_done = server_done || server_stream.do_handshake().is_ok();
+            client_done = client_done || client_stream.do_handshake().is_ok();
+
+            if server_done && client_done {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(server_done && client_done);
+
+        (server_stream, client_stream)
+    }
+
+    fn recv_before(stub: &mut TlsPipeStub) -> Message {
+        let mut received = stub.start_recv().unwrap();
+
+        for _ in 0..500 {
+            if let Some(msg) = received {
+                return msg;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+            received = stub.resume_recv().unwrap();
+        }
+
+        panic!("no message received")
+    }
+
+    #[test]
+    fn messages_sent_in_a_single_record_are_reported_as_buffered_input() {
+        let (mut server_stream, client_stream) = create_stream_pair();
+        let mut stub = TlsPipeStub::new(client_stream, 1024);
+        let frames = [0u8, 0, 0, 0, 0, 0, 0, 2, 65, 66, 0, 0, 0, 0, 0, 0, 0, 1, 67];
+
+        assert_eq!(frames.len(), server_stream.write(&frames).unwrap());
+        assert_eq!(&[65, 66], recv_before(&mut stub).get_body());
+        assert!(stub.has_buffered_input());
+
+        let msg = stub.start_recv().unwrap().expect("second message should be buffered");
+
+        assert_eq!(&[67], msg.get_body());
+        assert_eq!(false, stub.has_buffered_input());
+    }
+}
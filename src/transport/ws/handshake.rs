@@ -0,0 +1,290 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+use std::str;
+
+use mio::tcp::TcpStream;
+
+use rand;
+use sha1::Sha1;
+use base64;
+
+use proto;
+use io_error::*;
+
+const ACCEPT_GUID: &'static str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_HEAD_LEN: usize = 8192;
+
+/// Returns the value of the `Sec-WebSocket-Protocol` header designating the specified SP protocol.
+pub fn subprotocol(proto_id: u16) -> io::Result<String> {
+    let name = match proto_id {
+        proto::PAIR       => "pair",
+        proto::PUB        => "pub",
+        proto::SUB        => "sub",
+        proto::REQ        => "req",
+        proto::REP        => "rep",
+        proto::PUSH       => "push",
+        proto::PULL       => "pull",
+        proto::SURVEYOR   => "surveyor",
+        proto::RESPONDENT => "respondent",
+        proto::BUS        => "bus",
+        _ => return Err(invalid_input_io_error("protocol has no websocket mapping"))
+    };
+
+    Ok(format!("{}.sp.nanomsg.org", name))
+}
+
+pub fn create_key() -> String {
+    let nonce: [u8; 16] = rand::random();
+
+    base64::encode(&nonce)
+}
+
+pub fn create_accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+
+    base64::encode(&sha1.digest().bytes())
+}
+
+pub fn create_request(host: &str, path: &str, key: &str, protocol: &str) -> String {
+    format!("GET {} HTTP/1.1\r\n\
+             Host: {}\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\n\
+             Sec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Protocol: {}\r\n\r\n", path, host, key, protocol)
+}
+
+pub fn create_response(accept_key: &str, protocol: &str) -> String {
+    format!("HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n\
+             Sec-WebSocket-Protocol: {}\r\n\r\n", accept_key, protocol)
+}
+
+pub fn create_rejection() -> &'static str {
+    "HTTP/1.1 400 Bad Request\r\n\r\n"
+}
+
+/// Streams that can look at incoming bytes without consuming them.
+pub trait Peek : io::Read {
+    fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+}
+
+impl Peek for TcpStream {
+    fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        TcpStream::peek(self, buf)
+    }
+}
+
+/// Reads the stream until the empty line terminating the HTTP head is received.
+/// Returns `WouldBlock` when the head is not complete yet, the bytes read so far being kept in the buffer.
+/// Bytes following the head are left in the stream so they are read as websocket frames.
+pub fn read_head<T:Peek>(stream: &mut T, buffer: &mut Vec<u8>) -> io::Result<HttpHead> {
+    let mut chunk = [0u8; 1024];
+    let peeked = try!(stream.peek(&mut chunk));
+
+    if peeked == 0 {
+        return Err(other_io_error("connection closed during handshake"));
+    }
+
+    let already_read = buffer.len();
+
+    buffer.extend_from_slice(&chunk[..peeked]);
+
+    match find_head_end(buffer) {
+        Some(head_len) => {
+            try!(stream.read_exact(&mut chunk[..head_len - already_read]));
+            buffer.truncate(head_len);
+
+            let head = HttpHead::parse(buffer);
+
+            buffer.clear();
+            head
+        },
+        None => {
+            try!(stream.read_exact(&mut chunk[..peeked]));
+
+            if buffer.len() > MAX_HEAD_LEN {
+                Err(invalid_data_io_error("handshake is too long"))
+            } else {
+                Err(would_block_io_error("handshake is incomplete"))
+            }
+        }
+    }
+}
+
+fn find_head_end(buffer: &[u8]) -> Option<usize> {
+    buffer.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| pos + 4)
+}
+
+pub struct HttpHead {
+    start_line: String,
+    headers: Vec<(String, String)>
+}
+
+impl HttpHead {
+    fn parse(bytes: &[u8]) -> io::Result<HttpHead> {
+        let text = try!(str::from_utf8(bytes).map_err(|_| invalid_data_io_error("handshake is not valid utf-8")));
+        let mut lines = text.split("\r\n");
+        let start_line = lines.next().unwrap_or("").to_owned();
+        let mut headers = Vec::new();
+
+        for line in lines.filter(|l| l.is_empty() == false) {
+            match line.find(':') {
+                Some(index) => {
+                    let (name, value) = line.split_at(index);
+
+                    headers.push((name.trim().to_owned(), value[1..].trim().to_owned()));
+                },
+                None => return Err(invalid_data_io_error("malformed handshake header"))
+            }
+        }
+
+        Ok(HttpHead {
+            start_line: start_line,
+            headers: headers
+        })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().
+            find(|&&(ref n, _)| n.eq_ignore_ascii_case(name)).
+            map(|&(_, ref v)| v.as_str())
+    }
+
+    fn header_contains(&self, name: &str, token: &str) -> bool {
+        self.header(name).map_or(false, |value| value.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+    }
+
+    /// Checks an upgrade request received by the server and returns the key sent by the client.
+    pub fn check_request(&self, protocol: &str) -> io::Result<&str> {
+        let mut words = self.start_line.split(' ');
+
+        if words.next() != Some("GET") || words.last() != Some("HTTP/1.1") {
+            return Err(invalid_data_io_error("handshake is not a GET request"));
+        }
+        if self.header_contains("Upgrade", "websocket") == false || self.header_contains("Connection", "upgrade") == false {
+            return Err(invalid_data_io_error("handshake is not an upgrade request"));
+        }
+        if self.header("Sec-WebSocket-Version") != Some("13") {
+            return Err(invalid_data_io_error("unsupported websocket version"));
+        }
+        if self.header_contains("Sec-WebSocket-Protocol", protocol) == false {
+            return Err(invalid_data_io_error("received bad handshake"));
+        }
+
+        self.header("Sec-WebSocket-Key").ok_or_else(|| invalid_data_io_error("missing websocket key"))
+    }
+
+    /// Checks the upgrade response received by the client.
+    pub fn check_response(&self, key: &str, protocol: &str) -> io::Result<()> {
+        let mut words = self.start_line.split(' ');
+
+        if words.next() != Some("HTTP/1.1") || words.next() != Some("101") {
+            return Err(invalid_data_io_error("handshake was rejected"));
+        }
+        if self.header("Sec-WebSocket-Accept") != Some(&create_accept_key(key)) {
+            return Err(invalid_data_io_error("bad websocket accept key"));
+        }
+        if self.header("Sec-WebSocket-Protocol") != Some(protocol) {
+            return Err(invalid_data_io_error("received bad handshake"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    /// Hands out the bytes it was given in chunks of at most `step` bytes.
+    struct TestStream {
+        bytes: Vec<u8>,
+        step: usize
+    }
+
+    impl io::Read for TestStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.peek(buf).unwrap_or(0);
+
+            self.bytes.drain(..len);
+            Ok(len)
+        }
+    }
+
+    impl Peek for TestStream {
+        fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.bytes.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::WouldBlock, "test"));
+            }
+
+            let len = ::std::cmp::min(self.step, ::std::cmp::min(buf.len(), self.bytes.len()));
+
+            buf[..len].copy_from_slice(&self.bytes[..len]);
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn accept_key_matches_rfc_sample() {
+        assert_eq!("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=", create_accept_key("dGhlIHNhbXBsZSBub25jZQ=="));
+    }
+
+    #[test]
+    fn request_is_accepted_by_server() {
+        let request = create_request("127.0.0.1:5454", "/", "dGhlIHNhbXBsZSBub25jZQ==", "rep.sp.nanomsg.org");
+        let mut stream = TestStream { bytes: request.into_bytes(), step: 1024 };
+        let mut buffer = Vec::new();
+        let head = read_head(&mut stream, &mut buffer).unwrap();
+
+        assert_eq!("dGhlIHNhbXBsZSBub25jZQ==", head.check_request("rep.sp.nanomsg.org").unwrap());
+        assert!(head.check_request("pair.sp.nanomsg.org").is_err());
+    }
+
+    #[test]
+    fn response_is_accepted_by_client_and_following_bytes_are_not_consumed() {
+        let key = create_key();
+        let mut response = create_response(&create_accept_key(&key), "rep.sp.nanomsg.org").into_bytes();
+        response.extend_from_slice(&[0x82, 0x00]);
+        let mut stream = TestStream { bytes: response, step: 1024 };
+        let mut buffer = Vec::new();
+        let head = read_head(&mut stream, &mut buffer).unwrap();
+
+        assert!(head.check_response(&key, "rep.sp.nanomsg.org").is_ok());
+        assert!(head.check_response(&key, "req.sp.nanomsg.org").is_err());
+        assert_eq!(vec![0x82, 0x00], stream.bytes);
+    }
+
+    #[test]
+    fn incomplete_head_is_kept_for_later() {
+        let request = create_request("127.0.0.1:5454", "/", "dGhlIHNhbXBsZSBub25jZQ==", "rep.sp.nanomsg.org");
+        let mut stream = TestStream { bytes: request.into_bytes(), step: 20 };
+        let mut buffer = Vec::new();
+        let err = read_head(&mut stream, &mut buffer).err().unwrap();
+
+        assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+        assert_eq!(20, buffer.len());
+
+        loop {
+            match read_head(&mut stream, &mut buffer) {
+                Ok(_) => break,
+                Err(e) => assert_eq!(io::ErrorKind::WouldBlock, e.kind())
+            }
+        }
+
+        assert!(stream.bytes.is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+/// This module provides a transport compatible with the websocket mapping of nanomsg.
+/// Each message is sent as a single binary frame, the SP protocols being checked
+/// against the `Sec-WebSocket-Protocol` header during the HTTP upgrade.
+
+mod handshake;
+mod stub;
+mod send;
+mod recv;
+mod acceptor;
+
+use std::io;
+use std::net;
+
+use mio::tcp::TcpListener;
+
+use self::stub::{WsPipeStub, Role};
+use self::acceptor::WsAcceptor;
+use transport::{Transport, Destination, PipeSettings};
+use transport::pipe::Pipe;
+use transport::acceptor::Acceptor;
+use transport::async::AsyncPipe;
+use transport::tcp::{addr, resolve};
+
+pub struct Ws;
+
+/// Splits `host:port/path` into the socket address part and the resource path.
+fn split_addr(addr: &str) -> (&str, &str) {
+    match addr.find('/') {
+        Some(index) => addr.split_at(index),
+        None => (addr, "/")
+    }
+}
+
+impl Ws {
+    fn connect(local_addr: Option<net::SocketAddr>, addr: &net::SocketAddr, role: Role, settings: PipeSettings) -> io::Result<Box<Pipe>> {
+        let stream = try!(addr::connect(local_addr, addr));
+        try!(stream.set_nodelay(settings.tcp_no_delay));
+        let stub = WsPipeStub::new(stream, role, settings.recv_max_size);
+        let pipe = AsyncPipe::new(stub, settings.pids, settings.proto_flags, settings.recv_buffer).with_remote_addr(addr.to_string());
+
+        Ok(box pipe)
+    }
+    fn bind(&self, addr: &net::SocketAddr, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let listener = try!(TcpListener::bind(addr));
+        let acceptor = box WsAcceptor::new(listener, dest);
+
+        Ok(acceptor)
+    }
+}
+
+impl Transport for Ws {
+    fn connect(&self, dest: &Destination) -> io::Result<Box<Pipe>> {
+        let (host, path) = split_addr(dest.addr);
+        let (_, remote) = addr::split_local(host);
+        let role = Role::Client(remote.to_owned(), path.to_owned());
+        let settings = dest.pipe_settings();
+
+        resolve::connect(host, move |local_addr, addr| Ws::connect(local_addr, &addr, role, settings))
+    }
+
+    fn bind(&self, dest: &Destination) -> io::Result<Box<Acceptor>> {
+        let (host, _) = split_addr(dest.addr);
+        let addr = try!(addr::resolve_bind_addr(host));
+
+        self.bind(&addr, dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_addr;
+
+    #[test]
+    fn addr_can_have_a_path() {
+        assert_eq!(("127.0.0.1:5454", "/chat"), split_addr("127.0.0.1:5454/chat"));
+        assert_eq!(("127.0.0.1:5454", "/"), split_addr("127.0.0.1:5454"));
+    }
+}
@@ -0,0 +1,306 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use core::Message;
+use transport::async::stub::*;
+use io_error::*;
+use super::send::apply_mask;
+use super::stub::Role;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT:         u8 = 0x1;
+const OPCODE_BINARY:       u8 = 0x2;
+const OPCODE_CLOSE:        u8 = 0x8;
+const OPCODE_PING:         u8 = 0x9;
+const OPCODE_PONG:         u8 = 0xA;
+
+/// Reads websocket frames until a complete message is received.
+/// Fragmented messages are reassembled, pings are kept aside so the stub can answer them.
+/// The frames sent by a client must be masked and the ones sent by a server must not.
+pub struct RecvOperation {
+    step: Option<RecvOperationStep>,
+    recv_max_size: u64,
+    expects_mask: bool,
+    payload: Vec<u8>,
+    pings: Vec<Vec<u8>>
+}
+
+struct FrameHeader {
+    fin: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    len: usize
+}
+
+enum RecvOperationStep {
+    Header([u8; 14], usize),
+    Payload(FrameHeader, Vec<u8>, usize),
+    Terminal
+}
+
+impl RecvOperation {
+    pub fn new(recv_max_size: u64, role: &Role) -> RecvOperation {
+        RecvOperation {
+            step: Some(RecvOperationStep::Header([0; 14], 0)),
+            recv_max_size: recv_max_size,
+            expects_mask: match *role {
+                Role::Client(..) => false,
+                Role::Server => true
+            },
+            payload: Vec::new(),
+            pings: Vec::new()
+        }
+    }
+
+    pub fn take_pings(&mut self) -> Vec<Vec<u8>> {
+        ::std::mem::replace(&mut self.pings, Vec::new())
+    }
+
+    pub fn run<T:io::Read>(&mut self, stream: &mut T) -> io::Result<Option<Message>> {
+        if let Some(step) = self.step.take() {
+            self.resume_at(stream, step)
+        } else {
+            Err(other_io_error("Cannot resume already finished recv operation"))
+        }
+    }
+
+    fn resume_at<T:io::Read>(&mut self, stream: &mut T, step: RecvOperationStep) -> io::Result<Option<Message>> {
+        let mut cur_step = step;
+
+        loop {
+            let (passed, next_step) = try!(self.advance(stream, cur_step));
+
+            if !passed {
+                self.step = Some(next_step);
+                return Ok(None);
+            }
+
+            match next_step {
+                RecvOperationStep::Terminal => {
+                    let payload = ::std::mem::replace(&mut self.payload, Vec::new());
+
+                    return Ok(Some(Message::from_body(payload)));
+                },
+                other => cur_step = other
+            }
+        }
+    }
+
+    fn advance<T:io::Read>(&mut self, stream: &mut T, step: RecvOperationStep) -> io::Result<(bool, RecvOperationStep)> {
+        match step {
+            RecvOperationStep::Header(buffer, read) => self.read_header(stream, buffer, read),
+            RecvOperationStep::Payload(hdr, buffer, read) => self.read_payload(stream, hdr, buffer, read),
+            RecvOperationStep::Terminal => Err(other_io_error("Cannot advance terminal step of recv operation"))
+        }
+    }
+
+    fn read_header<T:io::Read>(&mut self, stream: &mut T, mut buffer: [u8; 14], mut read: usize) -> io::Result<(bool, RecvOperationStep)> {
+        loop {
+            let expected = header_len(&buffer[..read]);
+
+            if read == expected {
+                break;
+            }
+
+            match try!(stream.read_buffer(&mut buffer[read..expected])) {
+                0 => return Ok((false, RecvOperationStep::Header(buffer, read))),
+                n => read += n
+            }
+        }
+
+        let hdr = parse_header(&buffer[..read]);
+
+        try!(self.check_header(&hdr));
+
+        let payload = vec![0u8; hdr.len];
+
+        Ok((true, RecvOperationStep::Payload(hdr, payload, 0)))
+    }
+
+    fn check_header(&self, hdr: &FrameHeader) -> io::Result<()> {
+        if hdr.mask.is_some() != self.expects_mask {
+            return Err(invalid_data_io_error("wrong websocket frame masking"));
+        }
+
+        match hdr.opcode {
+            OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY => {
+                let msg_len = (self.payload.len() + hdr.len) as u64;
+
+                if msg_len > self.recv_max_size {
+                    Err(invalid_data_io_error("message is too long"))
+                } else {
+                    Ok(())
+                }
+            },
+            OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG => {
+                if hdr.fin && hdr.len < 126 {
+                    Ok(())
+                } else {
+                    Err(invalid_data_io_error("malformed control frame"))
+                }
+            },
+            _ => Err(invalid_data_io_error("unknown websocket opcode"))
+        }
+    }
+
+    fn read_payload<T:io::Read>(&mut self, stream: &mut T, hdr: FrameHeader, mut buffer: Vec<u8>, mut read: usize) -> io::Result<(bool, RecvOperationStep)> {
+        if read < buffer.len() {
+            read += try!(stream.read_buffer(&mut buffer[read..]));
+        }
+
+        if read < buffer.len() {
+            return Ok((false, RecvOperationStep::Payload(hdr, buffer, read)));
+        }
+
+        if let Some(key) = hdr.mask {
+            apply_mask(&mut buffer, key);
+        }
+
+        match hdr.opcode {
+            OPCODE_CLOSE => Err(connection_reset_io_error("websocket closed by peer")),
+            OPCODE_PING => {
+                self.pings.push(buffer);
+                Ok((true, RecvOperationStep::Header([0; 14], 0)))
+            },
+            OPCODE_PONG => Ok((true, RecvOperationStep::Header([0; 14], 0))),
+            _ => {
+                self.payload.extend_from_slice(&buffer);
+
+                if hdr.fin {
+                    Ok((true, RecvOperationStep::Terminal))
+                } else {
+                    Ok((true, RecvOperationStep::Header([0; 14], 0)))
+                }
+            }
+        }
+    }
+}
+
+/// Computes the length of the frame header from the bytes received so far.
+fn header_len(buffer: &[u8]) -> usize {
+    if buffer.len() < 2 {
+        return 2;
+    }
+
+    let ext_len = match buffer[1] & 0x7F {
+        126 => 2,
+        127 => 8,
+        _ => 0
+    };
+    let mask_len = if buffer[1] & 0x80 == 0 { 0 } else { 4 };
+
+    2 + ext_len + mask_len
+}
+
+fn parse_header(buffer: &[u8]) -> FrameHeader {
+    let (len, mask_offset) = match buffer[1] & 0x7F {
+        126 => (BigEndian::read_u16(&buffer[2..4]) as usize, 4),
+        127 => (BigEndian::read_u64(&buffer[2..10]) as usize, 10),
+        x => (x as usize, 2)
+    };
+    let mask = if buffer[1] & 0x80 == 0 {
+        None
+    } else {
+        let mut key = [0u8; 4];
+
+        key.copy_from_slice(&buffer[mask_offset..mask_offset + 4]);
+        Some(key)
+    };
+
+    FrameHeader {
+        fin: buffer[0] & 0x80 != 0,
+        opcode: buffer[0] & 0x0F,
+        mask: mask,
+        len: len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use transport::ws::stub::Role;
+    use super::*;
+
+    fn client() -> Role {
+        Role::Client(String::from("localhost"), String::from("/"))
+    }
+
+    #[test]
+    fn recv_unmasked_in_one_run() {
+        let buffer = [0x82u8, 3, 65, 66, 67];
+        let mut stream = io::Cursor::new(buffer.to_vec());
+        let mut operation = RecvOperation::new(1024, &client());
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+
+    #[test]
+    fn recv_masked_in_one_run() {
+        let buffer = [0x82u8, 0x83, 1, 2, 3, 4, 64, 64, 64];
+        let mut stream = io::Cursor::new(buffer.to_vec());
+        let mut operation = RecvOperation::new(1024, &Role::Server);
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+    }
+
+    #[test]
+    fn recv_fragmented_message_with_interleaved_ping() {
+        let buffer = [0x02u8, 1, 65, 0x89, 1, 7, 0x80, 2, 66, 67];
+        let mut stream = io::Cursor::new(buffer.to_vec());
+        let mut operation = RecvOperation::new(1024, &client());
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[65, 66, 67], msg.get_body());
+        assert_eq!(vec![vec![7]], operation.take_pings());
+    }
+
+    #[test]
+    fn recv_keeps_every_ping() {
+        let buffer = [0x89u8, 1, 7, 0x89, 1, 8, 0x82, 1, 65];
+        let mut stream = io::Cursor::new(buffer.to_vec());
+        let mut operation = RecvOperation::new(1024, &client());
+        let msg = operation.run(&mut stream).unwrap().unwrap();
+
+        assert_eq!(&[65], msg.get_body());
+        assert_eq!(vec![vec![7], vec![8]], operation.take_pings());
+    }
+
+    #[test]
+    fn recv_too_long_message_fails() {
+        let buffer = [0x82u8, 3, 65, 66, 67];
+        let mut stream = io::Cursor::new(buffer.to_vec());
+        let mut operation = RecvOperation::new(2, &client());
+
+        assert!(operation.run(&mut stream).is_err());
+    }
+
+    #[test]
+    fn recv_unmasked_frame_as_server_fails() {
+        let buffer = [0x82u8, 3, 65, 66, 67];
+        let mut stream = io::Cursor::new(buffer.to_vec());
+        let mut operation = RecvOperation::new(1024, &Role::Server);
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn recv_masked_frame_as_client_fails() {
+        let buffer = [0x82u8, 0x83, 1, 2, 3, 4, 64, 64, 64];
+        let mut stream = io::Cursor::new(buffer.to_vec());
+        let mut operation = RecvOperation::new(1024, &client());
+        let err = operation.run(&mut stream).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
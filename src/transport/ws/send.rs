@@ -0,0 +1,193 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::io;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use core::Message;
+use transport::async::stub::*;
+
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_PONG:   u8 = 0xA;
+
+/// Writes a single websocket frame.
+/// The header and body of the message are sent as is when no mask is required,
+/// otherwise they are copied into the masked payload.
+pub struct SendOperation {
+    chunks: VecDeque<Chunk>,
+    written: usize,
+    started: bool
+}
+
+enum Chunk {
+    Owned(Vec<u8>),
+    ProtocolHdr(Rc<Message>),
+    UsrPayload(Rc<Message>)
+}
+
+impl Chunk {
+    fn as_slice(&self) -> &[u8] {
+        match *self {
+            Chunk::Owned(ref buffer) => buffer,
+            Chunk::ProtocolHdr(ref msg) => msg.get_header(),
+            Chunk::UsrPayload(ref msg) => msg.get_body()
+        }
+    }
+}
+
+impl SendOperation {
+    pub fn new(msg: Rc<Message>, mask: Option<[u8; 4]>) -> SendOperation {
+        let frame_hdr = create_frame_header(OPCODE_BINARY, msg.len(), mask);
+        let mut chunks = VecDeque::with_capacity(3);
+
+        chunks.push_back(Chunk::Owned(frame_hdr));
+
+        match mask {
+            Some(key) => {
+                let mut payload = Vec::with_capacity(msg.len());
+
+                payload.extend_from_slice(msg.get_header());
+                payload.extend_from_slice(msg.get_body());
+                apply_mask(&mut payload, key);
+                chunks.push_back(Chunk::Owned(payload));
+            },
+            None => {
+                chunks.push_back(Chunk::ProtocolHdr(msg.clone()));
+                chunks.push_back(Chunk::UsrPayload(msg));
+            }
+        }
+
+        SendOperation {
+            chunks: chunks,
+            written: 0,
+            started: false
+        }
+    }
+
+    pub fn control(opcode: u8, mut payload: Vec<u8>, mask: Option<[u8; 4]>) -> SendOperation {
+        let frame_hdr = create_frame_header(opcode, payload.len(), mask);
+        let mut chunks = VecDeque::with_capacity(2);
+
+        if let Some(key) = mask {
+            apply_mask(&mut payload, key);
+        }
+
+        chunks.push_back(Chunk::Owned(frame_hdr));
+        chunks.push_back(Chunk::Owned(payload));
+
+        SendOperation {
+            chunks: chunks,
+            written: 0,
+            started: false
+        }
+    }
+
+    /// Tells whether a part of the frame is written, no other frame can be sent before it is done.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Writes as much of the frame as possible.
+    /// Returns whether the frame was completely sent.
+    pub fn run<T:io::Write>(&mut self, stream: &mut T) -> io::Result<bool> {
+        while let Some(chunk) = self.chunks.pop_front() {
+            let sent = chunk.as_slice().is_empty() || try!(stream.write_buffer(chunk.as_slice(), &mut self.written));
+
+            self.started = self.started || sent || self.written > 0;
+
+            if sent {
+                self.written = 0;
+            } else {
+                self.chunks.push_front(chunk);
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+fn create_frame_header(opcode: u8, len: usize, mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(14);
+    let mask_bit = if mask.is_some() { 0x80 } else { 0x00 };
+
+    buffer.push(0x80 | opcode);
+
+    if len < 126 {
+        buffer.push(mask_bit | len as u8);
+    } else if len <= 0xFFFF {
+        let mut ext_len = [0u8; 2];
+
+        BigEndian::write_u16(&mut ext_len, len as u16);
+        buffer.push(mask_bit | 126);
+        buffer.extend_from_slice(&ext_len);
+    } else {
+        let mut ext_len = [0u8; 8];
+
+        BigEndian::write_u64(&mut ext_len, len as u64);
+        buffer.push(mask_bit | 127);
+        buffer.extend_from_slice(&ext_len);
+    }
+
+    if let Some(key) = mask {
+        buffer.extend_from_slice(&key);
+    }
+
+    buffer
+}
+
+pub fn apply_mask(buffer: &mut [u8], key: [u8; 4]) {
+    for (i, x) in buffer.iter_mut().enumerate() {
+        *x ^= key[i % 4];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+    use std::rc::Rc;
+
+    use core::Message;
+    use super::*;
+
+    #[test]
+    fn send_unmasked_in_one_run() {
+        let msg = Message::from_header_and_body(vec!(1, 4), vec!(65, 66, 67));
+        let mut operation = SendOperation::new(Rc::new(msg), None);
+        let mut stream = Vec::new();
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [0x82u8, 5, 1, 4, 65, 66, 67];
+
+        assert!(result);
+        assert_eq!(&expected_bytes, stream.deref());
+    }
+
+    #[test]
+    fn send_masked_in_one_run() {
+        let msg = Message::from_header_and_body(vec!(1, 4), vec!(65, 66, 67));
+        let mut operation = SendOperation::new(Rc::new(msg), Some([1, 2, 3, 4]));
+        let mut stream = Vec::new();
+        let result = operation.run(&mut stream).expect("send should have succeeded");
+        let expected_bytes = [0x82u8, 0x85, 1, 2, 3, 4, 0, 6, 66, 70, 66];
+
+        assert!(result);
+        assert_eq!(&expected_bytes, stream.deref());
+    }
+
+    #[test]
+    fn send_with_extended_length() {
+        let msg = Message::from_body(vec![0; 300]);
+        let mut operation = SendOperation::new(Rc::new(msg), None);
+        let mut stream = Vec::new();
+
+        assert!(operation.run(&mut stream).unwrap());
+        assert_eq!(&[0x82u8, 126, 1, 44], &stream[..4]);
+        assert_eq!(304, stream.len());
+    }
+}
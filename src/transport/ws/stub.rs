@@ -0,0 +1,381 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+use std::ops::Deref;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::io;
+use std::io::Write;
+#[cfg(windows)]
+use std::io::Read;
+
+use mio;
+use mio::tcp::{TcpStream, Shutdown};
+use rand;
+
+use core::Message;
+use transport::ws::send::{SendOperation, OPCODE_PONG};
+use transport::ws::recv::RecvOperation;
+use transport::ws::handshake;
+use transport::async::stub::*;
+use io_error::*;
+
+/// The client sends the upgrade request and masks its frames,
+/// the server answers the upgrade request and sends its frames unmasked.
+pub enum Role {
+    Client(String, String),
+    Server
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* WsPipeStub                                                                */
+/*                                                                           */
+/*****************************************************************************/
+
+pub struct WsPipeStub {
+    stream: TcpStream,
+    role: Role,
+    recv_max_size: u64,
+    handshake_key: Option<String>,
+    handshake_buffer: Vec<u8>,
+    send_operations: VecDeque<SendOperation>,
+    pong_operations: VecDeque<SendOperation>,
+    recv_operation: Option<RecvOperation>,
+    /// The message read while the socket was not receiving, to answer the pings behind it.
+    received: Option<Message>
+}
+
+impl Deref for WsPipeStub {
+    type Target = mio::Evented;
+    fn deref(&self) -> &Self::Target {
+        &self.stream
+    }
+}
+
+impl WsPipeStub {
+    pub fn new(stream: TcpStream, role: Role, recv_max_size: u64) -> WsPipeStub {
+        WsPipeStub {
+            stream: stream,
+            role: role,
+            recv_max_size: recv_max_size,
+            handshake_key: None,
+            handshake_buffer: Vec::new(),
+            send_operations: VecDeque::new(),
+            pong_operations: VecDeque::new(),
+            recv_operation: None,
+            received: None
+        }
+    }
+
+    fn create_mask(&self) -> Option<[u8; 4]> {
+        match self.role {
+            Role::Client(..) => Some(rand::random()),
+            Role::Server => None
+        }
+    }
+
+    fn run_pong_operations(&mut self) -> io::Result<bool> {
+        while let Some(mut pong_operation) = self.pong_operations.pop_front() {
+            if try!(pong_operation.run(&mut self.stream)) == false {
+                self.pong_operations.push_front(pong_operation);
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Each message is sent in its own data frame, one after the other.
+    /// Control frames must not be interleaved with the data frame being sent,
+    /// so the pongs are written before the next data frame is started.
+    fn run_send_operations(&mut self) -> io::Result<usize> {
+        let mut sent = 0;
+
+        loop {
+            let is_frame_started = self.send_operations.front().map_or(false, |op| op.is_started());
+
+            if is_frame_started == false && try!(self.run_pong_operations()) == false {
+                break;
+            }
+
+            match self.send_operations.pop_front() {
+                Some(mut send_operation) => {
+                    if try!(send_operation.run(&mut self.stream)) {
+                        sent += 1;
+                    } else {
+                        self.send_operations.push_front(send_operation);
+                        break;
+                    }
+                },
+                None => break
+            }
+        }
+
+        Ok(sent)
+    }
+
+    fn run_recv_operation(&mut self, mut recv_operation: RecvOperation) -> io::Result<Option<Message>> {
+        let res = recv_operation.run(&mut self.stream);
+
+        for ping in recv_operation.take_pings() {
+            try!(self.answer_ping(ping));
+        }
+
+        match try!(res) {
+            Some(msg) => Ok(Some(msg)),
+            None => {
+                self.recv_operation = Some(recv_operation);
+                Ok(None)
+            }
+        }
+    }
+
+    fn answer_ping(&mut self, payload: Vec<u8>) -> io::Result<()> {
+        let mask = self.create_mask();
+
+        self.pong_operations.push_back(SendOperation::control(OPCODE_PONG, payload, mask));
+
+        try!(self.run_send_operations());
+
+        Ok(())
+    }
+
+    fn send_request(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let (_, peer_proto_id) = pids;
+        let key = handshake::create_key();
+        let request = match self.role {
+            Role::Client(ref host, ref path) => {
+                let protocol = try!(handshake::subprotocol(peer_proto_id));
+
+                handshake::create_request(host, path, &key, &protocol)
+            },
+            Role::Server => return Ok(())
+        };
+
+        self.handshake_key = Some(key);
+        write_all_or_fail(&mut self.stream, request.as_bytes())
+    }
+
+    fn recv_response(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let (_, peer_proto_id) = pids;
+        let protocol = try!(handshake::subprotocol(peer_proto_id));
+        let head = try!(handshake::read_head(&mut self.stream, &mut self.handshake_buffer));
+        let key = match self.handshake_key {
+            Some(ref key) => key,
+            None => return Err(other_io_error("handshake request was not sent"))
+        };
+
+        head.check_response(key, &protocol)
+    }
+
+    fn recv_request(&mut self, pids: (u16, u16)) -> io::Result<()> {
+        let (proto_id, _) = pids;
+        let protocol = try!(handshake::subprotocol(proto_id));
+        let head = try!(handshake::read_head(&mut self.stream, &mut self.handshake_buffer));
+        let response = match head.check_request(&protocol) {
+            Ok(key) => handshake::create_response(&handshake::create_accept_key(key), &protocol),
+            Err(e) => {
+                let _ = self.stream.write(handshake::create_rejection().as_bytes());
+
+                return Err(e);
+            }
+        };
+
+        write_all_or_fail(&mut self.stream, response.as_bytes())
+    }
+}
+
+fn write_all_or_fail(stream: &mut TcpStream, buffer: &[u8]) -> io::Result<()> {
+    let mut written = 0;
+
+    if try!(stream.write_buffer(buffer, &mut written)) {
+        Ok(())
+    } else {
+        Err(other_io_error("failed to send handshake"))
+    }
+}
+
+impl Drop for WsPipeStub {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Sender for WsPipeStub                                                     */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Sender for WsPipeStub {
+    fn start_send(&mut self, msgs: Vec<Rc<Message>>) -> io::Result<usize> {
+        for msg in msgs {
+            let mask = self.create_mask();
+
+            self.send_operations.push_back(SendOperation::new(msg, mask));
+        }
+
+        self.run_send_operations()
+    }
+
+    fn resume_send(&mut self) -> io::Result<usize> {
+        if self.has_pending_send() {
+            self.run_send_operations()
+        } else {
+            Err(other_io_error("Cannot resume send: no pending operation"))
+        }
+    }
+
+    /// The pongs not written yet are resumed like the messages.
+    fn has_pending_send(&self) -> bool {
+        self.send_operations.is_empty() == false || self.pong_operations.is_empty() == false
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Receiver for WsPipeStub                                                   */
+/*                                                                           */
+/*****************************************************************************/
+
+impl Receiver for WsPipeStub {
+    fn start_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(msg) = self.received.take() {
+            return Ok(Some(msg));
+        }
+
+        let recv_operation = RecvOperation::new(self.recv_max_size, &self.role);
+
+        self.run_recv_operation(recv_operation)
+    }
+
+    fn resume_recv(&mut self) -> io::Result<Option<Message>> {
+        if let Some(recv_operation) = self.recv_operation.take() {
+            self.run_recv_operation(recv_operation)
+        } else {
+            Err(other_io_error("Cannot resume recv: no pending operation"))
+        }
+    }
+
+    fn has_pending_recv(&self) -> bool {
+        self.recv_operation.is_some()
+    }
+
+    fn has_buffered_input(&self) -> bool {
+        self.received.is_some()
+    }
+
+    /// The frames are read even when the socket does not receive, so the pings are answered,
+    /// until a message is read, which is kept until the socket receives it.
+    fn read_idle(&mut self) -> io::Result<()> {
+        if self.received.is_some() {
+            return Ok(());
+        }
+
+        let recv_operation = match self.recv_operation.take() {
+            Some(recv_operation) => recv_operation,
+            None => RecvOperation::new(self.recv_max_size, &self.role)
+        };
+
+        self.received = try!(self.run_recv_operation(recv_operation));
+
+        Ok(())
+    }
+}
+
+/*****************************************************************************/
+/*                                                                           */
+/* Handshake for WsPipeStub                                                  */
+/*                                                                           */
+/*****************************************************************************/
+
+/// The websocket upgrade replaces the SP handshake, the protocols are checked
+/// against the `Sec-WebSocket-Protocol` header exchanged during the upgrade.
+/// The server has nothing to send until it has received the upgrade request,
+/// so it answers while receiving its part of the handshake.
+/// The upgrade has no room for the protocol flags, none are sent nor received.
+impl Handshake for WsPipeStub {
+    fn send_handshake(&mut self, pids: (u16, u16), _: u8) -> io::Result<()> {
+        self.send_request(pids)
+    }
+    fn recv_handshake(&mut self, pids: (u16, u16)) -> io::Result<u8> {
+        let res = match self.role {
+            Role::Client(..) => self.recv_response(pids),
+            Role::Server => self.recv_request(pids)
+        };
+
+        res.map(|_| 0)
+    }
+}
+
+impl AsyncPipeStub for WsPipeStub {
+    #[cfg(windows)]
+    fn read_and_write_void(&mut self) {
+        let mut buffer: [u8; 0] = [0; 0];
+
+        let _ = self.stream.read(&mut buffer);
+        let _ = self.stream.write(&buffer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net;
+    use std::thread;
+    use std::time::Duration;
+
+    use mio::tcp::TcpStream;
+
+    use transport::async::stub::Receiver;
+    use super::*;
+
+    fn create_server_stub() -> (WsPipeStub, net::TcpStream) {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = net::TcpStream::connect(&addr).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let server = TcpStream::from_stream(server).unwrap();
+
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        (WsPipeStub::new(server, Role::Server, 1024), client)
+    }
+
+    #[test]
+    fn pings_are_answered_while_no_message_is_being_received() {
+        let (mut stub, mut client) = create_server_stub();
+        let frames = [
+            0x89, 0x81, 0, 0, 0, 0, 7,
+            0x89, 0x81, 0, 0, 0, 0, 8,
+            0x82, 0x82, 0, 0, 0, 0, 65, 66];
+
+        client.write_all(&frames).unwrap();
+
+        for _ in 0..500 {
+            stub.read_idle().unwrap();
+
+            if stub.has_buffered_input() {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let mut pongs = [0u8; 6];
+
+        client.read_exact(&mut pongs).unwrap();
+
+        assert_eq!([0x8A, 1, 7, 0x8A, 1, 8], pongs);
+        assert!(stub.has_buffered_input());
+
+        let msg = stub.start_recv().unwrap().expect("message read while idle should be kept");
+
+        assert_eq!(&[65, 66], msg.get_body());
+        assert_eq!(false, stub.has_buffered_input());
+    }
+}
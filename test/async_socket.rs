@@ -0,0 +1,106 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use futures::Future;
+pub use futures::future;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+describe! async_socket {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+    }
+
+    it "can recv a message sent later" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !").
+            into_async().
+            expect("Failed to create async socket !");
+
+        pull.get_mut().bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        let received = pull.recv();
+        let child = thread::spawn(move || {
+            sleep_some();
+            push.send(vec![65, 66, 67]).unwrap();
+        });
+
+        let msg = received.wait().unwrap();
+        child.join().unwrap();
+
+        assert_eq!(vec![65, 66, 67], msg);
+    }
+
+    it "can send a request and recv the reply" {
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !").
+            into_async().
+            expect("Failed to create async socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        rep.set_recv_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        req.get_mut().connect(&url).unwrap();
+        sleep_some();
+
+        let child = thread::spawn(move || {
+            let request = rep.recv().unwrap();
+            rep.send(request).unwrap();
+        });
+
+        req.send(vec![65, 66, 67]).wait().unwrap();
+        let reply = req.recv().wait().unwrap();
+        child.join().unwrap();
+
+        assert_eq!(vec![65, 66, 67], reply);
+    }
+
+    it "can time out" {
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !").
+            into_async().
+            expect("Failed to create async socket !");
+
+        pull.get_mut().set_recv_timeout(timeout).unwrap();
+
+        let err = pull.recv().wait().err().expect("recv should have failed");
+
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "keep the socket open and the message when a pending recv is dropped" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !").
+            into_async().
+            expect("Failed to create async socket !");
+
+        pull.get_mut().bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        sleep_some();
+
+        {
+            let cancelled = pull.recv().select2(future::ok::<(), io::Error>(())).wait();
+
+            assert!(cancelled.is_ok(), "recv should have been cancelled");
+        }
+
+        push.send(vec![65]).unwrap();
+        push.send(vec![66]).unwrap();
+
+        assert_eq!(vec![65], pull.recv().wait().unwrap());
+        assert_eq!(vec![66], pull.recv().wait().unwrap());
+    }
+}
@@ -0,0 +1,17 @@
+#!/bin/sh
+# Generates the certificates used by the tls+tcp transport tests.
+set -e
+cd "$(dirname "$0")"
+
+openssl req -x509 -newkey rsa:2048 -nodes -days 36500 -subj "/CN=scaproust test ca" \
+  -keyout ca.key -out ca.pem
+
+for name in server client; do
+  openssl req -newkey rsa:2048 -nodes -subj "/CN=$name" -keyout $name.key -out $name.csr
+  printf "subjectAltName=DNS:localhost,IP:127.0.0.1\n" > $name.ext
+  openssl x509 -req -days 36500 -in $name.csr -CA ca.pem -CAkey ca.key -CAcreateserial \
+    -extfile $name.ext -out $name.pem
+  rm $name.csr $name.ext
+done
+
+rm -f ca.srl ca.key
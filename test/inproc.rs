@@ -0,0 +1,104 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+describe! can {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::inproc::get();
+        let timeout = make_timeout();
+    }
+
+    it "send a message through local endpoint" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+        push.bind(&url).unwrap();
+        pull.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received)
+    }
+
+    it "connect before the peer is bound" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+        push.connect(&url).unwrap();
+        pull.bind(&url).unwrap();
+
+        sleep_some();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received)
+    }
+
+    it "exchange request and reply" {
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        req.set_recv_timeout(timeout).unwrap();
+        rep.set_recv_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], request);
+
+        rep.send(vec![67, 66, 65]).unwrap();
+        let reply = req.recv().unwrap();
+        assert_eq!(vec![67, 66, 65], reply);
+    }
+
+    it "broadcast a message to several subscribers" {
+        let mut publ = session.create_socket::<Pub>().expect("Failed to create socket !");
+        let mut sub1 = session.create_socket::<Sub>().expect("Failed to create socket !");
+        let mut sub2 = session.create_socket::<Sub>().expect("Failed to create socket !");
+
+        sub1.set_recv_timeout(timeout).unwrap();
+        sub2.set_recv_timeout(timeout).unwrap();
+        sub1.set_option(ConfigOption::Subscribe(String::from(""))).unwrap();
+        sub2.set_option(ConfigOption::Subscribe(String::from(""))).unwrap();
+        publ.bind(&url).unwrap();
+        sub1.connect(&url).unwrap();
+        sub2.connect(&url).unwrap();
+
+        sleep_some();
+
+        publ.send(vec![65, 66, 67]).unwrap();
+
+        assert_eq!(vec![65, 66, 67], sub1.recv().unwrap());
+        assert_eq!(vec![65, 66, 67], sub2.recv().unwrap());
+    }
+
+    it "not bind twice to the same name" {
+        let mut pull1 = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut pull2 = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        pull1.bind(&url).unwrap();
+        let err = pull2.bind(&url).err().expect("Bind should have failed !");
+
+        assert_eq!(io::ErrorKind::AddrInUse, err.kind());
+    }
+}
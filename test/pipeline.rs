@@ -47,4 +47,19 @@ describe! can {
 
         assert_eq!(vec![65, 66, 67], received)
     }
+
+    it "receive messages read ahead in order" {
+        pull.set_option(ConfigOption::RecvBuffer(1024)).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        for i in 0..10 {
+            push.send(vec![65, i]).unwrap();
+        }
+        sleep_some();
+
+        for i in 0..10 {
+            assert_eq!(vec![65, i], pull.recv().unwrap());
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::os::unix::io::RawFd;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, SYS_TIMEOUT};
+
+pub fn wait_readable(fd: RawFd, timeout: u64) -> bool {
+    let mut pollfd = ::libc::pollfd {
+        fd: fd,
+        events: ::libc::POLLIN,
+        revents: 0
+    };
+
+    unsafe { ::libc::poll(&mut pollfd, 1, timeout as ::libc::c_int) == 1 }
+}
+
+describe! pollfd {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::tcp::get();
+    }
+
+    it "becomes readable when a message can be received" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let fd = pull.recv_fd().expect("Failed to get recv fd !");
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+        pull.set_recv_timeout(make_timeout()).unwrap();
+
+        assert!(wait_readable(fd, 0) == false);
+
+        push.send(vec![65, 66, 67]).unwrap();
+
+        assert!(wait_readable(fd, SYS_TIMEOUT));
+        assert_eq!(vec![65, 66, 67], pull.recv().unwrap());
+        assert!(wait_readable(fd, 0) == false);
+        assert_eq!(fd, pull.recv_fd().unwrap());
+    }
+
+    it "becomes readable when a message can be sent" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let fd = push.send_fd().expect("Failed to get send fd !");
+
+        assert!(wait_readable(fd, 0) == false);
+
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        assert!(wait_readable(fd, SYS_TIMEOUT));
+    }
+}
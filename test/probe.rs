@@ -64,4 +64,60 @@ describe! can {
         assert!(!poll_result[1].send, "After recv, Pull should not be send ready");
     }
 
+    it "report socket readiness to every probe watching it" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut probe1 = session.create_probe(vec![pull.create_poll_req(true, false)]).expect("Failed to create probe !");
+        let mut probe2 = session.create_probe(vec![
+            push.create_poll_req(false, true),
+            pull.create_poll_req(true, false)]).expect("Failed to create probe !");
+        let url = urls::tcp::get();
+
+        push.bind(&url).unwrap();
+        pull.connect(&url).unwrap();
+        push.send_msg(Message::new()).expect("Failed to send a message !");
+
+        let poll_result = probe1.poll(timeout).expect("First probe poll should have succeed");
+        assert!(poll_result[0].recv, "First probe should see Pull recv ready");
+
+        let poll_result = probe2.poll(timeout).expect("Second probe poll should have succeed");
+        assert!(poll_result[0].send, "Second probe should see Push send ready");
+        assert!(poll_result[1].recv, "Second probe should see Pull recv ready");
+
+        drop(probe2);
+
+        let poll_result = probe1.poll(timeout).expect("First probe poll should still succeed");
+        assert!(poll_result[0].recv, "First probe should still see Pull recv ready");
+    }
+
+    it "report socket readiness to probes polling concurrently" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut probe1 = session.create_probe(vec![pull.create_poll_req(true, false)]).expect("Failed to create probe !");
+        let mut probe2 = session.create_probe(vec![pull.create_poll_req(true, false)]).expect("Failed to create probe !");
+        let url = urls::tcp::get();
+        let barrier = Arc::new(Barrier::new(3));
+        let barrier1 = barrier.clone();
+        let barrier2 = barrier.clone();
+
+        push.bind(&url).unwrap();
+        pull.connect(&url).unwrap();
+
+        let thread1 = thread::spawn(move || {
+            barrier1.wait();
+            probe1.poll(timeout).expect("First probe poll should have succeed")[0].recv
+        });
+        let thread2 = thread::spawn(move || {
+            barrier2.wait();
+            probe2.poll(timeout).expect("Second probe poll should have succeed")[0].recv
+        });
+
+        barrier.wait();
+        thread::sleep(Duration::from_millis(50));
+        push.send_msg(Message::new()).expect("Failed to send a message !");
+
+        assert!(thread1.join().unwrap(), "First probe should see Pull recv ready");
+        assert!(thread2.join().unwrap(), "Second probe should see Pull recv ready");
+    }
+
 }
\ No newline at end of file
@@ -106,4 +106,141 @@ describe! can {
         assert_eq!(io::ErrorKind::TimedOut, not_received2.kind());
         assert_eq!(vec![65, 66, 67], received3);
     }
-}
\ No newline at end of file
+
+    it "ignores messages based on binary subscriptions" {
+        let url = urls::tcp::get();
+
+        publ.bind(&url).unwrap();
+        sub1.connect(&url).unwrap();
+        sub2.connect(&url).unwrap();
+
+        sub1.set_option(ConfigOption::SubscribeTopic(vec![255, 0])).unwrap();
+        sub2.set_option(ConfigOption::SubscribeTopic(vec![255, 1])).unwrap();
+
+        sleep_some();
+
+        let sent = vec![255, 0, 128];
+        publ.send(sent).unwrap();
+        let received1 = sub1.recv().unwrap();
+        let not_received2 = sub2.recv().unwrap_err();
+
+        assert_eq!(vec![255, 0, 128], received1);
+        assert_eq!(io::ErrorKind::TimedOut, not_received2.kind());
+    }
+
+    it "forward subscriptions to the publisher" {
+        let url = urls::tcp::get();
+
+        publ.set_option(ConfigOption::SubscriptionForwarding(true)).unwrap();
+        sub1.set_option(ConfigOption::SubscriptionForwarding(true)).unwrap();
+        publ.bind(&url).unwrap();
+        sub1.connect(&url).unwrap();
+        sub2.connect(&url).unwrap();
+
+        sub1.set_option(ConfigOption::Subscribe(String::from("A"))).unwrap();
+        sub2.set_option(ConfigOption::Subscribe(String::from(""))).unwrap();
+
+        sleep_some();
+
+        publ.send(vec![66, 66]).unwrap();
+        sleep_some();
+        publ.send(vec![65, 65]).unwrap();
+
+        assert_eq!(vec![65, 65], sub1.recv().unwrap());
+        assert_eq!(vec![66, 66], sub2.recv().unwrap());
+        assert_eq!(vec![65, 65], sub2.recv().unwrap());
+    }
+
+    it "forward subscriptions through inproc" {
+        let url = urls::inproc::get();
+
+        publ.set_option(ConfigOption::SubscriptionForwarding(true)).unwrap();
+        sub1.set_option(ConfigOption::SubscriptionForwarding(true)).unwrap();
+        sub1.set_option(ConfigOption::Subscribe(String::from("A"))).unwrap();
+        publ.bind(&url).unwrap();
+        sub1.connect(&url).unwrap();
+
+        sleep_some();
+
+        publ.send(vec![66, 66]).unwrap();
+        sleep_some();
+        publ.send(vec![65, 65]).unwrap();
+
+        assert_eq!(vec![65, 65], sub1.recv().unwrap());
+        assert_eq!(ConfigOption::SubscriptionForwarding(true), sub1.get_option(ConfigOptionKind::SubscriptionForwarding).unwrap());
+    }
+
+    it "forward subscriptions made once connected" {
+        let url = urls::inproc::get();
+
+        publ.set_option(ConfigOption::SubscriptionForwarding(true)).unwrap();
+        sub1.set_option(ConfigOption::SubscriptionForwarding(true)).unwrap();
+        publ.bind(&url).unwrap();
+        sub1.connect(&url).unwrap();
+
+        sleep_some();
+        sub1.set_option(ConfigOption::Subscribe(String::from("A"))).unwrap();
+        sleep_some();
+
+        publ.send(vec![66, 66]).unwrap();
+        publ.send(vec![65, 65]).unwrap();
+
+        assert_eq!(vec![65, 65], sub1.recv().unwrap());
+    }
+
+    it "broadcast a burst of messages through the send buffer" {
+        let url = urls::tcp::get();
+
+        publ.set_option(ConfigOption::SendBuffer(SendBuffer::Messages(16))).unwrap();
+        publ.bind(&url).unwrap();
+        sub1.connect(&url).unwrap();
+        sub1.set_option(ConfigOption::Subscribe(String::from(""))).unwrap();
+
+        sleep_some();
+
+        for i in 0..10 {
+            publ.send(vec![i]).unwrap();
+        }
+
+        for i in 0..10 {
+            assert_eq!(vec![i], sub1.recv().unwrap());
+        }
+    }
+}
+describe! typed {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let mut publ = session.create_typed_socket::<Pub>().expect("Failed to create socket !");
+        let mut sub = session.create_typed_socket::<Sub>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        publ.set_send_timeout(timeout).expect("Failed to set send timeout !");
+        sub.set_recv_timeout(timeout).expect("Failed to set recv timeout !");
+    }
+
+    it "sub can subscribe directly" {
+        publ.bind(&url).unwrap();
+        sub.connect(&url).unwrap();
+        sub.subscribe(&[65]).unwrap();
+        sleep_some();
+
+        publ.send(vec![65, 66, 67]).unwrap();
+
+        assert_eq!(vec![65, 66, 67], sub.recv().unwrap());
+    }
+
+    it "sub can unsubscribe directly" {
+        publ.bind(&url).unwrap();
+        sub.connect(&url).unwrap();
+        sub.subscribe(&[65]).unwrap();
+        sub.unsubscribe(&[65]).unwrap();
+        sleep_some();
+
+        publ.send(vec![65, 66, 67]).unwrap();
+
+        assert_eq!(io::ErrorKind::TimedOut, sub.recv().unwrap_err().kind());
+    }
+}
@@ -83,4 +83,166 @@ describe! can {
         let received_reply = req.recv().unwrap();
         assert_eq!(vec![66, 65, 67], received_reply);
     }
-}
\ No newline at end of file
+}
+describe! raw {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+    }
+
+    it "rep can reply to requests in any order" {
+        let mut rep = session.create_raw_socket::<Rep>().expect("Failed to create socket !");
+        let mut req1 = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut req2 = session.create_socket::<Req>().expect("Failed to create socket !");
+
+        rep.set_recv_timeout(timeout).unwrap();
+        req1.set_recv_timeout(timeout).unwrap();
+        req2.set_recv_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        req1.connect(&url).unwrap();
+        req2.connect(&url).unwrap();
+        sleep_some();
+
+        req1.send(vec![65]).unwrap();
+        let request1 = rep.recv_msg().unwrap();
+        req2.send(vec![66]).unwrap();
+        let request2 = rep.recv_msg().unwrap();
+
+        assert!(request1.get_header().len() >= 8);
+        assert!(request2.get_header().len() >= 8);
+
+        let (header2, body2) = request2.split();
+        rep.send_msg(Message::from_header_and_body(header2, body2)).unwrap();
+        let (header1, body1) = request1.split();
+        rep.send_msg(Message::from_header_and_body(header1, body1)).unwrap();
+
+        assert_eq!(vec![66], req2.recv().unwrap());
+        assert_eq!(vec![65], req1.recv().unwrap());
+    }
+
+    it "req exposes the request id" {
+        let mut req = session.create_raw_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        req.set_recv_timeout(timeout).unwrap();
+        rep.set_recv_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+
+        req.send_msg(Message::from_header_and_body(vec![0x80, 0, 0, 7], vec![65, 66, 67])).unwrap();
+        assert_eq!(vec![65, 66, 67], rep.recv().unwrap());
+
+        rep.send(vec![67, 66, 65]).unwrap();
+        let reply = req.recv_msg().unwrap();
+        assert_eq!(&[0x80, 0, 0, 7], reply.get_header());
+        assert_eq!(&[67, 66, 65], reply.get_body());
+    }
+}
+
+describe! context {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        req.set_recv_timeout(timeout).unwrap();
+        rep.set_recv_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+        sleep_some();
+    }
+
+    it "let requests be processed concurrently" {
+        let mut req_ctx1 = req.create_context().expect("Failed to create context !");
+        let mut req_ctx2 = req.create_context().expect("Failed to create context !");
+        let mut rep_ctx1 = rep.create_context().expect("Failed to create context !");
+        let mut rep_ctx2 = rep.create_context().expect("Failed to create context !");
+
+        req_ctx1.send(vec![65]).unwrap();
+        req_ctx2.send(vec![66]).unwrap();
+
+        let request1 = rep_ctx1.recv().unwrap();
+        let request2 = rep_ctx2.recv().unwrap();
+
+        rep_ctx2.send(request2.clone()).unwrap();
+        rep_ctx1.send(request1.clone()).unwrap();
+
+        assert_eq!(vec![65], req_ctx1.recv().unwrap());
+        assert_eq!(vec![66], req_ctx2.recv().unwrap());
+    }
+
+    it "can be used from another thread" {
+        let mut req_ctx = req.create_context().expect("Failed to create context !");
+        let child = thread::spawn(move || {
+            req_ctx.send(vec![65, 66, 67]).unwrap();
+            req_ctx.recv().unwrap()
+        });
+
+        let request = rep.recv().unwrap();
+        rep.send(request).unwrap();
+
+        assert_eq!(vec![65, 66, 67], child.join().unwrap());
+    }
+}
+
+describe! reply_token {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let url = urls::tcp::get();
+        let timeout = make_timeout();
+
+        rep.set_recv_timeout(timeout).unwrap();
+        rep.set_send_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+    }
+
+    it "lets requests be replied in any order" {
+        let mut req1 = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut req2 = session.create_socket::<Req>().expect("Failed to create socket !");
+
+        req1.set_recv_timeout(timeout).unwrap();
+        req2.set_recv_timeout(timeout).unwrap();
+        req1.connect(&url).unwrap();
+        req2.connect(&url).unwrap();
+        sleep_some();
+
+        req1.send(vec![65]).unwrap();
+        let (request1, token1) = rep.recv_request().unwrap();
+        req2.send(vec![66]).unwrap();
+        let (request2, token2) = rep.recv_request().unwrap();
+
+        rep.send_reply(token2, request2).unwrap();
+        rep.send_reply(token1, request1).unwrap();
+
+        assert_eq!(vec![66], req2.recv().unwrap());
+        assert_eq!(vec![65], req1.recv().unwrap());
+    }
+
+    it "cannot be used once the requester is gone" {
+        let (request, token) = {
+            let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+
+            req.connect(&url).unwrap();
+            sleep_some();
+            req.send(vec![65]).unwrap();
+            rep.recv_request().unwrap()
+        };
+
+        sleep_some();
+
+        let err = rep.send_reply(token, request).unwrap_err();
+
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+    }
+}
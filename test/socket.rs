@@ -10,7 +10,7 @@ pub use std::io;
 
 pub use scaproust::*;
 
-pub use super::{urls, make_session, make_timeout, sleep_some};
+pub use super::{urls, make_session, make_timeout, make_hard_timeout, sleep_some};
 
 describe! send {
 
@@ -142,6 +142,66 @@ describe! try_send {
         }
     }
 
+    it "never block when the send buffer drops messages" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let url = urls::inproc::get();
+
+        push.set_option(ConfigOption::SendBuffer(SendBuffer::Messages(4))).unwrap();
+        push.set_option(ConfigOption::SendBufferPolicy(SendBufferPolicy::DropNewest)).unwrap();
+        pull.set_recv_timeout(make_timeout()).unwrap();
+        push.bind(&url).unwrap();
+        pull.connect(&url).unwrap();
+        sleep_some();
+
+        for _ in 0..1000 {
+            assert!(push.try_send(vec![6; 512]).is_ok());
+        }
+
+        let mut received = 0;
+        while pull.recv().is_ok() {
+            received += 1;
+        }
+
+        // the inproc mailbox of the stalled pull holds 64 messages,
+        // a bit more when the whole send buffer is flushed into it, and the send buffer 4 more
+        assert!(received > 0);
+        assert!(received <= 64 + 4 + 4);
+    }
+
+    it "stop being send ready when the send buffer blocks" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut probe = session.create_probe(vec![push.create_poll_req(false, true)]).expect("Failed to create probe !");
+        let url = urls::inproc::get();
+        let timeout = make_hard_timeout();
+
+        push.set_option(ConfigOption::SendBuffer(SendBuffer::Messages(4))).unwrap();
+        push.set_option(ConfigOption::SendBufferPolicy(SendBufferPolicy::Block)).unwrap();
+        pull.set_recv_timeout(make_timeout()).unwrap();
+        push.bind(&url).unwrap();
+        pull.connect(&url).unwrap();
+        sleep_some();
+
+        let mut sent = 0;
+        while sent < 1000 && probe.poll(timeout).unwrap()[0].send {
+            push.try_send(vec![6; 512]).unwrap();
+            sent += 1;
+        }
+
+        let err = push.try_send(vec![6; 512]).unwrap_err();
+
+        assert!(sent > 0);
+        assert!(sent < 1000);
+        assert_eq!(io::ErrorKind::WouldBlock, err.kind());
+
+        for _ in 0..sent {
+            pull.recv().expect("Blocking send buffer should not drop messages !");
+        }
+
+        assert!(probe.poll(timeout).unwrap()[0].send, "Push should be send ready once the messages are received");
+    }
+
 }
 
 describe! try_recv {
@@ -172,3 +232,261 @@ describe! try_recv {
     }
 
 }
+
+describe! drop {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::tcp::get();
+    }
+
+    it "let pending messages be sent before closing" {
+        let mut sender = session.create_socket::<Bus>().expect("Failed to create socket !");
+        let mut receiver = session.create_socket::<Bus>().expect("Failed to create socket !");
+
+        receiver.set_recv_timeout(make_timeout()).unwrap();
+        receiver.bind(&url).unwrap();
+        sender.set_option(ConfigOption::Linger(Duration::from_millis(500))).unwrap();
+        sender.connect(&url).unwrap();
+        sleep_some();
+
+        sender.send(vec![65; 1000000]).unwrap();
+        drop(sender);
+
+        let received = receiver.recv().unwrap();
+        assert_eq!(1000000, received.len());
+    }
+
+    it "not wait when linger is zero" {
+        let mut sender = session.create_socket::<Bus>().expect("Failed to create socket !");
+
+        sender.set_option(ConfigOption::Linger(Duration::from_millis(0))).unwrap();
+        sender.connect(&url).unwrap();
+        sender.send(vec![65, 66, 67]).unwrap();
+        drop(sender);
+    }
+}
+
+describe! endpoint {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::tcp::get();
+    }
+
+    it "can be closed while waiting to reconnect" {
+        let mut client = session.create_socket::<Pair>().expect("Failed to create socket !");
+        let mut server = session.create_socket::<Pair>().expect("Failed to create socket !");
+
+        client.set_option(ConfigOption::RetryIvl(Duration::from_millis(50))).unwrap();
+        client.set_send_timeout(Some(Duration::from_millis(200))).unwrap();
+
+        let ep = client.connect(&url).unwrap();
+        sleep_some();
+
+        ep.close().unwrap();
+        server.bind(&url).unwrap();
+        sleep_some();
+
+        let err = client.send(vec![65, 66, 67]).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "can be closed once" {
+        let mut server = session.create_socket::<Pair>().expect("Failed to create socket !");
+        let ep = server.bind(&url).unwrap();
+
+        ep.close().unwrap();
+    }
+
+    it "tell the port picked when binding to port zero" {
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let bound = pull.bind("tcp://127.0.0.1:0").expect("Failed to bind socket !");
+        let local_addr = bound.local_addr().expect("Bound endpoint should know its address !").to_owned();
+
+        assert!(local_addr.starts_with("127.0.0.1:"));
+        assert!(local_addr.ends_with(":0") == false);
+        assert_eq!(format!("tcp://{}", local_addr), bound.url());
+
+        pull.set_recv_timeout(make_timeout()).unwrap();
+        let connected = push.connect(bound.url()).expect("Failed to connect socket !");
+        push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+
+        assert_eq!(vec![65, 66, 67], pull.recv().expect("Pull should have received a message"));
+        assert_eq!(bound.url(), connected.url());
+        assert_eq!(None, connected.local_addr());
+        assert_eq!(Some(local_addr), connected.remote_addr().unwrap());
+    }
+
+    it "tell the port picked when binding a websocket with a path to port zero" {
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let bound = pull.bind("ws://127.0.0.1:0/path").expect("Failed to bind socket !");
+        let local_addr = bound.local_addr().expect("Bound endpoint should know its address !").to_owned();
+
+        assert!(local_addr.ends_with(":0") == false);
+        assert_eq!(format!("ws://{}/path", local_addr), bound.url());
+
+        pull.set_recv_timeout(make_timeout()).unwrap();
+        push.connect(bound.url()).expect("Failed to connect socket !");
+        push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+
+        assert_eq!(vec![65, 66, 67], pull.recv().expect("Pull should have received a message"));
+    }
+
+    it "can be bound to any interface and connected to a host name" {
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let bound = pull.bind("tcp://*:0").expect("Failed to bind socket !");
+        let port = bound.local_addr().unwrap().rsplit(':').next().unwrap().to_owned();
+
+        pull.set_recv_timeout(make_timeout()).unwrap();
+        push.connect(&format!("tcp://127.0.0.1;localhost:{}", port)).expect("Failed to connect socket !");
+        push.send(vec![65, 66, 67]).expect("Push should have sent a message");
+
+        assert_eq!(vec![65, 66, 67], pull.recv().expect("Pull should have received a message"));
+    }
+}
+
+describe! statistics {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::tcp::get();
+        let mut client = session.create_socket::<Pair>().expect("Failed to create socket !");
+        let mut server = session.create_socket::<Pair>().expect("Failed to create socket !");
+
+        client.set_recv_timeout(make_timeout()).unwrap();
+        server.set_recv_timeout(make_timeout()).unwrap();
+    }
+
+    it "count connections and messages" {
+        server.bind(&url).unwrap();
+        client.connect(&url).unwrap();
+        sleep_some();
+
+        client.send(vec![65, 66, 67]).unwrap();
+        server.recv().unwrap();
+        server.send(vec![65, 66]).unwrap();
+        client.recv().unwrap();
+
+        let client_stats = client.statistics().unwrap();
+        let server_stats = server.statistics().unwrap();
+
+        assert_eq!(1, client_stats.established_connections);
+        assert_eq!(1, client_stats.current_connections);
+        assert_eq!(1, client_stats.messages_sent);
+        assert_eq!(3, client_stats.bytes_sent);
+        assert_eq!(1, client_stats.messages_received);
+        assert_eq!(2, client_stats.bytes_received);
+        assert_eq!(1, server_stats.accepted_connections);
+        assert_eq!(1, server_stats.current_connections);
+    }
+}
+
+describe! monitor {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::tcp::get();
+        let mut client = session.create_socket::<Pair>().expect("Failed to create socket !");
+        let mut server = session.create_socket::<Pair>().expect("Failed to create socket !");
+        let timeout = make_hard_timeout();
+    }
+
+    it "notify established and accepted connections" {
+        let client_events = client.monitor().unwrap();
+        let server_events = server.monitor().unwrap();
+
+        server.bind(&url).unwrap();
+        let ep = client.connect(&url).unwrap();
+
+        match client_events.recv_timeout(timeout).unwrap() {
+            SocketEvent::Connected(eid, connected_url) => {
+                assert_eq!(ep.id(), eid);
+                assert_eq!(url, connected_url);
+            },
+            other => panic!("unexpected event {:?}", other)
+        }
+        match server_events.recv_timeout(timeout).unwrap() {
+            SocketEvent::Accepted(_, peer_addr) => assert!(peer_addr.is_some()),
+            other => panic!("unexpected event {:?}", other)
+        }
+    }
+
+    it "notify rejected handshakes" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let push_events = push.monitor().unwrap();
+
+        server.bind(&url).unwrap();
+        let ep = push.connect(&url).unwrap();
+
+        match push_events.recv_timeout(timeout).unwrap() {
+            SocketEvent::HandshakeRejected(eid, _) => assert_eq!(ep.id(), eid),
+            other => panic!("unexpected event {:?}", other)
+        }
+    }
+}
+
+describe! get_option {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+    }
+
+    it "can read back generic options" {
+        let mut socket = session.create_socket::<Pair>().expect("Failed to create socket !");
+
+        assert_eq!(ConfigOption::SendPriority(8), socket.get_option(ConfigOptionKind::SendPriority).unwrap());
+
+        socket.set_send_priority(2).unwrap();
+        socket.set_recv_timeout(make_timeout()).unwrap();
+        socket.set_option(ConfigOption::RecvMaxSize(512)).unwrap();
+        socket.set_option(ConfigOption::RecvBuffer(4096)).unwrap();
+
+        assert_eq!(ConfigOption::SendPriority(2), socket.get_option(ConfigOptionKind::SendPriority).unwrap());
+        assert_eq!(ConfigOption::RecvTimeout(make_timeout()), socket.get_option(ConfigOptionKind::RecvTimeout).unwrap());
+        assert_eq!(ConfigOption::RecvMaxSize(512), socket.get_option(ConfigOptionKind::RecvMaxSize).unwrap());
+        assert_eq!(ConfigOption::RecvBuffer(4096), socket.get_option(ConfigOptionKind::RecvBuffer).unwrap());
+    }
+
+    it "can read back protocol options" {
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut surv = session.create_socket::<Surveyor>().expect("Failed to create socket !");
+        let mut sub = session.create_socket::<Sub>().expect("Failed to create socket !");
+        let ivl = Duration::from_millis(500);
+
+        req.set_option(ConfigOption::ReqResendIvl(ivl)).unwrap();
+        surv.set_option(ConfigOption::SurveyDeadline(ivl)).unwrap();
+        sub.set_option(ConfigOption::Subscribe(String::from("A"))).unwrap();
+
+        assert_eq!(ConfigOption::ReqResendIvl(ivl), req.get_option(ConfigOptionKind::ReqResendIvl).unwrap());
+        assert_eq!(ConfigOption::SurveyDeadline(ivl), surv.get_option(ConfigOptionKind::SurveyDeadline).unwrap());
+        assert_eq!(ConfigOption::Subscriptions(vec![b"A".to_vec()]), sub.get_option(ConfigOptionKind::Subscriptions).unwrap());
+    }
+
+    it "can read back send buffer options" {
+        let mut socket = session.create_socket::<Push>().expect("Failed to create socket !");
+
+        assert_eq!(ConfigOption::SendBuffer(SendBuffer::Messages(0)), socket.get_option(ConfigOptionKind::SendBuffer).unwrap());
+
+        socket.set_option(ConfigOption::SendBuffer(SendBuffer::Bytes(4096))).unwrap();
+        socket.set_option(ConfigOption::SendBufferPolicy(SendBufferPolicy::DropOldest)).unwrap();
+
+        assert_eq!(ConfigOption::SendBuffer(SendBuffer::Bytes(4096)), socket.get_option(ConfigOptionKind::SendBuffer).unwrap());
+        assert_eq!(ConfigOption::SendBufferPolicy(SendBufferPolicy::DropOldest), socket.get_option(ConfigOptionKind::SendBufferPolicy).unwrap());
+    }
+
+    it "fails for options the protocol does not have" {
+        let req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let err = req.get_option(ConfigOptionKind::SurveyDeadline).unwrap_err();
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+}
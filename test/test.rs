@@ -11,6 +11,9 @@ extern crate log;
 extern crate env_logger;
 extern crate scaproust;
 extern crate rand;
+extern crate futures;
+#[cfg(unix)]
+extern crate libc;
 
 mod socket;
 mod fair_queue;
@@ -23,6 +26,13 @@ mod bus;
 mod device;
 mod reqrep_device;
 mod probe;
+mod inproc;
+mod ws;
+mod async_socket;
+#[cfg(feature = "tls")]
+mod tls;
+#[cfg(unix)]
+mod pollfd;
 
 pub use std::time::Duration;
 pub use std::thread;
@@ -55,6 +65,8 @@ pub fn make_session() -> Session {
     SessionBuilder::new().
         with("tcp", Tcp).
         with("ipc", Ipc).
+        with("inproc", Inproc).
+        with("ws", Ws).
         build().
         expect("Failed to create session !")
 }
@@ -85,6 +97,25 @@ pub mod urls {
         }
     }
 
+    pub mod ws {
+        pub fn get() -> String {
+            format!("ws://127.0.0.1:{}", super::next_port())
+        }
+    }
+
+    #[cfg(feature = "tls")]
+    pub mod tls {
+        pub fn get() -> String {
+            format!("tls+tcp://127.0.0.1:{}", super::next_port())
+        }
+    }
+
+    pub mod inproc {
+        pub fn get() -> String {
+            format!("inproc://test_{}", super::next_port())
+        }
+    }
+
     pub mod ipc {
 
         use rand;
@@ -0,0 +1,191 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_timeout, sleep_some};
+
+pub fn make_tls_session(tls: TlsTcp) -> Session {
+    SessionBuilder::new().
+        with("tls+tcp", tls).
+        build().
+        expect("Failed to create session !")
+}
+
+pub fn make_server_transport(client_auth: bool) -> TlsTcp {
+    TlsTcpBuilder::new().
+        with_ca_file("test/certs/ca.pem").
+        with_certificate_chain_file("test/certs/server.pem").
+        with_private_key_file("test/certs/server.key").
+        with_client_auth(client_auth).
+        build().
+        expect("Failed to create transport !")
+}
+
+pub fn make_client_transport(with_certificate: bool) -> TlsTcp {
+    let builder = TlsTcpBuilder::new().
+        with_ca_file("test/certs/ca.pem").
+        with_server_name("localhost");
+    let builder = if with_certificate {
+        builder.
+            with_certificate_chain_file("test/certs/client.pem").
+            with_private_key_file("test/certs/client.key")
+    } else {
+        builder
+    };
+
+    builder.build().expect("Failed to create transport !")
+}
+
+describe! can {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let url = urls::tls::get();
+        let timeout = make_timeout();
+    }
+
+    it "send a message through secured endpoint" {
+        let mut server_session = make_tls_session(make_server_transport(false));
+        let mut client_session = make_tls_session(make_client_transport(false));
+        let mut pull = server_session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = client_session.create_socket::<Push>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received)
+    }
+
+    it "exchange request and reply with client authentication" {
+        let mut server_session = make_tls_session(make_server_transport(true));
+        let mut client_session = make_tls_session(make_client_transport(true));
+        let mut rep = server_session.create_socket::<Rep>().expect("Failed to create socket !");
+        let mut req = client_session.create_socket::<Req>().expect("Failed to create socket !");
+
+        req.set_recv_timeout(timeout).unwrap();
+        rep.set_recv_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], request);
+
+        rep.send(vec![67; 70000]).unwrap();
+        let reply = req.recv().unwrap();
+        assert_eq!(vec![67; 70000], reply);
+    }
+
+    it "not bind without a certificate" {
+        let mut session = make_tls_session(make_client_transport(false));
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+        let err = pull.bind(&url).err().expect("Bind should have failed !");
+
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+
+    it "not exchange messages with an unauthenticated client" {
+        let mut server_session = make_tls_session(make_server_transport(true));
+        let mut client_session = make_tls_session(make_client_transport(false));
+        let mut pull = server_session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = client_session.create_socket::<Push>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let err = push.send(vec![65, 66, 67]).unwrap_err();
+
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "not exchange messages with an untrusted server" {
+        let untrusted = TlsTcpBuilder::new().with_server_name("localhost").build().unwrap();
+        let mut server_session = make_tls_session(make_server_transport(false));
+        let mut client_session = make_tls_session(untrusted);
+        let mut pull = server_session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = client_session.create_socket::<Push>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let err = push.send(vec![65, 66, 67]).unwrap_err();
+
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "verify the connected address when no server name is set" {
+        let client = TlsTcpBuilder::new().with_ca_file("test/certs/ca.pem").build().unwrap();
+        let mut server_session = make_tls_session(make_server_transport(false));
+        let mut client_session = make_tls_session(client);
+        let mut pull = server_session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = client_session.create_socket::<Push>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received)
+    }
+
+    it "not exchange messages with a server whose certificate is for another name" {
+        let client = TlsTcpBuilder::new().
+            with_ca_file("test/certs/ca.pem").
+            with_server_name("example.com").
+            build().
+            unwrap();
+        let mut server_session = make_tls_session(make_server_transport(false));
+        let mut client_session = make_tls_session(client);
+        let mut pull = server_session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = client_session.create_socket::<Push>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let err = push.send(vec![65, 66, 67]).unwrap_err();
+
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    it "skip the name verification only when asked to" {
+        let client = TlsTcpBuilder::new().
+            with_ca_file("test/certs/ca.pem").
+            with_server_name("example.com").
+            danger_skip_hostname_verification().
+            build().
+            unwrap();
+        let mut server_session = make_tls_session(make_server_transport(false));
+        let mut client_session = make_tls_session(client);
+        let mut pull = server_session.create_socket::<Pull>().expect("Failed to create socket !");
+        let mut push = client_session.create_socket::<Push>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received)
+    }
+}
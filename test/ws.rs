@@ -0,0 +1,70 @@
+// Copyright (c) 2015-2017 Contributors as noted in the AUTHORS file.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or http://www.apache.org/licenses/LICENSE-2.0>
+// or the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option.
+// This file may not be copied, modified, or distributed except according to those terms.
+
+pub use std::time::Duration;
+pub use std::thread;
+pub use std::io;
+
+pub use scaproust::*;
+
+pub use super::{urls, make_session, make_timeout, sleep_some};
+
+describe! can {
+
+    before_each {
+        let _ = ::env_logger::init();
+        let mut session = make_session();
+        let url = urls::ws::get();
+        let timeout = make_timeout();
+    }
+
+    it "send a message through remote endpoint" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut pull = session.create_socket::<Pull>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        pull.set_recv_timeout(timeout).unwrap();
+        pull.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        push.send(vec![65, 66, 67]).unwrap();
+        let received = pull.recv().unwrap();
+
+        assert_eq!(vec![65, 66, 67], received)
+    }
+
+    it "exchange request and reply with a resource path" {
+        let mut req = session.create_socket::<Req>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+        let url = format!("{}/service", url);
+
+        req.set_recv_timeout(timeout).unwrap();
+        rep.set_recv_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        req.connect(&url).unwrap();
+
+        req.send(vec![65, 66, 67]).unwrap();
+        let request = rep.recv().unwrap();
+        assert_eq!(vec![65, 66, 67], request);
+
+        rep.send(vec![67; 70000]).unwrap();
+        let reply = req.recv().unwrap();
+        assert_eq!(vec![67; 70000], reply);
+    }
+
+    it "not exchange messages between incompatible protocols" {
+        let mut push = session.create_socket::<Push>().expect("Failed to create socket !");
+        let mut rep = session.create_socket::<Rep>().expect("Failed to create socket !");
+
+        push.set_send_timeout(timeout).unwrap();
+        rep.bind(&url).unwrap();
+        push.connect(&url).unwrap();
+
+        let err = push.send(vec![65, 66, 67]).unwrap_err();
+
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+}